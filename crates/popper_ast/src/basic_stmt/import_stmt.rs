@@ -1,5 +1,6 @@
 use crate::Ident;
 use crate::{Span, Statement};
+use std::path::PathBuf;
 
 #[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -7,6 +8,14 @@ use crate::{Span, Statement};
 pub struct ImportStmt {
     pub path: PathImport,
     pub module_stmts: Vec<Statement>, // module_stmts is the statements of the imported module
+    /// canonical path of the imported file, set once the module is loaded
+    pub file: Option<PathBuf>,
+    /// source of the imported file, set once the module is loaded
+    pub source: Option<String>,
+    /// `import path as alias`: name the module is bound to, instead of the last segment of the path
+    pub alias: Option<Ident>,
+    /// `import path.{a, b}`: items brought in scope, instead of the module itself
+    pub items: Option<Vec<Ident>>,
     pub span: Span,
 }
 
@@ -16,9 +25,37 @@ impl ImportStmt {
             span,
             path,
             module_stmts,
+            file: None,
+            source: None,
+            alias: None,
+            items: None,
         }
     }
 
+    /// fill the import with the module loaded from `file`, made of `source`
+    pub fn resolve(&mut self, file: PathBuf, source: String, module_stmts: Vec<Statement>) {
+        self.file = Some(file);
+        self.source = Some(source);
+        self.module_stmts = module_stmts;
+    }
+
+    pub fn with_alias(mut self, alias: Option<Ident>) -> Self {
+        self.alias = alias;
+        self
+    }
+
+    pub fn with_items(mut self, items: Option<Vec<Ident>>) -> Self {
+        self.items = items;
+        self
+    }
+
+    /// name the imported module is bound to
+    pub fn module_name(&self) -> &Ident {
+        self.alias
+            .as_ref()
+            .unwrap_or_else(|| self.path.segments.last().unwrap())
+    }
+
     pub fn span(&self) -> Span {
         self.span
     }
@@ -45,11 +82,11 @@ impl PathImport {
 #[cfg(feature = "extra-trait")]
 impl std::fmt::Display for PathImport {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let mut path = String::new();
-        for segment in &self.segments {
-            path.push_str(&segment.name);
-            path.push_str("::");
-        }
-        write!(f, "{}", path)
+        let segments = self
+            .segments
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        write!(f, "{}", segments.join("."))
     }
 }
@@ -34,3 +34,37 @@ impl LetStmt {
         self.span
     }
 }
+
+#[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
+/// A let statement destructuring a tuple. Syntax: `let (<ident>, <ident>*): <type?> = <expr>;`
+pub struct LetTupleStmt {
+    pub names: Vec<Ident>,
+    pub value: Expression,
+    pub r#type: Option<Type>,
+    pub mutable: bool,
+    pub span: Span,
+}
+
+impl LetTupleStmt {
+    pub fn new(
+        span: Span,
+        names: Vec<Ident>,
+        r#type: Option<Type>,
+        mutable: bool,
+        value: Expression,
+    ) -> Self {
+        Self {
+            names,
+            value,
+            r#type,
+            mutable,
+            span,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
@@ -7,6 +7,8 @@ mod let_stmt;
 mod while_stmt;
 mod break_stmt;
 mod assign;
+mod test_stmt;
+mod module_doc;
 
 pub use self::block::Block;
 pub use self::external::*;
@@ -16,6 +18,9 @@ pub use self::if_stmt::IfElse;
 pub use self::import_stmt::ImportStmt;
 pub use self::import_stmt::PathImport;
 pub use self::let_stmt::LetStmt;
+pub use self::let_stmt::LetTupleStmt;
 pub use self::while_stmt::While;
 pub use self::break_stmt::BreakStmt;
 pub use self::assign::*;
+pub use self::test_stmt::TestStmt;
+pub use self::module_doc::ModuleDoc;
@@ -0,0 +1,16 @@
+use crate::Span;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
+#[derive(Clone)]
+/// documentation of a module: the `//!` comments starting its file
+pub struct ModuleDoc {
+    pub doc: String,
+    pub span: Span,
+}
+
+impl ModuleDoc {
+    pub fn new(doc: String, span: Span) -> Self {
+        Self { doc, span }
+    }
+}
@@ -0,0 +1,17 @@
+use crate::*;
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
+#[derive(Clone)]
+/// a unit test, run by `popper test`. Syntax: `test "<name>" { <stmt*> }`
+pub struct TestStmt {
+    pub name: String,
+    pub body: Block,
+    pub span: Span,
+}
+
+impl TestStmt {
+    pub fn new(name: String, body: Block, span: Span) -> Self {
+        Self { name, body, span }
+    }
+}
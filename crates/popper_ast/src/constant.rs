@@ -76,6 +76,24 @@ impl StringLiteral {
     }
 }
 
+#[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
+/// any char literal starts by `'` and end by `'`: ex `'a'`, `'\n'`
+pub struct CharLiteral {
+    span: Span,
+    pub value: char,
+}
+
+impl CharLiteral {
+    pub fn new(span: Span, value: char) -> Self {
+        Self { span, value }
+    }
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
 #[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone)]
@@ -112,6 +130,25 @@ impl List {
     }
 }
 
+#[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
+/// a tuple literal: ex `(1, true)`, `(a, "b", c)`
+pub struct Tuple {
+    span: Span,
+    pub value: Vec<Expression>,
+}
+
+impl Tuple {
+    pub fn new(span: Span, value: Vec<Expression>) -> Self {
+        Self { span, value }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
 #[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone)]
@@ -137,7 +174,9 @@ pub enum Constant {
     Int(Int),
     Float(Float),
     StringLiteral(StringLiteral),
+    Char(CharLiteral),
     List(List),
+    Tuple(Tuple),
     Bool(Bool),
     Null(Null),
 }
@@ -149,9 +188,11 @@ impl Constant {
             Self::Int(int) => int.span,
             Self::Float(float) => float.span,
             Self::StringLiteral(string_literal) => string_literal.span,
+            Self::Char(char_literal) => char_literal.span,
             Self::Bool(bool) => bool.span,
             Self::Null(null) => null.span,
             Self::List(list) => list.span(),
+            Self::Tuple(tuple) => tuple.span(),
         }
     }
 }
@@ -1,9 +1,12 @@
 use crate::BinOp;
+use crate::Cast;
 use crate::Index;
 use crate::ParenGroup;
+use crate::Slice;
 use crate::Span;
 use crate::StructFieldAccess;
 use crate::StructInstance;
+use crate::TupleAccess;
 use crate::UnaryOp;
 use crate::{Call, Constant, Deref, Reference, Return, VaArg};
 
@@ -14,11 +17,14 @@ pub enum Expression {
     Constant(Constant),
     BinOp(BinOp),
     UnaryOp(UnaryOp),
+    Cast(Cast),
     Group(ParenGroup),
     Call(Call),
     StructInstance(StructInstance),
     StructFieldAccess(StructFieldAccess),
     Index(Index),
+    Slice(Slice),
+    TupleAccess(TupleAccess),
     VaArg(VaArg),
     Reference(Reference),
     Deref(Deref),
@@ -30,11 +36,14 @@ impl Expression {
             Expression::Constant(c) => c.span(),
             Expression::BinOp(b) => b.span(),
             Expression::UnaryOp(u) => u.span(),
+            Expression::Cast(c) => c.span(),
             Expression::Group(g) => g.span(),
             Expression::Call(c) => c.span,
             Expression::StructInstance(s) => s.span,
             Expression::StructFieldAccess(s) => s.span,
             Expression::Index(i) => i.span,
+            Expression::Slice(s) => s.span,
+            Expression::TupleAccess(t) => t.span,
             Expression::VaArg(v) => v.span(),
             Expression::Reference(r) => r.span,
             Expression::Deref(p) => p.span,
@@ -43,7 +52,10 @@ impl Expression {
 
     pub fn is_assignable(&self) -> bool {
         match self {
-            Expression::Reference(_) | Expression::Deref(_) | Expression::Index(_) => true,
+            Expression::Reference(_)
+            | Expression::Deref(_)
+            | Expression::Index(_)
+            | Expression::TupleAccess(_) => true,
             Expression::Group(g) => g.expr.is_assignable(),
             Expression::StructFieldAccess(s) => true,
             Expression::Constant(Constant::Ident(_)) => true,
@@ -1,10 +1,13 @@
 use crate::Expression;
+use crate::Ident;
 use crate::Span;
 
 #[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[derive(Clone)]
 pub struct Call {
+    /// `module` in `module.name(args)`
+    pub module: Option<Ident>,
     pub name: String,
     pub arguments: Vec<Expression>,
     pub span: Span,
@@ -13,6 +16,17 @@ pub struct Call {
 impl Call {
     pub fn new(name: String, arguments: Vec<Expression>, span: Span) -> Self {
         Self {
+            module: None,
+            name,
+            arguments,
+            span,
+        }
+    }
+
+    /// call of a function through the module it's defined in: `module.name(args)`
+    pub fn qualified(module: Ident, name: String, arguments: Vec<Expression>, span: Span) -> Self {
+        Self {
+            module: Some(module),
             name,
             arguments,
             span,
@@ -23,6 +23,12 @@ pub struct Function {
     pub returntype: Type,
     pub body: Vec<Statement>,
     pub is_var_args: bool,
+    /// `pub func`: visible from the modules importing this one
+    pub is_pub: bool,
+    /// `export func`: exported by the WebAssembly modules
+    pub is_export: bool,
+    /// `///` comments written before the function
+    pub doc: Option<String>,
     pub span: Span,
 }
 
@@ -33,6 +39,7 @@ impl Function {
         returntype: Type,
         body: Vec<Statement>,
         is_var_args: bool,
+        is_pub: bool,
         span: Span,
     ) -> Self {
         Self {
@@ -41,10 +48,23 @@ impl Function {
             returntype,
             body,
             is_var_args,
+            is_pub,
+            is_export: false,
+            doc: None,
             span,
         }
     }
 
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        self.doc = doc;
+        self
+    }
+
+    pub fn with_export(mut self, is_export: bool) -> Self {
+        self.is_export = is_export;
+        self
+    }
+
     pub fn span(&self) -> Span {
         self.span
     }
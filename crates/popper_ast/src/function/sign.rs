@@ -11,6 +11,8 @@ pub struct FunctionSign {
     pub arguments: Arguments,
     pub return_type: Type,
     pub is_var_args: bool,
+    /// `///` comments written before the signature
+    pub doc: Option<String>,
 }
 
 impl FunctionSign {
@@ -27,9 +29,15 @@ impl FunctionSign {
             arguments,
             return_type,
             is_var_args,
+            doc: None,
         }
     }
 
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        self.doc = doc;
+        self
+    }
+
     pub fn span(&self) -> Span {
         self.span
     }
@@ -18,3 +18,50 @@ impl Index {
         }
     }
 }
+
+#[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
+/// part of a sequence. Syntax: `<expr>:<start>..<end>`, both bounds being optional
+pub struct Slice {
+    pub value: Box<Expression>,
+    pub start: Option<Box<Expression>>,
+    pub end: Option<Box<Expression>>,
+    pub span: Span,
+}
+
+impl Slice {
+    pub fn new(
+        value: Expression,
+        start: Option<Expression>,
+        end: Option<Expression>,
+        span: Span,
+    ) -> Self {
+        Self {
+            value: Box::new(value),
+            start: start.map(Box::new),
+            end: end.map(Box::new),
+            span,
+        }
+    }
+}
+
+#[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
+/// access to a tuple element. Syntax: `<expr>.<int>`
+pub struct TupleAccess {
+    pub value: Box<Expression>,
+    pub index: usize,
+    pub span: Span,
+}
+
+impl TupleAccess {
+    pub fn new(value: Expression, index: usize, span: Span) -> Self {
+        Self {
+            value: Box::new(value),
+            index,
+            span,
+        }
+    }
+}
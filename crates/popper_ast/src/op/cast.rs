@@ -0,0 +1,27 @@
+use crate::expr::Expression;
+use crate::span::Span;
+use crate::Type;
+
+#[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone)]
+/// explicit conversion: `<expr> as <type>`
+pub struct Cast {
+    pub expr: Box<Expression>,
+    pub ty: Type,
+    pub span: Span,
+}
+
+impl Cast {
+    pub fn new(span: Span, expr: Expression, ty: Type) -> Self {
+        Self {
+            expr: Box::new(expr),
+            ty,
+            span,
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
@@ -1,7 +1,9 @@
 pub mod bin_op;
+pub mod cast;
 pub mod group;
 pub mod unary_op;
 
 pub use bin_op::*;
+pub use cast::*;
 pub use group::*;
 pub use unary_op::*;
@@ -20,16 +20,23 @@ impl Span {
     }
 
     pub fn find_line(&self, source: &str) -> usize {
-        let mut line = 1;
-        for (i, c) in source.chars().enumerate() {
-            if i >= self.start {
-                break;
-            }
-            if c == '\n' {
-                line += 1;
-            }
+        source[..self.start_in(source)].matches('\n').count() + 1
+    }
+
+    pub fn find_column(&self, source: &str) -> usize {
+        let start = self.start_in(source);
+        let line_start = source[..start].rfind('\n').map_or(0, |x| x + 1);
+        source[line_start..start].chars().count() + 1
+    }
+
+    /// start of the span in `source`, clamped to its end and moved back to the start of
+    /// a character: the span may come from another version of the source
+    fn start_in(&self, source: &str) -> usize {
+        let mut start = self.start.min(source.len());
+        while !source.is_char_boundary(start) {
+            start -= 1;
         }
-        line
+        start
     }
 }
 
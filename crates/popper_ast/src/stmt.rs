@@ -10,9 +10,12 @@ use crate::If;
 use crate::IfElse;
 use crate::ImportStmt;
 use crate::LetStmt;
+use crate::LetTupleStmt;
 use crate::Return;
 use crate::Span;
 use crate::StructStmt;
+use crate::TestStmt;
+use crate::ModuleDoc;
 use crate::While;
 
 #[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
@@ -23,6 +26,7 @@ pub enum Statement {
     While(While),
     Block(Block),
     Let(LetStmt),
+    LetTuple(LetTupleStmt),
     If(If),
     IfElse(IfElse),
     Function(Function),
@@ -33,7 +37,9 @@ pub enum Statement {
     For(ForStmt),
     Struct(StructStmt),
     BreakStmt(BreakStmt),
-    Assign(Assign)
+    Assign(Assign),
+    Test(TestStmt),
+    ModuleDoc(ModuleDoc),
 }
 
 impl Statement {
@@ -43,6 +49,7 @@ impl Statement {
             Statement::While(while_stmt) => while_stmt.span(),
             Statement::Block(block) => block.span(),
             Statement::Let(let_stmt) => let_stmt.span(),
+            Statement::LetTuple(let_tuple_stmt) => let_tuple_stmt.span(),
             Statement::If(if_stmt) => if_stmt.span(),
             Statement::IfElse(if_else_stmt) => if_else_stmt.span(),
             Statement::Function(fn_stmt) => fn_stmt.span(),
@@ -54,6 +61,8 @@ impl Statement {
             Statement::Extern(extern_stmt) => extern_stmt.span(),
             Statement::BreakStmt(break_stmt) => break_stmt.span,
             Statement::Assign(assign) => assign.span,
+            Statement::Test(test) => test.span,
+            Statement::ModuleDoc(doc) => doc.span,
         }
     }
 }
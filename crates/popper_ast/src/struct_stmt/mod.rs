@@ -7,12 +7,27 @@ use crate::*;
 pub struct StructStmt {
     pub name: String,
     pub fields: Vec<StructField>,
+    /// `pub struct`: visible from the modules importing this one
+    pub is_pub: bool,
+    /// `///` comments written before the struct
+    pub doc: Option<String>,
     pub span: Span,
 }
 
 impl StructStmt {
-    pub fn new(name: String, fields: Vec<StructField>, span: Span) -> Self {
-        Self { name, fields, span }
+    pub fn new(name: String, fields: Vec<StructField>, is_pub: bool, span: Span) -> Self {
+        Self {
+            name,
+            fields,
+            is_pub,
+            doc: None,
+            span,
+        }
+    }
+
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        self.doc = doc;
+        self
     }
 }
 
@@ -22,12 +37,27 @@ impl StructStmt {
 pub struct StructField {
     pub name: String,
     pub ty: Type,
+    /// `pub` field: readable and settable outside of the module defining the struct
+    pub is_pub: bool,
+    /// `///` comments written before the field
+    pub doc: Option<String>,
     pub span: Span,
 }
 
 impl StructField {
-    pub fn new(name: String, ty: Type, span: Span) -> Self {
-        Self { name, ty, span }
+    pub fn new(name: String, ty: Type, is_pub: bool, span: Span) -> Self {
+        Self {
+            name,
+            ty,
+            is_pub,
+            doc: None,
+            span,
+        }
+    }
+
+    pub fn with_doc(mut self, doc: Option<String>) -> Self {
+        self.doc = doc;
+        self
     }
 }
 
@@ -27,8 +27,12 @@ impl Type {
 pub enum TypeKind {
     /// `(type,*)`
     Tuple(Vec<Type>),
-    /// `[type]`
+    /// `[type: size]`
     List(Box<Type>, usize),
+    /// `[type]`: view over a part of a list or a vector
+    Slice(Box<Type>),
+    /// `vec<type>`: growable list
+    Vec(Box<Type>),
     /// `func(type,*) : type`
     Function(Vec<Type>, Box<Type>, bool),
     /// `*type`
@@ -37,19 +41,40 @@ pub enum TypeKind {
     Unit,
     /// `int`
     Int,
+    /// `i8`, `i16`, `i64`, `u8`, `u16`, `u32`, `u64`, `usize`
+    SizedInt(IntKind),
     /// `float`
     Float,
     /// `bool`
     Bool,
     /// `char`
     Char,
-    /// `string`
-    String(u32), // u32: size of string
+    /// `string`: heap allocated, growable string
+    String,
     Struct(String),
     /// `struct name
     StructInstance(String),
 }
 
+impl TypeKind {
+    /// type of the elements of a list, a slice or a vector
+    pub fn element_type(&self) -> Option<&Type> {
+        match self {
+            TypeKind::List(ty, _) | TypeKind::Slice(ty) | TypeKind::Vec(ty) => Some(ty),
+            _ => None,
+        }
+    }
+
+    /// integer kind of `int` and sized integer types
+    pub fn int_kind(&self) -> Option<IntKind> {
+        match self {
+            TypeKind::Int => Some(IntKind::I32),
+            TypeKind::SizedInt(kind) => Some(*kind),
+            _ => None,
+        }
+    }
+}
+
 impl Display for TypeKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -64,6 +89,8 @@ impl Display for TypeKind {
                 write!(f, ")")
             }
             TypeKind::List(ty, size) => write!(f, "[{}; {}]", ty.type_kind, size),
+            TypeKind::Slice(ty) => write!(f, "[{}]", ty.type_kind),
+            TypeKind::Vec(ty) => write!(f, "vec<{}>", ty.type_kind),
             TypeKind::Function(args, ret, var_args) => {
                 write!(f, "func(")?;
                 for (i, ty) in args.iter().enumerate() {
@@ -82,10 +109,11 @@ impl Display for TypeKind {
             TypeKind::Pointer(ty) => write!(f, "*{}", ty.type_kind),
             TypeKind::Unit => write!(f, "()"),
             TypeKind::Int => write!(f, "int"),
+            TypeKind::SizedInt(kind) => write!(f, "{}", kind),
             TypeKind::Float => write!(f, "float"),
             TypeKind::Bool => write!(f, "bool"),
             TypeKind::Char => write!(f, "char"),
-            TypeKind::String(size) => write!(f, "string[{}]", size),
+            TypeKind::String => write!(f, "string"),
             TypeKind::Struct(name) => {
                 write!(f, "struct {}", name)
             }
@@ -94,3 +122,77 @@ impl Display for TypeKind {
 
     }
 }
+
+#[cfg_attr(feature = "extra-trait", derive(Debug, PartialEq))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[derive(Clone, Copy)]
+/// width and signedness of an integer type, `int` being `i32`
+pub enum IntKind {
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
+    USize,
+}
+
+impl IntKind {
+    /// size of the integer in bits
+    pub fn bits(&self) -> u32 {
+        match self {
+            IntKind::I8 | IntKind::U8 => 8,
+            IntKind::I16 | IntKind::U16 => 16,
+            IntKind::I32 | IntKind::U32 => 32,
+            IntKind::I64 | IntKind::U64 | IntKind::USize => 64,
+        }
+    }
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            IntKind::I8 | IntKind::I16 | IntKind::I32 | IntKind::I64
+        )
+    }
+
+    /// smallest value of this integer type
+    pub fn min(&self) -> i128 {
+        if self.is_signed() {
+            -(1i128 << (self.bits() - 1))
+        } else {
+            0
+        }
+    }
+
+    /// biggest value of this integer type
+    pub fn max(&self) -> i128 {
+        if self.is_signed() {
+            (1i128 << (self.bits() - 1)) - 1
+        } else {
+            (1i128 << self.bits()) - 1
+        }
+    }
+
+    /// check if `value` can be represented without overflow
+    pub fn fits(&self, value: i128) -> bool {
+        self.min() <= value && value <= self.max()
+    }
+}
+
+impl Display for IntKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntKind::I8 => write!(f, "i8"),
+            IntKind::I16 => write!(f, "i16"),
+            IntKind::I32 => write!(f, "i32"),
+            IntKind::I64 => write!(f, "i64"),
+            IntKind::U8 => write!(f, "u8"),
+            IntKind::U16 => write!(f, "u16"),
+            IntKind::U32 => write!(f, "u32"),
+            IntKind::U64 => write!(f, "u64"),
+            IntKind::USize => write!(f, "usize"),
+        }
+    }
+}
@@ -14,12 +14,15 @@ pub trait ExprVisitor {
     visit!(visit_constant, constant => Constant );
     visit!(visit_bin_op, bin_op => BinOp );
     visit!(visit_unary_op, unary_op => UnaryOp );
+    visit!(visit_cast, cast => Cast );
     visit!(visit_group, group => ParenGroup );
     visit!(visit_expr, expr => Expression );
     visit!(visit_call, call => Call);
     visit!(visit_struct_instance, struct_instance => StructInstance);
     visit!(visit_struct_field_access, struct_field_access => StructFieldAccess);
     visit!(visit_index, index => Index);
+    visit!(visit_slice, slice => Slice);
+    visit!(visit_tuple_access, tuple_access => TupleAccess);
     visit!(visit_va_arg, va_arg => VaArg);
     visit!(visit_reference, reference => Reference);
     visit!(visit_deref, pointer => Deref);
@@ -32,6 +35,7 @@ pub trait StmtVisitor {
 
     visit!(visit_expr_stmt, expr => Expression);
     visit!(visit_let_stmt, let_stmt => LetStmt);
+    visit!(visit_let_tuple_stmt, let_tuple_stmt => LetTupleStmt);
     visit!(visit_stmt, stmt => Statement);
     visit!(visit_block, block => Block);
     visit!(visit_while_stmt, while_stmt => While);
@@ -46,4 +50,6 @@ pub trait StmtVisitor {
     visit!(visit_struct_stmt, struct_stmt => StructStmt);
     visit!(visit_break, break_stmt => BreakStmt);
     visit!(visit_assign, assign => Assign);
+    visit!(visit_test, test => TestStmt);
+    visit!(visit_module_doc, module_doc => ModuleDoc);
 }
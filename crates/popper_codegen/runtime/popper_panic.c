@@ -0,0 +1,68 @@
+// Popper runtime: panics, `assert` and the checks emitted with `--checks`.
+//
+// A panic prints a message, with the source location when it is known, and
+// exits with a non-zero status. WebAssembly modules without a libc hand the
+// panic to `popper_wasm_panic`.
+
+#include <stdint.h>
+
+#ifdef POPPER_FREESTANDING
+#include "popper_wasm.h"
+#else
+#include <stdio.h>
+#include <stdlib.h>
+#endif
+
+#define POPPER_PANIC_STATUS 101
+
+__attribute__((noreturn)) void popper_panic(const char *msg, const char *file, int32_t line,
+                                            int32_t col) {
+#ifdef POPPER_FREESTANDING
+    popper_wasm_panic(msg, file, line, col);
+#else
+    fflush(stdout);
+    if (file != NULL) {
+        fprintf(stderr, "panic at %s:%d:%d: %s\n", file, line, col, msg);
+    } else {
+        fprintf(stderr, "panic: %s\n", msg);
+    }
+    exit(POPPER_PANIC_STATUS);
+#endif
+}
+
+const char *popper_str_cstr(const void *s);
+
+int32_t popper_assert(int8_t cond, const void *msg, const char *file, int32_t line, int32_t col) {
+    if (!cond) {
+        char text[256];
+        snprintf(text, sizeof(text), "assertion failed: %s", popper_str_cstr(msg));
+        popper_panic(text, file, line, col);
+    }
+    return 0;
+}
+
+// the index is widened to 64 bits, whatever its type: it isn't truncated before the check
+int64_t popper_check_index(int64_t index, int64_t len, const char *file, int32_t line,
+                           int32_t col) {
+    if (index < 0 || index >= len) {
+        char msg[96];
+        snprintf(msg, sizeof(msg), "index %lld out of range for length %lld", (long long)index,
+                 (long long)len);
+        popper_panic(msg, file, line, col);
+    }
+    return index;
+}
+
+int64_t popper_check_div(int64_t divisor, const char *file, int32_t line, int32_t col) {
+    if (divisor == 0) {
+        popper_panic("division by zero", file, line, col);
+    }
+    return divisor;
+}
+
+const void *popper_check_null(const void *ptr, const char *file, int32_t line, int32_t col) {
+    if (ptr == NULL) {
+        popper_panic("null pointer dereference", file, line, col);
+    }
+    return ptr;
+}
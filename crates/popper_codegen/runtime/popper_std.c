@@ -0,0 +1,157 @@
+// Popper runtime: helpers of the standard library.
+//
+// These functions are declared in the `extern` blocks of the `std/` modules, and
+// wrapped by Popper functions. Like every C function they receive strings as NUL
+// terminated C strings, but the strings they return are popper strings.
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+#include <unistd.h>
+
+typedef struct popper_string popper_string;
+typedef struct popper_vec popper_vec;
+
+__attribute__((noreturn)) void popper_panic(const char *msg, const char *file, int32_t line,
+                                            int32_t col);
+popper_string *popper_str_new(const char *data, int32_t len);
+popper_vec *popper_vec_new(int32_t elem_size);
+int32_t popper_vec_push(popper_vec *v, const void *elem);
+
+static int popper_argc = 0;
+static char **popper_argv = NULL;
+
+// called by the `main` of the executable, before the program runs
+int32_t popper_std_init(int32_t argc, char **argv) {
+    popper_argc = argc;
+    popper_argv = argv;
+    return 0;
+}
+
+static popper_string *popper_std_string(const char *s) {
+    return popper_str_new(s, (int32_t)strlen(s));
+}
+
+// std.io
+
+int32_t popper_std_eprint(const char *s) {
+    return fputs(s, stderr);
+}
+
+int32_t popper_std_flush(void) {
+    return fflush(stdout);
+}
+
+popper_string *popper_std_read_line(void) {
+    char *line = NULL;
+    size_t cap = 0;
+    ssize_t len = getline(&line, &cap, stdin);
+    if (len < 0) {
+        free(line);
+        return popper_str_new("", 0);
+    }
+    if (len > 0 && line[len - 1] == '\n') {
+        len -= 1;
+    }
+    if (len > 0 && line[len - 1] == '\r') {
+        len -= 1;
+    }
+    popper_string *res = popper_str_new(line, (int32_t)len);
+    free(line);
+    return res;
+}
+
+// std.mem
+
+int32_t popper_std_dealloc(void *ptr) {
+    free(ptr);
+    return 0;
+}
+
+// std.process
+
+int32_t popper_std_arg_count(void) {
+    return popper_argc;
+}
+
+// also called by the `main` the compiler generates for `func main(args: vec<string>)`
+popper_vec *popper_std_args(void) {
+    popper_vec *args = popper_vec_new(sizeof(popper_string *));
+    for (int i = 0; i < popper_argc; i++) {
+        popper_string *arg = popper_std_string(popper_argv[i]);
+        popper_vec_push(args, &arg);
+    }
+    return args;
+}
+
+popper_string *popper_std_env_var(const char *name) {
+    const char *value = getenv(name);
+    return popper_std_string(value == NULL ? "" : value);
+}
+
+// std.fs
+
+popper_string *popper_std_read_file(const char *path) {
+    FILE *file = fopen(path, "rb");
+    if (file == NULL) {
+        char msg[512];
+        snprintf(msg, sizeof(msg), "unable to read `%s`", path);
+        popper_panic(msg, NULL, 0, 0);
+    }
+    fseek(file, 0, SEEK_END);
+    long len = ftell(file);
+    fseek(file, 0, SEEK_SET);
+    char *buf = malloc((size_t)len + 1);
+    if (buf == NULL) {
+        popper_panic("out of memory", NULL, 0, 0);
+    }
+    size_t read = fread(buf, 1, (size_t)len, file);
+    fclose(file);
+    popper_string *res = popper_str_new(buf, (int32_t)read);
+    free(buf);
+    return res;
+}
+
+static int32_t popper_std_write(const char *path, const char *content, const char *mode) {
+    FILE *file = fopen(path, mode);
+    if (file == NULL) {
+        return -1;
+    }
+    size_t len = strlen(content);
+    size_t written = fwrite(content, 1, len, file);
+    if (fclose(file) != 0 || written != len) {
+        return -1;
+    }
+    return 0;
+}
+
+int32_t popper_std_write_file(const char *path, const char *content) {
+    return popper_std_write(path, content, "wb");
+}
+
+int32_t popper_std_append_file(const char *path, const char *content) {
+    return popper_std_write(path, content, "ab");
+}
+
+int8_t popper_std_exists(const char *path) {
+    return access(path, F_OK) == 0;
+}
+
+// std.time
+
+int64_t popper_std_now(void) {
+    return (int64_t)time(NULL);
+}
+
+int64_t popper_std_millis(void) {
+    struct timespec ts;
+    clock_gettime(CLOCK_MONOTONIC, &ts);
+    return (int64_t)ts.tv_sec * 1000 + ts.tv_nsec / 1000000;
+}
+
+int32_t popper_std_sleep_ms(int32_t ms) {
+    struct timespec ts = {ms / 1000, (long)(ms % 1000) * 1000000};
+    return nanosleep(&ts, NULL);
+}
@@ -0,0 +1,128 @@
+// Popper runtime: heap allocated strings.
+//
+// A popper `string` is a pointer to a `popper_string`. The buffer is always
+// NUL terminated, so it can be handed to C without copy.
+
+#ifdef POPPER_FREESTANDING
+#include "popper_wasm.h"
+#else
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#endif
+
+typedef struct {
+    char *data;
+    int64_t len;
+    int64_t cap;
+} popper_string;
+
+__attribute__((noreturn)) void popper_panic(const char *msg, const char *file, int32_t line,
+                                            int32_t col);
+
+static void *popper_alloc(size_t size) {
+    void *ptr = malloc(size);
+    if (ptr == NULL) {
+        popper_panic("out of memory", NULL, 0, 0);
+    }
+    return ptr;
+}
+
+static popper_string *popper_string_with_capacity(int64_t cap) {
+    popper_string *s = popper_alloc(sizeof(popper_string));
+    s->data = popper_alloc((size_t)cap + 1);
+    s->data[0] = '\0';
+    s->len = 0;
+    s->cap = cap;
+    return s;
+}
+
+static void popper_string_reserve(popper_string *s, int64_t additional) {
+    if (s->len + additional <= s->cap) {
+        return;
+    }
+    int64_t cap = s->cap * 2;
+    if (cap < s->len + additional) {
+        cap = s->len + additional;
+    }
+    char *data = realloc(s->data, (size_t)cap + 1);
+    if (data == NULL) {
+        popper_panic("out of memory", NULL, 0, 0);
+    }
+    s->data = data;
+    s->cap = cap;
+}
+
+popper_string *popper_str_new(const char *data, int32_t len) {
+    popper_string *s = popper_string_with_capacity(len);
+    memcpy(s->data, data, (size_t)len);
+    s->data[len] = '\0';
+    s->len = len;
+    return s;
+}
+
+void popper_str_push(popper_string *s, const popper_string *other) {
+    popper_string_reserve(s, other->len);
+    memcpy(s->data + s->len, other->data, (size_t)other->len);
+    s->len += other->len;
+    s->data[s->len] = '\0';
+}
+
+popper_string *popper_str_concat(const popper_string *a, const popper_string *b) {
+    popper_string *s = popper_string_with_capacity(a->len + b->len);
+    popper_str_push(s, a);
+    popper_str_push(s, b);
+    return s;
+}
+
+popper_string *popper_str_slice(const popper_string *s, int32_t start, int32_t end) {
+    if (start < 0 || end < start || end > s->len) {
+        char msg[128];
+        snprintf(msg, sizeof(msg), "slice %d..%d out of range for string of length %lld", start,
+                 end, (long long)s->len);
+        popper_panic(msg, NULL, 0, 0);
+    }
+    return popper_str_new(s->data + start, end - start);
+}
+
+int32_t popper_str_len(const popper_string *s) {
+    return (int32_t)s->len;
+}
+
+const char *popper_str_cstr(const popper_string *s) {
+    return s->data;
+}
+
+static int popper_str_cmp(const popper_string *a, const popper_string *b) {
+    int64_t len = a->len < b->len ? a->len : b->len;
+    int res = memcmp(a->data, b->data, (size_t)len);
+    if (res != 0) {
+        return res;
+    }
+    return (a->len > b->len) - (a->len < b->len);
+}
+
+int8_t popper_str_eq(const popper_string *a, const popper_string *b) {
+    return popper_str_cmp(a, b) == 0;
+}
+
+int8_t popper_str_ne(const popper_string *a, const popper_string *b) {
+    return popper_str_cmp(a, b) != 0;
+}
+
+int8_t popper_str_lt(const popper_string *a, const popper_string *b) {
+    return popper_str_cmp(a, b) < 0;
+}
+
+int8_t popper_str_le(const popper_string *a, const popper_string *b) {
+    return popper_str_cmp(a, b) <= 0;
+}
+
+int8_t popper_str_gt(const popper_string *a, const popper_string *b) {
+    return popper_str_cmp(a, b) > 0;
+}
+
+int8_t popper_str_ge(const popper_string *a, const popper_string *b) {
+    return popper_str_cmp(a, b) >= 0;
+}
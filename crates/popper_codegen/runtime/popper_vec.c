@@ -0,0 +1,93 @@
+// Popper runtime: growable vectors and slices.
+//
+// A popper `vec<T>` is a pointer to a `popper_vec` and a `[T]` is a pointer to
+// a `popper_slice`. A vector starts with the same fields as a slice, so every
+// slice function also works on a vector. A slice of a vector is invalidated
+// when the vector grows.
+
+#ifdef POPPER_FREESTANDING
+#include "popper_wasm.h"
+#else
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#endif
+
+typedef struct {
+    char *data;
+    int64_t len;
+    int64_t elem_size;
+} popper_slice;
+
+typedef struct {
+    char *data;
+    int64_t len;
+    int64_t elem_size;
+    int64_t cap;
+} popper_vec;
+
+__attribute__((noreturn)) void popper_panic(const char *msg, const char *file, int32_t line,
+                                            int32_t col);
+
+static void *popper_realloc(void *ptr, size_t size) {
+    void *res = realloc(ptr, size);
+    if (res == NULL && size != 0) {
+        popper_panic("out of memory", NULL, 0, 0);
+    }
+    return res;
+}
+
+popper_vec *popper_vec_new(int32_t elem_size) {
+    popper_vec *v = popper_realloc(NULL, sizeof(popper_vec));
+    v->data = NULL;
+    v->len = 0;
+    v->elem_size = elem_size;
+    v->cap = 0;
+    return v;
+}
+
+int32_t popper_vec_push(popper_vec *v, const void *elem) {
+    if (v->len == v->cap) {
+        v->cap = v->cap == 0 ? 4 : v->cap * 2;
+        v->data = popper_realloc(v->data, (size_t)(v->cap * v->elem_size));
+    }
+    memcpy(v->data + v->len * v->elem_size, elem, (size_t)v->elem_size);
+    v->len += 1;
+    return (int32_t)v->len;
+}
+
+void *popper_vec_pop(popper_vec *v) {
+    if (v->len == 0) {
+        popper_panic("pop from an empty vec", NULL, 0, 0);
+    }
+    v->len -= 1;
+    return v->data + v->len * v->elem_size;
+}
+
+popper_slice *popper_slice_new(void *data, int32_t len, int32_t elem_size) {
+    popper_slice *s = popper_realloc(NULL, sizeof(popper_slice));
+    s->data = data;
+    s->len = len;
+    s->elem_size = elem_size;
+    return s;
+}
+
+int32_t popper_slice_len(const popper_slice *s) {
+    return (int32_t)s->len;
+}
+
+// the index is checked by `popper_check_index` when checks are enabled
+void *popper_slice_at(const popper_slice *s, int32_t index) {
+    return s->data + index * s->elem_size;
+}
+
+popper_slice *popper_slice_sub(const popper_slice *s, int32_t start, int32_t end) {
+    if (start < 0 || end < start || end > s->len) {
+        char msg[128];
+        snprintf(msg, sizeof(msg), "slice %d..%d out of range for length %lld", start, end,
+                 (long long)s->len);
+        popper_panic(msg, NULL, 0, 0);
+    }
+    return popper_slice_new(s->data + start * s->elem_size, end - start, (int32_t)s->elem_size);
+}
@@ -0,0 +1,241 @@
+// Popper runtime: allocator and libc functions of WebAssembly modules built
+// without a sysroot.
+//
+// Memory is handed out by a bump allocator, growing the linear memory when it's
+// full. Each block starts with its size, so the last block can be freed or
+// resized in place; the other freed blocks are only reclaimed with the module.
+// A panic traps, or on WASI prints its message to stderr and exits.
+
+#include "popper_wasm.h"
+
+#define POPPER_PANIC_STATUS 101
+#define POPPER_PAGE_SIZE 65536
+#define POPPER_ALIGN 8
+
+// first byte after the data and the stack, given by `wasm-ld`
+extern unsigned char __heap_base;
+
+typedef struct {
+    size_t size;
+    size_t _pad;
+} popper_block;
+
+static uintptr_t popper_heap_top = 0;
+static popper_block *popper_last_block = NULL;
+
+static size_t popper_align(size_t size) {
+    return (size + POPPER_ALIGN - 1) & ~(size_t)(POPPER_ALIGN - 1);
+}
+
+// make the memory reach `end`, return 0 when it can't grow
+static int popper_reserve(uintptr_t end) {
+    uintptr_t memory_end = __builtin_wasm_memory_size(0) * POPPER_PAGE_SIZE;
+    if (end <= memory_end) {
+        return 1;
+    }
+    size_t pages = (end - memory_end + POPPER_PAGE_SIZE - 1) / POPPER_PAGE_SIZE;
+    return __builtin_wasm_memory_grow(0, pages) != (size_t)-1;
+}
+
+void *malloc(size_t size) {
+    if (popper_heap_top == 0) {
+        popper_heap_top = popper_align((uintptr_t)&__heap_base);
+    }
+    size = popper_align(size);
+    uintptr_t start = popper_heap_top;
+    uintptr_t end = start + sizeof(popper_block) + size;
+    if (!popper_reserve(end)) {
+        return NULL;
+    }
+    popper_block *block = (popper_block *)start;
+    block->size = size;
+    popper_heap_top = end;
+    popper_last_block = block;
+    return block + 1;
+}
+
+void free(void *ptr) {
+    if (ptr == NULL) {
+        return;
+    }
+    popper_block *block = (popper_block *)ptr - 1;
+    if (block == popper_last_block) {
+        popper_heap_top = (uintptr_t)block;
+        popper_last_block = NULL;
+    }
+}
+
+void *realloc(void *ptr, size_t size) {
+    if (ptr == NULL) {
+        return malloc(size);
+    }
+    popper_block *block = (popper_block *)ptr - 1;
+    size = popper_align(size);
+    if (size <= block->size) {
+        return ptr;
+    }
+    if (block == popper_last_block) {
+        uintptr_t end = (uintptr_t)ptr + size;
+        if (!popper_reserve(end)) {
+            return NULL;
+        }
+        block->size = size;
+        popper_heap_top = end;
+        return ptr;
+    }
+    void *res = malloc(size);
+    if (res != NULL) {
+        memcpy(res, ptr, block->size);
+    }
+    return res;
+}
+
+void *memcpy(void *dst, const void *src, size_t len) {
+    unsigned char *d = dst;
+    const unsigned char *s = src;
+    while (len--) {
+        *d++ = *s++;
+    }
+    return dst;
+}
+
+void *memmove(void *dst, const void *src, size_t len) {
+    unsigned char *d = dst;
+    const unsigned char *s = src;
+    if (d < s) {
+        return memcpy(dst, src, len);
+    }
+    while (len--) {
+        d[len] = s[len];
+    }
+    return dst;
+}
+
+void *memset(void *dst, int c, size_t len) {
+    unsigned char *d = dst;
+    while (len--) {
+        *d++ = (unsigned char)c;
+    }
+    return dst;
+}
+
+int memcmp(const void *a, const void *b, size_t len) {
+    const unsigned char *x = a;
+    const unsigned char *y = b;
+    for (size_t i = 0; i < len; i++) {
+        if (x[i] != y[i]) {
+            return x[i] < y[i] ? -1 : 1;
+        }
+    }
+    return 0;
+}
+
+size_t strlen(const char *s) {
+    size_t len = 0;
+    while (s[len] != '\0') {
+        len++;
+    }
+    return len;
+}
+
+typedef struct {
+    char *buf;
+    size_t size;
+    size_t len;
+} popper_writer;
+
+static void popper_write_char(popper_writer *w, char c) {
+    if (w->len + 1 < w->size) {
+        w->buf[w->len] = c;
+    }
+    w->len++;
+}
+
+static void popper_write_str(popper_writer *w, const char *s) {
+    while (*s != '\0') {
+        popper_write_char(w, *s++);
+    }
+}
+
+static void popper_write_int(popper_writer *w, long long n) {
+    char digits[20];
+    int len = 0;
+    unsigned long long u = n < 0 ? 0ULL - (unsigned long long)n : (unsigned long long)n;
+    do {
+        digits[len++] = (char)('0' + u % 10);
+        u /= 10;
+    } while (u != 0);
+    if (n < 0) {
+        popper_write_char(w, '-');
+    }
+    while (len > 0) {
+        popper_write_char(w, digits[--len]);
+    }
+}
+
+int snprintf(char *buf, size_t size, const char *fmt, ...) {
+    popper_writer w = {buf, size, 0};
+    va_list args;
+    va_start(args, fmt);
+    for (const char *c = fmt; *c != '\0'; c++) {
+        if (*c != '%') {
+            popper_write_char(&w, *c);
+        } else if (c[1] == 'd') {
+            popper_write_int(&w, va_arg(args, int));
+            c += 1;
+        } else if (c[1] == 'l' && c[2] == 'l' && c[3] == 'd') {
+            popper_write_int(&w, va_arg(args, long long));
+            c += 3;
+        } else if (c[1] == 's') {
+            const char *s = va_arg(args, const char *);
+            popper_write_str(&w, s == NULL ? "(null)" : s);
+            c += 1;
+        } else if (c[1] == '%') {
+            popper_write_char(&w, '%');
+            c += 1;
+        } else {
+            popper_write_char(&w, '%');
+        }
+    }
+    va_end(args);
+    if (size > 0) {
+        buf[w.len < size ? w.len : size - 1] = '\0';
+    }
+    return (int)w.len;
+}
+
+#ifdef __wasi__
+typedef struct {
+    const char *buf;
+    size_t len;
+} popper_ciovec;
+
+__attribute__((import_module("wasi_snapshot_preview1"), import_name("fd_write"))) int32_t
+popper_wasi_fd_write(int32_t fd, const popper_ciovec *iovs, size_t iovs_len, size_t *written);
+
+__attribute__((import_module("wasi_snapshot_preview1"), import_name("proc_exit"), noreturn)) void
+popper_wasi_proc_exit(int32_t code);
+#endif
+
+__attribute__((noreturn)) void popper_wasm_panic(const char *msg, const char *file,
+                                                 int32_t line, int32_t col) {
+#ifdef __wasi__
+    char text[512];
+    int len;
+    if (file != NULL) {
+        len = snprintf(text, sizeof(text), "panic at %s:%d:%d: %s\n", file, line, col, msg);
+    } else {
+        len = snprintf(text, sizeof(text), "panic: %s\n", msg);
+    }
+    popper_ciovec iov = {text, len < (int)sizeof(text) ? (size_t)len : sizeof(text) - 1};
+    size_t written;
+    popper_wasi_fd_write(2, &iov, 1, &written);
+    popper_wasi_proc_exit(POPPER_PANIC_STATUS);
+#else
+    (void)msg;
+    (void)file;
+    (void)line;
+    (void)col;
+    __builtin_trap();
+#endif
+}
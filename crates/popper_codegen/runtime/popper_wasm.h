@@ -0,0 +1,27 @@
+// Popper runtime: the part of libc the runtime uses, for WebAssembly modules
+// built without a sysroot. It's implemented by `popper_wasm.c`.
+
+#ifndef POPPER_WASM_H
+#define POPPER_WASM_H
+
+#include <stdarg.h>
+#include <stddef.h>
+#include <stdint.h>
+
+void *malloc(size_t size);
+void *realloc(void *ptr, size_t size);
+void free(void *ptr);
+
+void *memcpy(void *dst, const void *src, size_t len);
+void *memmove(void *dst, const void *src, size_t len);
+void *memset(void *dst, int c, size_t len);
+int memcmp(const void *a, const void *b, size_t len);
+size_t strlen(const char *s);
+
+// only `%d`, `%lld`, `%s` and `%%`
+int snprintf(char *buf, size_t size, const char *fmt, ...);
+
+__attribute__((noreturn)) void popper_wasm_panic(const char *msg, const char *file,
+                                                 int32_t line, int32_t col);
+
+#endif
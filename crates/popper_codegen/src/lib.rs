@@ -5,12 +5,23 @@ use mirage::frontend::object::label::{Command, LabelBodyInstr, Value};
 use mirage::frontend::object::meta::Flag;
 use mirage::frontend::object::stringify::Stringify;
 use mirage::frontend::object::{function::*, StructValue};
-use mirage::frontend::object::{MirageObject, MirageTypeEnum, MirageValueEnum};
-use std::collections::HashMap;
+use mirage::frontend::object::{
+    IntPredicate, IntType, MirageObject, MirageTypeEnum, MirageValueEnum,
+};
+use popper_semantic_analyzer::Types;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+pub mod library;
 pub mod output;
+pub mod runtime;
+mod symbols;
 mod tag;
+pub mod target;
 
+use library::Library;
+use symbols::Scope;
 use tag::*;
+use target::Target;
 
 #[derive(Debug, Clone)]
 pub struct Compiler {
@@ -23,13 +34,44 @@ pub struct Compiler {
     is_not_loadable: bool,
     struct_env: HashMap<String, (MirageTypeEnum, popper_ast::StructStmt)>,
     shoulb_be_stored: bool,
+    fn_types: HashMap<String, (Vec<popper_ast::TypeKind>, popper_ast::TypeKind)>,
+    return_type: Option<popper_ast::TypeKind>,
+    expected_type: Option<popper_ast::TypeKind>,
+    externs: HashSet<String>,
+    modules: HashSet<String>,
+    /// imported modules are compiled to their own object file, and only declared here
+    link_modules: bool,
+    /// file and source of the module being compiled, where the failing checks are located
+    filename: String,
+    checks: bool,
+    source: String,
+    /// names of the files, as global strings
+    file_globals: HashMap<String, MirageValueEnum>,
+    target: Target,
+    /// libraries of the `external` blocks, in order
+    libraries: Vec<Library>,
+    /// directory of the module being compiled, `external` paths are relative to it
+    dir: PathBuf,
+    /// types the analyzer resolved for the expressions
+    types: Types,
+    /// file of the imported module being compiled, `None` for the compiled file
+    module_file: Option<PathBuf>,
+    /// names of the functions of the module being compiled. The functions of a module
+    /// are named `module$name`, so that the modules can use the same names
+    scope: Scope,
+    /// basic blocks created, numbering their names
+    blocks: usize,
+    /// the current basic block ended with a `ret` or a branch
+    terminated: bool,
+    /// blocks following the loops being compiled, where `break` jumps
+    loop_ends: Vec<BasicBlock>,
 }
 
 impl Compiler {
     pub fn new(stmts: Vec<popper_ast::Statement>, filename: &str) -> Self {
         let module = Module::new(filename.to_string());
         Self {
-            stmts,
+            stmts: stmts.clone(),
             env: HashMap::new(),
             module: module.clone(),
             builder: Builder::new(module),
@@ -38,17 +80,420 @@ impl Compiler {
             is_not_loadable: false,
             struct_env: HashMap::new(),
             shoulb_be_stored: false,
+            fn_types: HashMap::new(),
+            return_type: None,
+            expected_type: None,
+            externs: HashSet::new(),
+            modules: HashSet::new(),
+            link_modules: false,
+            filename: filename.to_string(),
+            checks: false,
+            source: String::new(),
+            file_globals: HashMap::new(),
+            target: Target::host(),
+            libraries: Vec::new(),
+            dir: Path::new(filename)
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default(),
+            types: Types::default(),
+            module_file: None,
+            scope: Scope::new(&symbols::module_prefix(filename), &stmts),
+            blocks: 0,
+            terminated: false,
+            loop_ends: Vec::new(),
         }
     }
 
+    /// declare the functions of the imported modules instead of compiling them,
+    /// each module being compiled to its own object file
+    pub fn link_modules(&mut self) {
+        self.link_modules = true;
+    }
+
+    /// emit runtime checks (bounds, division by zero, null pointers), reporting
+    /// the location of the failing expression in `source`
+    pub fn enable_checks(&mut self, source: &str) {
+        self.checks = true;
+        self.set_source(source);
+    }
+
+    /// machine the module is compiled for, giving the size of `usize` and pointers
+    pub fn set_target(&mut self, target: Target) {
+        self.target = target;
+    }
+
+    /// types the analyzer resolved for the expressions of the module and the modules
+    /// it imports, see `popper_semantic_analyzer::analyze_types`
+    pub fn set_types(&mut self, types: Types) {
+        self.types = types;
+    }
+
+    /// source of the module, where the failing assertions are located
+    pub fn set_source(&mut self, source: &str) {
+        self.source = source.to_string();
+    }
+
+    /// arguments giving the location of `span` to the runtime checks
+    fn location(&mut self, span: popper_ast::Span) -> Vec<MirageValueEnum> {
+        let file = match self.file_globals.get(&self.filename) {
+            Some(file) => file.clone(),
+            None => {
+                let file = self.build_c_string(&self.filename.clone());
+                self.file_globals
+                    .insert(self.filename.clone(), file.clone());
+                file
+            }
+        };
+        let line = span.find_line(&self.source) as i64;
+        let column = span.find_column(&self.source) as i64;
+        vec![
+            file,
+            self.const_int(popper_ast::IntKind::I32, line),
+            self.const_int(popper_ast::IntKind::I32, column),
+        ]
+    }
+
+    /// call a runtime check. Checks return their first argument, the result is not needed
+    fn check(&mut self, name: &str, mut args: Vec<MirageValueEnum>, span: popper_ast::Span) {
+        if self.checks {
+            args.extend(self.location(span));
+            self.call_runtime(name, args);
+        }
+    }
+
+    /// NUL terminated global string
+    fn build_c_string(&mut self, s: &str) -> MirageValueEnum {
+        let ty = MirageTypeEnum::type_array(MirageTypeEnum::type_int8().into(), s.len() + 1);
+        let value = ty
+            .const_value(
+                s.as_bytes()
+                    .iter()
+                    .chain(std::iter::once(&0))
+                    .map(|x| {
+                        let ty = MirageTypeEnum::type_int8();
+                        ty.const_value(*x as i8).to_value_enum()
+                    })
+                    .collect(),
+            )
+            .to_mirage_value();
+        self.builder.build_global(MirageObject::from(value))
+    }
+
+    /// integer representation of a type: `bool` and `char` are unsigned bytes
+    fn int_repr(ty: &popper_ast::TypeKind) -> Option<popper_ast::IntKind> {
+        match ty {
+            popper_ast::TypeKind::Bool | popper_ast::TypeKind::Char => {
+                Some(popper_ast::IntKind::U8)
+            }
+            ty => ty.int_kind(),
+        }
+    }
+
+    /// integer literal, negated or not
+    fn is_int_literal(expr: &popper_ast::Expression) -> bool {
+        match expr {
+            popper_ast::Expression::Constant(popper_ast::Constant::Int(_)) => true,
+            popper_ast::Expression::UnaryOp(u) => {
+                matches!(u.op, popper_ast::UnaryOpKind::Neg) && Self::is_int_literal(&u.expr)
+            }
+            _ => false,
+        }
+    }
+
+    /// size of an integer in bits, `usize` is as large as a pointer of the target
+    fn int_bits(&self, kind: popper_ast::IntKind) -> u32 {
+        match kind {
+            popper_ast::IntKind::USize => self.target.pointer_width,
+            kind => kind.bits(),
+        }
+    }
+
+    fn int_kind_to_mirage_ty(&self, kind: popper_ast::IntKind) -> IntType {
+        match self.int_bits(kind) {
+            8 => MirageTypeEnum::type_int8(),
+            16 => MirageTypeEnum::type_int16(),
+            32 => MirageTypeEnum::type_int32(),
+            _ => MirageTypeEnum::type_int64(),
+        }
+    }
+
+    fn const_int(&self, kind: popper_ast::IntKind, value: i64) -> MirageValueEnum {
+        let ty = self.int_kind_to_mirage_ty(kind);
+        match self.int_bits(kind) {
+            8 => ty.const_value(value as i8).to_value_enum(),
+            16 => ty.const_value(value as i16).to_value_enum(),
+            32 => ty.const_value(value as i32).to_value_enum(),
+            _ => ty.const_value(value).to_value_enum(),
+        }
+    }
+
+    /// type the analyzer resolved for an expression
+    fn analyzed_type(&self, expr: &popper_ast::Expression) -> Option<popper_ast::TypeKind> {
+        self.types
+            .get(self.module_file.as_deref(), expr.span())
+            .and_then(|x| x.to_ty_kind())
+    }
+
+    /// type of an expression: the one the analyzer resolved, or the one its syntax gives
+    /// when the types weren't set
+    fn type_of(&self, expr: &popper_ast::Expression) -> Option<popper_ast::TypeKind> {
+        self.analyzed_type(expr)
+            .or_else(|| self.syntactic_type(expr))
+    }
+
+    /// type of an expression, when it can be known from its syntax alone
+    fn syntactic_type(&self, expr: &popper_ast::Expression) -> Option<popper_ast::TypeKind> {
+        match expr {
+            popper_ast::Expression::Constant(c) => match c {
+                popper_ast::Constant::Int(_) => Some(popper_ast::TypeKind::Int),
+                popper_ast::Constant::Char(_) => Some(popper_ast::TypeKind::Char),
+                popper_ast::Constant::StringLiteral(_) => Some(popper_ast::TypeKind::String),
+                popper_ast::Constant::Float(_) => Some(popper_ast::TypeKind::Float),
+                _ => None,
+            },
+            popper_ast::Expression::Cast(c) => Some(c.ty.type_kind.clone()),
+            popper_ast::Expression::Call(c)
+                if Self::is_builtin(&c.name) && !self.fn_types.contains_key(&self.symbol(c)) =>
+            {
+                match c.name.as_str() {
+                    "len" => Some(popper_ast::TypeKind::Int),
+                    "push" | "assert" => Some(popper_ast::TypeKind::Unit),
+                    _ => self
+                        .type_of(&c.arguments[0])
+                        .and_then(|x| x.element_type().map(|x| x.type_kind.clone())),
+                }
+            }
+            popper_ast::Expression::Call(c) => {
+                self.fn_types.get(&self.symbol(c)).map(|x| x.1.clone())
+            }
+            popper_ast::Expression::Slice(s) => match self.type_of(&s.value)? {
+                popper_ast::TypeKind::String => Some(popper_ast::TypeKind::String),
+                ty => ty
+                    .element_type()
+                    .map(|x| popper_ast::TypeKind::Slice(Box::new(x.clone()))),
+            },
+            popper_ast::Expression::Index(i) => self
+                .type_of(&i.value)?
+                .element_type()
+                .map(|x| x.type_kind.clone()),
+            popper_ast::Expression::TupleAccess(t) => match self.type_of(&t.value)? {
+                popper_ast::TypeKind::Tuple(types) => {
+                    types.get(t.index).map(|x| x.type_kind.clone())
+                }
+                _ => None,
+            },
+            popper_ast::Expression::Group(g) => self.type_of(&g.expr),
+            popper_ast::Expression::BinOp(b) => match self.type_of(&b.lhs) {
+                Some(popper_ast::TypeKind::Int) | None => self.type_of(&b.rhs),
+                ty => ty,
+            },
+            _ => None,
+        }
+    }
+
+    /// type expected for the next compiled expression, used to type literals
+    fn expect_type(&mut self, ty: Option<popper_ast::TypeKind>) {
+        self.expected_type = ty;
+    }
+
+    /// size and alignment in bytes of a value of type `ty`
+    fn layout_of(&self, ty: &popper_ast::TypeKind) -> (i64, i64) {
+        let fields_layout = |fields: Vec<&popper_ast::TypeKind>| {
+            let (mut size, mut align) = (0, 1);
+            for field in fields {
+                let (field_size, field_align) = self.layout_of(field);
+                size = (size + field_align - 1) / field_align * field_align + field_size;
+                align = align.max(field_align);
+            }
+            ((size + align - 1) / align * align, align)
+        };
+
+        match ty {
+            popper_ast::TypeKind::Int | popper_ast::TypeKind::Float => (4, 4),
+            popper_ast::TypeKind::SizedInt(kind) => {
+                let size = self.int_bits(*kind) as i64 / 8;
+                (size, size)
+            }
+            popper_ast::TypeKind::Bool | popper_ast::TypeKind::Char => (1, 1),
+            popper_ast::TypeKind::Unit => (0, 1),
+            popper_ast::TypeKind::List(ty, n) => {
+                let (size, align) = self.layout_of(&ty.type_kind);
+                (size * *n as i64, align)
+            }
+            popper_ast::TypeKind::Tuple(types) => {
+                fields_layout(types.iter().map(|x| &x.type_kind).collect())
+            }
+            popper_ast::TypeKind::Struct(name) | popper_ast::TypeKind::StructInstance(name) => {
+                let s = &self.struct_env.get(name).unwrap().1;
+                fields_layout(s.fields.iter().map(|x| &x.ty.type_kind).collect())
+            }
+            // pointers, and the runtime values behind one
+            _ => {
+                let size = self.target.pointer_width as i64 / 8;
+                (size, size)
+            }
+        }
+    }
+
+    fn size_of(&self, ty: &popper_ast::TypeKind) -> MirageValueEnum {
+        self.const_int(popper_ast::IntKind::I32, self.layout_of(ty).0)
+    }
+
+    /// compile a list, a slice or a vector into a pointer to a runtime slice
+    fn compile_as_slice(&mut self, expr: popper_ast::Expression) -> MirageValueEnum {
+        let ty = self.type_of(&expr);
+        let val = self.compile_expr(expr).value;
+        match ty {
+            Some(popper_ast::TypeKind::List(elem, n)) => {
+                let size = self.size_of(&elem.type_kind);
+                let len = self.const_int(popper_ast::IntKind::I32, n as i64);
+                let basic_block = self.current_basic_block.as_mut().unwrap();
+                let data = basic_block.build_ref(val).unwrap();
+                self.call_runtime("popper_slice_new", vec![data, len, size])
+            }
+            _ => val,
+        }
+    }
+
+    /// sign extend, zero extend or truncate an integer
+    fn convert_int(
+        &mut self,
+        val: MirageValueEnum,
+        from: popper_ast::IntKind,
+        to: popper_ast::IntKind,
+    ) -> MirageValueEnum {
+        let ty: MirageTypeEnum = self.int_kind_to_mirage_ty(to).into();
+        let (from_bits, to_bits) = (self.int_bits(from), self.int_bits(to));
+        let basic_block = self.current_basic_block.as_mut().unwrap();
+        if to_bits > from_bits && from.is_signed() {
+            basic_block.build_int_sext(val, ty).unwrap()
+        } else if to_bits > from_bits {
+            basic_block.build_int_zext(val, ty).unwrap()
+        } else if to_bits < from_bits {
+            basic_block.build_int_trunc(val, ty).unwrap()
+        } else {
+            val
+        }
+    }
+
+    /// convert a value of type `from` to `to`, as the analyzer allows with `as`
+    fn compile_cast(
+        &mut self,
+        val: MirageValueEnum,
+        from: popper_ast::TypeKind,
+        to: popper_ast::TypeKind,
+    ) -> MirageValueEnum {
+        use popper_ast::{IntKind, TypeKind};
+
+        if let (Some(from), Some(to)) = (Self::int_repr(&from), Self::int_repr(&to)) {
+            return self.convert_int(val, from, to);
+        }
+        let target = self
+            .popper_ty_to_mirage_ty(popper_ast::Type::new(
+                popper_ast::Span::new(0, 0),
+                to.clone(),
+                vec![],
+            ))
+            .value;
+        let basic_block = self.current_basic_block.as_mut().unwrap();
+        match (&from, &to) {
+            (TypeKind::Float, to) if to.int_kind().is_some_and(|x| x.is_signed()) => {
+                basic_block.build_float_to_signed_int(val, target).unwrap()
+            }
+            (TypeKind::Float, _) => basic_block
+                .build_float_to_unsigned_int(val, target)
+                .unwrap(),
+            (from, TypeKind::Float) if from.int_kind().is_some_and(|x| x.is_signed()) => {
+                basic_block.build_signed_int_to_float(val, target).unwrap()
+            }
+            // `bool` and `char` are unsigned
+            (_, TypeKind::Float) => basic_block
+                .build_unsigned_int_to_float(val, target)
+                .unwrap(),
+            (TypeKind::Pointer(_), TypeKind::Pointer(_)) => val,
+            (TypeKind::Pointer(_), TypeKind::SizedInt(IntKind::USize)) => {
+                basic_block.build_ptr_to_int(val, target).unwrap()
+            }
+            (TypeKind::SizedInt(IntKind::USize), TypeKind::Pointer(_)) => {
+                basic_block.build_int_to_ptr(val, target).unwrap()
+            }
+            (from, to) if from == to => val,
+            (from, to) => unreachable!(
+                "cast from `{}` to `{}` is rejected by the analyzer",
+                from, to
+            ),
+        }
+    }
+
+    /// compile an integer expression, with its integer type
+    fn compile_int(
+        &mut self,
+        expr: popper_ast::Expression,
+    ) -> (MirageValueEnum, popper_ast::IntKind) {
+        let kind = self
+            .type_of(&expr)
+            .and_then(|x| x.int_kind())
+            .unwrap_or(popper_ast::IntKind::I32);
+        self.expected_type = Some(popper_ast::TypeKind::SizedInt(kind));
+        (self.compile_expr(expr).value, kind)
+    }
+
+    /// compile an integer expression as an `int`, whatever its integer type
+    fn compile_as_int(&mut self, expr: popper_ast::Expression) -> MirageValueEnum {
+        let (val, kind) = self.compile_int(expr);
+        self.convert_int(val, kind, popper_ast::IntKind::I32)
+    }
+
+    /// check an index of kind `kind` against the `i64` length `len`, widening the
+    /// index to 64 bits first so that it isn't truncated
+    fn check_index(
+        &mut self,
+        index: MirageValueEnum,
+        kind: popper_ast::IntKind,
+        len: MirageValueEnum,
+        span: popper_ast::Span,
+    ) {
+        let index = self.convert_int(index, kind, popper_ast::IntKind::I64);
+        self.check("popper_check_index", vec![index, len], span);
+    }
+
+    /// symbol of the function called by `call`, in the module being compiled
+    fn symbol(&self, call: &popper_ast::Call) -> String {
+        self.scope
+            .resolve(call.module.as_ref().map(|x| x.name.as_str()), &call.name)
+    }
+
+    fn is_builtin(name: &str) -> bool {
+        matches!(name, "len" | "push" | "pop" | "assert")
+    }
+
+    /// call a function of the runtime library, declaring it on first use
+    fn call_runtime(&mut self, name: &str, args: Vec<MirageValueEnum>) -> MirageValueEnum {
+        if self.externs.insert(name.to_string()) {
+            self.builder
+                .build_extern(name.to_string(), runtime::signature(name));
+        }
+        let basic_block = self.current_basic_block.as_mut().unwrap();
+        basic_block.build_call(name.to_string(), args).unwrap()
+    }
+
+    /// LLVM type of a Popper type. This layout is relied on by the C headers of the
+    /// libraries, it doesn't change:
+    /// - `int` is an `i32`, sized integers have their size, `usize` the size of a pointer
+    /// - `bool` and `char` are bytes, `float` is a 32 bits float
+    /// - `string`, `vec<T>` and `[T]` are pointers to runtime values
+    /// - lists are arrays, tuples and structs are structs with the C layout: the fields in
+    ///   order, with their natural alignment
     pub fn popper_ty_to_mirage_ty(&self, ty: popper_ast::Type) -> Tagged<MirageTypeEnum> {
         Tagged::void(match ty.type_kind {
             popper_ast::TypeKind::Int => MirageTypeEnum::type_int32().into(),
+            popper_ast::TypeKind::SizedInt(kind) => self.int_kind_to_mirage_ty(kind).into(),
+            popper_ast::TypeKind::Char => MirageTypeEnum::type_int8().into(),
             popper_ast::TypeKind::Float => MirageTypeEnum::type_float32().into(),
-            popper_ast::TypeKind::String(length) => {
-                MirageTypeEnum::type_array(MirageTypeEnum::type_int8().into(), length as usize)
-                    .into()
-            }
+            popper_ast::TypeKind::String
+            | popper_ast::TypeKind::Slice(_)
+            | popper_ast::TypeKind::Vec(_) => runtime::ptr_type(),
             popper_ast::TypeKind::Bool => MirageTypeEnum::type_int8().into(),
             popper_ast::TypeKind::List(t, u) => {
                 MirageTypeEnum::type_array(self.popper_ty_to_mirage_ty(*t).value, u).into()
@@ -56,6 +501,13 @@ impl Compiler {
             popper_ast::TypeKind::Pointer(t) => {
                 MirageTypeEnum::type_ptr(self.popper_ty_to_mirage_ty(*t).value).into()
             }
+            popper_ast::TypeKind::Tuple(types) => MirageTypeEnum::type_struct(
+                types
+                    .into_iter()
+                    .map(|t| self.popper_ty_to_mirage_ty(t).value)
+                    .collect(),
+            )
+            .into(),
             popper_ast::TypeKind::Struct(s) => {
                 return self.struct_env.get(&s).unwrap().0.clone().tag(s)
             }
@@ -75,26 +527,47 @@ impl Compiler {
 
     pub fn compile_statement(&mut self, stmt: popper_ast::Statement) {
         match stmt {
+            // `unit` isn't compiled, a `main` returning it returns 0 instead
+            popper_ast::Statement::Function(mut f)
+                if f.name == "main"
+                    && matches!(f.returntype.type_kind, popper_ast::TypeKind::Unit) =>
+            {
+                f.returntype.type_kind = popper_ast::TypeKind::Int;
+                f.body = return_zero(f.body, f.span);
+                self.compile_statement(popper_ast::Statement::Function(f));
+            }
+            // the `main` of the program is called by the one of the executable, which
+            // gives the arguments to the runtime
+            popper_ast::Statement::Function(f)
+                if f.name == "main" && (!f.arguments.args.is_empty() || !self.target.is_wasm()) =>
+            {
+                let mut popper_main = f.clone();
+                popper_main.name = "popper_main".to_string();
+                self.compile_function(&popper_main);
+                self.compile_main(!f.arguments.args.is_empty());
+            }
             popper_ast::Statement::Function(f) => {
                 self.compile_function(&f);
             }
             popper_ast::Statement::Expression(expr) => {
                 self.compile_expr(expr);
             }
-            popper_ast::Statement::Extern(ext) => {
-                for sign in ext.signs {
-                    let args = sign
-                        .arguments
-                        .args
-                        .iter()
-                        .map(|x| self.popper_ty_to_mirage_ty(x.ty.clone()).value)
-                        .collect();
-                    let return_ty = self.popper_ty_to_mirage_ty(sign.return_type.clone()).value;
-                    let fn_ty = FunctionType::new(args, return_ty, sign.is_var_args);
-                    self.builder.build_extern(sign.name.clone(), fn_ty);
+            popper_ast::Statement::Extern(ext) => self.declare_externs(ext.signs),
+            // the library is linked by the driver, see `libraries`
+            popper_ast::Statement::External(external) => {
+                let library = Library::new(&external.file, &self.dir);
+                if !self.libraries.contains(&library) {
+                    self.libraries.push(library);
                 }
+                self.declare_externs(external.signs)
             }
             popper_ast::Statement::Let(l) => {
+                let ty = l
+                    .r#type
+                    .as_ref()
+                    .map(|x| x.type_kind.clone())
+                    .or_else(|| self.type_of(&l.value));
+                self.expect_type(ty);
                 let val = self.compile_expr(l.value);
                 if val.value.is_const() {
                     let basic_block = self.current_basic_block.as_mut().unwrap();
@@ -105,13 +578,80 @@ impl Compiler {
                     self.env.insert(l.name.name.clone(), val);
                 }
             }
+            popper_ast::Statement::LetTuple(l) => {
+                let ty = l
+                    .r#type
+                    .as_ref()
+                    .map(|x| x.type_kind.clone())
+                    .or_else(|| self.type_of(&l.value));
+                self.expect_type(ty);
+                let val = self.compile_expr(l.value).value;
+                for (index, name) in l.names.iter().enumerate() {
+                    let element = self.compile_tuple_access(val.clone(), index);
+                    self.env
+                        .insert(name.name.clone(), element.tag(name.name.clone()));
+                }
+            }
             popper_ast::Statement::Return(r) => {
+                self.expect_type(self.return_type.clone());
                 let val = self.compile_expr(*r.expression.unwrap()).value;
                 let basic_block = self.current_basic_block.as_mut().unwrap();
 
                 basic_block.build_ret(val).unwrap();
+                self.terminated = true;
+            }
+            popper_ast::Statement::Block(block) => self.compile_block(block.statements),
+            popper_ast::Statement::If(i) => {
+                let then = self.new_block("then");
+                let end = self.new_block("end");
+                self.compile_branch(i.condition, &then, &end);
+                self.switch_to(then);
+                self.compile_block(vec![*i.body]);
+                self.branch(&end);
+                self.switch_to(end);
+            }
+            popper_ast::Statement::IfElse(i) => {
+                let then = self.new_block("then");
+                let otherwise = self.new_block("else");
+                self.compile_branch(i.condition, &then, &otherwise);
+                self.switch_to(then);
+                self.compile_block(vec![*i.body]);
+                let then_returned = self.terminated;
+                let end = self.new_block("end");
+                self.branch(&end);
+                self.switch_to(otherwise);
+                self.compile_block(vec![*i.else_body]);
+                // nothing follows an `if` whose branches both return
+                if !(then_returned && self.terminated) {
+                    self.branch(&end);
+                    self.switch_to(end);
+                }
+            }
+            popper_ast::Statement::While(w) => {
+                let condition = self.new_block("while");
+                let body = self.new_block("body");
+                let end = self.new_block("end");
+                self.branch(&condition);
+                self.switch_to(condition.clone());
+                self.compile_branch(w.condition, &body, &end);
+                self.switch_to(body);
+                self.loop_ends.push(end.clone());
+                self.compile_block(vec![*w.body]);
+                self.loop_ends.pop();
+                self.branch(&condition);
+                self.switch_to(end);
+            }
+            popper_ast::Statement::For(f) => self.compile_for(f),
+            popper_ast::Statement::BreakStmt(_) => {
+                let end = self
+                    .loop_ends
+                    .last()
+                    .cloned()
+                    .expect("`break` is checked to be in a loop");
+                self.branch(&end);
             }
             popper_ast::Statement::Assign(a) => {
+                let ty = self.type_of(&a.name);
                 self.is_not_loadable = true;
                 let n = self
                     .compile_expr(a.name)
@@ -119,6 +659,7 @@ impl Compiler {
                     .expect_register_value()
                     .unwrap();
                 self.is_not_loadable = false;
+                self.expect_type(ty);
                 let v = self.compile_expr(a.value).value;
                 let basic_block = self.current_basic_block.as_mut().unwrap();
                 basic_block.build_store(n, MirageObject::from(v)).unwrap();
@@ -131,7 +672,117 @@ impl Compiler {
                 let ty = MirageTypeEnum::type_struct(fields);
                 self.struct_env.insert(s.name.clone(), (ty.into(), s));
             }
-            e => todo!("{:?}", e),
+            popper_ast::Statement::Import(i) => {
+                // a module is compiled once, however many times it's imported.
+                // Qualified calls use the name the function is defined with
+                let key = i
+                    .file
+                    .as_ref()
+                    .map(|x| x.display().to_string())
+                    .unwrap_or_else(|| i.path.to_string());
+                if !self.modules.insert(key.clone()) {
+                    return;
+                }
+                let dir = self.dir.clone();
+                if let Some(parent) = i.file.as_ref().and_then(|x| x.parent()) {
+                    self.dir = parent.to_path_buf();
+                }
+                // the checks of the module are located in its own file
+                let source = i.source.clone().unwrap_or_default();
+                let source = std::mem::replace(&mut self.source, source);
+                let filename = std::mem::replace(&mut self.filename, key);
+                let module_file = std::mem::replace(&mut self.module_file, i.file.clone());
+                let prefix = symbols::import_prefix(&i);
+                if self.link_modules {
+                    self.declare_module(&prefix, i.module_stmts);
+                } else {
+                    let scope = Scope::new(&prefix, &i.module_stmts);
+                    let scope = std::mem::replace(&mut self.scope, scope);
+                    for stmt in i.module_stmts {
+                        self.compile_statement(stmt);
+                    }
+                    self.scope = scope;
+                }
+                self.module_file = module_file;
+                self.filename = filename;
+                self.source = source;
+                self.dir = dir;
+            }
+            // tests are only compiled by `popper test`, each one in its own program
+            popper_ast::Statement::Test(_) => {}
+            popper_ast::Statement::ModuleDoc(_) => {}
+        }
+    }
+
+    /// declare the functions of an `extern` or `external` block, defined in C
+    fn declare_externs(&mut self, signs: Vec<popper_ast::FunctionSign>) {
+        for sign in signs {
+            // declared by several modules
+            if self.externs.insert(sign.name.clone()) {
+                self.declare_function(
+                    &sign.name,
+                    &sign.arguments,
+                    &sign.return_type,
+                    sign.is_var_args,
+                );
+            }
+        }
+    }
+
+    /// declare a function defined in another object file
+    fn declare_function(
+        &mut self,
+        name: &str,
+        arguments: &popper_ast::Arguments,
+        return_type: &popper_ast::Type,
+        is_var_args: bool,
+    ) {
+        let args = arguments
+            .args
+            .iter()
+            .map(|x| self.popper_ty_to_mirage_ty(x.ty.clone()).value)
+            .collect();
+        let return_ty = self.popper_ty_to_mirage_ty(return_type.clone()).value;
+        self.fn_types.insert(
+            name.to_string(),
+            (
+                arguments
+                    .args
+                    .iter()
+                    .map(|x| x.ty.type_kind.clone())
+                    .collect(),
+                return_type.type_kind.clone(),
+            ),
+        );
+        let fn_ty = FunctionType::new(args, return_ty, is_var_args);
+        self.builder.build_extern(name.to_string(), fn_ty);
+    }
+
+    /// declare what a module compiled separately exports, to link with its object file.
+    /// `prefix` is the one of the symbols of the module
+    fn declare_module(&mut self, prefix: &str, stmts: Vec<popper_ast::Statement>) {
+        for stmt in stmts {
+            match stmt {
+                popper_ast::Statement::Function(f) if f.is_pub => {
+                    let symbol = symbols::function_symbol(prefix, &f);
+                    if !self.fn_types.contains_key(&symbol) {
+                        self.declare_function(&symbol, &f.arguments, &f.returntype, f.is_var_args);
+                    }
+                }
+                popper_ast::Statement::Extern(_)
+                | popper_ast::Statement::External(_)
+                | popper_ast::Statement::Struct(_) => self.compile_statement(stmt),
+                // struct types of the module can come from its own imports
+                popper_ast::Statement::Import(i) => {
+                    let dir = self.dir.clone();
+                    if let Some(parent) = i.file.as_ref().and_then(|x| x.parent()) {
+                        self.dir = parent.to_path_buf();
+                    }
+                    self.declare_module(&symbols::import_prefix(&i), i.module_stmts);
+                    self.dir = dir;
+                }
+                _ => {}
+            }
         }
     }
 
@@ -149,7 +800,21 @@ impl Compiler {
 
         let fn_ty = FunctionType::new(args, return_ty, f.is_var_args);
 
-        let mut fn_value = fn_ty.fn_value(f.name.clone());
+        let symbol = self.scope.resolve(None, &f.name);
+        let fn_value = fn_ty.fn_value(symbol.clone());
+
+        self.fn_types.insert(
+            symbol,
+            (
+                f.arguments
+                    .args
+                    .iter()
+                    .map(|x| x.ty.type_kind.clone())
+                    .collect(),
+                f.returntype.type_kind.clone(),
+            ),
+        );
+        self.return_type = Some(f.returntype.type_kind.clone());
 
         for ((avalue, aname), tag) in fn_value
             .get_args()
@@ -160,14 +825,14 @@ impl Compiler {
             self.env.insert(aname.name.clone(), avalue.clone().tag(tag));
         }
 
-        self.current_function = Some(fn_value.clone());
+        self.current_function = Some(fn_value);
 
         self.current_basic_block = Some(self.builder.new_basic_block("entry"));
+        self.terminated = false;
 
-        for stmt in f.body.clone() {
-            self.compile_statement(stmt);
-        }
+        self.compile_block(f.body.clone());
 
+        let mut fn_value = self.current_function.take().unwrap();
         self.builder.join_function(
             &mut fn_value,
             self.current_basic_block.as_ref().unwrap().clone(),
@@ -176,21 +841,219 @@ impl Compiler {
         self.builder.build_function(fn_value.clone());
     }
 
+    /// statements in their own scope, up to the one returning or breaking
+    fn compile_block(&mut self, stmts: Vec<popper_ast::Statement>) {
+        let env = self.env.clone();
+        for stmt in stmts {
+            if self.terminated {
+                break;
+            }
+            self.compile_statement(stmt);
+        }
+        self.env = env;
+    }
+
+    /// `for it in seq { body }`: a counter going through the elements of `seq`
+    fn compile_for(&mut self, f: popper_ast::ForStmt) {
+        let elem = self
+            .type_of(&f.expr)
+            .and_then(|x| x.element_type().cloned())
+            .expect("`for` is checked to go through a list, a slice or a vec");
+        let seq = self.compile_as_slice(f.expr);
+        let len = self.call_runtime("popper_slice_len", vec![seq.clone()]);
+        let zero = self.const_int(popper_ast::IntKind::I32, 0);
+        let basic_block = self.current_basic_block.as_mut().unwrap();
+        let counter = basic_block
+            .build_const(zero)
+            .unwrap()
+            .expect_register_value()
+            .unwrap();
+
+        let condition = self.new_block("for");
+        let body = self.new_block("body");
+        let end = self.new_block("end");
+        self.branch(&condition);
+        self.switch_to(condition.clone());
+        let basic_block = self.current_basic_block.as_mut().unwrap();
+        let more = basic_block
+            .build_int_cmp(
+                IntPredicate::SLT,
+                MirageValueEnum::from(counter.clone())
+                    .expect_int_value()
+                    .unwrap(),
+                len.expect_int_value().unwrap(),
+            )
+            .unwrap();
+        basic_block.build_cond_br(more, &body, &end).unwrap();
+        self.terminated = true;
+
+        self.switch_to(body);
+        let env = self.env.clone();
+        let ptr = self.call_runtime("popper_slice_at", vec![seq, counter.clone().into()]);
+        // struct elements are tagged with their struct, for the field accesses
+        let tag = self.popper_ty_to_mirage_ty(elem.clone()).tag;
+        let it = self.load_unless_assigned(elem, ptr);
+        self.env.insert(f.it.name, it.tag(tag));
+        self.loop_ends.push(end.clone());
+        self.compile_block(f.body.statements);
+        self.loop_ends.pop();
+        self.env = env;
+
+        if !self.terminated {
+            let one = self.const_int(popper_ast::IntKind::I32, 1);
+            let basic_block = self.current_basic_block.as_mut().unwrap();
+            let next = basic_block
+                .build_int_add(
+                    MirageValueEnum::from(counter.clone())
+                        .expect_int_value()
+                        .unwrap(),
+                    one.expect_int_value().unwrap(),
+                )
+                .unwrap();
+            basic_block
+                .build_store(counter, MirageObject::from(next))
+                .unwrap();
+        }
+        self.branch(&condition);
+        self.switch_to(end);
+    }
+
+    /// basic block of the current function, named after what it compiles
+    fn new_block(&mut self, name: &str) -> BasicBlock {
+        self.blocks += 1;
+        self.builder
+            .new_basic_block(&format!("{}{}", name, self.blocks))
+    }
+
+    /// end the current basic block, and continue the function in `block`
+    fn switch_to(&mut self, block: BasicBlock) {
+        let done = self.current_basic_block.replace(block).unwrap();
+        let function = self.current_function.as_mut().unwrap();
+        self.builder.join_function(function, done);
+        self.terminated = false;
+    }
+
+    /// jump to `block`, unless the current block already returned or broke out
+    fn branch(&mut self, block: &BasicBlock) {
+        if !self.terminated {
+            let basic_block = self.current_basic_block.as_mut().unwrap();
+            basic_block.build_br(block).unwrap();
+            self.terminated = true;
+        }
+    }
+
+    /// jump to `then` when `condition` holds, to `otherwise` when it doesn't
+    fn compile_branch(
+        &mut self,
+        condition: popper_ast::Expression,
+        then: &BasicBlock,
+        otherwise: &BasicBlock,
+    ) {
+        self.expect_type(Some(popper_ast::TypeKind::Bool));
+        let condition = self.compile_expr(condition).value;
+        let zero = self.const_int(popper_ast::IntKind::U8, 0);
+        // booleans are bytes, a branch takes an `i1`
+        let basic_block = self.current_basic_block.as_mut().unwrap();
+        let holds = basic_block
+            .build_int_cmp(
+                IntPredicate::NE,
+                condition.expect_int_value().unwrap(),
+                zero.expect_int_value().unwrap(),
+            )
+            .unwrap();
+        basic_block.build_cond_br(holds, then, otherwise).unwrap();
+        self.terminated = true;
+    }
+
+    /// `main(argc, argv)` of the executable: it initializes the runtime with the
+    /// arguments of the program, then calls `popper_main`, with them if `with_args`.
+    /// WebAssembly modules are run without arguments
+    fn compile_main(&mut self, with_args: bool) {
+        let int: MirageTypeEnum = MirageTypeEnum::type_int32().into();
+        let wasm = self.target.is_wasm();
+        let params = if wasm {
+            vec![]
+        } else {
+            vec![int.clone(), runtime::ptr_type()]
+        };
+        let fn_ty = FunctionType::new(params, int, false);
+        let mut fn_value = fn_ty.fn_value("main".to_string());
+
+        self.current_function = Some(fn_value.clone());
+        self.current_basic_block = Some(self.builder.new_basic_block("entry"));
+
+        if !wasm {
+            self.call_runtime("popper_std_init", fn_value.get_args());
+        }
+        let args = if with_args {
+            vec![self.call_runtime("popper_std_args", vec![])]
+        } else {
+            vec![]
+        };
+        let basic_block = self.current_basic_block.as_mut().unwrap();
+        let code = basic_block
+            .build_call("popper_main".to_string(), args)
+            .unwrap();
+        basic_block.build_ret(code).unwrap();
+
+        self.builder.join_function(
+            &mut fn_value,
+            self.current_basic_block.as_ref().unwrap().clone(),
+        );
+        self.builder.build_function(fn_value);
+    }
+
     fn compile_expr(&mut self, expr: popper_ast::Expression) -> Tagged<MirageValueEnum> {
+        let expected_type = self.expected_type.take();
+        let expected_int = expected_type.as_ref().and_then(|x| x.int_kind());
         Tagged::void(match expr {
+            popper_ast::Expression::Call(call)
+                if Self::is_builtin(&call.name)
+                    && !self.fn_types.contains_key(&self.symbol(&call)) =>
+            {
+                self.compile_builtin(call)
+            }
             popper_ast::Expression::Call(call) => {
+                let symbol = self.symbol(&call);
+                let is_extern = self.externs.contains(&symbol);
+                let params = self
+                    .fn_types
+                    .get(&symbol)
+                    .map(|x| x.0.clone())
+                    .unwrap_or_default();
                 let args: Vec<_> = call
                     .arguments
                     .iter()
-                    .map(|x| self.compile_expr(x.clone()).value)
+                    .enumerate()
+                    .map(|(i, x)| {
+                        let is_string = self.type_of(x) == Some(popper_ast::TypeKind::String);
+                        self.expect_type(params.get(i).cloned());
+                        let val = self.compile_expr(x.clone()).value;
+                        // C functions take NUL terminated strings
+                        if is_extern && is_string {
+                            self.call_runtime("popper_str_cstr", vec![val])
+                        } else {
+                            val
+                        }
+                    })
                     .collect();
                 let basic_block = self.current_basic_block.as_mut().unwrap();
-                basic_block.build_call(call.name, args).unwrap()
+                basic_block.build_call(symbol, args).unwrap()
             }
             popper_ast::Expression::Constant(constant) => match constant {
                 popper_ast::Constant::Int(i) => {
-                    let ty = MirageTypeEnum::type_int32();
-                    ty.const_value(i.value as i32).to_value_enum()
+                    let kind =
+                        expected_int.unwrap_or(if popper_ast::IntKind::I32.fits(i.value as i128) {
+                            popper_ast::IntKind::I32
+                        } else {
+                            popper_ast::IntKind::I64
+                        });
+                    self.const_int(kind, i.value)
+                }
+
+                popper_ast::Constant::Char(c) => {
+                    let ty = MirageTypeEnum::type_int8();
+                    ty.const_value(c.value as u8 as i8).to_value_enum()
                 }
 
                 popper_ast::Constant::Float(f) => {
@@ -200,36 +1063,66 @@ impl Compiler {
 
                 popper_ast::Constant::Null(_n) => todo!(),
                 popper_ast::Constant::List(l) => {
+                    let elem = expected_type
+                        .as_ref()
+                        .and_then(|x| x.element_type())
+                        .cloned();
                     let mut values = Vec::new();
                     for v in l.value.iter() {
+                        self.expect_type(elem.clone().map(|x| x.type_kind));
                         values.push(self.compile_expr(v.clone()).value);
                     }
-                    if values.is_empty() {
-                        panic!("A list can't be 0")
+
+                    match (expected_type, elem) {
+                        (Some(popper_ast::TypeKind::Vec(_)), Some(elem)) => {
+                            let size = self.size_of(&elem.type_kind);
+                            let vec = self.call_runtime("popper_vec_new", vec![size]);
+                            for value in values {
+                                let basic_block = self.current_basic_block.as_mut().unwrap();
+                                let value = basic_block.build_ref(value).unwrap();
+                                self.call_runtime("popper_vec_push", vec![vec.clone(), value]);
+                            }
+                            vec
+                        }
+                        (_, elem) => {
+                            let ty = match values.first() {
+                                Some(value) => value.get_type(),
+                                None => self.popper_ty_to_mirage_ty(elem.unwrap()).value,
+                            };
+                            let ty = MirageTypeEnum::type_array(ty, values.len());
+                            ty.const_value(values).to_mirage_value()
+                        }
                     }
+                }
 
-                    let ty = values.first().unwrap().get_type();
-                    let ty = MirageTypeEnum::type_array(ty, values.len());
-                    ty.const_value(values).to_mirage_value()
+                popper_ast::Constant::Tuple(t) => {
+                    let types = match &expected_type {
+                        Some(popper_ast::TypeKind::Tuple(types)) => types.clone(),
+                        _ => vec![],
+                    };
+                    let mut values = Vec::new();
+                    for (index, v) in t.value.iter().enumerate() {
+                        self.expect_type(types.get(index).map(|x| x.type_kind.clone()));
+                        values.push(self.compile_expr(v.clone()).value);
+                    }
+                    let ty =
+                        MirageTypeEnum::type_struct(values.iter().map(|x| x.get_type()).collect());
+                    let val = MirageValueEnum::Struct(StructValue::new(ty, values));
+                    let basic_block = self.current_basic_block.as_mut().unwrap();
+                    let mut reg = basic_block
+                        .build_const(val)
+                        .unwrap()
+                        .expect_register_value()
+                        .unwrap();
+
+                    reg.add_flag(Flag::not_loadable());
+                    reg.into()
                 }
 
                 popper_ast::Constant::StringLiteral(s) => {
-                    let ty =
-                        MirageTypeEnum::type_array(MirageTypeEnum::type_int8().into(), s.len());
-                    let value = ty
-                        .const_value(
-                            s.value
-                                .as_bytes()
-                                .to_vec()
-                                .iter()
-                                .map(|x| {
-                                    let ty = MirageTypeEnum::type_int8();
-                                    ty.const_value(*x as i8).to_value_enum()
-                                })
-                                .collect(),
-                        )
-                        .to_mirage_value();
-                    self.builder.build_global(MirageObject::from(value))
+                    let data = self.build_c_string(&s.value);
+                    let len = self.const_int(popper_ast::IntKind::I32, s.len() as i64);
+                    self.call_runtime("popper_str_new", vec![data, len])
                 }
 
                 popper_ast::Constant::Ident(id) => return self.env.get(&id.name).unwrap().clone(),
@@ -245,9 +1138,51 @@ impl Compiler {
                 let basic_block = self.current_basic_block.as_mut().unwrap();
                 return basic_block.build_ref(val.value).unwrap().tag(val.tag);
             }
+            popper_ast::Expression::BinOp(bin_op)
+                if self.type_of(&bin_op.lhs) == Some(popper_ast::TypeKind::String) =>
+            {
+                let l = self.compile_expr(*bin_op.lhs).value;
+                let r = self.compile_expr(*bin_op.rhs).value;
+                let name = match bin_op.op {
+                    popper_ast::BinOpKind::Add => "popper_str_concat",
+                    popper_ast::BinOpKind::Eq => "popper_str_eq",
+                    popper_ast::BinOpKind::Neq => "popper_str_ne",
+                    popper_ast::BinOpKind::Lt => "popper_str_lt",
+                    popper_ast::BinOpKind::Lte => "popper_str_le",
+                    popper_ast::BinOpKind::Gt => "popper_str_gt",
+                    popper_ast::BinOpKind::Gte => "popper_str_ge",
+                    e => todo!("{:?}", e),
+                };
+                self.call_runtime(name, vec![l, r])
+            }
             popper_ast::Expression::BinOp(bin_op) => {
+                // integer literals take the type of the other operand, or of the context
+                // when it isn't a comparison, giving a `bool`
+                let operand_kind = [&bin_op.lhs, &bin_op.rhs]
+                    .into_iter()
+                    .filter(|x| !Self::is_int_literal(x))
+                    .find_map(|x| self.type_of(x))
+                    .filter(|x| Self::int_repr(x).is_some())
+                    .or(expected_type.filter(|_| !bin_op.op.is_comparison()));
+                self.expect_type(operand_kind.clone());
                 let l = self.compile_expr(*bin_op.lhs).value;
+                self.expect_type(operand_kind.clone());
                 let r = self.compile_expr(*bin_op.rhs).value;
+
+                let kind = operand_kind
+                    .as_ref()
+                    .and_then(Self::int_repr)
+                    .unwrap_or(popper_ast::IntKind::I32);
+                if bin_op.op.is_comparison() {
+                    return Tagged::void(self.compile_comparison(bin_op.op, kind, l, r));
+                }
+                if matches!(
+                    bin_op.op,
+                    popper_ast::BinOpKind::Div | popper_ast::BinOpKind::Mod
+                ) {
+                    let divisor = self.convert_int(r.clone(), kind, popper_ast::IntKind::I64);
+                    self.check("popper_check_div", vec![divisor], bin_op.span);
+                }
                 let basic_block = self.current_basic_block.as_mut().unwrap();
 
                 match bin_op.op {
@@ -257,11 +1192,21 @@ impl Compiler {
                     popper_ast::BinOpKind::Sub => basic_block
                         .build_int_sub(l.expect_int_value().unwrap(), r.expect_int_value().unwrap())
                         .unwrap(),
+                    popper_ast::BinOpKind::Mul => basic_block
+                        .build_int_mul(l.expect_int_value().unwrap(), r.expect_int_value().unwrap())
+                        .unwrap(),
+                    popper_ast::BinOpKind::Div => basic_block
+                        .build_int_div(l.expect_int_value().unwrap(), r.expect_int_value().unwrap())
+                        .unwrap(),
+                    popper_ast::BinOpKind::Mod => basic_block
+                        .build_int_rem(l.expect_int_value().unwrap(), r.expect_int_value().unwrap())
+                        .unwrap(),
                     e => todo!("{:?}", e),
                 }
             }
             popper_ast::Expression::Deref(d) => {
                 let val = self.compile_expr(*d.expr);
+                self.check("popper_check_null", vec![val.value.clone()], d.span);
                 if self.is_not_loadable {
                     return val;
                 }
@@ -329,11 +1274,245 @@ impl Compiler {
                 }
                 .into()
             }
+            popper_ast::Expression::Cast(c) => {
+                let from = self.type_of(&c.expr).expect("type of the value cast");
+                let to = c.ty.type_kind.clone();
+                self.expect_type(Some(from.clone()));
+                let val = self.compile_expr(*c.expr).value;
+                self.compile_cast(val, from, to)
+            }
+            popper_ast::Expression::UnaryOp(u) => match (u.op, u.span(), *u.expr) {
+                // a negated integer literal is a literal of its own: `-128` is an `i8`
+                (
+                    popper_ast::UnaryOpKind::Neg,
+                    span,
+                    popper_ast::Expression::Constant(popper_ast::Constant::Int(i)),
+                ) => {
+                    self.expected_type = expected_type;
+                    let literal = popper_ast::Int::new(span, -i.value);
+                    return self.compile_expr(popper_ast::Expression::Constant(
+                        popper_ast::Constant::Int(literal),
+                    ));
+                }
+                (op, _, expr) => {
+                    let ty = self.type_of(&expr);
+                    self.expect_type(ty.clone());
+                    let val = self.compile_expr(expr).value;
+                    let kind = ty.as_ref().and_then(Self::int_repr);
+                    if let (popper_ast::UnaryOpKind::Neg, None) = (op, kind) {
+                        let basic_block = self.current_basic_block.as_mut().unwrap();
+                        return Tagged::void(basic_block.build_float_neg(val).unwrap());
+                    }
+                    // `-x` is `0 - x` and, booleans being 0 or 1, `!x` is `1 - x`
+                    let lhs = match op {
+                        popper_ast::UnaryOpKind::Neg => 0,
+                        popper_ast::UnaryOpKind::Not => 1,
+                    };
+                    let lhs = self.const_int(kind.unwrap_or(popper_ast::IntKind::U8), lhs);
+                    let basic_block = self.current_basic_block.as_mut().unwrap();
+                    basic_block
+                        .build_int_sub(
+                            lhs.expect_int_value().unwrap(),
+                            val.expect_int_value().unwrap(),
+                        )
+                        .unwrap()
+                }
+            },
+            popper_ast::Expression::Slice(s) => {
+                let (value, len, sub) =
+                    if self.type_of(&s.value) == Some(popper_ast::TypeKind::String) {
+                        let value = self.compile_expr(*s.value).value;
+                        (value, "popper_str_len", "popper_str_slice")
+                    } else {
+                        let value = self.compile_as_slice(*s.value);
+                        (value, "popper_slice_len", "popper_slice_sub")
+                    };
+                let start = match s.start {
+                    Some(start) => self.compile_as_int(*start),
+                    None => self.const_int(popper_ast::IntKind::I32, 0),
+                };
+                let end = match s.end {
+                    Some(end) => self.compile_as_int(*end),
+                    None => self.call_runtime(len, vec![value.clone()]),
+                };
+                self.call_runtime(sub, vec![value, start, end])
+            }
+            popper_ast::Expression::Index(i) => {
+                let ty = self
+                    .type_of(&i.value)
+                    .expect("indexing is checked to be on a list, a slice or a vec");
+                let elem = ty.element_type().unwrap().clone();
+                let is_not_loadable = std::mem::take(&mut self.is_not_loadable);
+                let (index, kind) = self.compile_int(*i.index);
+                self.is_not_loadable = is_not_loadable;
+
+                match ty {
+                    popper_ast::TypeKind::List(_, n) => {
+                        if self.checks {
+                            let len = self.const_int(popper_ast::IntKind::I64, n as i64);
+                            self.check_index(index.clone(), kind, len, i.span);
+                        }
+                        let index = self.convert_int(index, kind, popper_ast::IntKind::I32);
+                        let array = self.compile_expr(*i.value).value;
+                        let elem_ty = self.popper_ty_to_mirage_ty(elem).value;
+                        let array_ty = MirageTypeEnum::type_array(elem_ty.clone(), n);
+                        let zero = self.const_int(popper_ast::IntKind::I32, 0);
+                        let basic_block = self.current_basic_block.as_mut().unwrap();
+                        let mut memory = basic_block
+                            .build_getelementptr(elem_ty, array_ty.into(), array, vec![zero, index])
+                            .unwrap()
+                            .expect_register_value()
+                            .unwrap();
+
+                        if self.is_not_loadable {
+                            memory.add_flag(Flag::not_loadable());
+                        }
+                        memory.into()
+                    }
+                    _ => {
+                        let seq = self.compile_expr(*i.value).value;
+                        if self.checks {
+                            let len = self.call_runtime("popper_slice_len", vec![seq.clone()]);
+                            let len = self.convert_int(
+                                len,
+                                popper_ast::IntKind::I32,
+                                popper_ast::IntKind::I64,
+                            );
+                            self.check_index(index.clone(), kind, len, i.span);
+                        }
+                        let index = self.convert_int(index, kind, popper_ast::IntKind::I32);
+                        let ptr = self.call_runtime("popper_slice_at", vec![seq, index]);
+                        self.load_unless_assigned(elem, ptr)
+                    }
+                }
+            }
+            popper_ast::Expression::TupleAccess(t) => {
+                let tuple = self.compile_expr(*t.value).value;
+                self.compile_tuple_access(tuple, t.index)
+            }
 
             _ => todo!(),
         })
     }
 
+    /// load the value behind a pointer returned by the runtime, except for an assignment target
+    fn load_unless_assigned(
+        &mut self,
+        ty: popper_ast::Type,
+        ptr: MirageValueEnum,
+    ) -> MirageValueEnum {
+        if self.is_not_loadable {
+            return ptr;
+        }
+        let ty = self.popper_ty_to_mirage_ty(ty).value;
+        let basic_block = self.current_basic_block.as_mut().unwrap();
+        basic_block.build_load(ty, ptr).unwrap()
+    }
+
+    /// compare two integers of kind `kind`, giving a `bool`
+    fn compile_comparison(
+        &mut self,
+        op: popper_ast::BinOpKind,
+        kind: popper_ast::IntKind,
+        l: MirageValueEnum,
+        r: MirageValueEnum,
+    ) -> MirageValueEnum {
+        use popper_ast::BinOpKind;
+
+        let predicate = match (op, kind.is_signed()) {
+            (BinOpKind::Eq, _) => IntPredicate::EQ,
+            (BinOpKind::Neq, _) => IntPredicate::NE,
+            (BinOpKind::Lt, true) => IntPredicate::SLT,
+            (BinOpKind::Lt, false) => IntPredicate::ULT,
+            (BinOpKind::Lte, true) => IntPredicate::SLE,
+            (BinOpKind::Lte, false) => IntPredicate::ULE,
+            (BinOpKind::Gt, true) => IntPredicate::SGT,
+            (BinOpKind::Gt, false) => IntPredicate::UGT,
+            (BinOpKind::Gte, true) => IntPredicate::SGE,
+            (BinOpKind::Gte, false) => IntPredicate::UGE,
+            (op, _) => unreachable!("{:?} is not a comparison", op),
+        };
+        let basic_block = self.current_basic_block.as_mut().unwrap();
+        let holds = basic_block
+            .build_int_cmp(
+                predicate,
+                l.expect_int_value().unwrap(),
+                r.expect_int_value().unwrap(),
+            )
+            .unwrap();
+        // an `i1`, widened to the byte of a `bool`
+        basic_block
+            .build_int_zext(holds, MirageTypeEnum::type_int8().into())
+            .unwrap()
+    }
+
+    /// builtin functions: `len(x)`, `push(v, x)`, `pop(v)` and `assert(cond, msg)`
+    fn compile_builtin(&mut self, call: popper_ast::Call) -> MirageValueEnum {
+        let mut arguments = call.arguments.into_iter();
+        let value = arguments.next().unwrap();
+        if call.name == "assert" {
+            let cond = self.compile_expr(value).value;
+            let msg = self.compile_expr(arguments.next().unwrap()).value;
+            let mut args = vec![cond, msg];
+            args.extend(self.location(call.span));
+            return self.call_runtime("popper_assert", args);
+        }
+        let ty = self
+            .type_of(&value)
+            .expect("the argument of a builtin is checked by the analyzer");
+
+        match (call.name.as_str(), ty) {
+            ("len", popper_ast::TypeKind::String) => {
+                let value = self.compile_expr(value).value;
+                self.call_runtime("popper_str_len", vec![value])
+            }
+            ("len", popper_ast::TypeKind::List(_, n)) => {
+                self.const_int(popper_ast::IntKind::I32, n as i64)
+            }
+            ("len", _) => {
+                let value = self.compile_expr(value).value;
+                self.call_runtime("popper_slice_len", vec![value])
+            }
+            ("push", ty) => {
+                let vec = self.compile_expr(value).value;
+                self.expect_type(ty.element_type().map(|x| x.type_kind.clone()));
+                let elem = self.compile_expr(arguments.next().unwrap()).value;
+                let basic_block = self.current_basic_block.as_mut().unwrap();
+                let elem = basic_block.build_ref(elem).unwrap();
+                self.call_runtime("popper_vec_push", vec![vec, elem])
+            }
+            ("pop", ty) => {
+                let vec = self.compile_expr(value).value;
+                let ptr = self.call_runtime("popper_vec_pop", vec![vec]);
+                let is_not_loadable = std::mem::take(&mut self.is_not_loadable);
+                let elem = self.load_unless_assigned(ty.element_type().unwrap().clone(), ptr);
+                self.is_not_loadable = is_not_loadable;
+                elem
+            }
+            (name, _) => unreachable!("{} is not a builtin", name),
+        }
+    }
+
+    fn compile_tuple_access(&mut self, tuple: MirageValueEnum, index: usize) -> MirageValueEnum {
+        let tuple_ty = tuple.get_type().expect_struct_type();
+        let field_ty = tuple_ty.fields[index].clone();
+        let zero = MirageTypeEnum::type_int32().const_value(0).to_value_enum();
+        let index = MirageTypeEnum::type_int32()
+            .const_value(index as i32)
+            .to_value_enum();
+        let basic_block = self.current_basic_block.as_mut().unwrap();
+        let mut memory = basic_block
+            .build_getelementptr(field_ty, tuple_ty.into(), tuple, vec![zero, index])
+            .unwrap()
+            .expect_register_value()
+            .unwrap();
+
+        if self.is_not_loadable {
+            memory.add_flag(Flag::not_loadable());
+        }
+        memory.into()
+    }
+
     pub fn print_to_string(&self) -> String {
         self.builder
             .asts
@@ -352,3 +1531,53 @@ impl Compiler {
         output::Output::new(self.clone(), compiler)
     }
 }
+
+/// `stmts` returning 0 where they return nothing, and at their end
+fn return_zero(
+    stmts: Vec<popper_ast::Statement>,
+    span: popper_ast::Span,
+) -> Vec<popper_ast::Statement> {
+    use popper_ast::Statement;
+
+    fn zero(span: popper_ast::Span) -> popper_ast::Expression {
+        popper_ast::Expression::Constant(popper_ast::Constant::Int(popper_ast::Int::new(span, 0)))
+    }
+    fn visit(stmt: Statement) -> Statement {
+        let visit_box = |stmt: Box<Statement>| Box::new(visit(*stmt));
+        match stmt {
+            Statement::Return(mut r) if r.expression.is_none() => {
+                r.expression = Some(Box::new(zero(r.span)));
+                Statement::Return(r)
+            }
+            Statement::Block(mut b) => {
+                b.statements = b.statements.into_iter().map(visit).collect();
+                Statement::Block(b)
+            }
+            Statement::If(mut i) => {
+                i.body = visit_box(i.body);
+                Statement::If(i)
+            }
+            Statement::IfElse(mut i) => {
+                i.body = visit_box(i.body);
+                i.else_body = visit_box(i.else_body);
+                Statement::IfElse(i)
+            }
+            Statement::While(mut w) => {
+                w.body = visit_box(w.body);
+                Statement::While(w)
+            }
+            Statement::For(mut f) => {
+                f.body.statements = f.body.statements.into_iter().map(visit).collect();
+                Statement::For(f)
+            }
+            stmt => stmt,
+        }
+    }
+
+    let mut stmts: Vec<Statement> = stmts.into_iter().map(visit).collect();
+    stmts.push(Statement::Return(popper_ast::Return::new(
+        Some(zero(span)),
+        span,
+    )));
+    stmts
+}
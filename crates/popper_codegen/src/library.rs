@@ -0,0 +1,83 @@
+use std::path::{Path, PathBuf};
+
+/// extensions of the files of libraries, the other names are searched by the linker
+const LIBRARY_EXTENSIONS: &[&str] = &["so", "dylib", "dll", "a", "lib"];
+
+/// library named by an `external "..." { ... };` block
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Library {
+    /// `external "m"` or `external "libm"`: `-lm`, searched by the linker
+    Name(String),
+    /// `external "vendor/libfoo.so"`: a file, relative to the module declaring it
+    Path(PathBuf),
+}
+
+impl Library {
+    /// library of `external "<file>"`, in a module of the directory `dir`
+    pub fn new(file: &str, dir: &Path) -> Self {
+        let path = Path::new(file);
+        let has_extension = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .is_some_and(|x| LIBRARY_EXTENSIONS.contains(&x));
+        if has_extension || file.contains('/') || file.contains('\\') {
+            return Library::Path(dir.join(path));
+        }
+        Library::Name(file.strip_prefix("lib").unwrap_or(file).to_string())
+    }
+
+    /// if the library is loaded when the program starts, and not copied in it
+    pub fn is_shared(&self) -> bool {
+        match self {
+            Library::Name(_) => true,
+            Library::Path(path) => {
+                !matches!(path.extension().and_then(|x| x.to_str()), Some("a" | "lib"))
+            }
+        }
+    }
+
+    /// load the library in the current process, for code compiled in memory: its
+    /// symbols are then resolved like the ones of the process.
+    /// The library stays loaded until the process exits
+    pub fn load(&self) -> Result<(), String> {
+        let file = match self {
+            Library::Name(name) => PathBuf::from(libloading::library_filename(name)),
+            Library::Path(path) => path.clone(),
+        };
+        if !self.is_shared() {
+            return Err(format!(
+                "`{}` is a static library, it can only be linked",
+                file.display()
+            ));
+        }
+
+        let mut library = open(&file);
+        // `libc.so` and the like are linker scripts, the library is the versioned one
+        if let (Library::Name(_), Err(_)) = (self, &library) {
+            for version in (0..10).rev() {
+                let versioned = PathBuf::from(format!("{}.{}", file.display(), version));
+                if let Ok(versioned) = open(&versioned) {
+                    library = Ok(versioned);
+                    break;
+                }
+            }
+        }
+        let library =
+            library.map_err(|err| format!("Unable to load `{}`: {}", file.display(), err))?;
+        std::mem::forget(library);
+        Ok(())
+    }
+}
+
+/// open a library, making its symbols visible to the libraries loaded after it
+fn open(file: &Path) -> Result<libloading::Library, libloading::Error> {
+    #[cfg(unix)]
+    unsafe {
+        use libloading::os::unix::{Library, RTLD_GLOBAL, RTLD_NOW};
+        Library::open(Some(file), RTLD_NOW | RTLD_GLOBAL).map(libloading::Library::from)
+    }
+    #[cfg(not(unix))]
+    unsafe {
+        libloading::Library::new(file)
+    }
+}
@@ -1,6 +1,8 @@
+use crate::library::Library;
 use crate::Compiler;
 use mirage::backend::codegen_llvm::Compiler as LLVMCompiler;
 use mirage::backend::output::*;
+use std::collections::HashSet;
 
 #[derive(Debug, Clone)]
 pub struct Output {
@@ -25,8 +27,15 @@ impl Output {
         std::fs::write(path, content)
     }
 
+    /// LLVM IR, declaring the target when it's not the host. WebAssembly modules
+    /// import the `extern` functions from the import module of the target
     pub fn print_llvm_to_string(&self) -> String {
-        self.llvm_compiler.print_to_string()
+        let target = &self.compiler.target;
+        let mut llvm = self.llvm_compiler.print_to_string();
+        if target.is_wasm() {
+            llvm = wasm_imports(&llvm, &self.compiler.externs, &target.import_module);
+        }
+        target.llvm_header() + &llvm
     }
 
     pub fn write_llvm_to_file(&self, path: &str) -> std::io::Result<()> {
@@ -34,6 +43,17 @@ impl Output {
         std::fs::write(path, content)
     }
 
+    /// libraries of the `external` blocks, to link with the object file
+    pub fn libraries(&self) -> &[Library] {
+        &self.compiler.libraries
+    }
+
+    /// load the libraries of the `external` blocks in the current process, so the
+    /// `execution_engine` resolves their functions
+    pub fn load_libraries(&self) -> Result<(), String> {
+        self.compiler.libraries.iter().try_for_each(Library::load)
+    }
+
     pub fn object_file(&mut self) -> ObjectOutput<'_> {
         self.llvm_compiler.object()
     }
@@ -42,3 +62,24 @@ impl Output {
         self.llvm_compiler.execution_engine()
     }
 }
+
+/// give the declarations of `externs` the attributes naming their wasm import.
+/// The runtime functions, prefixed with `popper_`, are linked with the module
+fn wasm_imports(llvm: &str, externs: &HashSet<String>, module: &str) -> String {
+    llvm.lines()
+        .map(|line| {
+            let name = line
+                .strip_prefix("declare ")
+                .and_then(|x| x.split_once('@'))
+                .and_then(|(_, x)| x.split_once('('))
+                .map(|(name, _)| name.trim_matches('"'));
+            match name {
+                Some(name) if externs.contains(name) && !name.starts_with("popper_") => format!(
+                    "{} \"wasm-import-module\"=\"{}\" \"wasm-import-name\"=\"{}\"\n",
+                    line, module, name
+                ),
+                _ => format!("{}\n", line),
+            }
+        })
+        .collect()
+}
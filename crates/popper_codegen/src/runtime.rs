@@ -0,0 +1,94 @@
+use mirage::frontend::object::function::FunctionType;
+use mirage::frontend::object::MirageTypeEnum;
+
+use crate::target::Target;
+
+/// C sources of the runtime library, linked into every executable
+pub const RUNTIME_SOURCES: &[(&str, &str)] = &[
+    (
+        "popper_string.c",
+        include_str!("../runtime/popper_string.c"),
+    ),
+    ("popper_vec.c", include_str!("../runtime/popper_vec.c")),
+    ("popper_panic.c", include_str!("../runtime/popper_panic.c")),
+    ("popper_std.c", include_str!("../runtime/popper_std.c")),
+];
+
+/// runtime of the WebAssembly modules built without a sysroot: `popper_wasm.c`
+/// replaces libc, and the standard library helpers are left out
+pub const FREESTANDING_RUNTIME_SOURCES: &[(&str, &str)] = &[
+    ("popper_wasm.h", include_str!("../runtime/popper_wasm.h")),
+    ("popper_wasm.c", include_str!("../runtime/popper_wasm.c")),
+    (
+        "popper_string.c",
+        include_str!("../runtime/popper_string.c"),
+    ),
+    ("popper_vec.c", include_str!("../runtime/popper_vec.c")),
+    ("popper_panic.c", include_str!("../runtime/popper_panic.c")),
+];
+
+/// C sources of the runtime of `target`, headers included
+pub fn runtime_sources(target: &Target) -> &'static [(&'static str, &'static str)] {
+    if target.is_freestanding() {
+        FREESTANDING_RUNTIME_SOURCES
+    } else {
+        RUNTIME_SOURCES
+    }
+}
+
+/// `string`, `vec<T>` and `[T]` are pointers to a runtime header
+pub fn ptr_type() -> MirageTypeEnum {
+    MirageTypeEnum::type_ptr(MirageTypeEnum::type_int8().into()).into()
+}
+
+/// signature of a runtime function
+pub fn signature(name: &str) -> FunctionType {
+    let string = ptr_type();
+    let ptr = ptr_type;
+    let int = || MirageTypeEnum::type_int32().into();
+    let boolean = MirageTypeEnum::type_int8().into();
+
+    match name {
+        "popper_str_new" => FunctionType::new(vec![string.clone(), int()], string, false),
+        "popper_str_concat" => {
+            FunctionType::new(vec![string.clone(), string.clone()], string, false)
+        }
+        "popper_str_slice" => FunctionType::new(vec![string.clone(), int(), int()], string, false),
+        "popper_str_len" => FunctionType::new(vec![string], int(), false),
+        "popper_str_cstr" => FunctionType::new(vec![string.clone()], string, false),
+        "popper_str_eq" | "popper_str_ne" | "popper_str_lt" | "popper_str_le" | "popper_str_gt"
+        | "popper_str_ge" => FunctionType::new(vec![string.clone(), string], boolean, false),
+        "popper_vec_new" => FunctionType::new(vec![int()], ptr(), false),
+        "popper_vec_push" => FunctionType::new(vec![ptr(), ptr()], int(), false),
+        "popper_vec_pop" => FunctionType::new(vec![ptr()], ptr(), false),
+        "popper_slice_new" => FunctionType::new(vec![ptr(), int(), int()], ptr(), false),
+        "popper_slice_len" => FunctionType::new(vec![ptr()], int(), false),
+        "popper_slice_at" => FunctionType::new(vec![ptr(), int()], ptr(), false),
+        "popper_slice_sub" => FunctionType::new(vec![ptr(), int(), int()], ptr(), false),
+        "popper_check_index" => FunctionType::new(
+            vec![
+                MirageTypeEnum::type_int64().into(),
+                MirageTypeEnum::type_int64().into(),
+                ptr(),
+                int(),
+                int(),
+            ],
+            MirageTypeEnum::type_int64().into(),
+            false,
+        ),
+        "popper_check_div" => FunctionType::new(
+            vec![MirageTypeEnum::type_int64().into(), ptr(), int(), int()],
+            MirageTypeEnum::type_int64().into(),
+            false,
+        ),
+        "popper_assert" => FunctionType::new(
+            vec![boolean, string, ptr(), int(), int()],
+            int(),
+            false,
+        ),
+        "popper_std_init" => FunctionType::new(vec![int(), ptr()], int(), false),
+        "popper_std_args" => FunctionType::new(vec![], ptr(), false),
+        "popper_check_null" => FunctionType::new(vec![ptr(), ptr(), int(), int()], ptr(), false),
+        e => panic!("unknown runtime function `{}`", e),
+    }
+}
@@ -0,0 +1,95 @@
+use popper_ast::{Function, ImportStmt, Statement};
+use popper_common::hash::hash_str;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// prefix of the symbols of the module in `file`: its name and a hash of its path,
+/// telling apart the modules of the same name
+pub fn module_prefix(file: &str) -> String {
+    let name = Path::new(file)
+        .file_stem()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!("{}.{}", name, &hash_str(file)[..8])
+}
+
+/// prefix of the symbols of an imported module
+pub fn import_prefix(import: &ImportStmt) -> String {
+    match &import.file {
+        Some(file) => module_prefix(&file.display().to_string()),
+        None => module_prefix(&import.path.to_string()),
+    }
+}
+
+/// symbol of a function of the module with the prefix `prefix`: `prefix$name`.
+/// `main` and the `export` functions keep their name, they're called from C
+pub fn function_symbol(prefix: &str, f: &Function) -> String {
+    if f.is_export || f.name == "main" {
+        f.name.clone()
+    } else {
+        format!("{}${}", prefix, f.name)
+    }
+}
+
+/// symbols of the functions defined by a module, by name
+pub fn module_functions(prefix: &str, stmts: &[Statement]) -> HashMap<String, String> {
+    stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Function(f) => Some((f.name.clone(), function_symbol(prefix, f))),
+            _ => None,
+        })
+        .collect()
+}
+
+///
+/// Scope holds the names a module calls its functions by: the functions it defines,
+/// the items it imports and the modules it qualifies calls with
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    /// symbol of each function called by its bare name
+    functions: HashMap<String, String>,
+    /// symbols of the functions of each imported module, by the name it's bound to
+    modules: HashMap<String, HashMap<String, String>>,
+}
+
+impl Scope {
+    /// scope of the module with the prefix `prefix`, made of `stmts`
+    pub fn new(prefix: &str, stmts: &[Statement]) -> Self {
+        let mut scope = Self {
+            functions: module_functions(prefix, stmts),
+            modules: HashMap::new(),
+        };
+        for stmt in stmts {
+            let Statement::Import(i) = stmt else {
+                continue;
+            };
+            let functions = module_functions(&import_prefix(i), &i.module_stmts);
+            match &i.items {
+                Some(items) => {
+                    for item in items {
+                        if let Some(symbol) = functions.get(&item.name) {
+                            scope.functions.insert(item.name.clone(), symbol.clone());
+                        }
+                    }
+                }
+                None => {
+                    scope
+                        .modules
+                        .insert(i.module_name().name.clone(), functions);
+                }
+            }
+        }
+        scope
+    }
+
+    /// symbol called by `module.name(...)`, or by `name(...)` without a module.
+    /// Builtins and C functions keep their name
+    pub fn resolve(&self, module: Option<&str>, name: &str) -> String {
+        let symbol = match module {
+            Some(module) => self.modules.get(module).and_then(|x| x.get(name)),
+            None => self.functions.get(name),
+        };
+        symbol.cloned().unwrap_or_else(|| name.to_string())
+    }
+}
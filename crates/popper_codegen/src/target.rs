@@ -0,0 +1,225 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// machine the code is compiled for
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    /// LLVM target triple: `arch-vendor-os`, optionally followed by `-env`
+    pub triple: String,
+    /// size of pointers and of `usize`, in bits
+    pub pointer_width: u32,
+    /// LLVM data layout, left to the backend when it's not known
+    pub data_layout: Option<&'static str>,
+    /// root of the headers and libraries of the target, given to the C compiler and the linker
+    pub sysroot: Option<PathBuf>,
+    /// module the `extern` functions are imported from, by WebAssembly modules
+    pub import_module: String,
+    is_host: bool,
+}
+
+impl Target {
+    /// the machine running the compiler, the tools are run with their defaults
+    pub fn host() -> Self {
+        let triple = host_triple();
+        Self::from_str(&triple).unwrap_or(Self {
+            triple,
+            pointer_width: usize::BITS,
+            data_layout: None,
+            sysroot: None,
+            import_module: DEFAULT_IMPORT_MODULE.to_string(),
+            is_host: true,
+        })
+    }
+
+    pub fn with_sysroot(mut self, sysroot: Option<PathBuf>) -> Self {
+        self.sysroot = sysroot;
+        self
+    }
+
+    pub fn with_import_module(mut self, import_module: Option<String>) -> Self {
+        if let Some(import_module) = import_module {
+            self.import_module = import_module;
+        }
+        self
+    }
+
+    pub fn is_host(&self) -> bool {
+        self.is_host
+    }
+
+    pub fn arch(&self) -> &str {
+        self.triple.split('-').next().unwrap_or_default()
+    }
+
+    pub fn is_wasm(&self) -> bool {
+        self.arch().starts_with("wasm")
+    }
+
+    /// WebAssembly without a sysroot has no libc, the runtime brings its own allocator
+    pub fn is_freestanding(&self) -> bool {
+        self.is_wasm() && self.sysroot.is_none()
+    }
+
+    /// extension of the executables, wasm modules end with `.wasm`
+    pub fn exe_extension(&self) -> &'static str {
+        if self.is_wasm() {
+            "wasm"
+        } else if self.triple.contains("windows") {
+            "exe"
+        } else {
+            ""
+        }
+    }
+
+    /// extension of the shared libraries
+    pub fn dylib_extension(&self) -> &'static str {
+        if self.triple.contains("apple") {
+            "dylib"
+        } else if self.triple.contains("windows") {
+            "dll"
+        } else {
+            "so"
+        }
+    }
+
+    /// arguments of `llc`. The code is position independent, to be linked in
+    /// executables as well as in shared libraries
+    pub fn llc_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if !self.is_host {
+            args.push(format!("-mtriple={}", self.triple));
+        }
+        if !self.is_wasm() {
+            args.push("-relocation-model=pic".to_string());
+        }
+        args
+    }
+
+    /// arguments of `clang` compiling position independent C code, like `llc` does
+    pub fn pic_args(&self) -> Vec<String> {
+        if self.is_wasm() {
+            vec![]
+        } else {
+            vec!["-fPIC".to_string()]
+        }
+    }
+
+    /// arguments of `clang`, compiling C or linking. Other targets are linked with `lld`,
+    /// which handles every object format
+    pub fn clang_args(&self) -> Vec<String> {
+        let mut args = vec![];
+        if !self.is_host {
+            args.push(format!("--target={}", self.triple));
+            if !self.is_wasm() {
+                args.push("-fuse-ld=lld".to_string());
+            }
+        }
+        if self.is_freestanding() {
+            args.extend(["-ffreestanding", "-nostdlib", "-DPOPPER_FREESTANDING"].map(String::from));
+        }
+        if let Some(sysroot) = &self.sysroot {
+            args.push(format!("--sysroot={}", sysroot.display()));
+        }
+        args
+    }
+
+    /// lines declaring the target at the start of the LLVM IR
+    pub fn llvm_header(&self) -> String {
+        if self.is_host {
+            return String::new();
+        }
+        let mut header = format!("target triple = \"{}\"\n", self.triple);
+        if let Some(data_layout) = self.data_layout {
+            header = format!("target datalayout = \"{}\"\n{}", data_layout, header);
+        }
+        header
+    }
+}
+
+impl Default for Target {
+    fn default() -> Self {
+        Self::host()
+    }
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    /// parse a target triple, `wasm32` standing for `wasm32-unknown-unknown`
+    fn from_str(triple: &str) -> Result<Self, Self::Err> {
+        let triple = match triple {
+            "wasm32" | "wasm64" => format!("{}-unknown-unknown", triple),
+            triple => triple.to_string(),
+        };
+        let mut parts = triple.split('-');
+        let arch = parts.next().unwrap_or_default();
+        let os = parts.nth(1).unwrap_or_default();
+        if os.is_empty() {
+            return Err(format!(
+                "invalid target `{}`, expected `arch-vendor-os`",
+                triple
+            ));
+        }
+
+        let pointer_width = match arch {
+            "x86_64" | "aarch64" | "arm64" | "powerpc64" | "powerpc64le" | "s390x"
+            | "loongarch64" | "sparc64" | "mips64" | "mips64el" | "wasm64" => 64,
+            _ if arch.starts_with("riscv64") => 64,
+            "i386" | "i586" | "i686" | "powerpc" | "mips" | "mipsel" | "wasm32" => 32,
+            _ if arch.starts_with("arm") || arch.starts_with("thumb") => 32,
+            _ if arch.starts_with("riscv32") => 32,
+            _ => return Err(format!("unknown architecture `{}`", arch)),
+        };
+
+        // data layouts given by LLVM, for the most common targets
+        let apple = os.starts_with("darwin") || os.starts_with("macos") || os.starts_with("ios");
+        let data_layout = match arch {
+            "x86_64" if apple => Some(
+                "e-m:o-p270:32:32-p271:32:32-p272:64:64-i64:64-i128:128-f80:128-n8:16:32:64-S128",
+            ),
+            "x86_64" if os == "linux" => Some(
+                "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-i128:128-f80:128-n8:16:32:64-S128",
+            ),
+            "aarch64" | "arm64" if apple => Some("e-m:o-i64:64-i128:128-n32:64-S128"),
+            "aarch64" if os == "linux" => {
+                Some("e-m:e-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128")
+            }
+            _ if arch.starts_with("riscv64") && os == "linux" => {
+                Some("e-m:e-p:64:64-i64:64-i128:128-n32:64-S128")
+            }
+            "wasm32" => Some("e-m:e-p:32:32-p10:8:8-p20:8:8-i64:64-n32:64-S128-ni:1:10:20"),
+            _ => None,
+        };
+
+        Ok(Self {
+            is_host: triple == host_triple(),
+            triple,
+            pointer_width,
+            data_layout,
+            sysroot: None,
+            import_module: DEFAULT_IMPORT_MODULE.to_string(),
+        })
+    }
+}
+
+/// module the undefined functions are imported from by `wasm-ld`
+const DEFAULT_IMPORT_MODULE: &str = "env";
+
+/// triple of the machine running the compiler
+fn host_triple() -> String {
+    let os = match std::env::consts::OS {
+        "linux" => "unknown-linux-gnu",
+        "macos" => "apple-darwin",
+        "windows" => "pc-windows-msvc",
+        "freebsd" => "unknown-freebsd",
+        _ => "unknown-unknown",
+    };
+    format!("{}-{}", std::env::consts::ARCH, os)
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.triple)
+    }
+}
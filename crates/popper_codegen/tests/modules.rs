@@ -0,0 +1,93 @@
+use popper_ast::Statement;
+use popper_codegen::Compiler;
+use popper_parser::parser::try_parse;
+use std::path::PathBuf;
+
+const MAIN: &str = r#"
+import left;
+import right.{find};
+
+func find(x: int): int {
+    return x;
+}
+
+func main(): int {
+    return left.find(1) + find(2);
+}
+"#;
+
+const LEFT: &str = r#"
+pub func find(x: int): int {
+    return x + 1;
+}
+"#;
+
+const RIGHT: &str = r#"
+pub func find(x: int): int {
+    return x + 2;
+}
+"#;
+
+/// `MAIN`, importing `LEFT` and `RIGHT` as the module loader does
+fn program() -> Vec<Statement> {
+    let mut stmts = try_parse(MAIN).expect("valid source");
+    for stmt in stmts.iter_mut() {
+        if let Statement::Import(i) = stmt {
+            let (file, source) = match i.module_name().name.as_str() {
+                "left" => ("/modules/left.pop", LEFT),
+                _ => ("/modules/right.pop", RIGHT),
+            };
+            i.resolve(
+                PathBuf::from(file),
+                source.to_string(),
+                try_parse(source).expect("valid module"),
+            );
+        }
+    }
+    stmts
+}
+
+/// functions defined by LLVM IR
+fn definitions(llvm: &str) -> Vec<&str> {
+    llvm.lines().filter(|x| x.starts_with("define")).collect()
+}
+
+#[test]
+fn functions_of_the_same_name() {
+    let llvm = Compiler::new(program(), "/modules/main.pop")
+        .compile(false)
+        .print_llvm_to_string();
+    let definitions = definitions(&llvm);
+
+    let finds: Vec<_> = definitions.iter().filter(|x| x.contains("find")).collect();
+    assert_eq!(finds.len(), 3, "{}", llvm);
+    assert!(finds.iter().any(|x| x.contains("main.")), "{}", llvm);
+    assert!(finds.iter().any(|x| x.contains("left.")), "{}", llvm);
+    assert!(finds.iter().any(|x| x.contains("right.")), "{}", llvm);
+    assert!(definitions.iter().any(|x| x.contains("@main(")), "{}", llvm);
+}
+
+#[test]
+fn main_returning_unit() {
+    let source = "func main(): unit {
+    let x = 1;
+    if x eq 1 {
+        return;
+    }
+}
+";
+    let stmts = try_parse(source).expect("valid source");
+    let llvm = Compiler::new(stmts, "/modules/unit.pop")
+        .compile(false)
+        .print_llvm_to_string();
+    // it's compiled as a `main` returning 0
+    let definitions = definitions(&llvm);
+    assert!(
+        definitions
+            .iter()
+            .any(|x| x.starts_with("define i32 @popper_main(")),
+        "{:?}",
+        definitions
+    );
+    assert_eq!(llvm.matches("ret i32 0").count(), 2, "{}", llvm);
+}
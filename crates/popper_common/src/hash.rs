@@ -7,13 +7,23 @@ pub fn hash_file(path: &str) -> String {
     let mut hasher = sha2::Sha256::new();
     std::io::copy(&mut file, &mut hasher).expect("failed to copy file");
 
-    let hash = hasher
+    to_hex(hasher)
+}
+
+pub fn hash_str(s: &str) -> String {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(s.as_bytes());
+
+    to_hex(hasher)
+}
+
+fn to_hex(hasher: sha2::Sha256) -> String {
+    hasher
         .finalize()
         .to_vec()
         .iter()
         .fold(String::new(), |mut acc, x| {
             write!(acc, "{:02x}", x).unwrap();
             acc
-        });
-    hash
+        })
 }
@@ -50,4 +50,12 @@ impl Error for AlreadyExist {
             .print((file, Source::from(source)))
             .unwrap();
     }
+
+    fn span(&self) -> Span {
+        self.recreated_at.1
+    }
+
+    fn message(&self) -> String {
+        format!("`{}` already exist", self.recreated_at.0)
+    }
 }
@@ -35,4 +35,12 @@ impl Error for CantMut {
         .print((file, ariadne::Source::from(source)))
         .unwrap();
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn message(&self) -> String {
+        "can't mutate a constant".to_string()
+    }
 }
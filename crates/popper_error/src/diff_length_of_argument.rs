@@ -52,4 +52,18 @@ impl Error for DiffLengthOfArgument {
             .print((file, Source::from(source)))
             .unwrap();
     }
+
+    fn span(&self) -> Span {
+        self.function_span
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "Expected {} {} got {} {}",
+            self.expected,
+            plurialize("argument", self.expected),
+            self.got,
+            plurialize("argument", self.got)
+        )
+    }
 }
@@ -54,4 +54,15 @@ impl Error for FieldNotFound {
             .print((file, ariadne::Source::from(source)))
             .unwrap();
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn message(&self) -> String {
+        match &self.similar {
+            Some(similar) => format!("field `{}` not found, did you mean `{}`?", self.name, similar),
+            None => format!("field `{}` not found", self.name),
+        }
+    }
 }
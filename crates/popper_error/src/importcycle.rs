@@ -0,0 +1,57 @@
+use crate::{ColorConfig, Error};
+use ariadne::Fmt;
+use ariadne::Source;
+use popper_ast::Span;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("import cycle")]
+/// this error is throw when a module imports itself, directly or through other modules
+pub struct ImportCycle {
+    /// modules of the cycle, starting and ending with the same module
+    pub cycle: Vec<String>,
+    pub span: Span,
+}
+
+impl ImportCycle {
+    pub fn new(cycle: Vec<String>, span: Span) -> Self {
+        Self { cycle, span }
+    }
+}
+
+impl Error for ImportCycle {
+    fn report(&self, color: ColorConfig, source: &str, file: &str) {
+        let module = color.get("module").expect("module color not found");
+
+        let cycle = self
+            .cycle
+            .iter()
+            .map(|x| x.clone().fg(*module).to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ");
+
+        ariadne::Report::build(
+            ariadne::ReportKind::Error,
+            file,
+            self.span.find_line(source),
+        )
+        .with_code(30)
+        .with_message("Import cycle")
+        .with_label(
+            ariadne::Label::new((file, self.span.into()))
+                .with_message("this import leads back to the current module"),
+        )
+        .with_note(format!("cycle: {}", cycle))
+        .finish()
+        .print((file, Source::from(source)))
+        .unwrap();
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn message(&self) -> String {
+        format!("Import cycle: {}", self.cycle.join(" -> "))
+    }
+}
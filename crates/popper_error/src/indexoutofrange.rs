@@ -0,0 +1,55 @@
+use crate::{ColorConfig, Error};
+use ariadne::Fmt;
+use ariadne::Source;
+use popper_ast::Span;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("index out of range")]
+/// this error is throw when a constant index is out of the bounds of a list
+pub struct IndexOutOfRange {
+    pub index: i64,
+    pub len: usize,
+    pub span: Span,
+}
+
+impl IndexOutOfRange {
+    pub fn new(index: i64, len: usize, span: Span) -> Self {
+        Self { index, len, span }
+    }
+}
+
+impl Error for IndexOutOfRange {
+    fn report(&self, color: ColorConfig, source: &str, file: &str) {
+        let constant = color.get("constant").expect("constant color not found");
+
+        ariadne::Report::build(
+            ariadne::ReportKind::Error,
+            file,
+            self.span.find_line(source),
+        )
+        .with_code(28)
+        .with_message("Index out of range")
+        .with_label(
+            ariadne::Label::new((file, self.span.into())).with_message(format!(
+                "index `{}` is out of range for a list of length `{}`",
+                self.index.to_string().fg(*constant),
+                self.len.to_string().fg(*constant)
+            )),
+        )
+        .finish()
+        .print((file, Source::from(source)))
+        .unwrap();
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "index `{}` is out of range for a list of length `{}`",
+            self.index, self.len
+        )
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{ColorConfig, Error};
+use ariadne::Fmt;
+use ariadne::Source;
+use popper_ast::Span;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("invalid cast")]
+/// this error is throw when an `as` conversion isn't allowed between two types
+pub struct InvalidCast {
+    pub from: String,
+    pub to: String,
+    pub span: Span,
+}
+
+impl InvalidCast {
+    pub fn new(from: String, to: String, span: Span) -> Self {
+        Self { from, to, span }
+    }
+}
+
+impl Error for InvalidCast {
+    fn report(&self, color: ColorConfig, source: &str, file: &str) {
+        let type_color = color.get("type").expect("type color not found");
+
+        ariadne::Report::build(
+            ariadne::ReportKind::Error,
+            file,
+            self.span.find_line(source),
+        )
+        .with_code(26)
+        .with_message("Invalid cast".to_string())
+        .with_label(
+            ariadne::Label::new((file, self.span.into())).with_message(format!(
+                "can't cast `{}` as `{}`",
+                self.from.clone().fg(*type_color),
+                self.to.clone().fg(*type_color)
+            )),
+        )
+        .finish()
+        .print((file, Source::from(source)))
+        .unwrap();
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn message(&self) -> String {
+        format!("can't cast `{}` as `{}`", self.from, self.to)
+    }
+}
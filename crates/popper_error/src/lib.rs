@@ -1,14 +1,20 @@
 pub mod alreadyexist;
 pub mod diff_length_of_argument;
 pub mod fieldnotfound;
+pub mod importcycle;
+pub mod indexoutofrange;
+pub mod invalidcast;
+pub mod literaloutofrange;
 pub mod modulenotfound;
 pub mod namenotfound;
+pub mod privateaccess;
 pub mod typemismatch;
 pub mod typenotspecified;
 pub mod notallowed;
 pub mod cantmut;
 
 use ariadne::{Color, Source};
+use popper_ast::Span;
 use std::fmt::Debug;
 
 pub type ColorConfig = std::collections::HashMap<String, Color>;
@@ -16,6 +22,12 @@ pub type ColorConfig = std::collections::HashMap<String, Color>;
 /// error trait for `Popper Lang`, all error in `Popper-Lang` will implement this trait
 pub trait Error: Debug {
     fn report(&self, color: ColorConfig, source: &str, file: &str);
+
+    /// span the error points at
+    fn span(&self) -> Span;
+
+    /// message of the error without colors, for the tools showing it next to the code
+    fn message(&self) -> String;
 }
 
 /// it is the default configuration of color of error in `Popper-lang` , if you want to add new , create a new function
@@ -28,6 +40,7 @@ pub fn generate_color() -> ColorConfig {
     color_map.insert("lambda".to_string(), Color::Magenta);
     color_map.insert("local".to_string(), Color::Cyan);
     color_map.insert("global".to_string(), Color::White);
+    color_map.insert("module".to_string(), Color::Cyan);
     color_map.insert("keyword".to_string(), Color::RGB(255, 128, 0)); // orange
 
     color_map
@@ -0,0 +1,53 @@
+use crate::{ColorConfig, Error};
+use ariadne::Fmt;
+use ariadne::Source;
+use popper_ast::Span;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("literal out of range")]
+/// this error is throw when an integer literal doesn't fit in the type it is used as
+pub struct LiteralOutOfRange {
+    pub value: i64,
+    pub ty: String,
+    pub span: Span,
+}
+
+impl LiteralOutOfRange {
+    pub fn new(value: i64, ty: String, span: Span) -> Self {
+        Self { value, ty, span }
+    }
+}
+
+impl Error for LiteralOutOfRange {
+    fn report(&self, color: ColorConfig, source: &str, file: &str) {
+        let type_color = color.get("type").expect("type color not found");
+        let constant = color.get("constant").expect("constant color not found");
+
+        ariadne::Report::build(
+            ariadne::ReportKind::Error,
+            file,
+            self.span.find_line(source),
+        )
+        .with_code(27)
+        .with_message(format!("Literal out of range for `{}`", self.ty))
+        .with_label(
+            ariadne::Label::new((file, self.span.into())).with_message(format!(
+                "`{}` doesn't fit in `{}`",
+                self.value.to_string().fg(*constant),
+                self.ty.clone().fg(*type_color)
+            )),
+        )
+        .finish()
+        .print((file, Source::from(source)))
+        .unwrap();
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn message(&self) -> String {
+        format!("`{}` doesn't fit in `{}`", self.value, self.ty)
+    }
+}
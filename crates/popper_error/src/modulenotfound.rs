@@ -39,4 +39,12 @@ impl Error for ModuleNotFound {
             .print((file, Source::from(source)))
             .unwrap();
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn message(&self) -> String {
+        format!("Module `{}` not found", self.module)
+    }
 }
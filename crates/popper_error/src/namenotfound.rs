@@ -49,4 +49,18 @@ impl Error for NameNotFound {
 
         report.finish().print((file, Source::from(source))).unwrap();
     }
+
+    fn span(&self) -> Span {
+        self.name.0
+    }
+
+    fn message(&self) -> String {
+        match &self.name_more_closed {
+            Some(similar) => format!(
+                "Variable `{}` not found, did you mean `{}`?",
+                self.name.1, similar
+            ),
+            None => format!("Variable `{}` not found", self.name.1),
+        }
+    }
 }
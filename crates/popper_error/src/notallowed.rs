@@ -28,14 +28,7 @@ impl NotAllowed {
 
 impl Error for NotAllowed {
     fn report(&self, _color: ColorConfig, source: &str, file: &str) {
-        let msg = if self.outside.is_empty() {
-            format!("The {} `{}` is not allowed here", self.datatype, self.data)
-        } else {
-            format!(
-                "The {} `{}` is not allowed outside `{}`",
-                self.datatype, self.data, self.outside
-            )
-        };
+        let msg = self.message();
         let mut report = ariadne::Report::build(
             ariadne::ReportKind::Error,
             file,
@@ -52,4 +45,19 @@ impl Error for NotAllowed {
 
         report.finish().print((file, Source::from(source))).unwrap();
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn message(&self) -> String {
+        if self.outside.is_empty() {
+            format!("The {} `{}` is not allowed here", self.datatype, self.data)
+        } else {
+            format!(
+                "The {} `{}` is not allowed outside `{}`",
+                self.datatype, self.data, self.outside
+            )
+        }
+    }
 }
@@ -0,0 +1,54 @@
+use crate::{ColorConfig, Error};
+use ariadne::Fmt;
+use ariadne::Source;
+use popper_ast::Span;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("private item")]
+/// this error is throw when an item that is not `pub` is used outside of its module
+pub struct PrivateAccess {
+    pub name: String,
+    pub owner: String,
+    pub span: Span,
+}
+
+impl PrivateAccess {
+    pub fn new(name: String, owner: String, span: Span) -> Self {
+        Self { name, owner, span }
+    }
+}
+
+impl Error for PrivateAccess {
+    fn report(&self, color: ColorConfig, source: &str, file: &str) {
+        let variable = color.get("variable").expect("variable color not found");
+        let module = color.get("module").expect("module color not found");
+
+        ariadne::Report::build(
+            ariadne::ReportKind::Error,
+            file,
+            self.span.find_line(source),
+        )
+        .with_code(29)
+        .with_message(format!("`{}` is private", self.name))
+        .with_label(
+            ariadne::Label::new((file, self.span.into())).with_message(format!(
+                "`{}` is private to `{}`",
+                self.name.clone().fg(*variable),
+                self.owner.clone().fg(*module)
+            )),
+        )
+        .with_note("mark it `pub` to use it from other modules")
+        .finish()
+        .print((file, Source::from(source)))
+        .unwrap();
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn message(&self) -> String {
+        format!("`{}` is private to `{}`", self.name, self.owner)
+    }
+}
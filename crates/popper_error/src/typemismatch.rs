@@ -41,4 +41,15 @@ impl PopperError for TypeMismatch {
             .print((file, Source::from(source)))
             .unwrap();
     }
+
+    fn span(&self) -> Span {
+        self.found.0
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "expected type `{}`, found type `{}`",
+            self.expected.1, self.found.1
+        )
+    }
 }
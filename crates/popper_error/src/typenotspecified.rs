@@ -37,4 +37,12 @@ impl PopperError for TypeNotSpecified {
 
         report.finish().print((file, Source::from(source))).unwrap();
     }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn message(&self) -> String {
+        format!("Type not specified for {}", self.ty)
+    }
 }
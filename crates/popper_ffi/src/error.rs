@@ -0,0 +1,48 @@
+use std::fmt::Display;
+
+/// why a foreign function can't be declared or called
+#[derive(Debug, Clone, PartialEq)]
+pub enum FfiError {
+    /// a library of an `external` block can't be loaded
+    Library(String),
+    /// no library loaded in the process defines the function
+    SymbolNotFound(String),
+    /// a function was called without being declared
+    UnknownFunction(String),
+    /// a struct was used without being declared
+    UnknownStruct(String),
+    /// the type can't be passed to or returned from C
+    Unsupported(String),
+    /// name of the function, arguments it takes and arguments it was given
+    ArgumentCount(String, usize, usize),
+    /// the value doesn't have the type of the argument
+    InvalidArgument(String, String),
+}
+
+impl Display for FfiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FfiError::Library(err) => write!(f, "{}", err),
+            FfiError::SymbolNotFound(name) => {
+                write!(
+                    f,
+                    "`{}` isn't defined by libc nor by the external libraries",
+                    name
+                )
+            }
+            FfiError::UnknownFunction(name) => write!(f, "`{}` isn't a foreign function", name),
+            FfiError::UnknownStruct(name) => write!(f, "struct `{}` isn't declared", name),
+            FfiError::Unsupported(ty) => write!(f, "`{}` can't be passed to C", ty),
+            FfiError::ArgumentCount(name, expected, found) => write!(
+                f,
+                "`{}` takes {} arguments but {} were given",
+                name, expected, found
+            ),
+            FfiError::InvalidArgument(value, ty) => {
+                write!(f, "{} can't be passed as `{}`", value, ty)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FfiError {}
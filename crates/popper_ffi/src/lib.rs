@@ -0,0 +1,297 @@
+mod error;
+mod ty;
+mod value;
+
+pub use error::FfiError;
+pub use ty::FfiType;
+pub use value::Value;
+
+use libffi::low::{ffi_abi_FFI_DEFAULT_ABI, ffi_cif, ffi_type, CodePtr};
+use popper_ast::{FunctionSign, Statement, StructStmt};
+use popper_codegen::library::Library;
+use popper_flag::ValueFlag;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::path::Path;
+
+///
+/// Ffi calls the `extern` and `external` functions of interpreted code through libffi.
+/// The functions are looked up in the process, which has libc loaded, and in the
+/// libraries of the `external` blocks.
+///
+/// Arguments are passed like the compiled code passes them: `bool` and `char` as
+/// `uint8_t`, strings as NUL terminated copies, structs and tuples by value with the
+/// C layout. The arguments given to the `...` of a variadic function take the type
+/// of their value, promoted like C promotes them: to `int` below 32 bits, and floats
+/// to doubles
+#[derive(Default)]
+pub struct Ffi {
+    /// field types of the declared structs
+    structs: HashMap<String, Vec<ValueFlag>>,
+    functions: HashMap<String, ForeignFunction>,
+}
+
+struct ForeignFunction {
+    code: CodePtr,
+    args: Vec<FfiType>,
+    return_type: FfiType,
+    is_var_args: bool,
+}
+
+impl Ffi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// declare the structs, then the foreign functions of a module and of the modules
+    /// it imports. `file` is the path of the module, `external` paths are relative to it
+    pub fn declare_module(&mut self, stmts: &[Statement], file: &Path) -> Result<(), FfiError> {
+        for stmt in stmts {
+            match stmt {
+                Statement::Struct(s) => self.declare_struct(s),
+                Statement::Import(i) => {
+                    let file = i.file.as_deref().unwrap_or(file);
+                    self.declare_module(&i.module_stmts, file)?;
+                }
+                _ => {}
+            }
+        }
+        for stmt in stmts {
+            match stmt {
+                Statement::Extern(ext) => self.declare_signs(&ext.signs)?,
+                Statement::External(external) => {
+                    let dir = file.parent().unwrap_or(Path::new(""));
+                    self.load_library(&Library::new(&external.file, dir))?;
+                    self.declare_signs(&external.signs)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// declare a struct, so it can be passed by value
+    pub fn declare_struct(&mut self, s: &StructStmt) {
+        let fields = s
+            .fields
+            .iter()
+            .map(|field| ValueFlag::from_ty(field.ty.clone()))
+            .collect();
+        self.declare_struct_fields(&s.name, fields);
+    }
+
+    /// declare a struct from the types of its fields, in order
+    pub fn declare_struct_fields(&mut self, name: &str, fields: Vec<ValueFlag>) {
+        self.structs.insert(name.to_string(), fields);
+    }
+
+    /// load a library in the process, its functions can then be declared
+    pub fn load_library(&mut self, library: &Library) -> Result<(), FfiError> {
+        library.load().map_err(FfiError::Library)
+    }
+
+    fn declare_signs(&mut self, signs: &[FunctionSign]) -> Result<(), FfiError> {
+        for sign in signs {
+            let args: Vec<ValueFlag> = sign
+                .arguments
+                .args
+                .iter()
+                .map(|arg| ValueFlag::from_ty(arg.ty.clone()))
+                .collect();
+            let return_type = ValueFlag::from_ty(sign.return_type.clone());
+            self.declare(&sign.name, &args, &return_type, sign.is_var_args)?;
+        }
+        Ok(())
+    }
+
+    /// declare a foreign function, looking its symbol up
+    pub fn declare(
+        &mut self,
+        name: &str,
+        args: &[ValueFlag],
+        return_type: &ValueFlag,
+        is_var_args: bool,
+    ) -> Result<(), FfiError> {
+        // arrays are given as pointers to C, and can't be returned
+        if let Some(ty) = args
+            .iter()
+            .chain(std::iter::once(return_type))
+            .find(|x| matches!(x, ValueFlag::List(..)))
+        {
+            return Err(FfiError::Unsupported(ty.to_string()));
+        }
+        let function = ForeignFunction {
+            code: symbol(name)?,
+            args: args
+                .iter()
+                .map(|x| FfiType::from_flag(x, &self.structs))
+                .collect::<Result<_, _>>()?,
+            return_type: FfiType::from_flag(return_type, &self.structs)?,
+            is_var_args,
+        };
+        self.functions.insert(name.to_string(), function);
+        Ok(())
+    }
+
+    pub fn is_declared(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// call a declared function
+    pub fn call(&self, name: &str, args: &[Value]) -> Result<Value, FfiError> {
+        let function = self
+            .functions
+            .get(name)
+            .ok_or_else(|| FfiError::UnknownFunction(name.to_string()))?;
+        let fixed = function.args.len();
+        if args.len() < fixed || (!function.is_var_args && args.len() != fixed) {
+            return Err(FfiError::ArgumentCount(name.to_string(), fixed, args.len()));
+        }
+
+        let types: Vec<FfiType> = function
+            .args
+            .iter()
+            .cloned()
+            .chain(args[fixed..].iter().map(|x| x.ty().promoted()))
+            .collect();
+        let mut strings = Vec::new();
+        let mut buffers = Vec::new();
+        for (arg, ty) in args.iter().zip(types.iter()) {
+            let mut buffer = Buffer::new(ty.size());
+            arg.write(ty, buffer.bytes_mut(), &mut strings)?;
+            buffers.push(buffer);
+        }
+        let mut arg_ptrs: Vec<*mut c_void> = buffers.iter_mut().map(|x| x.as_ptr()).collect();
+
+        let mut cif = CallInterface::new(
+            &types,
+            &function.return_type,
+            function.is_var_args.then_some(fixed),
+        )?;
+        // integers are returned in a whole register
+        let mut result = Buffer::new(function.return_type.size().max(8));
+        unsafe {
+            libffi::raw::ffi_call(
+                &mut cif.cif,
+                Some(*function.code.as_fun()),
+                result.as_ptr(),
+                arg_ptrs.as_mut_ptr(),
+            );
+        }
+        Ok(Value::read_return(
+            &function.return_type,
+            result.bytes_mut(),
+        ))
+    }
+
+    /// read a `ty` from memory of the C library
+    ///
+    /// # Safety
+    /// `ptr` must point to a valid, initialized `ty`
+    pub unsafe fn read(&self, ptr: *const c_void, ty: &ValueFlag) -> Result<Value, FfiError> {
+        let ty = FfiType::from_flag(ty, &self.structs)?;
+        let buf = std::slice::from_raw_parts(ptr as *const u8, ty.size());
+        Ok(Value::read(&ty, buf))
+    }
+
+    /// write a value as a `ty` to memory of the C library. Strings can't be written,
+    /// their copy wouldn't outlive the call
+    ///
+    /// # Safety
+    /// `ptr` must point to memory large enough for a `ty`
+    pub unsafe fn write(
+        &self,
+        ptr: *mut c_void,
+        ty: &ValueFlag,
+        value: &Value,
+    ) -> Result<(), FfiError> {
+        let ty = FfiType::from_flag(ty, &self.structs)?;
+        let mut buffer = Buffer::new(ty.size());
+        let mut strings = Vec::new();
+        value.write(&ty, buffer.bytes_mut(), &mut strings)?;
+        if !strings.is_empty() {
+            return Err(FfiError::Unsupported(ty.to_string()));
+        }
+        std::ptr::copy_nonoverlapping(buffer.bytes_mut().as_ptr(), ptr as *mut u8, ty.size());
+        Ok(())
+    }
+}
+
+/// address of a function of the process or of a loaded library
+fn symbol(name: &str) -> Result<CodePtr, FfiError> {
+    #[cfg(unix)]
+    let process = libloading::os::unix::Library::this();
+    #[cfg(windows)]
+    let process = libloading::os::windows::Library::this()
+        .map_err(|err| FfiError::Library(err.to_string()))?;
+
+    let function = unsafe { process.get::<unsafe extern "C" fn()>(name.as_bytes()) }
+        .map_err(|_| FfiError::SymbolNotFound(name.to_string()))?;
+    Ok(CodePtr::from_fun(*function))
+}
+
+/// memory of an argument or of the result, aligned for any of them
+struct Buffer(Vec<u64>);
+
+impl Buffer {
+    fn new(size: usize) -> Self {
+        Self(vec![0; size.div_ceil(8).max(1)])
+    }
+
+    fn as_ptr(&mut self) -> *mut c_void {
+        self.0.as_mut_ptr() as *mut c_void
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        let len = self.0.len() * 8;
+        unsafe { std::slice::from_raw_parts_mut(self.0.as_mut_ptr() as *mut u8, len) }
+    }
+}
+
+/// libffi description of a call, keeping the types it points to alive
+struct CallInterface {
+    cif: ffi_cif,
+    _types: Vec<libffi::middle::Type>,
+    _raw_types: Vec<*mut ffi_type>,
+    _return_type: libffi::middle::Type,
+}
+
+impl CallInterface {
+    /// `fixed` is the number of arguments before the `...` of variadic functions
+    fn new(
+        args: &[FfiType],
+        return_type: &FfiType,
+        fixed: Option<usize>,
+    ) -> Result<Self, FfiError> {
+        let types: Vec<_> = args.iter().map(|x| x.libffi()).collect();
+        let mut raw_types: Vec<_> = types.iter().map(|x| x.as_raw_ptr()).collect();
+        let return_type = return_type.libffi();
+        let mut cif = ffi_cif::default();
+        let prepared = unsafe {
+            match fixed {
+                Some(fixed) => libffi::low::prep_cif_var(
+                    &mut cif,
+                    ffi_abi_FFI_DEFAULT_ABI,
+                    fixed,
+                    raw_types.len(),
+                    return_type.as_raw_ptr(),
+                    raw_types.as_mut_ptr(),
+                ),
+                None => libffi::low::prep_cif(
+                    &mut cif,
+                    ffi_abi_FFI_DEFAULT_ABI,
+                    raw_types.len(),
+                    return_type.as_raw_ptr(),
+                    raw_types.as_mut_ptr(),
+                ),
+            }
+        };
+        prepared.map_err(|err| FfiError::Unsupported(format!("{:?}", err)))?;
+        Ok(Self {
+            cif,
+            _types: types,
+            _raw_types: raw_types,
+            _return_type: return_type,
+        })
+    }
+}
@@ -0,0 +1,169 @@
+use crate::FfiError;
+use libffi::middle::Type;
+use popper_ast::IntKind;
+use popper_flag::ValueFlag;
+use std::collections::HashMap;
+use std::fmt::Display;
+
+/// C type a Popper value is passed as, laid out like the compiled code lays it out
+#[derive(Debug, Clone, PartialEq)]
+pub enum FfiType {
+    Void,
+    /// `uint8_t`, read back as a `bool`
+    Bool,
+    /// `uint8_t`, read back as a `char`
+    Char,
+    Int(IntKind),
+    Float,
+    /// floats given to variadic functions
+    Double,
+    Pointer,
+    /// `const char *`, read back as a `string`
+    String,
+    /// fields of a struct, elements of a tuple or of a list in a struct
+    Struct(Vec<FfiType>),
+}
+
+impl FfiType {
+    /// C type of a Popper type, `structs` giving the fields of the struct types
+    pub fn from_flag(
+        flag: &ValueFlag,
+        structs: &HashMap<String, Vec<ValueFlag>>,
+    ) -> Result<Self, FfiError> {
+        Ok(match flag {
+            ValueFlag::None => FfiType::Void,
+            ValueFlag::Boolean => FfiType::Bool,
+            ValueFlag::Char => FfiType::Char,
+            ValueFlag::Integer => FfiType::Int(IntKind::I32),
+            ValueFlag::SizedInteger(kind) => FfiType::Int(*kind),
+            ValueFlag::Float => FfiType::Float,
+            ValueFlag::String => FfiType::String,
+            ValueFlag::Pointer(_)
+            | ValueFlag::Slice(_)
+            | ValueFlag::Vec(_)
+            | ValueFlag::Function(..) => FfiType::Pointer,
+            ValueFlag::Struct(name) | ValueFlag::StructInstance(name) => {
+                let fields = structs
+                    .get(name)
+                    .ok_or_else(|| FfiError::UnknownStruct(name.clone()))?;
+                Self::structure(fields, structs)?
+            }
+            ValueFlag::Tuple(types) => Self::structure(types, structs)?,
+            // an array is laid out like a struct of its elements
+            ValueFlag::List(elem, len) => {
+                FfiType::Struct(vec![Self::from_flag(elem, structs)?; *len])
+            }
+            ValueFlag::Module(_) => return Err(FfiError::Unsupported(flag.to_string())),
+        })
+    }
+
+    fn structure(
+        fields: &[ValueFlag],
+        structs: &HashMap<String, Vec<ValueFlag>>,
+    ) -> Result<Self, FfiError> {
+        fields
+            .iter()
+            .map(|x| Self::from_flag(x, structs))
+            .collect::<Result<_, _>>()
+            .map(FfiType::Struct)
+    }
+
+    /// type of a variadic argument: integers smaller than `int` are promoted to `int`,
+    /// and floats to doubles
+    pub fn promoted(&self) -> Self {
+        match self {
+            FfiType::Bool | FfiType::Char => FfiType::Int(IntKind::I32),
+            FfiType::Int(kind) if kind.bits() < 32 => FfiType::Int(IntKind::I32),
+            FfiType::Float => FfiType::Double,
+            ty => ty.clone(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            FfiType::Void => 0,
+            FfiType::Bool | FfiType::Char => 1,
+            FfiType::Int(kind) if *kind == IntKind::USize => std::mem::size_of::<usize>(),
+            FfiType::Int(kind) => kind.bits() as usize / 8,
+            FfiType::Float => 4,
+            FfiType::Double => 8,
+            FfiType::Pointer | FfiType::String => std::mem::size_of::<usize>(),
+            FfiType::Struct(fields) => {
+                let end = self
+                    .offsets()
+                    .last()
+                    .zip(fields.last())
+                    .map_or(0, |(offset, field)| offset + field.size());
+                end.next_multiple_of(self.align())
+            }
+        }
+    }
+
+    pub fn align(&self) -> usize {
+        match self {
+            FfiType::Struct(fields) => fields.iter().map(|x| x.align()).max().unwrap_or(1),
+            ty => ty.size().max(1),
+        }
+    }
+
+    /// offsets of the fields of a struct, each one aligned to its natural alignment
+    pub fn offsets(&self) -> Vec<usize> {
+        let FfiType::Struct(fields) = self else {
+            return Vec::new();
+        };
+        let mut offset = 0usize;
+        fields
+            .iter()
+            .map(|field| {
+                let start = offset.next_multiple_of(field.align());
+                offset = start + field.size();
+                start
+            })
+            .collect()
+    }
+
+    pub(crate) fn libffi(&self) -> Type {
+        match self {
+            FfiType::Void => Type::void(),
+            FfiType::Bool | FfiType::Char => Type::u8(),
+            FfiType::Int(kind) => match kind {
+                IntKind::I8 => Type::i8(),
+                IntKind::I16 => Type::i16(),
+                IntKind::I32 => Type::i32(),
+                IntKind::I64 => Type::i64(),
+                IntKind::U8 => Type::u8(),
+                IntKind::U16 => Type::u16(),
+                IntKind::U32 => Type::u32(),
+                IntKind::U64 => Type::u64(),
+                IntKind::USize => Type::usize(),
+            },
+            FfiType::Float => Type::f32(),
+            FfiType::Double => Type::f64(),
+            FfiType::Pointer | FfiType::String => Type::pointer(),
+            FfiType::Struct(fields) => Type::structure(fields.iter().map(|x| x.libffi())),
+        }
+    }
+}
+
+impl Display for FfiType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FfiType::Void => write!(f, "void"),
+            FfiType::Bool => write!(f, "bool"),
+            FfiType::Char => write!(f, "char"),
+            FfiType::Int(kind) => write!(f, "{}", kind),
+            FfiType::Float => write!(f, "float"),
+            FfiType::Double => write!(f, "double"),
+            FfiType::Pointer => write!(f, "pointer"),
+            FfiType::String => write!(f, "string"),
+            FfiType::Struct(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "struct {{ {} }}", fields)
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use crate::{FfiError, FfiType};
+use popper_ast::IntKind;
+use popper_flag::ValueFlag;
+use std::ffi::{c_void, CStr, CString};
+use std::fmt::Display;
+
+/// value given to or returned by a foreign function
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// an integer of the given kind, unsigned ones keeping their bits
+    Int(IntKind, i64),
+    Bool(bool),
+    Char(u8),
+    Float(f64),
+    Pointer(*mut c_void),
+    /// given as a NUL terminated copy, valid during the call
+    String(String),
+    /// fields of a struct, elements of a tuple or of a list
+    Struct(Vec<Value>),
+    Unit,
+}
+
+impl Value {
+    /// C type of the value, before the promotions of variadic arguments
+    pub fn ty(&self) -> FfiType {
+        match self {
+            Value::Int(kind, _) => FfiType::Int(*kind),
+            Value::Bool(_) => FfiType::Bool,
+            Value::Char(_) => FfiType::Char,
+            Value::Float(_) => FfiType::Float,
+            Value::Pointer(_) => FfiType::Pointer,
+            Value::String(_) => FfiType::String,
+            Value::Struct(fields) => FfiType::Struct(fields.iter().map(|x| x.ty()).collect()),
+            Value::Unit => FfiType::Void,
+        }
+    }
+
+    /// value read by `va_arg(ty)` from a variadic argument, which was promoted when
+    /// it was passed: the `int` of an `u8` is truncated back, the double of a float rounded
+    pub fn va_arg(&self, ty: &ValueFlag) -> Result<Value, FfiError> {
+        let invalid = || FfiError::InvalidArgument(self.to_string(), ty.to_string());
+        match (self, ty) {
+            (Value::Float(x), ValueFlag::Float) => Ok(Value::Float(*x as f32 as f64)),
+            (value, ValueFlag::Boolean) => value.as_int().map(|x| Value::Bool(x != 0)),
+            (value, ValueFlag::Char) => value.as_int().map(|x| Value::Char(x as u8)),
+            (value, ty) => match ty.int_kind() {
+                Some(kind) => value
+                    .as_int()
+                    .map(|x| Value::Int(kind, truncate(kind, x as u64))),
+                None if value.ty() == FfiType::Pointer || value.ty() == FfiType::String => {
+                    Ok(value.clone())
+                }
+                None => Err(invalid()),
+            },
+        }
+        .map_err(|_| invalid())
+    }
+
+    fn as_int(&self) -> Result<i64, FfiError> {
+        match self {
+            Value::Int(_, x) => Ok(*x),
+            Value::Bool(x) => Ok(*x as i64),
+            Value::Char(x) => Ok(*x as i64),
+            value => Err(FfiError::InvalidArgument(
+                value.to_string(),
+                "int".to_string(),
+            )),
+        }
+    }
+
+    /// write the value in `buf` as a `ty`, keeping the strings it points to in `strings`
+    pub(crate) fn write(
+        &self,
+        ty: &FfiType,
+        buf: &mut [u8],
+        strings: &mut Vec<CString>,
+    ) -> Result<(), FfiError> {
+        let invalid = || FfiError::InvalidArgument(self.to_string(), ty.to_string());
+        match (self, ty) {
+            (value, FfiType::Int(_) | FfiType::Bool | FfiType::Char) => {
+                let bytes = value.as_int().map_err(|_| invalid())?.to_ne_bytes();
+                let size = ty.size();
+                // the low bytes of the integer
+                let low = if cfg!(target_endian = "little") {
+                    &bytes[..size]
+                } else {
+                    &bytes[8 - size..]
+                };
+                buf[..size].copy_from_slice(low);
+            }
+            (Value::Float(x), FfiType::Float) => {
+                buf[..4].copy_from_slice(&(*x as f32).to_ne_bytes())
+            }
+            (Value::Float(x), FfiType::Double) => buf[..8].copy_from_slice(&x.to_ne_bytes()),
+            (Value::Pointer(ptr), FfiType::Pointer | FfiType::String) => write_pointer(buf, *ptr),
+            (Value::String(s), FfiType::String | FfiType::Pointer) => {
+                let s = CString::new(s.as_str()).map_err(|_| invalid())?;
+                write_pointer(buf, s.as_ptr() as *mut c_void);
+                // the heap buffer of the string doesn't move with it
+                strings.push(s);
+            }
+            (Value::Struct(values), FfiType::Struct(fields)) if values.len() == fields.len() => {
+                for ((value, field), offset) in values.iter().zip(fields).zip(ty.offsets()) {
+                    value.write(field, &mut buf[offset..], strings)?;
+                }
+            }
+            (Value::Unit, FfiType::Void) => {}
+            _ => return Err(invalid()),
+        }
+        Ok(())
+    }
+
+    /// read a `ty` from `buf`
+    pub(crate) fn read(ty: &FfiType, buf: &[u8]) -> Value {
+        match ty {
+            FfiType::Void => Value::Unit,
+            FfiType::Bool => Value::Bool(buf[0] != 0),
+            FfiType::Char => Value::Char(buf[0]),
+            FfiType::Int(_) => {
+                let size = ty.size();
+                let mut bytes = [0; 8];
+                if cfg!(target_endian = "little") {
+                    bytes[..size].copy_from_slice(&buf[..size]);
+                } else {
+                    bytes[8 - size..].copy_from_slice(&buf[..size]);
+                }
+                Self::from_bits(ty, u64::from_ne_bytes(bytes))
+            }
+            FfiType::Float => Value::Float(f32::from_ne_bytes(buf[..4].try_into().unwrap()) as f64),
+            FfiType::Double => Value::Float(f64::from_ne_bytes(buf[..8].try_into().unwrap())),
+            FfiType::Pointer => Value::Pointer(read_pointer(buf)),
+            FfiType::String => {
+                let ptr = read_pointer(buf);
+                if ptr.is_null() {
+                    return Value::Pointer(ptr);
+                }
+                let s = unsafe { CStr::from_ptr(ptr as *const _) };
+                Value::String(s.to_string_lossy().into_owned())
+            }
+            FfiType::Struct(fields) => Value::Struct(
+                fields
+                    .iter()
+                    .zip(ty.offsets())
+                    .map(|(field, offset)| Self::read(field, &buf[offset..]))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// read the value returned as a `ty`: libffi widens the integers smaller than
+    /// a register to a whole register
+    pub(crate) fn read_return(ty: &FfiType, buf: &[u8]) -> Value {
+        match ty {
+            FfiType::Int(_) | FfiType::Bool | FfiType::Char if ty.size() < 8 => {
+                Self::from_bits(ty, u64::from_ne_bytes(buf[..8].try_into().unwrap()))
+            }
+            ty => Self::read(ty, buf),
+        }
+    }
+
+    /// integer value of the low bits of `bits`
+    fn from_bits(ty: &FfiType, bits: u64) -> Value {
+        match ty {
+            FfiType::Bool => Value::Bool(bits as u8 != 0),
+            FfiType::Char => Value::Char(bits as u8),
+            FfiType::Int(kind) => Value::Int(*kind, truncate(*kind, bits)),
+            _ => unreachable!("{} isn't an integer", ty),
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(kind, x) if kind.is_signed() => write!(f, "{}", x),
+            Value::Int(_, x) => write!(f, "{}", *x as u64),
+            Value::Bool(x) => write!(f, "{}", x),
+            Value::Char(x) => write!(f, "'{}'", *x as char),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Pointer(x) => write!(f, "{:p}", x),
+            Value::String(x) => write!(f, "{:?}", x),
+            Value::Struct(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "({})", fields)
+            }
+            Value::Unit => write!(f, "()"),
+        }
+    }
+}
+
+/// the low bits of `bits` for an integer of kind `kind`, sign extended when it's signed
+fn truncate(kind: IntKind, bits: u64) -> i64 {
+    let shift = 64 - kind.bits();
+    if kind.is_signed() {
+        ((bits << shift) as i64) >> shift
+    } else {
+        ((bits << shift) >> shift) as i64
+    }
+}
+
+fn write_pointer(buf: &mut [u8], ptr: *mut c_void) {
+    let bytes = (ptr as usize).to_ne_bytes();
+    buf[..bytes.len()].copy_from_slice(&bytes);
+}
+
+fn read_pointer(buf: &[u8]) -> *mut c_void {
+    let size = std::mem::size_of::<usize>();
+    usize::from_ne_bytes(buf[..size].try_into().unwrap()) as *mut c_void
+}
@@ -0,0 +1,127 @@
+use popper_ast::IntKind;
+use popper_ffi::{Ffi, FfiError, Value};
+use popper_flag::ValueFlag;
+use popper_parser::parser::try_parse;
+use std::path::Path;
+
+const SOURCE: &str = r#"
+struct div_t {
+    quot = int,
+    rem = int
+}
+
+extern {
+    func abs(x: int): int,
+    func labs(x: i64): i64,
+    func strlen(s: string): usize,
+    func toupper(c: int): int,
+    func div(a: int, b: int): struct div_t,
+    func getenv(name: string): string,
+    func snprintf(buf: *char, size: usize, format: string ...): int
+};
+"#;
+
+fn ffi() -> Ffi {
+    let stmts = try_parse(SOURCE).expect("valid source");
+    let mut ffi = Ffi::new();
+    ffi.declare_module(&stmts, Path::new("libc.pop"))
+        .expect("libc functions");
+    ffi
+}
+
+fn int(x: i64) -> Value {
+    Value::Int(IntKind::I32, x)
+}
+
+#[test]
+fn integers() {
+    let ffi = ffi();
+    assert_eq!(ffi.call("abs", &[int(-3)]), Ok(int(3)));
+    assert_eq!(
+        ffi.call("labs", &[Value::Int(IntKind::I64, -(1 << 40))]),
+        Ok(Value::Int(IntKind::I64, 1 << 40))
+    );
+    // a char is given as an `int`
+    assert_eq!(
+        ffi.call("toupper", &[Value::Char(b'a')]),
+        Ok(int(b'A' as i64))
+    );
+}
+
+#[test]
+fn strings() {
+    let ffi = ffi();
+    assert_eq!(
+        ffi.call("strlen", &[Value::String("popper".to_string())]),
+        Ok(Value::Int(IntKind::USize, 6))
+    );
+    std::env::set_var("POPPER_FFI_TEST", "value");
+    assert_eq!(
+        ffi.call("getenv", &[Value::String("POPPER_FFI_TEST".to_string())]),
+        Ok(Value::String("value".to_string()))
+    );
+}
+
+#[test]
+fn struct_by_value() {
+    assert_eq!(
+        ffi().call("div", &[int(7), int(2)]),
+        Ok(Value::Struct(vec![int(3), int(1)]))
+    );
+}
+
+#[test]
+fn variadic() {
+    let ffi = ffi();
+    let mut buf = vec![0u8; 64];
+    let len = ffi.call(
+        "snprintf",
+        &[
+            Value::Pointer(buf.as_mut_ptr() as *mut _),
+            Value::Int(IntKind::USize, buf.len() as i64),
+            Value::String("%d %lld %c %d %s %.1f".to_string()),
+            int(-1),
+            Value::Int(IntKind::I64, 1 << 40),
+            // promoted to `int`
+            Value::Char(b'x'),
+            Value::Bool(true),
+            Value::String("s".to_string()),
+            // promoted to `double`
+            Value::Float(1.5),
+        ],
+    );
+    let expected = "-1 1099511627776 x 1 s 1.5";
+    assert_eq!(len, Ok(int(expected.len() as i64)));
+    assert_eq!(&buf[..expected.len()], expected.as_bytes());
+}
+
+#[test]
+fn va_arg_promotions() {
+    let promoted = int(0x1ff);
+    assert_eq!(
+        promoted.va_arg(&ValueFlag::SizedInteger(IntKind::U8)),
+        Ok(Value::Int(IntKind::U8, 0xff))
+    );
+    assert_eq!(promoted.va_arg(&ValueFlag::Boolean), Ok(Value::Bool(true)));
+    assert_eq!(
+        Value::Float(0.1).va_arg(&ValueFlag::Float),
+        Ok(Value::Float(0.1f32 as f64))
+    );
+}
+
+#[test]
+fn errors() {
+    let mut ffi = ffi();
+    assert_eq!(
+        ffi.call("abs", &[]),
+        Err(FfiError::ArgumentCount("abs".to_string(), 1, 0))
+    );
+    assert!(matches!(
+        ffi.call("abs", &[Value::String("1".to_string())]),
+        Err(FfiError::InvalidArgument(..))
+    ));
+    assert_eq!(
+        ffi.declare("popper_not_a_symbol", &[], &ValueFlag::Integer, false),
+        Err(FfiError::SymbolNotFound("popper_not_a_symbol".to_string()))
+    );
+}
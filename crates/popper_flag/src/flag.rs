@@ -2,6 +2,7 @@ use crate::value_flag::ValueFlag;
 use crate::variable_flag::VariableFlag;
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// Flag in `Popper-Lang`, flag is used for save program information as a flag(s)
 pub enum Flag {
     Variable(VariableFlag),
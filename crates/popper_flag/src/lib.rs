@@ -1,4 +1,4 @@
-#![allow(clippy::borrowed_box)]
+#![allow(clippy::borrowed_box, clippy::large_enum_variant)]
 
 mod flag;
 mod scope_flag;
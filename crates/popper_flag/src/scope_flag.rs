@@ -1,5 +1,6 @@
 /// Scope flag is used to save the scope
 #[derive(Debug, Default, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ScopeFlag {
     #[default]
     Global,
@@ -3,6 +3,7 @@ use popper_ast::Span;
 
 /// save all flags in this struct
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SymbolFlags {
     pub symbols: Vec<Flag>,
     pub span: Span,
@@ -51,8 +52,8 @@ impl SymbolFlags {
         self
     }
 
-    pub fn set_string(&mut self, size: u32) -> &mut Self {
-        self.add_flag(Flag::Value(ValueFlag::String(size)));
+    pub fn set_string(&mut self) -> &mut Self {
+        self.add_flag(Flag::Value(ValueFlag::String));
         self
     }
 
@@ -66,6 +67,11 @@ impl SymbolFlags {
         self
     }
 
+    pub fn set_tuple(&mut self, value_flags: Vec<ValueFlag>) -> &mut Self {
+        self.add_flag(Flag::Value(ValueFlag::Tuple(value_flags)));
+        self
+    }
+
     pub fn set_function(
         &mut self,
         args: Vec<ValueFlag>,
@@ -135,7 +141,7 @@ impl SymbolFlags {
     pub fn is_string(&self) -> bool {
         self.symbols
             .iter()
-            .any(|s| matches!(s, Flag::Value(ValueFlag::String(_))))
+            .any(|s| s == &Flag::Value(ValueFlag::String))
     }
 
     pub fn is_boolean(&self) -> bool {
@@ -180,6 +186,14 @@ impl SymbolFlags {
         })
     }
 
+    pub fn get_tuple(&self) -> Option<Vec<ValueFlag>> {
+        self.symbols.iter().find_map(|s| match s {
+            Flag::Value(ValueFlag::Tuple(v)) => Some(v.clone()),
+            Flag::Variable(v) => v.value.get_tuple(),
+            _ => None,
+        })
+    }
+
     pub fn get_minor_type(&self) -> Option<ValueFlag> {
         self.symbols.iter().find_map(|s| match s {
             Flag::Value(ValueFlag::Pointer(v)) => Some(*v.clone()),
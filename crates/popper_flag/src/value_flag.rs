@@ -1,31 +1,50 @@
-use popper_ast::{Type, TypeKind};
-use std::collections::HashMap;
+use crate::Environment;
+use popper_ast::{IntKind, Span, Type, TypeKind};
 use std::fmt::Display;
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ValueFlag {
     Integer,
+    SizedInteger(IntKind),
+    Char,
     Float,
-    String(u32),
+    String,
     Boolean,
     None,
     List(Box<ValueFlag>, usize),
+    Slice(Box<ValueFlag>),
+    Vec(Box<ValueFlag>),
+    Tuple(Vec<ValueFlag>),
     Function(Vec<ValueFlag>, Box<ValueFlag>, bool),
     Struct(String),
     StructInstance(String),
     Pointer(Box<ValueFlag>),
-    Module(HashMap<String, String>),
+    /// an imported module, with the environment it was analyzed in
+    Module(Box<Environment>),
 }
 
 impl Display for ValueFlag {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ValueFlag::Integer => write!(f, "int"),
+            ValueFlag::SizedInteger(kind) => write!(f, "{}", kind),
+            ValueFlag::Char => write!(f, "char"),
             ValueFlag::Float => write!(f, "float"),
-            ValueFlag::String(len) => write!(f, "string:{}", len),
+            ValueFlag::String => write!(f, "string"),
             ValueFlag::Boolean => write!(f, "bool"),
             ValueFlag::None => write!(f, "unit"),
             ValueFlag::List(t, u) => write!(f, "[{}: {}]", t, u),
+            ValueFlag::Slice(t) => write!(f, "[{}]", t),
+            ValueFlag::Vec(t) => write!(f, "vec<{}>", t),
+            ValueFlag::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "({})", elements)
+            }
             ValueFlag::Function(args, returntype, is_var_args) => {
                 let mut args_string = String::new();
                 for arg in args {
@@ -47,7 +66,7 @@ impl Display for ValueFlag {
             }
 
             ValueFlag::StructInstance(name) => write!(f, "struct({})", name),
-            ValueFlag::Module(hash) => write!(f, "module({:?})", hash),
+            ValueFlag::Module(_) => write!(f, "module"),
         }
     }
 }
@@ -55,11 +74,18 @@ impl Display for ValueFlag {
 impl ValueFlag {
     pub fn from_ty_kind(ty: TypeKind) -> Self {
         match ty {
-            TypeKind::String(len) => ValueFlag::String(len),
+            TypeKind::String => ValueFlag::String,
             TypeKind::Bool => ValueFlag::Boolean,
             TypeKind::Int => ValueFlag::Integer,
+            TypeKind::SizedInt(kind) => Self::from_int_kind(kind),
+            TypeKind::Char => ValueFlag::Char,
             TypeKind::Unit => ValueFlag::None,
             TypeKind::List(ty, l) => ValueFlag::List(Box::new(Self::from_ty(*ty)), l),
+            TypeKind::Slice(ty) => ValueFlag::Slice(Box::new(Self::from_ty(*ty))),
+            TypeKind::Vec(ty) => ValueFlag::Vec(Box::new(Self::from_ty(*ty))),
+            TypeKind::Tuple(types) => {
+                ValueFlag::Tuple(types.into_iter().map(Self::from_ty).collect())
+            }
             TypeKind::Function(args, ret, var) => ValueFlag::Function(
                 args.iter().cloned().map(Self::from_ty).collect(),
                 Box::new(Self::from_ty(*ret)),
@@ -78,9 +104,81 @@ impl ValueFlag {
         Self::from_ty_kind(ty.type_kind)
     }
 
+    /// the type values of this type are written with, none for a module
+    pub fn to_ty_kind(&self) -> Option<TypeKind> {
+        let ty = |x: &ValueFlag| {
+            x.to_ty_kind()
+                .map(|kind| Type::new(Span::new(0, 0), kind, vec![]))
+        };
+        Some(match self {
+            ValueFlag::Integer => TypeKind::Int,
+            ValueFlag::SizedInteger(kind) => TypeKind::SizedInt(*kind),
+            ValueFlag::Char => TypeKind::Char,
+            ValueFlag::Float => TypeKind::Float,
+            ValueFlag::String => TypeKind::String,
+            ValueFlag::Boolean => TypeKind::Bool,
+            ValueFlag::None => TypeKind::Unit,
+            ValueFlag::List(t, n) => TypeKind::List(Box::new(ty(t)?), *n),
+            ValueFlag::Slice(t) => TypeKind::Slice(Box::new(ty(t)?)),
+            ValueFlag::Vec(t) => TypeKind::Vec(Box::new(ty(t)?)),
+            ValueFlag::Tuple(elements) => {
+                TypeKind::Tuple(elements.iter().map(ty).collect::<Option<_>>()?)
+            }
+            ValueFlag::Function(args, ret, var) => TypeKind::Function(
+                args.iter().map(ty).collect::<Option<_>>()?,
+                Box::new(ty(ret)?),
+                *var,
+            ),
+            ValueFlag::Struct(name) => TypeKind::Struct(name.clone()),
+            ValueFlag::StructInstance(name) => TypeKind::StructInstance(name.clone()),
+            ValueFlag::Pointer(t) => TypeKind::Pointer(Box::new(ty(t)?)),
+            ValueFlag::Module(_) => return None,
+        })
+    }
+
+    pub fn from_int_kind(kind: IntKind) -> Self {
+        match kind {
+            IntKind::I32 => ValueFlag::Integer,
+            kind => ValueFlag::SizedInteger(kind),
+        }
+    }
+
+    /// the integer kind of an integer value, `int` being `i32`
+    pub fn int_kind(&self) -> Option<IntKind> {
+        match self {
+            ValueFlag::Integer => Some(IntKind::I32),
+            ValueFlag::SizedInteger(kind) => Some(*kind),
+            _ => None,
+        }
+    }
+
+    /// check if an `as` conversion from `self` to `other` is allowed
+    pub fn is_castable_to(&self, other: &Self) -> bool {
+        let is_integer_like = |x: &Self| x.int_kind().is_some() || matches!(x, ValueFlag::Char);
+        match (self, other) {
+            (a, b) if a == b => true,
+            (ValueFlag::Pointer(_), ValueFlag::Pointer(_)) => true,
+            (ValueFlag::Pointer(_), ValueFlag::SizedInteger(IntKind::USize)) => true,
+            (ValueFlag::SizedInteger(IntKind::USize), ValueFlag::Pointer(_)) => true,
+            (ValueFlag::Float, b) => b.int_kind().is_some(),
+            (a, b) if is_integer_like(a) || matches!(a, ValueFlag::Boolean) => {
+                is_integer_like(b) || matches!(b, ValueFlag::Float)
+            }
+            _ => false,
+        }
+    }
+
+    /// type of the elements of an indexable value: a list, a slice or a vector
+    pub fn get_element_type(&self) -> Option<&ValueFlag> {
+        match self {
+            ValueFlag::List(ty, _) | ValueFlag::Slice(ty) | ValueFlag::Vec(ty) => Some(ty),
+            _ => None,
+        }
+    }
+
     pub fn get_minor_type(&self) -> Option<&ValueFlag> {
         match self {
-            ValueFlag::List(ty, _) => Some(ty),
+            ValueFlag::List(ty, _) | ValueFlag::Slice(ty) | ValueFlag::Vec(ty) => Some(ty),
             ValueFlag::Pointer(ty) => Some(ty),
             _ => None,
         }
@@ -107,17 +205,22 @@ impl PartialEq for ValueFlag {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (ValueFlag::Integer, ValueFlag::Integer) => true,
+            (ValueFlag::SizedInteger(kind1), ValueFlag::SizedInteger(kind2)) => kind1 == kind2,
+            (ValueFlag::Char, ValueFlag::Char) => true,
             (ValueFlag::Float, ValueFlag::Float) => true,
-            (ValueFlag::String(_), ValueFlag::String(_)) => true,
+            (ValueFlag::String, ValueFlag::String) => true,
             (ValueFlag::Boolean, ValueFlag::Boolean) => true,
             (ValueFlag::None, ValueFlag::None) => true,
             (ValueFlag::List(ty1, len1), ValueFlag::List(ty2, len2)) => ty1 == ty2 && len1 == len2,
+            (ValueFlag::Slice(ty1), ValueFlag::Slice(ty2)) => ty1 == ty2,
+            (ValueFlag::Vec(ty1), ValueFlag::Vec(ty2)) => ty1 == ty2,
+            (ValueFlag::Tuple(elements1), ValueFlag::Tuple(elements2)) => elements1 == elements2,
             (ValueFlag::Function(args1, ret1, a1), ValueFlag::Function(args2, ret2, a2)) => {
                 args1 == args2 && ret1 == ret2 && a1 == a2
             }
             (ValueFlag::Struct(fields1), ValueFlag::Struct(fields2)) => fields1 == fields2,
             (ValueFlag::StructInstance(name1), ValueFlag::StructInstance(name2)) => name1 == name2,
-            (ValueFlag::Module(hash1), ValueFlag::Module(hash2)) => hash1 == hash2,
+            (ValueFlag::Module(env1), ValueFlag::Module(env2)) => env1 == env2,
             (ValueFlag::Pointer(ty1), ValueFlag::Pointer(ty2)) => ty1 == ty2,
             _ => false,
         }
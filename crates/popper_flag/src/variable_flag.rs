@@ -1,10 +1,11 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::scope_flag::ScopeFlag;
 
 use crate::{SymbolFlags, ValueFlag};
 use popper_ast::Span;
 
 #[derive(PartialEq, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// variable flag is used to store Variable information
 pub struct VariableFlag {
     pub name: String,
@@ -44,9 +45,14 @@ impl VariableFlag {
 
 /// store Variable in environement
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Environment {
     pub variables: Vec<VariableFlag>,
-    pub struct_env: HashMap<String, HashMap<String, ValueFlag>>
+    pub struct_env: HashMap<String, HashMap<String, ValueFlag>>,
+    /// names visible from the modules importing this one
+    pub public: HashSet<String>,
+    /// fields of structs defined in other modules that are not `pub`
+    pub private_fields: HashMap<String, HashSet<String>>,
 }
 
 impl Default for Environment {
@@ -59,13 +65,17 @@ impl Environment {
     pub fn new() -> Self {
         Self {
             variables: Vec::new(),
-            struct_env: HashMap::new()
+            struct_env: HashMap::new(),
+            public: HashSet::new(),
+            private_fields: HashMap::new(),
         }
     }
     
     pub fn keep_static_member(&self) -> Self {
         let mut new_env = Environment::new();
         new_env.struct_env = self.struct_env.clone();
+        new_env.public = self.public.clone();
+        new_env.private_fields = self.private_fields.clone();
         for variable in &self.variables {
             if variable.value
                 .get_value().unwrap().is_static() {
@@ -135,4 +145,41 @@ impl Environment {
     pub fn get_struct(&self, name: &str) -> Option<&HashMap<String, ValueFlag>> {
         self.struct_env.get(name)
     }
+
+    pub fn set_public(&mut self, name: String) -> &mut Self {
+        self.public.insert(name);
+        self
+    }
+
+    pub fn is_public(&self, name: &str) -> bool {
+        self.public.contains(name)
+    }
+
+    /// check if `field` of the struct `name` can't be used here
+    pub fn is_private_field(&self, name: &str, field: &str) -> bool {
+        self.private_fields
+            .get(name)
+            .map(|x| x.contains(field))
+            .unwrap_or(false)
+    }
+
+    /// make the structs of an imported module known, hiding their private `fields`
+    pub fn import_structs(
+        &mut self,
+        module: &Environment,
+        private_fields: HashMap<String, HashSet<String>>,
+    ) -> &mut Self {
+        for (name, fields) in &module.struct_env {
+            self.struct_env
+                .entry(name.clone())
+                .or_insert_with(|| fields.clone());
+        }
+        for (name, fields) in module.private_fields.iter().chain(&private_fields) {
+            self.private_fields
+                .entry(name.clone())
+                .or_default()
+                .extend(fields.iter().cloned());
+        }
+        self
+    }
 }
@@ -0,0 +1,99 @@
+/// what the source holds between two statements, besides the tokens the printer writes again
+#[derive(Debug, PartialEq)]
+pub enum GapItem {
+    /// at least one empty line
+    Blank,
+    /// a `//` comment on its own line
+    Comment(String),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Gap {
+    /// `//` comment at the end of the line of the previous statement
+    pub trailing: Option<String>,
+    pub items: Vec<GapItem>,
+}
+
+impl Gap {
+    ///
+    /// scan the text between two statements, or between the start of a block and its
+    /// first statement when `after_stmt` is false
+    pub fn scan(text: &str, after_stmt: bool) -> Self {
+        let mut gap = Gap::default();
+        let lines: Vec<&str> = text.split('\n').collect();
+        let last = lines.len() - 1;
+
+        for (i, line) in lines.iter().enumerate() {
+            // documentation comments are in the ast, and printed with their item
+            let comment = line
+                .find("//")
+                .filter(|x| !line[*x..].starts_with("///") && !line[*x..].starts_with("//!"));
+            match comment {
+                Some(start) => {
+                    let comment = line[start..].trim_end().to_string();
+                    if i == 0 && after_stmt {
+                        gap.trailing = Some(comment);
+                    } else {
+                        gap.items.push(GapItem::Comment(comment));
+                    }
+                }
+                // the first and the last lines are shared with the code around the gap
+                None if i != 0
+                    && i != last
+                    && line.trim().is_empty()
+                    && gap.items.last() != Some(&GapItem::Blank) =>
+                {
+                    gap.items.push(GapItem::Blank);
+                }
+                None => {}
+            }
+        }
+
+        gap
+    }
+}
+
+/// a `//` comment of the source, documentation comments left out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// line of the comment, from 1
+    pub line: usize,
+    pub text: String,
+}
+
+/// the `//` comments of `source`, the ones in string and character literals left out
+pub fn comments(source: &str) -> Vec<Comment> {
+    let mut comments = Vec::new();
+    let mut line = 1;
+    let mut chars = source.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            '"' | '\'' => {
+                while let Some((_, x)) = chars.next() {
+                    match x {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '\n' => line += 1,
+                        x if x == c => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if source[i..].starts_with("//") => {
+                let end = source[i..].find('\n').map_or(source.len(), |x| i + x);
+                let text = &source[i..end];
+                if !text.starts_with("///") && !text.starts_with("//!") {
+                    comments.push(Comment {
+                        line,
+                        text: text.trim_end().to_string(),
+                    });
+                }
+                while chars.next_if(|(x, _)| *x < end).is_some() {}
+            }
+            _ => {}
+        }
+    }
+    comments
+}
@@ -0,0 +1,629 @@
+use popper_ast::visitor::{ExprVisitor, StmtVisitor};
+use popper_ast::*;
+use std::convert::Infallible;
+
+use crate::comments::{Gap, GapItem};
+
+const INDENT: &str = "    ";
+
+pub struct Formatter<'a> {
+    /// source the statements were parsed from, where the comments are taken from
+    source: &'a str,
+    out: String,
+    indent: usize,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            out: String::new(),
+            indent: 0,
+        }
+    }
+
+    pub fn finish(self) -> String {
+        let out = self.out.trim_start_matches('\n');
+        if out.is_empty() {
+            String::new()
+        } else {
+            format!("{}\n", out)
+        }
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    ///
+    /// write `stmts` one per line, with the comments and the empty lines of the source
+    /// between `start` and `end`
+    ///
+    /// return: if anything was written
+    pub fn statements(&mut self, stmts: Vec<Statement>, start: usize, end: usize) -> bool {
+        let mut written = false;
+        let mut pos = start;
+        for stmt in stmts {
+            let span = stmt.span();
+            self.gap(pos, span.start, &mut written, true);
+            self.newline();
+            let Ok(()) = self.visit_stmt(stmt);
+            written = true;
+            pos = span.end;
+        }
+        self.gap(pos, end, &mut written, false);
+        written
+    }
+
+    /// write the comments of the source between `start` and `end`, and the empty line
+    /// before the next statement if `before_stmt`
+    fn gap(&mut self, start: usize, end: usize, written: &mut bool, before_stmt: bool) {
+        let text = self.source.get(start..end.max(start)).unwrap_or_default();
+        let gap = Gap::scan(text, *written);
+        if let Some(comment) = gap.trailing {
+            self.out.push(' ');
+            self.out.push_str(&comment);
+        }
+
+        let mut blank = false;
+        for item in gap.items {
+            match item {
+                GapItem::Blank => blank = *written,
+                GapItem::Comment(comment) => {
+                    if blank {
+                        self.out.push('\n');
+                        blank = false;
+                    }
+                    self.newline();
+                    self.out.push_str(&comment);
+                    *written = true;
+                }
+            }
+        }
+        // empty lines at the end of a block are dropped
+        if blank && before_stmt {
+            self.out.push('\n');
+        }
+    }
+
+    /// `{ <stmts> }`, `start` and `end` being the span of the block in the source
+    fn block(&mut self, stmts: Vec<Statement>, start: usize, end: usize) {
+        self.out.push('{');
+        self.indent += 1;
+        let written = self.statements(stmts, start, end);
+        self.indent -= 1;
+        if written {
+            self.newline();
+        }
+        self.out.push('}');
+    }
+
+    fn expr(&mut self, expr: Expression) -> String {
+        let Ok(expr) = self.visit_expr(expr);
+        expr
+    }
+
+    fn exprs(&mut self, exprs: Vec<Expression>) -> String {
+        exprs
+            .into_iter()
+            .map(|expr| self.expr(expr))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// `///` comments of an item, each one on its own line
+    fn doc(&mut self, doc: &Option<String>) {
+        for line in doc.iter().flat_map(|x| x.split('\n')) {
+            self.out.push_str("///");
+            if !line.is_empty() {
+                self.out.push(' ');
+                self.out.push_str(line);
+            }
+            self.newline();
+        }
+    }
+
+    fn sign(&self, sign: &FunctionSign) -> String {
+        format!(
+            "func {}({}): {}",
+            sign.name,
+            arguments(&sign.arguments, sign.is_var_args),
+            ty(&sign.return_type)
+        )
+    }
+
+    /// `{ <signs> }` of `extern` and `external`, `span` being the one of the block
+    fn signs(&mut self, signs: &[FunctionSign], span: Span) {
+        let items = signs
+            .iter()
+            .map(|sign| (sign.span(), sign.doc.clone(), self.sign(sign)))
+            .collect();
+        self.items(items, span);
+    }
+
+    /// `{ <items> }` separated by commas, one per line with its documentation, and the
+    /// comments of the source between them. `span` is the one of the statement holding
+    /// them
+    fn items(&mut self, items: Vec<(Span, Option<String>, String)>, span: Span) {
+        // the comments start after the `{`
+        let start = span.start
+            + self
+                .source
+                .get(span.start..span.end)
+                .and_then(|x| x.find('{'))
+                .map_or(0, |x| x + 1);
+        self.out.push('{');
+        self.indent += 1;
+        let mut written = false;
+        let mut pos = start;
+        let last = items.len().saturating_sub(1);
+        for (i, (span, doc, item)) in items.into_iter().enumerate() {
+            self.gap(pos, span.start, &mut written, true);
+            self.newline();
+            self.doc(&doc);
+            self.out.push_str(&item);
+            if i != last {
+                self.out.push(',');
+            }
+            written = true;
+            pos = span.end;
+        }
+        self.gap(pos, span.end, &mut written, false);
+        self.indent -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+}
+
+/// a type, as written in the source
+pub fn ty(ty: &Type) -> String {
+    let kind = match &ty.type_kind {
+        TypeKind::Tuple(types) => format!("({})", types_list(types)),
+        TypeKind::List(elt, size) => format!("[{}: {}]", self::ty(elt), size),
+        TypeKind::Slice(elt) => format!("[{}]", self::ty(elt)),
+        TypeKind::Vec(elt) => format!("vec<{}>", self::ty(elt)),
+        TypeKind::Pointer(elt) => format!("*{}", self::ty(elt)),
+        TypeKind::Unit => "unit".to_string(),
+        TypeKind::Struct(name) | TypeKind::StructInstance(name) => format!("struct {}", name),
+        kind => kind.to_string(),
+    };
+
+    if ty.generics.is_empty() {
+        kind
+    } else {
+        format!("{}<{}>", kind, types_list(&ty.generics))
+    }
+}
+
+fn types_list(types: &[Type]) -> String {
+    types.iter().map(ty).collect::<Vec<_>>().join(", ")
+}
+
+/// arguments of a function, without the parentheses
+fn arguments(arguments: &Arguments, is_var_args: bool) -> String {
+    let args = arguments
+        .args
+        .iter()
+        .map(|arg| format!("{}: {}", arg.name, ty(&arg.ty)))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    match (is_var_args, args.is_empty()) {
+        (false, _) => args,
+        (true, true) => "...".to_string(),
+        (true, false) => format!("{} ...", args),
+    }
+}
+
+fn bin_op(op: &BinOpKind) -> &'static str {
+    match op {
+        BinOpKind::Add => "+",
+        BinOpKind::Sub => "-",
+        BinOpKind::Mul => "*",
+        BinOpKind::Div => "/",
+        BinOpKind::Mod => "%",
+        BinOpKind::Pow => "^",
+        BinOpKind::Eq => "eq",
+        BinOpKind::Neq => "ne",
+        BinOpKind::Lt => "lt",
+        BinOpKind::Lte => "le",
+        BinOpKind::Gt => "gt",
+        BinOpKind::Gte => "ge",
+        BinOpKind::And => "&&",
+        BinOpKind::Or => "||",
+    }
+}
+
+fn char_literal(c: char) -> String {
+    match c {
+        '\n' => "'\\n'".to_string(),
+        '\t' => "'\\t'".to_string(),
+        '\r' => "'\\r'".to_string(),
+        '\0' => "'\\0'".to_string(),
+        '\\' => "'\\\\'".to_string(),
+        '\'' => "'\\''".to_string(),
+        c => format!("'{}'", c),
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '\n' => literal.push_str("\\n"),
+            '\t' => literal.push_str("\\t"),
+            '\r' => literal.push_str("\\r"),
+            '\0' => literal.push_str("\\0"),
+            '\\' => literal.push_str("\\\\"),
+            '"' => literal.push_str("\\\""),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+impl ExprVisitor for Formatter<'_> {
+    type Output = String;
+    type Error = Infallible;
+
+    fn visit_constant(&mut self, constant: Constant) -> Result<Self::Output, Self::Error> {
+        Ok(match constant {
+            Constant::Ident(ident) => ident.name,
+            Constant::Int(int) => int.value.to_string(),
+            Constant::Float(float) => format!("{:?}", float.value),
+            // the parser keeps the escapes of string literals as they are written
+            Constant::StringLiteral(string) => string_literal(&string.value),
+            Constant::Char(c) => char_literal(c.value),
+            Constant::List(list) => format!("[{}]", self.exprs(list.value)),
+            Constant::Tuple(tuple) => format!("({})", self.exprs(tuple.value)),
+            Constant::Bool(bool) => bool.value.to_string(),
+            Constant::Null(_) => "null".to_string(),
+        })
+    }
+
+    fn visit_bin_op(&mut self, bin_op: BinOp) -> Result<Self::Output, Self::Error> {
+        Ok(format!(
+            "{} {} {}",
+            self.expr(*bin_op.lhs),
+            self::bin_op(&bin_op.op),
+            self.expr(*bin_op.rhs)
+        ))
+    }
+
+    fn visit_unary_op(&mut self, unary_op: UnaryOp) -> Result<Self::Output, Self::Error> {
+        Ok(format!("{}{}", unary_op.op, self.expr(*unary_op.expr)))
+    }
+
+    fn visit_cast(&mut self, cast: Cast) -> Result<Self::Output, Self::Error> {
+        Ok(format!("{} as {}", self.expr(*cast.expr), ty(&cast.ty)))
+    }
+
+    fn visit_group(&mut self, group: ParenGroup) -> Result<Self::Output, Self::Error> {
+        Ok(format!("({})", self.expr(*group.expr)))
+    }
+
+    fn visit_expr(&mut self, expr: Expression) -> Result<Self::Output, Self::Error> {
+        match expr {
+            Expression::Constant(constant) => self.visit_constant(constant),
+            Expression::BinOp(bin_op) => self.visit_bin_op(bin_op),
+            Expression::UnaryOp(unary_op) => self.visit_unary_op(unary_op),
+            Expression::Cast(cast) => self.visit_cast(cast),
+            Expression::Group(group) => self.visit_group(group),
+            Expression::Call(call) => self.visit_call(call),
+            Expression::StructInstance(struct_instance) => {
+                self.visit_struct_instance(struct_instance)
+            }
+            Expression::StructFieldAccess(struct_field_access) => {
+                self.visit_struct_field_access(struct_field_access)
+            }
+            Expression::Index(index) => self.visit_index(index),
+            Expression::Slice(slice) => self.visit_slice(slice),
+            Expression::TupleAccess(tuple_access) => self.visit_tuple_access(tuple_access),
+            Expression::VaArg(va_arg) => self.visit_va_arg(va_arg),
+            Expression::Reference(reference) => self.visit_reference(reference),
+            Expression::Deref(pointer) => self.visit_deref(pointer),
+        }
+    }
+
+    fn visit_call(&mut self, call: Call) -> Result<Self::Output, Self::Error> {
+        let args = self.exprs(call.arguments);
+        Ok(match call.module {
+            Some(module) => format!("{}.{}({})", module.name, call.name, args),
+            None => format!("{}({})", call.name, args),
+        })
+    }
+
+    fn visit_struct_instance(
+        &mut self,
+        struct_instance: StructInstance,
+    ) -> Result<Self::Output, Self::Error> {
+        let fields = struct_instance
+            .fields
+            .into_iter()
+            .map(|field| format!("{} = {}", field.name, self.expr(field.value)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok(format!("init {} {{ {} }}", struct_instance.name, fields))
+    }
+
+    fn visit_struct_field_access(
+        &mut self,
+        struct_field_access: StructFieldAccess,
+    ) -> Result<Self::Output, Self::Error> {
+        Ok(format!(
+            "{}{}{}",
+            self.expr(*struct_field_access.name),
+            if struct_field_access.is_ptr {
+                "->"
+            } else {
+                "."
+            },
+            struct_field_access.field
+        ))
+    }
+
+    fn visit_index(&mut self, index: Index) -> Result<Self::Output, Self::Error> {
+        Ok(format!(
+            "{}:{}",
+            self.expr(*index.value),
+            self.expr(*index.index)
+        ))
+    }
+
+    fn visit_slice(&mut self, slice: Slice) -> Result<Self::Output, Self::Error> {
+        let start = slice.start.map(|x| self.expr(*x)).unwrap_or_default();
+        let end = slice.end.map(|x| self.expr(*x)).unwrap_or_default();
+        Ok(format!("{}:{}..{}", self.expr(*slice.value), start, end))
+    }
+
+    fn visit_tuple_access(
+        &mut self,
+        tuple_access: TupleAccess,
+    ) -> Result<Self::Output, Self::Error> {
+        Ok(format!(
+            "{}.{}",
+            self.expr(*tuple_access.value),
+            tuple_access.index
+        ))
+    }
+
+    fn visit_va_arg(&mut self, va_arg: VaArg) -> Result<Self::Output, Self::Error> {
+        Ok(format!("arg {}", ty(&va_arg.ty)))
+    }
+
+    fn visit_reference(&mut self, reference: Reference) -> Result<Self::Output, Self::Error> {
+        Ok(format!("ref {}", self.expr(*reference.expr)))
+    }
+
+    fn visit_deref(&mut self, pointer: Deref) -> Result<Self::Output, Self::Error> {
+        Ok(format!("@{}", self.expr(*pointer.expr)))
+    }
+}
+
+impl StmtVisitor for Formatter<'_> {
+    type Output = ();
+    type Error = Infallible;
+
+    fn visit_expr_stmt(&mut self, expr: Expression) -> Result<Self::Output, Self::Error> {
+        let expr = self.expr(expr);
+        self.out.push_str(&format!("{};", expr));
+        Ok(())
+    }
+
+    fn visit_let_stmt(&mut self, let_stmt: LetStmt) -> Result<Self::Output, Self::Error> {
+        let r#type = let_stmt
+            .r#type
+            .map(|x| format!(": {}", ty(&x)))
+            .unwrap_or_default();
+        let value = self.expr(let_stmt.value);
+        self.out.push_str(&format!(
+            "let {}{} = {};",
+            let_stmt.name.name, r#type, value
+        ));
+        Ok(())
+    }
+
+    fn visit_let_tuple_stmt(
+        &mut self,
+        let_tuple_stmt: LetTupleStmt,
+    ) -> Result<Self::Output, Self::Error> {
+        let names = let_tuple_stmt
+            .names
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let r#type = let_tuple_stmt
+            .r#type
+            .map(|x| format!(": {}", ty(&x)))
+            .unwrap_or_default();
+        let value = self.expr(let_tuple_stmt.value);
+        self.out
+            .push_str(&format!("let ({}){} = {};", names, r#type, value));
+        Ok(())
+    }
+
+    fn visit_stmt(&mut self, stmt: Statement) -> Result<Self::Output, Self::Error> {
+        match stmt {
+            Statement::Expression(expr) => self.visit_expr_stmt(expr),
+            Statement::While(while_stmt) => self.visit_while_stmt(while_stmt),
+            Statement::Block(block) => self.visit_block(block),
+            Statement::Let(let_stmt) => self.visit_let_stmt(let_stmt),
+            Statement::LetTuple(let_tuple_stmt) => self.visit_let_tuple_stmt(let_tuple_stmt),
+            Statement::If(if_stmt) => self.visit_if_stmt(if_stmt),
+            Statement::IfElse(if_else_stmt) => self.visit_if_else_stmt(if_else_stmt),
+            Statement::Function(function) => self.visit_function(function),
+            Statement::Return(return_expr) => self.visit_return(return_expr),
+            Statement::Import(import) => self.visit_import(import),
+            Statement::External(external) => self.visit_external(external),
+            Statement::Extern(extern_stmt) => self.visit_extern(extern_stmt),
+            Statement::For(for_stmt) => self.visit_for_stmt(for_stmt),
+            Statement::Struct(struct_stmt) => self.visit_struct_stmt(struct_stmt),
+            Statement::BreakStmt(break_stmt) => self.visit_break(break_stmt),
+            Statement::Assign(assign) => self.visit_assign(assign),
+            Statement::Test(test) => self.visit_test(test),
+            Statement::ModuleDoc(module_doc) => self.visit_module_doc(module_doc),
+        }
+    }
+
+    fn visit_block(&mut self, block: Block) -> Result<Self::Output, Self::Error> {
+        let span = block.span();
+        self.block(block.statements, span.start, span.end);
+        Ok(())
+    }
+
+    fn visit_while_stmt(&mut self, while_stmt: While) -> Result<Self::Output, Self::Error> {
+        let condition = self.expr(while_stmt.condition);
+        self.out.push_str(&format!("while {} ", condition));
+        self.visit_stmt(*while_stmt.body)
+    }
+
+    fn visit_if_stmt(&mut self, if_stmt: If) -> Result<Self::Output, Self::Error> {
+        let condition = self.expr(if_stmt.condition);
+        self.out.push_str(&format!("if {} ", condition));
+        self.visit_stmt(*if_stmt.body)
+    }
+
+    fn visit_if_else_stmt(&mut self, if_else_stmt: IfElse) -> Result<Self::Output, Self::Error> {
+        let condition = self.expr(if_else_stmt.condition);
+        self.out.push_str(&format!("if {} ", condition));
+        self.visit_stmt(*if_else_stmt.body)?;
+        self.out.push_str(" else ");
+        self.visit_stmt(*if_else_stmt.else_body)
+    }
+
+    fn visit_function(&mut self, function: Function) -> Result<Self::Output, Self::Error> {
+        self.doc(&function.doc);
+        self.out.push_str(&format!(
+            "{}{}func {}({}): {} ",
+            if function.is_pub { "pub " } else { "" },
+            if function.is_export { "export " } else { "" },
+            function.name,
+            arguments(&function.arguments, function.is_var_args),
+            ty(&function.returntype)
+        ));
+        // the body starts after the return type
+        self.block(
+            function.body,
+            function.returntype.span.end,
+            function.span.end,
+        );
+        Ok(())
+    }
+
+    fn visit_return(&mut self, return_expr: Return) -> Result<Self::Output, Self::Error> {
+        match return_expr.expression {
+            Some(expr) => {
+                let expr = self.expr(*expr);
+                self.out.push_str(&format!("return {};", expr));
+            }
+            None => self.out.push_str("return;"),
+        }
+        Ok(())
+    }
+
+    fn visit_import(&mut self, import: ImportStmt) -> Result<Self::Output, Self::Error> {
+        self.out.push_str(&format!("import {}", import.path));
+        if let Some(items) = import.items {
+            let items = items
+                .iter()
+                .map(|x| x.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            self.out.push_str(&format!(".{{{}}}", items));
+        }
+        if let Some(alias) = import.alias {
+            self.out.push_str(&format!(" as {}", alias.name));
+        }
+        self.out.push(';');
+        Ok(())
+    }
+
+    fn visit_external(&mut self, external: External) -> Result<Self::Output, Self::Error> {
+        self.out
+            .push_str(&format!("external \"{}\" ", external.file));
+        let span = external.span();
+        self.signs(&external.signs, span);
+        self.out.push(';');
+        Ok(())
+    }
+
+    fn visit_extern(&mut self, extern_stmt: Extern) -> Result<Self::Output, Self::Error> {
+        self.out.push_str("extern ");
+        let span = extern_stmt.span();
+        self.signs(&extern_stmt.signs, span);
+        self.out.push(';');
+        Ok(())
+    }
+
+    fn visit_for_stmt(&mut self, for_stmt: ForStmt) -> Result<Self::Output, Self::Error> {
+        let expr = self.expr(for_stmt.expr);
+        self.out
+            .push_str(&format!("for {} in {} ", for_stmt.it.name, expr));
+        self.visit_block(for_stmt.body)
+    }
+
+    fn visit_struct_stmt(&mut self, struct_stmt: StructStmt) -> Result<Self::Output, Self::Error> {
+        self.doc(&struct_stmt.doc);
+        self.out.push_str(&format!(
+            "{}struct {} ",
+            if struct_stmt.is_pub { "pub " } else { "" },
+            struct_stmt.name
+        ));
+        let fields = struct_stmt
+            .fields
+            .iter()
+            .map(|field| {
+                let item = format!(
+                    "{}{} = {}",
+                    if field.is_pub { "pub " } else { "" },
+                    field.name,
+                    ty(&field.ty)
+                );
+                (field.span, field.doc.clone(), item)
+            })
+            .collect();
+        self.items(fields, struct_stmt.span);
+        Ok(())
+    }
+
+    fn visit_break(&mut self, _break_stmt: BreakStmt) -> Result<Self::Output, Self::Error> {
+        self.out.push_str("break;");
+        Ok(())
+    }
+
+    fn visit_assign(&mut self, assign: Assign) -> Result<Self::Output, Self::Error> {
+        let name = self.expr(assign.name);
+        let value = self.expr(assign.value);
+        self.out.push_str(&format!("{} = {};", name, value));
+        Ok(())
+    }
+
+    fn visit_test(&mut self, test: TestStmt) -> Result<Self::Output, Self::Error> {
+        self.out
+            .push_str(&format!("test {} ", string_literal(&test.name)));
+        self.visit_block(test.body)
+    }
+
+    fn visit_module_doc(&mut self, module_doc: ModuleDoc) -> Result<Self::Output, Self::Error> {
+        let lines = module_doc
+            .doc
+            .split('\n')
+            .map(|line| match line {
+                "" => "//!".to_string(),
+                line => format!("//! {}", line),
+            })
+            .collect::<Vec<_>>();
+        for (i, line) in lines.iter().enumerate() {
+            if i != 0 {
+                self.newline();
+            }
+            self.out.push_str(line);
+        }
+        Ok(())
+    }
+}
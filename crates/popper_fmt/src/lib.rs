@@ -0,0 +1,38 @@
+mod comments;
+mod formatter;
+
+pub use comments::Comment;
+use popper_ast::Statement;
+
+///
+/// format is used to print a parsed file back to source, in the canonical style:
+/// four spaces of indentation, one statement per line, and at most one empty line
+/// between two statements.
+/// The `//` comments between statements, between the fields of a struct and between
+/// the functions of an `extern` block, and the empty lines separating them, are taken
+/// from `source`. Formatting the output again gives the same output.
+/// # Arguments
+/// * `stmts` - statements of the file
+/// * `source` - source code the statements were parsed from
+///
+/// return: the formatted source, or the first comment it would lose, written where the
+/// formatter can't keep it, like in an expression
+pub fn format(stmts: Vec<Statement>, source: &str) -> Result<String, Comment> {
+    let mut formatter = formatter::Formatter::new(source);
+    formatter.statements(stmts, 0, source.len());
+    let formatted = formatter.finish();
+
+    let mut kept: Vec<String> = comments::comments(&formatted)
+        .into_iter()
+        .map(|x| x.text)
+        .collect();
+    for comment in comments::comments(source) {
+        match kept.iter().position(|x| *x == comment.text) {
+            Some(i) => {
+                kept.swap_remove(i);
+            }
+            None => return Err(comment),
+        }
+    }
+    Ok(formatted)
+}
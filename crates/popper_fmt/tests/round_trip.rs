@@ -0,0 +1,203 @@
+use popper_ast::Statement;
+use popper_parser::parser::try_parse;
+use serde_json::Value;
+
+const SOURCE: &str = r#"//! A module exercising
+//! the whole syntax
+import std.io;
+import a.b.{c, d} as e;
+
+external "libm.so" {
+    func sqrt(x: int): int,
+    func pow(x: int, y: int): int
+};
+
+extern {
+    /// prints `s`
+    func printf(s: string ...): int
+};
+
+/// a point
+///
+/// in space
+pub struct Point {
+    /// abscissa
+    pub x = int,
+    y = i64,
+    z = [*u8: 4]
+}
+
+struct Other {
+    points = vec<struct Point>,
+    pair = (int, bool),
+    view = [char]
+}
+
+/// entry point
+pub func main(argc: int, argv: *string): int { // main
+    let p: struct Point = init Point { x = 1, y = 2, z = [ref a, ref b] };
+    let (a, b) = (1, "two\n\"2\"\t\\");
+    let c = '\'';
+    p.x = p.x + 2 * 3 eq 4 - 1;
+    p->y = p.z:0;
+    let s = l:1..;
+    let t = l:..len(l);
+    let u = t.0 as u8; // cast
+
+    // conditions
+
+    if a lt b {
+        printf("%d", @p);
+    } else if a gt b {
+        return 1;
+    } else {
+        while true {
+            break;
+        }
+    }
+
+    if a eq 0 io.println("zero");
+    while a lt 3 a = a + 1;
+
+    for i in [1, 2, 3] {
+        io.println(i);
+    }
+    {}
+    return arg int;
+}
+
+func empty(...): unit {}
+
+pub export func exported(a: int): int {
+    return a;
+}
+
+test "main returns zero" {
+    assert(main(0, ref s) eq 0, "main failed");
+}
+"#;
+
+fn parse(source: &str) -> Vec<Statement> {
+    try_parse(source).unwrap_or_else(|_| panic!("unable to parse:\n{}", source))
+}
+
+/// the ast as json, without the spans
+fn without_spans(stmts: &[Statement]) -> Value {
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                map.remove("span");
+                map.values_mut().for_each(strip);
+            }
+            Value::Array(values) => values.iter_mut().for_each(strip),
+            _ => {}
+        }
+    }
+
+    let mut value = serde_json::to_value(stmts).unwrap();
+    strip(&mut value);
+    value
+}
+
+fn assert_round_trip(source: &str) {
+    let ast = parse(source);
+    let formatted = popper_fmt::format(ast.clone(), source).unwrap();
+    assert_eq!(
+        without_spans(&ast),
+        without_spans(&parse(&formatted)),
+        "{}",
+        formatted
+    );
+    assert_eq!(
+        popper_fmt::format(parse(&formatted), &formatted).unwrap(),
+        formatted
+    );
+}
+
+#[test]
+fn round_trip() {
+    assert_round_trip(SOURCE);
+}
+
+#[test]
+fn round_trip_sources() {
+    assert_round_trip(include_str!("../../../std/fs.pop"));
+    assert_round_trip(include_str!("../../../std/io.pop"));
+    assert_round_trip(include_str!("../../../std/math.pop"));
+    assert_round_trip(include_str!("../../../std/mem.pop"));
+    assert_round_trip(include_str!("../../../std/process.pop"));
+    assert_round_trip(include_str!("../../../std/string.pop"));
+    assert_round_trip(include_str!("../../../std/time.pop"));
+    assert_round_trip(include_str!("../../../examples/args.pop"));
+    assert_round_trip(include_str!("../../../examples/helloworld.pop"));
+    assert_round_trip(include_str!("../../../examples/wasm.pop"));
+    assert_round_trip(include_str!("../../../examples/external.pop"));
+}
+
+#[test]
+fn canonical_style() {
+    let source = "func main(): int {\n\n  let a =  1;\n\n\n\n  return a;\n\n}\nfunc f(): unit {}";
+    assert_eq!(
+        popper_fmt::format(parse(source), source).unwrap(),
+        "func main(): int {\n    let a = 1;\n\n    return a;\n}\nfunc f(): unit {}\n"
+    );
+}
+
+#[test]
+fn comments() {
+    let source =
+        "//! doc\n\n// a\nfunc main(): int { // b\n\n    return 0; // d\n    // e\n}\n// f";
+    assert_eq!(
+        popper_fmt::format(parse(source), source).unwrap(),
+        "//! doc\n\n// a\nfunc main(): int {\n    // b\n\n    return 0; // d\n    // e\n}\n// f\n"
+    );
+}
+
+#[test]
+fn string_escapes() {
+    let source = "test \"a \\\"b\\\"\" {\n    let s = \"x\\n\\t\\\\\";\n}\n";
+    let ast = parse(source);
+    let Statement::Test(test) = &ast[0] else {
+        panic!("expected a test");
+    };
+    assert_eq!(test.name, "a \"b\"");
+    assert_eq!(popper_fmt::format(ast, source).unwrap(), source);
+}
+
+#[test]
+fn comments_in_items() {
+    let source = "struct Point {
+    // abscissa
+    x = int, // in pixels
+
+    /// ordinate
+    y = int // last
+    // after the fields
+}
+
+extern {
+    // from libc
+    func abs(x: int): int,
+    func puts(s: string): int // prints a line
+};
+
+external \"libm.so\" {
+    func sqrt(x: int): int
+    // more to come
+};
+";
+    assert_eq!(popper_fmt::format(parse(source), source).unwrap(), source);
+    assert_round_trip(source);
+}
+
+#[test]
+fn lost_comments() {
+    let source = "func main(): int {\n    return f(1, // one\n        2);\n}\n";
+    let comment = popper_fmt::format(parse(source), source).unwrap_err();
+    assert_eq!(comment.line, 2);
+    assert_eq!(comment.text, "// one");
+
+    // not comments
+    let source = "func main(): int {\n    let s = \"http://x\";\n    return 0;\n}\n";
+    assert_eq!(popper_fmt::format(parse(source), source).unwrap(), source);
+}
@@ -6,7 +6,20 @@ use popper_error::{ColorConfig, Error};
 
 /// parser errpr type implemented with lalrpop error
 #[derive(Clone, Debug)]
-pub struct ParserErrorType<'a>(pub ParseError<usize, Token<'a>, &'static str>);
+pub struct ParserErrorType<'a>(pub ParseError<usize, Token<'a>, GrammarError>);
+
+/// error reported by an action of the grammar, where the source it rejects is
+#[derive(Clone, Debug)]
+pub struct GrammarError {
+    pub span: popper_ast::Span,
+    pub message: &'static str,
+}
+
+impl GrammarError {
+    pub fn new(span: popper_ast::Span, message: &'static str) -> Self {
+        Self { span, message }
+    }
+}
 
 /// PopperLang Parser error
 #[derive(Clone, Debug)]
@@ -15,8 +28,8 @@ pub struct ParserError<'a> {
     pub span: popper_ast::Span,
 }
 
-impl<'a> From<ParseError<usize, Token<'a>, &'static str>> for ParserErrorType<'a> {
-    fn from(val: ParseError<usize, Token<'a>, &'static str>) -> Self {
+impl<'a> From<ParseError<usize, Token<'a>, GrammarError>> for ParserErrorType<'a> {
+    fn from(val: ParseError<usize, Token<'a>, GrammarError>) -> Self {
         ParserErrorType(val)
     }
 }
@@ -50,7 +63,11 @@ impl<'a> std::fmt::Display for ParserErrorType<'a> {
                 write!(f, "Extra token {:?} at {}:{}", token, start, end)
             }
             ParseError::User { error } => {
-                write!(f, "User error: {}", error)
+                write!(
+                    f,
+                    "{} at {}:{}",
+                    error.message, error.span.start, error.span.end
+                )
             }
         }
     }
@@ -107,17 +124,55 @@ impl<'a> Error for ParserError<'a> {
                         self.span.extract_from_str(source).fg(*keyword_color)
                     )));
             }
-            ParseError::User { error: _ } => {
+            ParseError::User { error } => {
                 report = report
                     .with_code(1)
-                    .with_message("User error".to_string())
-                    .with_label(Label::new((file, self.span.into())).with_message(format!(
-                        "user error `{}`",
-                        self.span.extract_from_str(source).fg(*keyword_color)
+                    .with_message(error.message.to_string())
+                    .with_label(Label::new((file, error.span.into())).with_message(format!(
+                        "in `{}`",
+                        error.span.extract_from_str(source).fg(*keyword_color)
                     )));
             }
         }
 
         report.finish().print((file, Source::from(source))).unwrap();
     }
+
+    fn span(&self) -> popper_ast::Span {
+        match &self.error.0 {
+            ParseError::InvalidToken { location } => popper_ast::Span::new(*location, *location),
+            ParseError::UnrecognizedEof { location, .. } => {
+                popper_ast::Span::new(*location, *location)
+            }
+            ParseError::UnrecognizedToken {
+                token: (start, _, end),
+                ..
+            }
+            | ParseError::ExtraToken {
+                token: (start, _, end),
+            } => popper_ast::Span::new(*start, *end),
+            ParseError::User { error } => error.span,
+        }
+    }
+
+    fn message(&self) -> String {
+        match &self.error.0 {
+            ParseError::InvalidToken { .. } => "Invalid token".to_string(),
+            ParseError::UnrecognizedEof { expected, .. } => {
+                format!("Unexpected end of file, expected `{}`", expected.join(", "))
+            }
+            ParseError::UnrecognizedToken {
+                token: (_, token, _),
+                expected,
+            } => format!(
+                "Unexpected token `{}`, expected `{}`",
+                token,
+                expected.join(", ")
+            ),
+            ParseError::ExtraToken {
+                token: (_, token, _),
+            } => format!("Extra token `{}`", token),
+            ParseError::User { error } => error.message.to_string(),
+        }
+    }
 }
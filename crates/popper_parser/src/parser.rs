@@ -1,6 +1,6 @@
 use lalrpop_util::lalrpop_mod;
 
-use crate::error::{ParserError, ParserErrorType};
+use crate::error::ParserError;
 use popper_ast::Statement;
 use popper_error::generate_color;
 use popper_error::Error;
@@ -8,18 +8,43 @@ use popper_error::Error;
 lalrpop_mod!(pub popper); // lalrpop gen code
 
 pub fn parse<'a>(input: &'a str, file: &'a str) -> Option<Vec<Statement>> {
-    let res = popper::FileParser::new().parse(input);
-
-    match res {
+    match try_parse(input) {
         Ok(ast) => Some(ast),
-        Err(e) => {
-            let err_type: ParserErrorType = e.into();
-            let err = ParserError {
-                error: err_type,
-                span: popper_ast::Span::new(0, input.len()),
-            };
+        Err(err) => {
             err.report(generate_color(), input, file);
             None
         }
     }
 }
+
+/// parse `input`, returning the error instead of reporting it
+pub fn try_parse(input: &str) -> Result<Vec<Statement>, ParserError<'_>> {
+    popper::FileParser::new()
+        .parse(input)
+        .map_err(|e| ParserError {
+            error: e.into(),
+            span: popper_ast::Span::new(0, input.len()),
+        })
+}
+
+/// value of a string literal, without its quotes: `\n`, `\t`, `\r`, `\0`, `\\` and `\"`
+/// are replaced by the character they stand for
+pub(crate) fn unescape(literal: &str) -> String {
+    let mut value = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('t') => value.push('\t'),
+            Some('r') => value.push('\r'),
+            Some('0') => value.push('\0'),
+            Some(c) => value.push(c), // `\\` and `\"`
+            None => value.push('\\'),
+        }
+    }
+    value
+}
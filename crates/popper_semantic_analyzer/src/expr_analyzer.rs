@@ -4,17 +4,28 @@ use popper_error::{
     diff_length_of_argument::DiffLengthOfArgument, namenotfound::NameNotFound,
     typemismatch::TypeMismatch, typenotspecified::TypeNotSpecified,
 };
-use popper_flag::{Environment, Flag, SymbolFlags, ValueFlag};
+use popper_flag::{Environment, Flag, SymbolFlags, ValueFlag, VariableFlag};
 
 use popper_ast::visitor::ExprVisitor;
 use popper_common::name_similarity::find_similar_name;
 use popper_error::fieldnotfound::FieldNotFound;
+use popper_error::indexoutofrange::IndexOutOfRange;
+use popper_error::invalidcast::InvalidCast;
+use popper_error::literaloutofrange::LiteralOutOfRange;
+use popper_error::privateaccess::PrivateAccess;
 use popper_error::Error;
 
+use crate::symbols::SharedSymbols;
+use crate::types::TypesRecorder;
+
 #[derive(Clone)]
 pub struct ExprAnalyzer {
     env: Environment,
     let_expected_value: Option<SymbolFlags>,
+    /// where the names resolved are recorded, if they are
+    symbols: Option<SharedSymbols>,
+    /// where the types of the expressions are recorded, if they are
+    types: Option<TypesRecorder>,
 }
 
 impl ExprAnalyzer {
@@ -22,6 +33,25 @@ impl ExprAnalyzer {
         Self {
             env,
             let_expected_value: None,
+            symbols: None,
+            types: None,
+        }
+    }
+
+    pub(crate) fn with_symbols(mut self, symbols: Option<SharedSymbols>) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    pub(crate) fn with_types(mut self, types: Option<TypesRecorder>) -> Self {
+        self.types = types;
+        self
+    }
+
+    /// record that `variable` is used at `span`
+    fn record(&self, span: Span, variable: &VariableFlag) {
+        if let Some(symbols) = &self.symbols {
+            symbols.borrow_mut().references.push((span, variable.clone()));
         }
     }
 
@@ -29,13 +59,194 @@ impl ExprAnalyzer {
         self.let_expected_value = Some(value);
     }
 
+    /// analyzer for a sub-expression whose type is expected to be `value`, if known
+    fn expecting(&self, value: Option<ValueFlag>, span: Span) -> Self {
+        let mut analyzer = self.clone();
+        analyzer.let_expected_value = value.map(|x| SymbolFlags::new(span).set_value(x).clone());
+        analyzer
+    }
+
+    /// an integer literal takes the integer type expected by its context.
+    /// Without context, it's an `int`, or an `i64` if it doesn't fit in an `int`
+    fn visit_int_literal(&self, int: Int) -> Result<SymbolFlags, Box<dyn Error>> {
+        let expected = self
+            .let_expected_value
+            .as_ref()
+            .and_then(|x| x.get_value())
+            .and_then(|x| x.int_kind());
+
+        let kind = match expected {
+            Some(kind) => kind,
+            None if IntKind::I32.fits(int.value as i128) => IntKind::I32,
+            None => IntKind::I64,
+        };
+
+        if !kind.fits(int.value as i128) {
+            return Err(Box::new(LiteralOutOfRange::new(
+                int.value,
+                ValueFlag::from_int_kind(kind).to_string(),
+                int.span(),
+            )));
+        }
+
+        Ok(SymbolFlags::new(int.span())
+            .set_value(ValueFlag::from_int_kind(kind))
+            .clone())
+    }
+
+    /// builtin functions: `len(x)`, `push(v, x)`, `pop(v)` and `assert(cond, msg)`
+    fn visit_builtin(&mut self, call: Call) -> Result<SymbolFlags, Box<dyn Error>> {
+        let arity = if matches!(call.name.as_str(), "push" | "assert") { 2 } else { 1 };
+        if call.arguments.len() != arity {
+            return Err(Box::new(DiffLengthOfArgument::new(
+                arity,
+                call.arguments.len(),
+                call.span,
+            )));
+        }
+
+        let arg_span = call.arguments[0].span();
+        let arg = self
+            .expecting(None, arg_span)
+            .visit_expr(call.arguments[0].clone())?
+            .get_value()
+            .unwrap();
+
+        match (call.name.as_str(), &arg) {
+            ("len", ValueFlag::String) => Ok(SymbolFlags::new(call.span).set_integer().clone()),
+            ("len", arg) if arg.get_element_type().is_some() => {
+                Ok(SymbolFlags::new(call.span).set_integer().clone())
+            }
+            ("push", ValueFlag::Vec(ty)) => {
+                let value_span = call.arguments[1].span();
+                let value = self
+                    .expecting(Some(*ty.clone()), value_span)
+                    .visit_expr(call.arguments[1].clone())?
+                    .get_value()
+                    .unwrap();
+                if value != **ty {
+                    return Err(Box::new(TypeMismatch::new(
+                        (value_span, ty.to_string()),
+                        (value_span, value.to_string()),
+                    )));
+                }
+                Ok(SymbolFlags::new(call.span).set_none().clone())
+            }
+            ("pop", ValueFlag::Vec(ty)) => Ok(SymbolFlags::new(call.span)
+                .set_value(*ty.clone())
+                .clone()),
+            ("assert", ValueFlag::Boolean) => {
+                let msg_span = call.arguments[1].span();
+                let msg = self
+                    .expecting(None, msg_span)
+                    .visit_expr(call.arguments[1].clone())?
+                    .get_value()
+                    .unwrap();
+                if msg != ValueFlag::String {
+                    return Err(Box::new(TypeMismatch::new(
+                        (msg_span, ValueFlag::String.to_string()),
+                        (msg_span, msg.to_string()),
+                    )));
+                }
+                Ok(SymbolFlags::new(call.span).set_none().clone())
+            }
+            (name, arg) => Err(Box::new(TypeMismatch::new(
+                (
+                    arg_span,
+                    match name {
+                        "len" => "string, list, slice or vec",
+                        "assert" => "bool",
+                        _ => "vec",
+                    }
+                    .to_string(),
+                ),
+                (arg_span, arg.to_string()),
+            ))),
+        }
+    }
+
+    /// check that a slice bound is an integer
+    fn visit_bound(&mut self, bound: Option<Box<Expression>>) -> Result<(), Box<dyn Error>> {
+        if let Some(bound) = bound {
+            let flag = self.expecting(None, bound.span()).visit_expr(*bound.clone())?;
+            if flag.get_value().and_then(|x| x.int_kind()).is_none() {
+                return Err(Box::new(TypeMismatch::new(
+                    (bound.span(), "int".to_string()),
+                    (bound.span(), flag.get_value().unwrap().to_string()),
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// item `name` of the module bound to `module`, if it's visible from here
+    fn get_module_item(
+        &self,
+        module: &Ident,
+        name: &str,
+        span: Span,
+    ) -> Result<VariableFlag, Box<dyn Error>> {
+        let Some(variable) = self.env.get_variable(&module.name) else {
+            let name_candidates = self.env.get_all_variables_name();
+            let similar_name = find_similar_name(name_candidates.as_slice(), &module.name);
+            return Err(Box::new(NameNotFound::new(
+                (module.span, module.name.clone()),
+                similar_name.cloned(),
+            )));
+        };
+        let value = variable.value.get_value().unwrap();
+        let ValueFlag::Module(env) = value else {
+            return Err(Box::new(TypeMismatch::new(
+                (module.span, "module".to_string()),
+                (module.span, value.to_string()),
+            )));
+        };
+
+        let Some(item) = env.get_variable(name) else {
+            let name_candidates = env.get_all_variables_name();
+            let similar_name = find_similar_name(name_candidates.as_slice(), name);
+            return Err(Box::new(NameNotFound::new(
+                (span, name.to_string()),
+                similar_name.cloned(),
+            )));
+        };
+        if !env.is_public(name) {
+            return Err(Box::new(PrivateAccess::new(
+                name.to_string(),
+                module.name.clone(),
+                span,
+            )));
+        }
+
+        Ok(item.clone())
+    }
+
+    /// check if `expr` names an imported module
+    fn is_module(&self, expr: &Expression) -> bool {
+        match expr {
+            Expression::Constant(Constant::Ident(ident)) => self
+                .env
+                .get_variable(&ident.name)
+                .and_then(|x| x.value.get_value())
+                .is_some_and(|x| matches!(x, ValueFlag::Module(_))),
+            _ => false,
+        }
+    }
+
     pub fn get_type(&self, ty: Type) -> ValueFlag {
         match ty.type_kind {
             TypeKind::Bool => ValueFlag::Boolean,
             TypeKind::Float => ValueFlag::Float,
             TypeKind::Int => ValueFlag::Integer,
-            TypeKind::String(size) => ValueFlag::String(size),
+            TypeKind::SizedInt(kind) => ValueFlag::from_int_kind(kind),
+            TypeKind::Char => ValueFlag::Char,
+            TypeKind::String => ValueFlag::String,
             TypeKind::List(ty, l) => ValueFlag::List(Box::new(self.get_type(*ty)), l),
+            TypeKind::Slice(ty) => ValueFlag::Slice(Box::new(self.get_type(*ty))),
+            TypeKind::Vec(ty) => ValueFlag::Vec(Box::new(self.get_type(*ty))),
+            TypeKind::Tuple(types) => {
+                ValueFlag::Tuple(types.into_iter().map(|ty| self.get_type(ty)).collect())
+            }
             TypeKind::Function(args, returnty, is_var_args) => {
                 let mut args_type = Vec::new();
                 for arg in args {
@@ -57,14 +268,18 @@ impl ExprVisitor for ExprAnalyzer {
 
     fn visit_constant(&mut self, constant: Constant) -> Result<Self::Output, Self::Error> {
         match constant {
-            Constant::Int(int) => Ok(SymbolFlags::new(int.span()).set_integer().clone()),
+            Constant::Int(int) => self.visit_int_literal(int),
+            Constant::Char(c) => Ok(SymbolFlags::new(c.span()).set_value(ValueFlag::Char).clone()),
             Constant::Float(float) => Ok(SymbolFlags::new(float.span()).set_float().clone()),
-            Constant::StringLiteral(string) => Ok(SymbolFlags::new(string.span())
-                .set_string(string.value.len() as u32)
-                .clone()),
+            Constant::StringLiteral(string) => {
+                Ok(SymbolFlags::new(string.span()).set_string().clone())
+            }
             Constant::Bool(bool) => Ok(SymbolFlags::new(bool.span()).set_boolean().clone()),
             Constant::Ident(ref ident) => match self.env.get_variable(&ident.name) {
-                Some(v) => Ok({ v.value.clone() }),
+                Some(v) => {
+                    self.record(ident.span, v);
+                    Ok(v.value.clone())
+                }
                 None => {
                     let name_candidates = self.env.get_all_variables_name();
 
@@ -81,8 +296,15 @@ impl ExprVisitor for ExprAnalyzer {
                 let mut flags = SymbolFlags::new(l.span());
                 let mut base_value_flag: Option<ValueFlag> = None;
                 let mut base_span: Option<Span> = None;
+                let expected_element = self
+                    .let_expected_value
+                    .as_ref()
+                    .and_then(|x| x.get_value())
+                    .and_then(|x| x.get_minor_type().cloned());
                 for expr in &l.value {
-                    let flag = self.visit_expr(expr.clone())?;
+                    let flag = self
+                        .expecting(expected_element.clone(), l.span())
+                        .visit_expr(expr.clone())?;
                     let value_flag = flag.get_value().unwrap();
                     if base_value_flag.is_some()
                         && !base_value_flag.as_ref().unwrap().is_same(&value_flag)
@@ -98,7 +320,11 @@ impl ExprVisitor for ExprAnalyzer {
                     base_value_flag = Some(value_flag);
                     base_span = Some(flag.span());
                 }
-                if let Some(value_flag) = base_value_flag {
+                // a list literal can initialize a vector: `let v: vec<int> = [];`
+                let expected = self.let_expected_value.as_ref().and_then(|x| x.get_value());
+                if let Some(ValueFlag::Vec(ty)) = expected {
+                    flags.set_value(ValueFlag::Vec(Box::new(base_value_flag.unwrap_or(*ty))));
+                } else if let Some(value_flag) = base_value_flag {
                     flags.set_list(value_flag, l.value.len());
                 } else if let Some(val) = self.let_expected_value.clone() {
                     if let ValueFlag::List(ty, size) = val.get_value().unwrap() {
@@ -139,6 +365,14 @@ impl ExprVisitor for ExprAnalyzer {
                 }
                 Ok(flags)
             }
+            Constant::Tuple(t) => {
+                let mut value_flags = Vec::new();
+                for expr in &t.value {
+                    let flag = self.expecting(None, t.span()).visit_expr(expr.clone())?;
+                    value_flags.push(flag.get_value().unwrap());
+                }
+                Ok(SymbolFlags::new(t.span()).set_tuple(value_flags).clone())
+            }
             Constant::Null(null) => Ok(SymbolFlags::new(null.span()).set_none().clone()),
         }
     }
@@ -173,8 +407,34 @@ impl ExprVisitor for ExprAnalyzer {
     }
 
     fn visit_bin_op(&mut self, bin_op: BinOp) -> Result<Self::Output, Self::Error> {
-        let flag_lhs = self.visit_expr(*bin_op.lhs)?;
-        let flag_rhs = self.visit_expr(*bin_op.rhs)?;
+        let mut flag_lhs = self.visit_expr(*bin_op.lhs.clone())?;
+        let mut flag_rhs = self.visit_expr(*bin_op.rhs.clone())?;
+
+        // an integer literal takes the type of the other operand: `x + 1` with `x: u8`
+        if !flag_lhs.is_same_value(flag_rhs.clone()) {
+            let is_int_literal =
+                |expr: &Expression| matches!(expr, Expression::Constant(Constant::Int(_)));
+            let lhs_kind = flag_lhs.get_value().and_then(|x| x.int_kind());
+            let rhs_kind = flag_rhs.get_value().and_then(|x| x.int_kind());
+
+            if is_int_literal(&bin_op.lhs) && rhs_kind.is_some() {
+                flag_lhs = self
+                    .expecting(flag_rhs.get_value(), bin_op.span)
+                    .visit_expr(*bin_op.lhs)?;
+            } else if is_int_literal(&bin_op.rhs) && lhs_kind.is_some() {
+                flag_rhs = self
+                    .expecting(flag_lhs.get_value(), bin_op.span)
+                    .visit_expr(*bin_op.rhs)?;
+            }
+        }
+        // strings only support concatenation and comparison
+        if flag_lhs.is_string() && bin_op.op.is_arithmetic() && bin_op.op != BinOpKind::Add {
+            return Err(Box::new(TypeMismatch::new(
+                (flag_lhs.span, "int or float".to_string()),
+                (flag_lhs.span, "string".to_string()),
+            )));
+        }
+
         if flag_lhs.is_same_value(flag_rhs.clone()) && bin_op.op.is_arithmetic() {
             Ok(flag_lhs)
         } else if flag_lhs.is_same_value(flag_rhs.clone()) && bin_op.op.is_comparison() {
@@ -194,6 +454,12 @@ impl ExprVisitor for ExprAnalyzer {
     }
 
     fn visit_unary_op(&mut self, unary_op: UnaryOp) -> Result<Self::Output, Self::Error> {
+        // a negated integer literal is a literal of its own: `-128` is an `i8`
+        if let (UnaryOpKind::Neg, Expression::Constant(Constant::Int(int))) =
+            (unary_op.op, unary_op.expr.as_ref())
+        {
+            return self.visit_int_literal(Int::new(unary_op.span(), -int.value));
+        }
         let flag_expr = self.visit_expr(*unary_op.expr)?;
         if unary_op.op == UnaryOpKind::Not {
             if flag_expr.clone().is_boolean() {
@@ -205,7 +471,8 @@ impl ExprVisitor for ExprAnalyzer {
                 )))
             }
         } else if unary_op.op == UnaryOpKind::Neg {
-            if flag_expr.is_integer() || flag_expr.is_float() {
+            let is_integer = flag_expr.get_value().and_then(|x| x.int_kind()).is_some();
+            if is_integer || flag_expr.is_float() {
                 Ok(flag_expr)
             } else {
                 Err(Box::new(TypeMismatch::new(
@@ -218,12 +485,37 @@ impl ExprVisitor for ExprAnalyzer {
         }
     }
 
+    fn visit_cast(&mut self, cast: Cast) -> Result<Self::Output, Self::Error> {
+        let target = self.get_type(cast.ty.clone());
+        let flag = self
+            .expecting(None, cast.span)
+            .visit_expr(*cast.expr.clone())?;
+        let source = flag.get_value().unwrap();
+
+        if !source.is_castable_to(&target) {
+            return Err(Box::new(InvalidCast::new(
+                source.to_string(),
+                target.to_string(),
+                cast.span,
+            )));
+        }
+
+        Ok(SymbolFlags::new(cast.span).set_value(target).clone())
+    }
+
     fn visit_group(&mut self, group: ParenGroup) -> Result<Self::Output, Self::Error> {
         self.visit_expr(*group.expr)
     }
 
     fn visit_call(&mut self, call: Call) -> Result<Self::Output, Self::Error> {
-        let x = self.env.get_variable(&call.name);
+        let x = match &call.module {
+            Some(module) => Some(self.get_module_item(module, &call.name, call.span)?),
+            None => self.env.get_variable(&call.name).cloned(),
+        };
+
+        if let Some(var) = &x {
+            self.record(call.span, var);
+        }
 
         match x {
             Some(var) => match var.value.get_function() {
@@ -231,7 +523,11 @@ impl ExprVisitor for ExprAnalyzer {
                     let mut args_s = call
                         .arguments
                         .iter()
-                        .map(|arg| self.clone().visit_expr(arg.clone()))
+                        .enumerate()
+                        .map(|(i, arg)| {
+                            self.expecting(args.get(i).cloned(), arg.span())
+                                .visit_expr(arg.clone())
+                        })
                         .collect::<Result<Vec<_>, _>>()?;
                     if is_var_args {
                         if args_s.len() < args.len() {
@@ -268,6 +564,11 @@ impl ExprVisitor for ExprAnalyzer {
                     (call.span, var.value.get_value().unwrap().to_string()),
                 ))),
             },
+            None if call.module.is_none()
+                && matches!(call.name.as_str(), "len" | "push" | "pop" | "assert") =>
+            {
+                self.visit_builtin(call)
+            }
             None => {
                 let name_candidates = self.env.get_all_variables_name();
                 let similar_name =
@@ -300,17 +601,25 @@ impl ExprVisitor for ExprAnalyzer {
             .env
             .get_variable(struct_instance.name.as_str())
             .unwrap();
+        self.record(struct_instance.span, struct_model);
         let struct_model_value = struct_model.value.get_value().unwrap();
         if let ValueFlag::Struct(ref name) = struct_model_value {
             let mut fields_s = Vec::new();
+            let fields = self.env.get_struct(name).unwrap();
 
             for field in struct_instance.fields {
+                if self.env.is_private_field(name, &field.name) {
+                    return Err(Box::new(PrivateAccess::new(field.name, name.clone(), field.span)));
+                }
+                let expected = fields.get(&field.name).cloned();
                 fields_s.push((
                     field.name.clone(),
-                    self.visit_expr(field.value)?.get_value().unwrap(),
+                    self.expecting(expected, field.span)
+                        .visit_expr(field.value)?
+                        .get_value()
+                        .unwrap(),
                 ))
             }
-            let fields = self.env.get_struct(name).unwrap();
             let mut sorted_fields = fields.iter().collect::<Vec<_>>();
             sorted_fields.sort_by(|a, b| a.0.cmp(b.0));
 
@@ -356,6 +665,20 @@ impl ExprVisitor for ExprAnalyzer {
         &mut self,
         struct_field_access: StructFieldAccess,
     ) -> Result<Self::Output, Self::Error> {
+        if let Expression::Constant(Constant::Ident(module)) = struct_field_access.name.as_ref() {
+            if !struct_field_access.is_ptr && self.is_module(&struct_field_access.name) {
+                let item = self.get_module_item(
+                    module,
+                    &struct_field_access.field,
+                    struct_field_access.span,
+                )?;
+                self.record(struct_field_access.span, &item);
+                return Ok(SymbolFlags::new(struct_field_access.span)
+                    .set_value(item.value.get_value().unwrap())
+                    .clone());
+            }
+        }
+
         let struct_model = self.visit_expr(*struct_field_access.name.clone())?;
         let mut struct_model_value = struct_model.get_value().unwrap();
         if struct_field_access.is_ptr {
@@ -370,6 +693,13 @@ impl ExprVisitor for ExprAnalyzer {
         if let ValueFlag::Struct(ref name) = struct_model_value {
             let s = self.env.get_struct(name).unwrap();
             match s.get(&struct_field_access.field) {
+                Some(_) if self.env.is_private_field(name, &struct_field_access.field) => {
+                    Err(Box::new(PrivateAccess::new(
+                        struct_field_access.field.clone(),
+                        name.clone(),
+                        struct_field_access.span,
+                    )))
+                }
                 Some(flag) => Ok(SymbolFlags::new(struct_field_access.span)
                     .set_value(flag.clone())
                     .clone()),
@@ -396,13 +726,21 @@ impl ExprVisitor for ExprAnalyzer {
     }
 
     fn visit_index(&mut self, index: Index) -> Result<Self::Output, Self::Error> {
-        let res = self.visit_expr(*index.value.clone())?;
-        let ind = self.visit_expr(*index.index.clone())?;
-        let list = res.get_list();
+        let res = self.expecting(None, index.span).visit_expr(*index.value.clone())?;
+        let ind = self.expecting(None, index.span).visit_expr(*index.index.clone())?;
+        let element = res.get_value().and_then(|x| x.get_element_type().cloned());
+
+        if let (Some((_, len)), Expression::Constant(Constant::Int(i))) =
+            (res.get_list(), index.index.as_ref())
+        {
+            if i.value < 0 || i.value as usize >= len {
+                return Err(Box::new(IndexOutOfRange::new(i.value, len, index.span)));
+            }
+        }
 
-        if let Some(l) = list {
-            if ind.is_integer() {
-                Ok(SymbolFlags::new(index.span).set_value(l.0).clone())
+        if let Some(element) = element {
+            if ind.get_value().and_then(|x| x.int_kind()).is_some() {
+                Ok(SymbolFlags::new(index.span).set_value(element).clone())
             } else {
                 Err(Box::new(TypeMismatch::new(
                     (index.index.span(), "int".to_string()),
@@ -417,11 +755,61 @@ impl ExprVisitor for ExprAnalyzer {
         }
     }
 
+    fn visit_slice(&mut self, slice: Slice) -> Result<Self::Output, Self::Error> {
+        let res = self.expecting(None, slice.span).visit_expr(*slice.value.clone())?;
+        self.visit_bound(slice.start)?;
+        self.visit_bound(slice.end)?;
+
+        let value = res.get_value().unwrap();
+        if res.is_string() {
+            Ok(SymbolFlags::new(slice.span).set_string().clone())
+        } else if let Some(element) = value.get_element_type() {
+            Ok(SymbolFlags::new(slice.span)
+                .set_value(ValueFlag::Slice(Box::new(element.clone())))
+                .clone())
+        } else {
+            Err(Box::new(TypeMismatch::new(
+                (slice.value.span(), "string, list, slice or vec".to_string()),
+                (slice.value.span(), res.get_value().unwrap().to_string()),
+            )))
+        }
+    }
+
+    fn visit_tuple_access(
+        &mut self,
+        tuple_access: TupleAccess,
+    ) -> Result<Self::Output, Self::Error> {
+        let res = self.visit_expr(*tuple_access.value.clone())?;
+
+        if let Some(elements) = res.get_tuple() {
+            match elements.get(tuple_access.index) {
+                Some(element) => Ok(SymbolFlags::new(tuple_access.span)
+                    .set_value(element.clone())
+                    .clone()),
+                None => Err(Box::new(FieldNotFound::new(
+                    tuple_access.index.to_string(),
+                    tuple_access.span,
+                    None,
+                ))),
+            }
+        } else {
+            Err(Box::new(TypeMismatch::new(
+                (tuple_access.value.span(), "tuple".to_string()),
+                (
+                    tuple_access.value.span(),
+                    res.get_value().unwrap().to_string(),
+                ),
+            )))
+        }
+    }
+
     fn visit_expr(&mut self, expr: Expression) -> Result<Self::Output, Self::Error> {
-        match expr {
+        let span = expr.span();
+        let flag = match expr {
             Expression::Constant(constant) => self.visit_constant(constant),
             Expression::BinOp(bin_op) => self.visit_bin_op(bin_op),
             Expression::UnaryOp(unary_op) => self.visit_unary_op(unary_op),
+            Expression::Cast(cast) => self.visit_cast(cast),
             Expression::Group(group) => self.visit_group(group),
             Expression::Call(call) => self.visit_call(call),
             Expression::StructInstance(struct_instance) => {
@@ -431,9 +819,15 @@ impl ExprVisitor for ExprAnalyzer {
                 self.visit_struct_field_access(struct_field_access)
             }
             Expression::Index(index) => self.visit_index(index),
+            Expression::Slice(slice) => self.visit_slice(slice),
+            Expression::TupleAccess(tuple_access) => self.visit_tuple_access(tuple_access),
             Expression::VaArg(va_arg) => self.visit_va_arg(va_arg),
             Expression::Reference(r) => self.visit_reference(r),
             Expression::Deref(p) => self.visit_deref(p),
+        }?;
+        if let (Some(types), Some(value)) = (&self.types, flag.get_value()) {
+            types.record(span, value);
         }
+        Ok(flag)
     }
 }
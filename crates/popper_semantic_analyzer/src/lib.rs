@@ -2,6 +2,11 @@
 
 mod expr_analyzer;
 mod stmt_analyzer;
+mod symbols;
+mod types;
+
+pub use symbols::Symbols;
+pub use types::Types;
 
 pub fn analyze(stmts: Vec<popper_ast::Statement>) -> Vec<Result<(), Box<dyn popper_error::Error>>> {
     use popper_ast::visitor::StmtVisitor;
@@ -22,3 +27,77 @@ pub fn analyze(stmts: Vec<popper_ast::Statement>) -> Vec<Result<(), Box<dyn popp
         })
         .collect()
 }
+
+/// analyze a module whose imports were already analyzed, `modules` giving their
+/// environment by file.
+///
+/// return: the environment of the module, holding the items it exports
+pub fn analyze_module(
+    stmts: Vec<popper_ast::Statement>,
+    modules: std::collections::HashMap<std::path::PathBuf, popper_flag::Environment>,
+) -> Result<popper_flag::Environment, Vec<Box<dyn popper_error::Error>>> {
+    use popper_ast::visitor::StmtVisitor;
+    use popper_flag::Environment;
+
+    let mut stmt_analyzer =
+        stmt_analyzer::StmtAnalyzer::new(Environment::new()).with_modules(modules);
+    let errs: Vec<_> = stmts
+        .into_iter()
+        .filter_map(|stmt| stmt_analyzer.visit_stmt(stmt).err())
+        .collect();
+
+    if errs.is_empty() {
+        Ok(stmt_analyzer.into_env())
+    } else {
+        Err(errs)
+    }
+}
+
+/// analyze a module like [`analyze_module`], recording the names it resolves on the way.
+/// Used by the tools working on the source, the analysis goes on after an error
+///
+/// return: the symbols of the module and the errors found
+pub fn analyze_symbols(
+    stmts: Vec<popper_ast::Statement>,
+    modules: std::collections::HashMap<std::path::PathBuf, popper_flag::Environment>,
+) -> (Symbols, Vec<Box<dyn popper_error::Error>>) {
+    use popper_ast::visitor::StmtVisitor;
+    use popper_flag::Environment;
+
+    let symbols = std::rc::Rc::new(std::cell::RefCell::new(Symbols::default()));
+    let mut stmt_analyzer = stmt_analyzer::StmtAnalyzer::new(Environment::new())
+        .with_modules(modules)
+        .with_symbols(symbols.clone());
+    let errs: Vec<_> = stmts
+        .into_iter()
+        .filter_map(|stmt| stmt_analyzer.visit_stmt(stmt).err())
+        .collect();
+
+    let env = stmt_analyzer.into_env();
+    let mut symbols = symbols.take();
+    symbols.env = env;
+    (symbols, errs)
+}
+
+/// analyze a module like [`analyze_module`], recording the type of each expression of
+/// the module and of the modules it imports. Used by the code generator
+///
+/// return: the types of the expressions and the errors found
+pub fn analyze_types(
+    stmts: Vec<popper_ast::Statement>,
+    modules: std::collections::HashMap<std::path::PathBuf, popper_flag::Environment>,
+) -> (Types, Vec<Box<dyn popper_error::Error>>) {
+    use popper_ast::visitor::StmtVisitor;
+    use popper_flag::Environment;
+
+    let types = types::TypesRecorder::default();
+    let mut stmt_analyzer = stmt_analyzer::StmtAnalyzer::new(Environment::new())
+        .with_modules(modules)
+        .with_types(types.clone());
+    let errs: Vec<_> = stmts
+        .into_iter()
+        .filter_map(|stmt| stmt_analyzer.visit_stmt(stmt).err())
+        .collect();
+
+    (types.take(), errs)
+}
@@ -2,10 +2,16 @@ use popper_ast::*;
 
 
 
+use popper_common::name_similarity::find_similar_name;
+use popper_error::namenotfound::NameNotFound;
 use popper_error::notallowed::NotAllowed;
-use std::collections::HashMap;
+use popper_error::privateaccess::PrivateAccess;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
 use crate::expr_analyzer::ExprAnalyzer;
+use crate::symbols::SharedSymbols;
+use crate::types::TypesRecorder;
 use popper_ast::visitor::ExprVisitor;
 use popper_error::modulenotfound::ModuleNotFound;
 use popper_error::{
@@ -20,6 +26,12 @@ pub struct StmtAnalyzer {
     current_scope: ScopeFlag,
     is_return: bool,
     return_type: Option<ValueFlag>,
+    /// environments of the modules already analyzed, by file
+    modules: HashMap<PathBuf, Environment>,
+    /// where the names resolved and the scopes are recorded, if they are
+    symbols: Option<SharedSymbols>,
+    /// where the types of the expressions are recorded, if they are
+    types: Option<TypesRecorder>,
 }
 
 impl StmtAnalyzer {
@@ -29,21 +41,56 @@ impl StmtAnalyzer {
             current_scope: ScopeFlag::Global,
             return_type: None,
             is_return: false,
+            modules: HashMap::new(),
+            symbols: None,
+            types: None,
         }
     }
+
+    pub(crate) fn with_symbols(mut self, symbols: SharedSymbols) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    pub(crate) fn with_types(mut self, types: TypesRecorder) -> Self {
+        self.types = Some(types);
+        self
+    }
+
+    fn expr_analyzer(&self) -> ExprAnalyzer {
+        ExprAnalyzer::new(self.env.clone())
+            .with_symbols(self.symbols.clone())
+            .with_types(self.types.clone())
+    }
+
+    /// record the environment at the end of the function or block at `span`
+    fn record_scope(&self, span: Span) {
+        if let Some(symbols) = &self.symbols {
+            symbols.borrow_mut().scopes.push((span, self.env.clone()));
+        }
+    }
+
+    pub fn with_modules(mut self, modules: HashMap<PathBuf, Environment>) -> Self {
+        self.modules = modules;
+        self
+    }
+
+    pub fn into_env(self) -> Environment {
+        self.env
+    }
 }
 
 impl visitor::StmtVisitor for StmtAnalyzer {
     type Output = SymbolFlags;
     type Error = Box<dyn Error>;
     fn visit_expr_stmt(&mut self, expr: Expression) -> Result<Self::Output, Self::Error> {
-        let mut analyzer = ExprAnalyzer::new(self.env.clone());
+        let mut analyzer = self.expr_analyzer();
 
         analyzer.visit_expr(expr)
     }
 
     fn visit_let_stmt(&mut self, let_stmt: LetStmt) -> Result<Self::Output, Self::Error> {
-        let mut analyzer = ExprAnalyzer::new(self.env.clone());
+        let mut analyzer = self.expr_analyzer();
 
         let value = if let Some(ref ty) = let_stmt.r#type {
             let r: ValueFlag = ValueFlag::from_ty(ty.clone());
@@ -76,6 +123,51 @@ impl visitor::StmtVisitor for StmtAnalyzer {
         Ok(value)
     }
 
+    fn visit_let_tuple_stmt(
+        &mut self,
+        let_tuple_stmt: LetTupleStmt,
+    ) -> Result<Self::Output, Self::Error> {
+        let mut analyzer = self.expr_analyzer();
+        let value = analyzer.visit_expr(let_tuple_stmt.value.clone())?;
+        let x = value.get_value().unwrap();
+
+        if let Some(ref ty) = let_tuple_stmt.r#type {
+            let r: ValueFlag = ValueFlag::from_ty(ty.clone());
+            if r != x {
+                return Err(Box::new(TypeMismatch::new(
+                    (ty.clone().span, ty.type_kind.to_string()),
+                    (let_tuple_stmt.value.span(), x.to_string()),
+                )));
+            }
+        }
+
+        let elements = match value.get_tuple() {
+            Some(elements) if elements.len() == let_tuple_stmt.names.len() => elements,
+            _ => {
+                return Err(Box::new(TypeMismatch::new(
+                    (
+                        let_tuple_stmt.span,
+                        format!("tuple of {} elements", let_tuple_stmt.names.len()),
+                    ),
+                    (let_tuple_stmt.value.span(), x.to_string()),
+                )))
+            }
+        };
+
+        for (name, element) in let_tuple_stmt.names.into_iter().zip(elements) {
+            let variable = VariableFlag::new(
+                name.name,
+                SymbolFlags::new(name.span).set_value(element).clone(),
+                self.current_scope.clone(),
+                let_tuple_stmt.mutable,
+                name.span,
+            );
+            self.env.add_variable(variable);
+        }
+
+        Ok(value)
+    }
+
     fn visit_assign(&mut self,assign: Assign) -> Result<Self::Output,Self::Error> {
 
         if !assign.name.is_assignable() {
@@ -87,8 +179,9 @@ impl visitor::StmtVisitor for StmtAnalyzer {
             )));
         }
 
-        let mut analyzer = ExprAnalyzer::new(self.env.clone());
+        let mut analyzer = self.expr_analyzer();
         let name = analyzer.visit_expr(assign.name.clone())?;
+        analyzer.set_let_expected_value(name.clone());
         let value = analyzer.visit_expr(assign.value.clone())?;
 
         if name.get_value().unwrap() != value.get_value().unwrap() && name.get_value().unwrap().get_minor_type().cloned().unwrap() != value.get_value().unwrap() {
@@ -108,16 +201,36 @@ impl visitor::StmtVisitor for StmtAnalyzer {
         let mut analyzer = self.clone();
 
         let mut result = SymbolFlags::new(block.span());
+        let span = block.span();
 
         for stmt in block.statements {
             result = analyzer.visit_stmt(stmt)?;
         }
+        analyzer.record_scope(span);
 
         Ok(result)
     }
 
+    fn visit_test(&mut self, test: TestStmt) -> Result<Self::Output, Self::Error> {
+        // the body of a test sees the items of the module, and can't return
+        let mut analyzer = self.clone();
+        analyzer.env = self.env.keep_static_member();
+        analyzer.return_type = None;
+
+        for stmt in test.body.statements {
+            analyzer.visit_stmt(stmt)?;
+        }
+        analyzer.record_scope(test.span);
+
+        Ok(SymbolFlags::new(test.span))
+    }
+
+    fn visit_module_doc(&mut self, module_doc: ModuleDoc) -> Result<Self::Output, Self::Error> {
+        Ok(SymbolFlags::new(module_doc.span))
+    }
+
     fn visit_while_stmt(&mut self, while_stmt: While) -> Result<Self::Output, Self::Error> {
-        let mut analyzer = ExprAnalyzer::new(self.env.clone());
+        let mut analyzer = self.expr_analyzer();
         let symbol_flag = SymbolFlags::new(while_stmt.span());
         let condition = analyzer.visit_expr(while_stmt.condition.clone())?;
 
@@ -142,7 +255,7 @@ impl visitor::StmtVisitor for StmtAnalyzer {
     }
 
     fn visit_if_stmt(&mut self, if_stmt: If) -> Result<Self::Output, Self::Error> {
-        let mut analyzer = ExprAnalyzer::new(self.env.clone());
+        let mut analyzer = self.expr_analyzer();
         let symbol_flag = SymbolFlags::new(if_stmt.span);
         let condition = analyzer.visit_expr(if_stmt.condition.clone())?;
 
@@ -157,6 +270,8 @@ impl visitor::StmtVisitor for StmtAnalyzer {
         }
 
         let mut analyzer = StmtAnalyzer::new(self.env.clone());
+        analyzer.symbols = self.symbols.clone();
+        analyzer.types = self.types.clone();
         analyzer.is_return = self.is_return;
         analyzer.return_type = self.return_type.clone();
         let _body = analyzer.visit_stmt(*if_stmt.body)?;
@@ -165,7 +280,7 @@ impl visitor::StmtVisitor for StmtAnalyzer {
     }
 
     fn visit_if_else_stmt(&mut self, if_else_stmt: IfElse) -> Result<Self::Output, Self::Error> {
-        let mut analyzer = ExprAnalyzer::new(self.env.clone());
+        let mut analyzer = self.expr_analyzer();
         let symbol_flag = SymbolFlags::new(if_else_stmt.span);
         let condition = analyzer.visit_expr(if_else_stmt.condition.clone())?;
 
@@ -183,6 +298,8 @@ impl visitor::StmtVisitor for StmtAnalyzer {
         }
 
         let mut analyzer = StmtAnalyzer::new(self.env.clone());
+        analyzer.symbols = self.symbols.clone();
+        analyzer.types = self.types.clone();
 
         let _body = analyzer.visit_stmt(*if_else_stmt.body)?;
         let _else_body = analyzer.visit_stmt(*if_else_stmt.else_body)?;
@@ -215,7 +332,7 @@ impl visitor::StmtVisitor for StmtAnalyzer {
         for arg in function.arguments.args {
             let mut symbol_flag = SymbolFlags::new(function.span);
             let name = arg.name;
-            let val = ExprAnalyzer::new(self.env.clone()).get_type(arg.ty);
+            let val = self.expr_analyzer().get_type(arg.ty);
             symbol_flag = symbol_flag.add_flag(Flag::Value(val.clone())).clone();
             let variable = VariableFlag::new(
                 name,
@@ -229,16 +346,41 @@ impl visitor::StmtVisitor for StmtAnalyzer {
         }
 
         let return_type = {
-            let expr_analyser = ExprAnalyzer::new(self.env.clone());
+            let expr_analyser = self.expr_analyzer();
             Box::new(expr_analyser.get_type(function.returntype.clone()))
         };
 
         self.return_type = Some(*return_type.clone());
 
+        // the program receives its arguments, the path of the executable first
+        if function.name == "main" {
+            let main_type =
+                ValueFlag::Function(args.clone(), return_type.clone(), function.is_var_args);
+            // a `main` returning `unit` exits with 0
+            let is_valid = !function.is_var_args
+                && (return_type.is_same(&ValueFlag::Integer)
+                    || return_type.is_same(&ValueFlag::None))
+                && match args.as_slice() {
+                    [] => true,
+                    [arg] => arg.is_same(&ValueFlag::Vec(Box::new(ValueFlag::String))),
+                    _ => false,
+                };
+            if !is_valid {
+                return Err(Box::new(TypeMismatch::new(
+                    (function.span, "func(vec<string>): int".to_string()),
+                    (function.span, main_type.to_string()),
+                )));
+            }
+        }
+
         let symbol_flag = SymbolFlags::new(function.span)
             .set_function(args, *return_type.clone(), function.is_var_args)
             .clone();
 
+        if function.is_pub {
+            old_env.set_public(function.name.clone());
+        }
+
         let function_flag = VariableFlag::new(
             function.name,
             symbol_flag,
@@ -252,6 +394,7 @@ impl visitor::StmtVisitor for StmtAnalyzer {
         for stmt in function.body {
             self.visit_stmt(stmt)?;
         }
+        self.record_scope(function.span);
 
         if !(self.is_return || self.return_type.is_some() && self.return_type.clone().unwrap().is_same(&ValueFlag::None)) {
             return Err(Box::new(TypeMismatch::new(
@@ -268,7 +411,7 @@ impl visitor::StmtVisitor for StmtAnalyzer {
     }
 
     fn visit_extern(&mut self, extern_stmt: Extern) -> Result<Self::Output, Self::Error> {
-        let _analyzer = ExprAnalyzer::new(self.env.clone());
+        let _analyzer = self.expr_analyzer();
 
         for sign in &extern_stmt.signs {
             let args: Vec<ValueFlag> = sign
@@ -276,13 +419,13 @@ impl visitor::StmtVisitor for StmtAnalyzer {
                 .args
                 .iter()
                 .map(|x| {
-                    let expr_analyzer = ExprAnalyzer::new(self.env.clone());
+                    let expr_analyzer = self.expr_analyzer();
                     expr_analyzer.get_type(x.ty.clone())
                 })
                 .collect();
 
             let return_type = {
-                let expr_analyzer = ExprAnalyzer::new(self.env.clone());
+                let expr_analyzer = self.expr_analyzer();
                 expr_analyzer.get_type(sign.return_type.clone())
             };
 
@@ -295,17 +438,23 @@ impl visitor::StmtVisitor for StmtAnalyzer {
                 false,
                 Default::default(),
             );
-            self.env.add_variable(var);
+            // C functions are always visible from other modules
+            self.env.add_variable(var).set_public(sign.name.clone());
         }
 
         Ok(SymbolFlags::new(extern_stmt.span()))
     }
 
     fn visit_return(&mut self, return_expr: Return) -> Result<Self::Output, Self::Error> {
-        let mut expr_analyzer = ExprAnalyzer::new(self.env.clone());
+        let mut expr_analyzer = self.expr_analyzer();
         if self.return_type.is_none() {
             return Err(Box::new(NotAllowed::new(return_expr.span, "function", "return", "keyword")));
         }
+        expr_analyzer.set_let_expected_value(
+            SymbolFlags::new(return_expr.span)
+                .set_value(self.return_type.clone().unwrap())
+                .clone(),
+        );
         let val = return_expr
             .expression
             .map(|x| expr_analyzer.visit_expr(*x).map(|x| x.get_value().unwrap()))
@@ -326,22 +475,84 @@ impl visitor::StmtVisitor for StmtAnalyzer {
     }
 
     fn visit_import(&mut self, import: ImportStmt) -> Result<Self::Output, Self::Error> {
-        let path = popper_common::ast_path_to_path::ast_path_to_path(import.path.clone());
-
-        if !path.exists() {
+        let Some(file) = import.file.clone() else {
             return Err(Box::new(ModuleNotFound::new(
                 import.path.to_string(),
                 import.path.span(),
             )));
-        }
+        };
 
-        let mut stmt_analyzer = StmtAnalyzer::new(Environment::new());
+        let module = match self.modules.get(&file) {
+            Some(module) => module.clone(),
+            None => {
+                let mut stmt_analyzer =
+                    StmtAnalyzer::new(Environment::new()).with_modules(self.modules.clone());
+                stmt_analyzer.types = self.types.as_ref().map(|x| x.module(file.clone()));
+                for stmt in import.module_stmts.clone() {
+                    stmt_analyzer.visit_stmt(stmt)?;
+                }
+                self.modules.insert(file, stmt_analyzer.env.clone());
+                stmt_analyzer.env
+            }
+        };
 
-        for stmt in import.module_stmts.clone() {
-            stmt_analyzer.visit_stmt(stmt)?;
+        let mut private_fields = HashMap::new();
+        for stmt in &import.module_stmts {
+            if let Statement::Struct(s) = stmt {
+                let fields: HashSet<_> = s
+                    .fields
+                    .iter()
+                    .filter(|x| !x.is_pub)
+                    .map(|x| x.name.clone())
+                    .collect();
+                private_fields.insert(s.name.clone(), fields);
+            }
         }
+        // struct types are shared so values of the module can be used, their
+        // names are only in scope when imported
+        self.env.import_structs(&module, private_fields);
+
+        let Some(items) = import.items.clone() else {
+            let name = import.module_name().clone();
+            if let Some(v) = self.env.get_variable(&name.name) {
+                return Err(Box::new(AlreadyExist::new(v.span, (name.name, name.span))));
+            }
+            let variable = VariableFlag::new(
+                name.name,
+                SymbolFlags::new(import.span)
+                    .set_value(ValueFlag::Module(Box::new(module)))
+                    .clone(),
+                ScopeFlag::Global,
+                false,
+                name.span,
+            );
+            self.env.add_variable(variable);
+            return Ok(SymbolFlags::new(import.span()));
+        };
 
-        self.env.extend(&mut stmt_analyzer.env.clone());
+        for item in items {
+            let Some(variable) = module.get_variable(&item.name) else {
+                let name_candidates = module.get_all_variables_name();
+                let similar_name = find_similar_name(name_candidates.as_slice(), &item.name);
+                return Err(Box::new(NameNotFound::new(
+                    (item.span, item.name.clone()),
+                    similar_name.cloned(),
+                )));
+            };
+            if !module.is_public(&item.name) {
+                return Err(Box::new(PrivateAccess::new(
+                    item.name,
+                    import.path.to_string(),
+                    item.span,
+                )));
+            }
+            if let Some(v) = self.env.get_variable(&item.name) {
+                return Err(Box::new(AlreadyExist::new(v.span, (item.name, item.span))));
+            }
+            let mut variable = variable.clone();
+            variable.span = item.span;
+            self.env.add_variable(variable);
+        }
 
         Ok(SymbolFlags::new(import.span()))
     }
@@ -350,6 +561,7 @@ impl visitor::StmtVisitor for StmtAnalyzer {
         match stmt {
             Statement::Expression(expr) => self.visit_expr_stmt(expr),
             Statement::Let(let_stmt) => self.visit_let_stmt(let_stmt),
+            Statement::LetTuple(let_tuple_stmt) => self.visit_let_tuple_stmt(let_tuple_stmt),
             Statement::Block(block) => self.visit_block(block),
             Statement::While(while_stmt) => self.visit_while_stmt(while_stmt),
             Statement::If(if_stmt) => self.visit_if_stmt(if_stmt),
@@ -363,11 +575,13 @@ impl visitor::StmtVisitor for StmtAnalyzer {
             Statement::Struct(struct_stmt) => self.visit_struct_stmt(struct_stmt),
             Statement::Extern(ext) => self.visit_extern(ext),
             Statement::Assign(a) => self.visit_assign(a),
+            Statement::Test(test) => self.visit_test(test),
+            Statement::ModuleDoc(module_doc) => self.visit_module_doc(module_doc),
         }
     }
 
     fn visit_external(&mut self, external: External) -> Result<Self::Output, Self::Error> {
-        let _analyzer = ExprAnalyzer::new(self.env.clone());
+        let _analyzer = self.expr_analyzer();
 
         for sign in &external.signs {
             let args: Vec<ValueFlag> = sign
@@ -375,13 +589,13 @@ impl visitor::StmtVisitor for StmtAnalyzer {
                 .args
                 .iter()
                 .map(|x| {
-                    let expr_analyzer = ExprAnalyzer::new(self.env.clone());
+                    let expr_analyzer = self.expr_analyzer();
                     expr_analyzer.get_type(x.ty.clone())
                 })
                 .collect();
 
             let return_type = {
-                let expr_analyzer = ExprAnalyzer::new(self.env.clone());
+                let expr_analyzer = self.expr_analyzer();
                 expr_analyzer.get_type(sign.return_type.clone())
             };
 
@@ -394,14 +608,40 @@ impl visitor::StmtVisitor for StmtAnalyzer {
                 false,
                 Default::default(),
             );
-            self.env.add_variable(var);
+            self.env.add_variable(var).set_public(sign.name.clone());
         }
 
         Ok(SymbolFlags::new(external.span()))
     }
 
-    fn visit_for_stmt(&mut self, _for_stmt: ForStmt) -> Result<Self::Output, Self::Error> {
-        todo!()
+    fn visit_for_stmt(&mut self, for_stmt: ForStmt) -> Result<Self::Output, Self::Error> {
+        let symbol_flag = SymbolFlags::new(for_stmt.span());
+        let mut analyzer = self.expr_analyzer();
+        let iterable = analyzer.visit_expr(for_stmt.expr.clone())?;
+        let iterable = iterable.get_value().unwrap();
+
+        let Some(element) = iterable.get_element_type() else {
+            return Err(Box::new(TypeMismatch::new(
+                (for_stmt.expr.span(), "list, slice or vec".to_string()),
+                (for_stmt.expr.span(), iterable.to_string()),
+            )));
+        };
+
+        let mut analyzer = self.clone();
+        analyzer.current_scope = ScopeFlag::Loop;
+        analyzer.env.add_variable(VariableFlag::new(
+            for_stmt.it.name.clone(),
+            SymbolFlags::new(for_stmt.it.span).set_value(element.clone()).clone(),
+            ScopeFlag::Loop,
+            false,
+            for_stmt.it.span,
+        ));
+
+        for stmt in for_stmt.body.statements {
+            analyzer.visit_stmt(stmt)?;
+        }
+
+        Ok(symbol_flag)
     }
 
     fn visit_struct_stmt(&mut self, struct_stmt: StructStmt) -> Result<Self::Output, Self::Error> {
@@ -414,7 +654,7 @@ impl visitor::StmtVisitor for StmtAnalyzer {
             .fields
             .iter()
             .map(|field| {
-                let expr_analyzer = ExprAnalyzer::new(self.env.clone());
+                let expr_analyzer = self.expr_analyzer();
                 let ty = expr_analyzer.get_type(field.ty.clone());
                 (field.name.clone(), ty)
             })
@@ -431,6 +671,9 @@ impl visitor::StmtVisitor for StmtAnalyzer {
 
         self.env.add_variable(variable);
         self.env.add_struct(struct_stmt.name.clone(), field);
+        if struct_stmt.is_pub {
+            self.env.set_public(struct_stmt.name.clone());
+        }
 
         Ok(SymbolFlags::new(struct_stmt.span))
     }
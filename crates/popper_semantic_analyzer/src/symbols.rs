@@ -0,0 +1,39 @@
+use popper_ast::Span;
+use popper_flag::{Environment, VariableFlag};
+
+///
+/// Symbols holds what the analyzer learned about the names of a module,
+/// for the tools working on its source, like the language server
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    /// environment of the module once analyzed
+    pub env: Environment,
+    /// names resolved by the analyzer: where each one is used and the variable it refers to
+    pub references: Vec<(Span, VariableFlag)>,
+    /// environment at the end of each function and block, by span
+    pub scopes: Vec<(Span, Environment)>,
+}
+
+impl Symbols {
+    /// innermost reference containing `offset`
+    pub fn reference_at(&self, offset: usize) -> Option<&(Span, VariableFlag)> {
+        self.references
+            .iter()
+            .filter(|(span, _)| span.start <= offset && offset <= span.end)
+            .min_by_key(|(span, _)| span.end - span.start)
+    }
+
+    /// environment visible at `offset`: the one of the innermost function or block, or the
+    /// one of the module
+    pub fn env_at(&self, offset: usize) -> &Environment {
+        self.scopes
+            .iter()
+            .filter(|(span, _)| span.start <= offset && offset <= span.end)
+            .min_by_key(|(span, _)| span.end - span.start)
+            .map(|(_, env)| env)
+            .unwrap_or(&self.env)
+    }
+}
+
+/// symbols recorded by the analyzers of a module as they go
+pub(crate) type SharedSymbols = std::rc::Rc<std::cell::RefCell<Symbols>>;
@@ -0,0 +1,53 @@
+use popper_ast::Span;
+use popper_flag::ValueFlag;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+///
+/// Types holds the type the analyzer resolved for each expression of a program, for
+/// the code generator
+#[derive(Debug, Clone, Default)]
+pub struct Types {
+    /// by module: `None` for the analyzed file, the file of an imported module otherwise
+    modules: HashMap<Option<PathBuf>, HashMap<Span, ValueFlag>>,
+}
+
+impl Types {
+    /// type of the expression at `span` in `module`
+    pub fn get(&self, module: Option<&Path>, span: Span) -> Option<&ValueFlag> {
+        self.modules
+            .get(&module.map(Path::to_path_buf))
+            .and_then(|x| x.get(&span))
+    }
+
+    fn insert(&mut self, module: Option<PathBuf>, span: Span, value: ValueFlag) {
+        self.modules.entry(module).or_default().insert(span, value);
+    }
+}
+
+/// where the analyzers of a module record the types of its expressions
+#[derive(Clone, Default)]
+pub(crate) struct TypesRecorder {
+    module: Option<PathBuf>,
+    types: std::rc::Rc<std::cell::RefCell<Types>>,
+}
+
+impl TypesRecorder {
+    /// recorder of the imported module `file`
+    pub fn module(&self, file: PathBuf) -> Self {
+        Self {
+            module: Some(file),
+            types: self.types.clone(),
+        }
+    }
+
+    pub fn record(&self, span: Span, value: ValueFlag) {
+        self.types
+            .borrow_mut()
+            .insert(self.module.clone(), span, value);
+    }
+
+    pub fn take(&self) -> Types {
+        self.types.take()
+    }
+}
@@ -0,0 +1,446 @@
+use crate::VmError;
+use popper_ast::IntKind;
+use popper_flag::ValueFlag;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// first bytes of a bytecode file
+const MAGIC: &[u8; 4] = b"POPC";
+/// version of the bytecode format, bumped when an instruction or a table changes
+pub const VERSION: u16 = 2;
+
+/// instruction of the stack machine. Operands are indices in the tables of the module,
+/// in the locals of the function or in its code
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Instr {
+    /// push a constant of the pool
+    Const(u32),
+    /// push `()`
+    Unit,
+    /// push a local
+    Load(u32),
+    /// assign a local, through the pointer taken to it if there is one
+    Store(u32),
+    /// declare a local: assign it a new variable, which no pointer points to yet
+    Let(u32),
+    /// push a pointer to a local
+    RefLocal(u32),
+    Pop,
+    Dup,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+    Neg,
+    Not,
+    /// convert the value to a type of the type table, as `as` does
+    Cast(u32),
+    Jump(u32),
+    /// pop a `bool`, jump if it's `false`
+    JumpIfFalse(u32),
+    /// call a function of the function table with that many arguments
+    Call(u32, u32),
+    /// call a function of the foreign table, defined in C or by the VM
+    CallForeign(u32, u32),
+    Return,
+    /// pop that many elements into a list
+    MakeList(u32),
+    /// pop that many elements into a `vec`
+    MakeVec(u32),
+    MakeTuple(u32),
+    /// pop the fields of a struct of the struct table, in the order they're declared
+    MakeStruct(u32),
+    /// read the field named by a constant
+    GetField(u32),
+    /// pop a value, then a struct: push the struct with the field named by a constant set
+    SetField(u32),
+    GetElement(u32),
+    /// pop a value, then a tuple: push the tuple with the element set
+    SetElement(u32),
+    /// pop an index, then a list, a slice or a `vec`: push the element
+    Index,
+    /// pop a value, an index, then a sequence: push the sequence with the element set
+    SetIndex,
+    /// pop the bounds which are given, then a sequence or a string: push the part
+    /// between them
+    Slice {
+        start: bool,
+        end: bool,
+    },
+    Len,
+    /// pop an element, then a `vec`: append the element
+    Push,
+    /// pop the last element of a `vec`
+    PopVec,
+    /// read what a pointer points to
+    Deref,
+    /// pop a value, then a pointer: write the value where the pointer points to
+    StoreDeref,
+    /// read the next variadic argument as a type of the type table
+    VaArg(u32),
+    /// pop a message, then a `bool`: panic with the message if it's `false`
+    Assert,
+}
+
+/// value of the constant pool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Constant {
+    Int(IntKind, i64),
+    Float(f64),
+    Bool(bool),
+    Char(u8),
+    String(String),
+}
+
+/// where the instructions starting at `pc`, until the next location, come from
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub pc: u32,
+    pub line: u32,
+    pub column: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Function {
+    pub name: String,
+    /// number of arguments before the `...`
+    pub arity: u32,
+    pub is_var_args: bool,
+    /// number of locals, the arguments included
+    pub locals: u32,
+    pub code: Vec<Instr>,
+    /// index of the source file in the file table
+    pub file: u32,
+    pub locations: Vec<Location>,
+    /// where the statements start, in order, for the debugger to stop at them
+    pub statements: Vec<u32>,
+    /// the named locals, for the debugger to show them
+    pub variables: Vec<Variable>,
+}
+
+impl Function {
+    /// location of the instruction at `pc`
+    pub fn location(&self, pc: usize) -> Option<Location> {
+        let index = self.locations.partition_point(|x| x.pc as usize <= pc);
+        index.checked_sub(1).map(|x| self.locations[x])
+    }
+
+    pub fn is_statement(&self, pc: usize) -> bool {
+        self.statements.binary_search(&(pc as u32)).is_ok()
+    }
+
+    /// the variables in scope at `pc`, the innermost last
+    pub fn variables_at(&self, pc: usize) -> impl Iterator<Item = &Variable> {
+        self.variables
+            .iter()
+            .filter(move |x| x.start as usize <= pc && pc < x.end as usize)
+    }
+}
+
+/// a variable of the source, in scope from the instruction at `start` until the one
+/// at `end`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Variable {
+    pub name: String,
+    /// its local
+    pub slot: u32,
+    /// the type it's declared with, when it's written
+    pub ty: Option<ValueFlag>,
+    pub start: u32,
+    pub end: u32,
+}
+
+/// fields of a struct, in the order they're declared
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StructLayout {
+    pub name: String,
+    /// names of the fields, as constants of the pool
+    pub fields: Vec<u32>,
+    pub types: Vec<ValueFlag>,
+}
+
+/// function of an `extern` or `external` block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForeignFunction {
+    pub name: String,
+    pub args: Vec<ValueFlag>,
+    pub return_type: ValueFlag,
+    pub is_var_args: bool,
+}
+
+/// library of an `external` block, resolved like the compiled code resolves it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExternalLibrary {
+    pub file: String,
+    /// directory of the module declaring the block
+    pub dir: std::path::PathBuf,
+}
+
+/// a compiled program: the module given to the compiler and the modules it imports
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Module {
+    pub constants: Vec<Constant>,
+    pub types: Vec<ValueFlag>,
+    pub functions: Vec<Function>,
+    pub structs: Vec<StructLayout>,
+    pub foreign: Vec<ForeignFunction>,
+    pub libraries: Vec<ExternalLibrary>,
+    /// paths of the source files
+    pub files: Vec<String>,
+}
+
+impl Module {
+    pub fn function(&self, name: &str) -> Option<u32> {
+        self.functions
+            .iter()
+            .position(|x| x.name == name)
+            .map(|x| x as u32)
+    }
+
+    pub fn struct_layout(&self, name: &str) -> Option<u32> {
+        self.structs
+            .iter()
+            .position(|x| x.name == name)
+            .map(|x| x as u32)
+    }
+
+    /// names of the fields of the struct `index` of the struct table
+    pub fn field_names(&self, index: u32) -> Vec<String> {
+        self.structs[index as usize]
+            .fields
+            .iter()
+            .map(|name| match &self.constants[*name as usize] {
+                Constant::String(name) => name.clone(),
+                constant => format!("{:?}", constant),
+            })
+            .collect()
+    }
+
+    /// the module, in the format of bytecode files
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, self).expect("a module can be serialized");
+        bytes
+    }
+
+    /// read a module written by [`Module::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VmError> {
+        let invalid = |msg: &str| VmError::InvalidBytecode(msg.to_string());
+        if bytes.len() < 6 || &bytes[..4] != MAGIC {
+            return Err(invalid("not a Popper bytecode file"));
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != VERSION {
+            return Err(VmError::InvalidBytecode(format!(
+                "bytecode version {} isn't supported, recompile the source (version {})",
+                version, VERSION
+            )));
+        }
+        let module: Self =
+            bincode::deserialize(&bytes[6..]).map_err(|err| invalid(&err.to_string()))?;
+        module.validate()?;
+        Ok(module)
+    }
+
+    /// check the operands of the instructions are in their tables, the jumps stay in
+    /// their function and the stack never runs out, as in the modules of the compiler
+    pub fn validate(&self) -> Result<(), VmError> {
+        for layout in &self.structs {
+            if layout.fields.len() != layout.types.len() {
+                return Err(VmError::InvalidBytecode(format!(
+                    "struct `{}`: {} field(s) but {} type(s)",
+                    layout.name,
+                    layout.fields.len(),
+                    layout.types.len()
+                )));
+            }
+            for &name in &layout.fields {
+                self.name_constant(name).map_err(|msg| {
+                    VmError::InvalidBytecode(format!("struct `{}`: {}", layout.name, msg))
+                })?;
+            }
+        }
+        for function in &self.functions {
+            self.validate_function(function).map_err(|msg| {
+                VmError::InvalidBytecode(format!("function `{}`: {}", function.name, msg))
+            })?;
+        }
+        Ok(())
+    }
+
+    fn validate_function(&self, function: &Function) -> Result<(), String> {
+        if function.file as usize >= self.files.len() {
+            return Err(format!("no file {}", function.file));
+        }
+        if function.arity > function.locals {
+            return Err(format!(
+                "{} argument(s) but {} local(s)",
+                function.arity, function.locals
+            ));
+        }
+        if let Some(variable) = function
+            .variables
+            .iter()
+            .find(|x| x.slot >= function.locals)
+        {
+            return Err(format!("variable `{}` isn't a local", variable.name));
+        }
+
+        for (pc, instr) in function.code.iter().enumerate() {
+            self.validate_operands(function, instr)
+                .map_err(|msg| format!("{} at {}", msg, pc))?;
+        }
+
+        // depth of the stack before each instruction, the same on every path to it
+        let code = &function.code;
+        let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+        let mut pending = vec![(0, 0)];
+        while let Some((pc, depth)) = pending.pop() {
+            let Some(instr) = code.get(pc) else {
+                return Err(format!("the code ends without a `return` at {}", pc));
+            };
+            match depths[pc] {
+                Some(known) if known == depth => continue,
+                Some(known) => {
+                    return Err(format!(
+                        "the stack has {} or {} value(s) at {}",
+                        known, depth, pc
+                    ))
+                }
+                None => depths[pc] = Some(depth),
+            }
+            let (pops, pushes) = self.stack_effect(instr);
+            let Some(depth) = depth.checked_sub(pops) else {
+                return Err(format!("the stack runs out at {}", pc));
+            };
+            let depth = depth + pushes;
+            match *instr {
+                Instr::Jump(target) => pending.push((target as usize, depth)),
+                Instr::JumpIfFalse(target) => {
+                    pending.push((target as usize, depth));
+                    pending.push((pc + 1, depth));
+                }
+                Instr::Return => {}
+                _ => pending.push((pc + 1, depth)),
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_operands(&self, function: &Function, instr: &Instr) -> Result<(), String> {
+        let check = |index: u32, len: usize, what: &str| {
+            if index as usize >= len {
+                return Err(format!("no {} {}", what, index));
+            }
+            Ok(())
+        };
+        let check_argc = |name: &str, arity: usize, is_var_args: bool, argc: u32| {
+            let argc = argc as usize;
+            if argc < arity || (argc > arity && !is_var_args) {
+                return Err(format!("`{}` called with {} argument(s)", name, argc));
+            }
+            Ok(())
+        };
+        match *instr {
+            Instr::Const(index) => check(index, self.constants.len(), "constant"),
+            Instr::Load(slot) | Instr::Store(slot) | Instr::Let(slot) | Instr::RefLocal(slot) => {
+                check(slot, function.locals as usize, "local")
+            }
+            Instr::Cast(ty) | Instr::VaArg(ty) => check(ty, self.types.len(), "type"),
+            Instr::Jump(target) | Instr::JumpIfFalse(target) => {
+                check(target, function.code.len(), "instruction")
+            }
+            Instr::Call(index, argc) => {
+                check(index, self.functions.len(), "function")?;
+                let f = &self.functions[index as usize];
+                check_argc(&f.name, f.arity as usize, f.is_var_args, argc)
+            }
+            Instr::CallForeign(index, argc) => {
+                check(index, self.foreign.len(), "foreign function")?;
+                let f = &self.foreign[index as usize];
+                check_argc(&f.name, f.args.len(), f.is_var_args, argc)
+            }
+            Instr::MakeStruct(index) => check(index, self.structs.len(), "struct"),
+            Instr::GetField(name) | Instr::SetField(name) => self.name_constant(name),
+            _ => Ok(()),
+        }
+    }
+
+    /// the name of a field: a string of the pool
+    fn name_constant(&self, index: u32) -> Result<(), String> {
+        match self.constants.get(index as usize) {
+            Some(Constant::String(_)) => Ok(()),
+            Some(_) => Err(format!("constant {} isn't a name", index)),
+            None => Err(format!("no constant {}", index)),
+        }
+    }
+
+    /// values the instruction pops from the stack and pushes to it
+    fn stack_effect(&self, instr: &Instr) -> (usize, usize) {
+        match *instr {
+            Instr::Const(_)
+            | Instr::Unit
+            | Instr::Load(_)
+            | Instr::RefLocal(_)
+            | Instr::VaArg(_) => (0, 1),
+            Instr::Store(_) | Instr::Let(_) | Instr::Pop | Instr::JumpIfFalse(_) => (1, 0),
+            Instr::Return => (1, 0),
+            Instr::Dup => (1, 2),
+            Instr::Add
+            | Instr::Sub
+            | Instr::Mul
+            | Instr::Div
+            | Instr::Mod
+            | Instr::Pow
+            | Instr::Eq
+            | Instr::Ne
+            | Instr::Lt
+            | Instr::Le
+            | Instr::Gt
+            | Instr::Ge
+            | Instr::And
+            | Instr::Or => (2, 1),
+            Instr::Neg
+            | Instr::Not
+            | Instr::Cast(_)
+            | Instr::GetField(_)
+            | Instr::GetElement(_)
+            | Instr::Len
+            | Instr::PopVec
+            | Instr::Deref => (1, 1),
+            Instr::Jump(_) => (0, 0),
+            Instr::Call(_, argc)
+            | Instr::CallForeign(_, argc)
+            | Instr::MakeList(argc)
+            | Instr::MakeVec(argc)
+            | Instr::MakeTuple(argc) => (argc as usize, 1),
+            Instr::MakeStruct(index) => (self.structs[index as usize].fields.len(), 1),
+            Instr::SetField(_) | Instr::SetElement(_) | Instr::Index => (2, 1),
+            Instr::SetIndex => (3, 1),
+            Instr::Slice { start, end } => (1 + start as usize + end as usize, 1),
+            Instr::Push | Instr::StoreDeref | Instr::Assert => (2, 0),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), VmError> {
+        std::fs::write(path, self.to_bytes())
+            .map_err(|err| VmError::InvalidBytecode(format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, VmError> {
+        let bytes = std::fs::read(path)
+            .map_err(|err| VmError::InvalidBytecode(format!("{}: {}", path.display(), err)))?;
+        Self::from_bytes(&bytes)
+    }
+}
@@ -0,0 +1,863 @@
+use crate::bytecode::{
+    Constant, ExternalLibrary, ForeignFunction, Function, Instr, Location, Module, StructLayout,
+    Variable,
+};
+use crate::VmError;
+use popper_ast::{
+    BinOpKind, Constant as AstConstant, Expression, IntKind, Span, Statement, UnaryOpKind,
+};
+use popper_flag::ValueFlag;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// a function of the program, defined in Popper or in C
+#[derive(Clone)]
+enum Callee {
+    Function(u32),
+    Foreign(u32),
+}
+
+#[derive(Clone)]
+struct Signature {
+    callee: Callee,
+    params: Vec<ValueFlag>,
+    return_type: ValueFlag,
+}
+
+/// source file of the module being compiled, to locate the instructions
+struct Source {
+    index: u32,
+    text: String,
+    /// offsets of the starts of the lines
+    line_starts: Vec<usize>,
+}
+
+impl Source {
+    fn new(index: u32, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            index,
+            text,
+            line_starts,
+        }
+    }
+
+    fn locate(&self, span: Span) -> (u32, u32) {
+        let line = self.line_starts.partition_point(|x| *x <= span.start);
+        let line_start = self.line_starts[line - 1];
+        let column = self
+            .text
+            .get(line_start..span.start)
+            .map_or(1, |x| x.chars().count() + 1);
+        (line as u32, column as u32)
+    }
+}
+
+/// state of the function being compiled
+#[derive(Default)]
+struct FunctionState {
+    code: Vec<Instr>,
+    locations: Vec<Location>,
+    /// locals in scope, innermost scope last
+    scopes: Vec<HashMap<String, u32>>,
+    next_local: u32,
+    locals: u32,
+    /// jumps of the `break`s of the enclosing loops, to patch at their end
+    loops: Vec<Vec<usize>>,
+    return_type: Option<ValueFlag>,
+    statements: Vec<u32>,
+    /// named locals, the ones still in scope ending at `u32::MAX`
+    variables: Vec<Variable>,
+}
+
+///
+/// Compiler turns a checked program into the bytecode of the VM.
+/// Imported modules are compiled with the program, once each, and functions are
+/// called by name: the functions of all the modules share one namespace.
+///
+/// The bytecode keeps few types: values carry theirs. Integer literals take the type
+/// expected where they're written, and the integers stored in a variable, a field or
+/// an element are converted to the type of the value they replace
+pub struct Compiler {
+    module: Module,
+    signatures: HashMap<String, Signature>,
+    /// struct table index by name
+    structs: HashMap<String, u32>,
+    /// modules declared and compiled, by file
+    declared: HashSet<String>,
+    compiled: HashSet<String>,
+    source: Source,
+    function: FunctionState,
+}
+
+impl Compiler {
+    /// `source` is the content of `file`, the file of the compiled module
+    pub fn new(file: &str, source: &str) -> Self {
+        Self {
+            module: Module {
+                files: vec![file.to_string()],
+                ..Module::default()
+            },
+            signatures: HashMap::new(),
+            structs: HashMap::new(),
+            declared: HashSet::new(),
+            compiled: HashSet::new(),
+            source: Source::new(0, source.to_string()),
+            function: FunctionState::default(),
+        }
+    }
+
+    pub fn compile(mut self, stmts: &[Statement]) -> Result<Module, VmError> {
+        let file = PathBuf::from(&self.module.files[0]);
+        self.declare_module(stmts, &file);
+        self.compile_module(stmts)?;
+        Ok(self.module)
+    }
+
+    /// add the structs, functions and foreign functions of a module and of its imports
+    /// to the tables, so they can be used before they're defined
+    fn declare_module(&mut self, stmts: &[Statement], file: &Path) {
+        for stmt in stmts {
+            match stmt {
+                Statement::Struct(s) => {
+                    let layout = StructLayout {
+                        name: s.name.clone(),
+                        fields: s
+                            .fields
+                            .iter()
+                            .map(|x| self.constant(Constant::String(x.name.clone())))
+                            .collect(),
+                        types: s
+                            .fields
+                            .iter()
+                            .map(|x| ValueFlag::from_ty(x.ty.clone()))
+                            .collect(),
+                    };
+                    self.structs
+                        .insert(s.name.clone(), self.module.structs.len() as u32);
+                    self.module.structs.push(layout);
+                }
+                Statement::Function(f) => {
+                    let index = self.module.functions.len() as u32;
+                    self.module.functions.push(Function {
+                        name: f.name.clone(),
+                        arity: f.arguments.args.len() as u32,
+                        is_var_args: f.is_var_args,
+                        locals: 0,
+                        code: Vec::new(),
+                        file: 0,
+                        locations: Vec::new(),
+                        statements: Vec::new(),
+                        variables: Vec::new(),
+                    });
+                    self.signatures.insert(
+                        f.name.clone(),
+                        Signature {
+                            callee: Callee::Function(index),
+                            params: f
+                                .arguments
+                                .args
+                                .iter()
+                                .map(|x| ValueFlag::from_ty(x.ty.clone()))
+                                .collect(),
+                            return_type: ValueFlag::from_ty(f.returntype.clone()),
+                        },
+                    );
+                }
+                Statement::Extern(ext) => self.declare_foreign(&ext.signs),
+                Statement::External(external) => {
+                    let library = ExternalLibrary {
+                        file: external.file.clone(),
+                        dir: file.parent().map(Path::to_path_buf).unwrap_or_default(),
+                    };
+                    if !self.module.libraries.contains(&library) {
+                        self.module.libraries.push(library);
+                    }
+                    self.declare_foreign(&external.signs);
+                }
+                Statement::Import(i) => {
+                    let key = module_key(i);
+                    if self.declared.insert(key) {
+                        let file = i.file.as_deref().unwrap_or(file);
+                        self.declare_module(&i.module_stmts, file);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn declare_foreign(&mut self, signs: &[popper_ast::FunctionSign]) {
+        for sign in signs {
+            // declared by several modules
+            if self.signatures.contains_key(&sign.name) {
+                continue;
+            }
+            let params: Vec<_> = sign
+                .arguments
+                .args
+                .iter()
+                .map(|x| ValueFlag::from_ty(x.ty.clone()))
+                .collect();
+            let return_type = ValueFlag::from_ty(sign.return_type.clone());
+            let index = self.module.foreign.len() as u32;
+            self.module.foreign.push(ForeignFunction {
+                name: sign.name.clone(),
+                args: params.clone(),
+                return_type: return_type.clone(),
+                is_var_args: sign.is_var_args,
+            });
+            self.signatures.insert(
+                sign.name.clone(),
+                Signature {
+                    callee: Callee::Foreign(index),
+                    params,
+                    return_type,
+                },
+            );
+        }
+    }
+
+    fn compile_module(&mut self, stmts: &[Statement]) -> Result<(), VmError> {
+        for stmt in stmts {
+            match stmt {
+                Statement::Function(f) => self.compile_function(f)?,
+                Statement::Import(i) => {
+                    if !self.compiled.insert(module_key(i)) {
+                        continue;
+                    }
+                    let file = i
+                        .file
+                        .as_ref()
+                        .map(|x| x.display().to_string())
+                        .unwrap_or_else(|| i.path.to_string());
+                    let text = i.source.clone().unwrap_or_default();
+                    let index = self.module.files.len() as u32;
+                    self.module.files.push(file);
+                    let source = std::mem::replace(&mut self.source, Source::new(index, text));
+                    self.compile_module(&i.module_stmts)?;
+                    self.source = source;
+                }
+                Statement::Let(l) => {
+                    return Err(VmError::Unsupported(format!(
+                        "`{}`, a variable outside of a function,",
+                        l.name.name
+                    )))
+                }
+                Statement::Expression(_) | Statement::LetTuple(_) => {
+                    return Err(VmError::Unsupported(
+                        "code outside of a function".to_string(),
+                    ))
+                }
+                // the other items were declared, tests are run by `popper test`
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_function(&mut self, f: &popper_ast::Function) -> Result<(), VmError> {
+        let signature = &self.signatures[&f.name];
+        let Callee::Function(index) = signature.callee else {
+            unreachable!("`{}` is a function", f.name)
+        };
+        self.function = FunctionState {
+            scopes: vec![HashMap::new()],
+            return_type: Some(signature.return_type.clone()),
+            ..FunctionState::default()
+        };
+        for arg in &f.arguments.args {
+            self.declare_local(&arg.name, Some(ValueFlag::from_ty(arg.ty.clone())));
+        }
+        for stmt in &f.body {
+            self.compile_stmt(stmt)?;
+        }
+        // the end of a function returning `unit`
+        self.emit(Instr::Unit);
+        self.emit(Instr::Return);
+
+        let mut state = std::mem::take(&mut self.function);
+        for variable in &mut state.variables {
+            variable.end = variable.end.min(state.code.len() as u32);
+        }
+        let function = &mut self.module.functions[index as usize];
+        function.locals = state.locals;
+        function.code = state.code;
+        function.locations = state.locations;
+        function.statements = state.statements;
+        function.variables = state.variables;
+        function.file = self.source.index;
+        Ok(())
+    }
+
+    fn compile_stmt(&mut self, stmt: &Statement) -> Result<(), VmError> {
+        self.locate(stmt.span());
+        if !matches!(stmt, Statement::Block(_)) {
+            let pc = self.function.code.len() as u32;
+            if self.function.statements.last() != Some(&pc) {
+                self.function.statements.push(pc);
+            }
+        }
+        match stmt {
+            Statement::Expression(expr) => {
+                self.compile_expr(expr, None)?;
+                self.emit(Instr::Pop);
+            }
+            Statement::Let(l) => {
+                let ty = l.r#type.clone().map(ValueFlag::from_ty);
+                self.compile_expr(&l.value, ty.as_ref())?;
+                let slot = self.declare_local(&l.name.name, ty);
+                self.emit(Instr::Let(slot));
+            }
+            Statement::LetTuple(l) => {
+                let ty = l.r#type.clone().map(ValueFlag::from_ty);
+                self.compile_expr(&l.value, ty.as_ref())?;
+                let tuple = self.scratch();
+                self.emit(Instr::Let(tuple));
+                for (index, name) in l.names.iter().enumerate() {
+                    self.emit(Instr::Load(tuple));
+                    self.emit(Instr::GetElement(index as u32));
+                    let element = match &ty {
+                        Some(ValueFlag::Tuple(types)) => types.get(index).cloned(),
+                        _ => None,
+                    };
+                    let slot = self.declare_local(&name.name, element);
+                    self.emit(Instr::Let(slot));
+                }
+            }
+            Statement::Assign(a) => {
+                self.compile_expr(&a.value, None)?;
+                if let Expression::Constant(AstConstant::Ident(id)) = &a.name {
+                    let slot = self.local(&id.name)?;
+                    self.emit(Instr::Store(slot));
+                } else {
+                    // the place is compiled after the value
+                    let value = self.scratch();
+                    self.emit(Instr::Let(value));
+                    self.compile_assign(&a.name, value)?;
+                }
+            }
+            Statement::Return(r) => {
+                match &r.expression {
+                    Some(expr) => {
+                        let ty = self.function.return_type.clone();
+                        self.compile_expr(expr, ty.as_ref())?;
+                    }
+                    None => self.emit(Instr::Unit),
+                }
+                self.emit(Instr::Return);
+            }
+            Statement::Block(block) => self.compile_block(&block.statements)?,
+            Statement::If(i) => {
+                self.compile_expr(&i.condition, None)?;
+                let jump = self.emit_jump(Instr::JumpIfFalse(0));
+                self.compile_block(std::slice::from_ref(&*i.body))?;
+                self.patch(jump);
+            }
+            Statement::IfElse(i) => {
+                self.compile_expr(&i.condition, None)?;
+                let to_else = self.emit_jump(Instr::JumpIfFalse(0));
+                self.compile_block(std::slice::from_ref(&*i.body))?;
+                let to_end = self.emit_jump(Instr::Jump(0));
+                self.patch(to_else);
+                self.compile_block(std::slice::from_ref(&*i.else_body))?;
+                self.patch(to_end);
+            }
+            Statement::While(w) => {
+                let start = self.function.code.len() as u32;
+                self.compile_expr(&w.condition, None)?;
+                let exit = self.emit_jump(Instr::JumpIfFalse(0));
+                self.function.loops.push(Vec::new());
+                self.compile_block(std::slice::from_ref(&*w.body))?;
+                self.emit(Instr::Jump(start));
+                self.patch(exit);
+                self.patch_breaks();
+            }
+            Statement::For(f) => {
+                // for it in seq { body }: a counter going through the elements of `seq`
+                self.compile_expr(&f.expr, None)?;
+                let seq = self.scratch();
+                self.emit(Instr::Let(seq));
+                let counter = self.scratch();
+                self.emit_const(Constant::Int(IntKind::I32, 0));
+                self.emit(Instr::Let(counter));
+
+                let start = self.function.code.len() as u32;
+                self.emit(Instr::Load(counter));
+                self.emit(Instr::Load(seq));
+                self.emit(Instr::Len);
+                self.emit(Instr::Lt);
+                let exit = self.emit_jump(Instr::JumpIfFalse(0));
+                self.function.loops.push(Vec::new());
+
+                let scope = self.push_scope();
+                self.emit(Instr::Load(seq));
+                self.emit(Instr::Load(counter));
+                self.emit(Instr::Index);
+                let it = self.declare_local(&f.it.name, None);
+                self.emit(Instr::Let(it));
+                for stmt in &f.body.statements {
+                    self.compile_stmt(stmt)?;
+                }
+                self.pop_scope(scope);
+
+                self.emit(Instr::Load(counter));
+                self.emit_const(Constant::Int(IntKind::I32, 1));
+                self.emit(Instr::Add);
+                self.emit(Instr::Store(counter));
+                self.emit(Instr::Jump(start));
+                self.patch(exit);
+                self.patch_breaks();
+            }
+            Statement::BreakStmt(_) => {
+                let jump = self.emit_jump(Instr::Jump(0));
+                self.function
+                    .loops
+                    .last_mut()
+                    .expect("`break` is checked to be in a loop")
+                    .push(jump);
+            }
+            Statement::Test(_) | Statement::ModuleDoc(_) => {}
+            Statement::Function(f) => {
+                return Err(VmError::Unsupported(format!(
+                    "`{}`, a function defined in a function,",
+                    f.name
+                )))
+            }
+            _ => {
+                return Err(VmError::Unsupported(
+                    "a declaration in a function".to_string(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// statements in their own scope
+    fn compile_block(&mut self, stmts: &[Statement]) -> Result<(), VmError> {
+        let stmts = match stmts {
+            [Statement::Block(block)] => &block.statements,
+            stmts => stmts,
+        };
+        let scope = self.push_scope();
+        for stmt in stmts {
+            self.compile_stmt(stmt)?;
+        }
+        self.pop_scope(scope);
+        Ok(())
+    }
+
+    /// return: what `pop_scope` restores
+    fn push_scope(&mut self) -> (u32, usize) {
+        self.function.scopes.push(HashMap::new());
+        (self.function.next_local, self.function.variables.len())
+    }
+
+    /// end the scope: its locals can be reused, its variables end here
+    fn pop_scope(&mut self, (next_local, variables): (u32, usize)) {
+        self.function.scopes.pop();
+        self.function.next_local = next_local;
+        let end = self.function.code.len() as u32;
+        for variable in &mut self.function.variables[variables..] {
+            variable.end = variable.end.min(end);
+        }
+    }
+
+    /// store the local `value` in an assignable expression. Fields and elements are
+    /// set in a copy of their struct, tuple or list, which is stored back
+    fn compile_assign(&mut self, place: &Expression, value: u32) -> Result<(), VmError> {
+        match place {
+            Expression::Constant(AstConstant::Ident(id)) => {
+                let slot = self.local(&id.name)?;
+                self.emit(Instr::Load(value));
+                self.emit(Instr::Store(slot));
+            }
+            Expression::Group(g) => self.compile_assign(&g.expr, value)?,
+            Expression::Deref(d) => {
+                self.compile_expr(&d.expr, None)?;
+                self.emit(Instr::Load(value));
+                self.emit_at(Instr::StoreDeref, d.span);
+            }
+            Expression::StructFieldAccess(s) if s.is_ptr => {
+                let field = self.constant(Constant::String(s.field.clone()));
+                self.compile_expr(&s.name, None)?;
+                self.emit(Instr::Dup);
+                self.emit_at(Instr::Deref, s.span);
+                self.emit(Instr::Load(value));
+                self.emit_at(Instr::SetField(field), s.span);
+                self.emit_at(Instr::StoreDeref, s.span);
+            }
+            Expression::StructFieldAccess(s) => {
+                let field = self.constant(Constant::String(s.field.clone()));
+                self.compile_expr(&s.name, None)?;
+                self.emit(Instr::Load(value));
+                self.emit_at(Instr::SetField(field), s.span);
+                self.assign_top(&s.name)?;
+            }
+            Expression::TupleAccess(t) => {
+                self.compile_expr(&t.value, None)?;
+                self.emit(Instr::Load(value));
+                self.emit(Instr::SetElement(t.index as u32));
+                self.assign_top(&t.value)?;
+            }
+            Expression::Index(i) => {
+                self.compile_expr(&i.value, None)?;
+                self.compile_expr(&i.index, None)?;
+                self.emit(Instr::Load(value));
+                self.emit_at(Instr::SetIndex, i.span);
+                self.assign_top(&i.value)?;
+            }
+            expr => {
+                return Err(VmError::Unsupported(format!(
+                    "an assignment to `{:?}`",
+                    expr
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    /// store the value on top of the stack in an assignable expression
+    fn assign_top(&mut self, place: &Expression) -> Result<(), VmError> {
+        let value = self.scratch();
+        self.emit(Instr::Let(value));
+        self.compile_assign(place, value)
+    }
+
+    /// compile an expression, `expected` being the type the context expects, which
+    /// integer literals and list literals take
+    fn compile_expr(
+        &mut self,
+        expr: &Expression,
+        expected: Option<&ValueFlag>,
+    ) -> Result<(), VmError> {
+        match expr {
+            Expression::Constant(constant) => self.compile_constant(constant, expected)?,
+            Expression::BinOp(b) => {
+                let operands = (b.op.is_arithmetic()).then_some(expected).flatten();
+                self.compile_expr(&b.lhs, operands)?;
+                self.compile_expr(&b.rhs, operands)?;
+                let instr = match b.op {
+                    BinOpKind::Add => Instr::Add,
+                    BinOpKind::Sub => Instr::Sub,
+                    BinOpKind::Mul => Instr::Mul,
+                    BinOpKind::Div => Instr::Div,
+                    BinOpKind::Mod => Instr::Mod,
+                    BinOpKind::Pow => Instr::Pow,
+                    BinOpKind::Eq => Instr::Eq,
+                    BinOpKind::Neq => Instr::Ne,
+                    BinOpKind::Lt => Instr::Lt,
+                    BinOpKind::Lte => Instr::Le,
+                    BinOpKind::Gt => Instr::Gt,
+                    BinOpKind::Gte => Instr::Ge,
+                    BinOpKind::And => Instr::And,
+                    BinOpKind::Or => Instr::Or,
+                };
+                self.emit_at(instr, b.span());
+            }
+            Expression::UnaryOp(u) => match (u.op, u.expr.as_ref()) {
+                // a negated integer literal is a literal of its own: `-128` is an `i8`
+                (UnaryOpKind::Neg, Expression::Constant(AstConstant::Int(i))) => {
+                    let literal = AstConstant::Int(popper_ast::Int::new(u.span(), -i.value));
+                    self.compile_constant(&literal, expected)?;
+                }
+                (op, expr) => {
+                    self.compile_expr(expr, expected)?;
+                    self.emit(match op {
+                        UnaryOpKind::Neg => Instr::Neg,
+                        UnaryOpKind::Not => Instr::Not,
+                    });
+                }
+            },
+            Expression::Group(g) => self.compile_expr(&g.expr, expected)?,
+            Expression::Cast(c) => {
+                self.compile_expr(&c.expr, None)?;
+                let ty = self.ty(ValueFlag::from_ty(c.ty.clone()));
+                self.emit_at(Instr::Cast(ty), c.span);
+            }
+            Expression::Call(call) => self.compile_call(call)?,
+            Expression::StructInstance(s) => {
+                let index = self.structs[&s.name];
+                let layout = self.module.structs[index as usize].clone();
+                for (name, ty) in layout.fields.iter().zip(&layout.types) {
+                    let Constant::String(name) = &self.module.constants[*name as usize] else {
+                        unreachable!("field names are strings")
+                    };
+                    let field = s
+                        .fields
+                        .iter()
+                        .find(|x| &x.name == name)
+                        .expect("the fields are checked");
+                    self.compile_expr(&field.value, Some(ty))?;
+                }
+                self.emit(Instr::MakeStruct(index));
+            }
+            Expression::StructFieldAccess(s) => {
+                self.compile_expr(&s.name, None)?;
+                if s.is_ptr {
+                    self.emit_at(Instr::Deref, s.span);
+                }
+                let field = self.constant(Constant::String(s.field.clone()));
+                self.emit(Instr::GetField(field));
+            }
+            Expression::TupleAccess(t) => {
+                self.compile_expr(&t.value, None)?;
+                self.emit(Instr::GetElement(t.index as u32));
+            }
+            Expression::Index(i) => {
+                self.compile_expr(&i.value, None)?;
+                self.compile_expr(&i.index, None)?;
+                self.emit_at(Instr::Index, i.span);
+            }
+            Expression::Slice(s) => {
+                self.compile_expr(&s.value, None)?;
+                if let Some(start) = &s.start {
+                    self.compile_expr(start, None)?;
+                }
+                if let Some(end) = &s.end {
+                    self.compile_expr(end, None)?;
+                }
+                let instr = Instr::Slice {
+                    start: s.start.is_some(),
+                    end: s.end.is_some(),
+                };
+                self.emit_at(instr, s.span);
+            }
+            Expression::VaArg(v) => {
+                let ty = self.ty(ValueFlag::from_ty(v.ty.clone()));
+                self.emit_at(Instr::VaArg(ty), v.span());
+            }
+            Expression::Reference(r) => match &*r.expr {
+                Expression::Constant(AstConstant::Ident(id)) => {
+                    let slot = self.local(&id.name)?;
+                    self.emit(Instr::RefLocal(slot));
+                }
+                _ => {
+                    return Err(VmError::Unsupported(
+                        "`ref` of something else than a variable".to_string(),
+                    ))
+                }
+            },
+            Expression::Deref(d) => {
+                self.compile_expr(&d.expr, None)?;
+                self.emit_at(Instr::Deref, d.span);
+            }
+        }
+        Ok(())
+    }
+
+    fn compile_constant(
+        &mut self,
+        constant: &AstConstant,
+        expected: Option<&ValueFlag>,
+    ) -> Result<(), VmError> {
+        match constant {
+            AstConstant::Int(i) => {
+                let kind = expected.and_then(|x| x.int_kind()).unwrap_or(
+                    if IntKind::I32.fits(i.value as i128) {
+                        IntKind::I32
+                    } else {
+                        IntKind::I64
+                    },
+                );
+                self.emit_const(Constant::Int(kind, i.value));
+            }
+            AstConstant::Float(f) => self.emit_const(Constant::Float(f.value as f32 as f64)),
+            AstConstant::StringLiteral(s) => self.emit_const(Constant::String(s.value.clone())),
+            AstConstant::Char(c) => self.emit_const(Constant::Char(c.value as u8)),
+            AstConstant::Bool(b) => self.emit_const(Constant::Bool(b.value)),
+            AstConstant::Ident(id) => {
+                let slot = self.local(&id.name)?;
+                self.emit(Instr::Load(slot));
+            }
+            AstConstant::List(l) => {
+                let element = expected.and_then(|x| x.get_element_type()).cloned();
+                for value in &l.value {
+                    self.compile_expr(value, element.as_ref())?;
+                }
+                let len = l.value.len() as u32;
+                match expected {
+                    Some(ValueFlag::Vec(_)) => self.emit(Instr::MakeVec(len)),
+                    Some(ValueFlag::Slice(_)) => {
+                        self.emit(Instr::MakeList(len));
+                        self.emit(Instr::Slice {
+                            start: false,
+                            end: false,
+                        });
+                    }
+                    _ => self.emit(Instr::MakeList(len)),
+                }
+            }
+            AstConstant::Tuple(t) => {
+                let types = match expected {
+                    Some(ValueFlag::Tuple(types)) => types.clone(),
+                    _ => Vec::new(),
+                };
+                for (i, value) in t.value.iter().enumerate() {
+                    self.compile_expr(value, types.get(i))?;
+                }
+                self.emit(Instr::MakeTuple(t.value.len() as u32));
+            }
+            AstConstant::Null(_) => return Err(VmError::Unsupported("`null`".to_string())),
+        }
+        Ok(())
+    }
+
+    fn compile_call(&mut self, call: &popper_ast::Call) -> Result<(), VmError> {
+        let Some(signature) = self.signatures.get(&call.name).cloned() else {
+            return self.compile_builtin(call);
+        };
+        for (i, arg) in call.arguments.iter().enumerate() {
+            self.compile_expr(arg, signature.params.get(i))?;
+        }
+        let argc = call.arguments.len() as u32;
+        let instr = match signature.callee {
+            Callee::Function(index) => Instr::Call(index, argc),
+            Callee::Foreign(index) => Instr::CallForeign(index, argc),
+        };
+        self.emit_at(instr, call.span);
+        Ok(())
+    }
+
+    /// `len(x)`, `push(v, x)`, `pop(v)` and `assert(cond, msg)`
+    fn compile_builtin(&mut self, call: &popper_ast::Call) -> Result<(), VmError> {
+        for arg in &call.arguments {
+            self.compile_expr(arg, None)?;
+        }
+        match call.name.as_str() {
+            "len" => self.emit(Instr::Len),
+            "pop" => self.emit_at(Instr::PopVec, call.span),
+            "push" => {
+                self.emit(Instr::Push);
+                self.emit(Instr::Unit);
+            }
+            "assert" => {
+                self.emit_at(Instr::Assert, call.span);
+                self.emit(Instr::Unit);
+            }
+            name => unreachable!("`{}` is checked to be a function", name),
+        }
+        Ok(())
+    }
+
+    fn local(&self, name: &str) -> Result<u32, VmError> {
+        self.function
+            .scopes
+            .iter()
+            .rev()
+            .find_map(|x| x.get(name).copied())
+            .ok_or_else(|| VmError::Unsupported(format!("`{}` used as a value", name)))
+    }
+
+    /// a local for the variable `name`, declared with the type `ty` if it's written
+    fn declare_local(&mut self, name: &str, ty: Option<ValueFlag>) -> u32 {
+        let slot = self.scratch();
+        self.function
+            .scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), slot);
+        self.function.variables.push(Variable {
+            name: name.to_string(),
+            slot,
+            ty,
+            start: self.function.code.len() as u32,
+            end: u32::MAX,
+        });
+        slot
+    }
+
+    /// a local without a name, for the values the compiler keeps
+    fn scratch(&mut self) -> u32 {
+        let slot = self.function.next_local;
+        self.function.next_local += 1;
+        self.function.locals = self.function.locals.max(self.function.next_local);
+        slot
+    }
+
+    fn constant(&mut self, constant: Constant) -> u32 {
+        let index = self
+            .module
+            .constants
+            .iter()
+            .position(|x| *x == constant)
+            .unwrap_or_else(|| {
+                self.module.constants.push(constant);
+                self.module.constants.len() - 1
+            });
+        index as u32
+    }
+
+    fn ty(&mut self, ty: ValueFlag) -> u32 {
+        let index = self
+            .module
+            .types
+            .iter()
+            .position(|x| *x == ty)
+            .unwrap_or_else(|| {
+                self.module.types.push(ty);
+                self.module.types.len() - 1
+            });
+        index as u32
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        self.function.code.push(instr);
+    }
+
+    fn emit_const(&mut self, constant: Constant) {
+        let index = self.constant(constant);
+        self.emit(Instr::Const(index));
+    }
+
+    /// emit an instruction which can panic, located at `span`
+    fn emit_at(&mut self, instr: Instr, span: Span) {
+        self.locate(span);
+        self.emit(instr);
+    }
+
+    /// the next instructions come from `span`
+    fn locate(&mut self, span: Span) {
+        let (line, column) = self.source.locate(span);
+        let pc = self.function.code.len() as u32;
+        let locations = &mut self.function.locations;
+        match locations.last_mut() {
+            Some(last) if last.line == line && last.column == column => {}
+            Some(last) if last.pc == pc => {
+                last.line = line;
+                last.column = column;
+            }
+            _ => locations.push(Location { pc, line, column }),
+        }
+    }
+
+    fn emit_jump(&mut self, instr: Instr) -> usize {
+        self.emit(instr);
+        self.function.code.len() - 1
+    }
+
+    /// make the jump at `at` go to the next instruction
+    fn patch(&mut self, at: usize) {
+        let target = self.function.code.len() as u32;
+        match &mut self.function.code[at] {
+            Instr::Jump(x) | Instr::JumpIfFalse(x) => *x = target,
+            instr => unreachable!("{:?} isn't a jump", instr),
+        }
+    }
+
+    fn patch_breaks(&mut self) {
+        for jump in self.function.loops.pop().unwrap_or_default() {
+            self.patch(jump);
+        }
+    }
+}
+
+/// a module is compiled once, however many times it's imported
+fn module_key(i: &popper_ast::ImportStmt) -> String {
+    i.file
+        .as_ref()
+        .map(|x| x.display().to_string())
+        .unwrap_or_else(|| i.path.to_string())
+}
@@ -0,0 +1,136 @@
+//! What a debugger sees of the VM: it's called before each statement, and can read the
+//! calls being run and their variables.
+
+use crate::value::Pointer;
+use crate::{Panic, Value, Vm};
+use popper_flag::ValueFlag;
+
+/// a debugger attached to the VM with [`Vm::with_debugger`]. It stops the program by
+/// not returning until the program should go on
+pub trait Debugger {
+    /// the VM is about to run a statement
+    fn statement(&mut self, vm: &mut Vm);
+
+    /// the program panicked, its calls not unwound yet
+    fn panic(&mut self, vm: &mut Vm, panic: &Panic);
+}
+
+/// a call being run
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    pub function: String,
+    pub file: String,
+    /// where the call is: the statement about to run, the instruction which panicked,
+    /// or the call of the next frame
+    pub line: u32,
+    pub column: u32,
+}
+
+/// a variable in scope in a call
+#[derive(Debug, Clone, PartialEq)]
+pub struct Local {
+    pub name: String,
+    /// the type it's declared with, or the one of its value
+    pub ty: ValueFlag,
+    pub value: Value,
+}
+
+impl Vm {
+    /// the call `frame` of [`Vm::backtrace`]
+    pub fn frame(&self, frame: usize) -> Option<StackFrame> {
+        let (function, pc, _) = self.frames().rev().nth(frame)?;
+        let f = &self.module().functions[function as usize];
+        let location = f.location(pc);
+        Some(StackFrame {
+            function: f.name.clone(),
+            file: self.module().files[f.file as usize].clone(),
+            line: location.map_or(0, |x| x.line),
+            column: location.map_or(0, |x| x.column),
+        })
+    }
+
+    /// the calls being run, the innermost first
+    pub fn backtrace(&self) -> Vec<StackFrame> {
+        (0..self.depth()).filter_map(|x| self.frame(x)).collect()
+    }
+
+    /// the variables in scope in the frame `frame` of [`Vm::backtrace`], a variable
+    /// shadowing the ones of the same name
+    pub fn locals(&self, frame: usize) -> Vec<Local> {
+        let Some((function, pc, base)) = self.frames().rev().nth(frame) else {
+            return Vec::new();
+        };
+        let mut locals: Vec<Local> = Vec::new();
+        for variable in self.module().functions[function as usize].variables_at(pc) {
+            let value = self.local(base + variable.slot as usize);
+            let local = Local {
+                name: variable.name.clone(),
+                ty: variable.ty.clone().unwrap_or_else(|| self.type_of(&value)),
+                value,
+            };
+            match locals.iter_mut().find(|x| x.name == local.name) {
+                Some(shadowed) => *shadowed = local,
+                None => locals.push(local),
+            }
+        }
+        locals
+    }
+
+    /// the type of a value, as far as it tells: the elements of an empty sequence are
+    /// `unit`s
+    pub fn type_of(&self, value: &Value) -> ValueFlag {
+        let element = |elements: Option<Vec<Value>>| {
+            let first = elements.unwrap_or_default().first().cloned();
+            Box::new(first.map_or(ValueFlag::None, |x| self.type_of(&x)))
+        };
+        match value {
+            Value::Unit => ValueFlag::None,
+            Value::Int(kind, _) => ValueFlag::from_int_kind(*kind),
+            Value::Bool(_) => ValueFlag::Boolean,
+            Value::Char(_) => ValueFlag::Char,
+            Value::Float(_) => ValueFlag::Float,
+            Value::String(_) => ValueFlag::String,
+            Value::List(elements) => ValueFlag::List(element(value.elements()), elements.len()),
+            Value::Tuple(elements) => {
+                ValueFlag::Tuple(elements.iter().map(|x| self.type_of(x)).collect())
+            }
+            Value::Struct(index, _) => {
+                ValueFlag::StructInstance(self.module().structs[*index as usize].name.clone())
+            }
+            Value::Vec(_) => ValueFlag::Vec(element(value.elements())),
+            Value::Slice(..) => ValueFlag::Slice(element(value.elements())),
+            Value::Pointer(Pointer::Cell(cell)) => {
+                ValueFlag::Pointer(Box::new(self.type_of(&cell.borrow())))
+            }
+            Value::Pointer(Pointer::Raw(_, pointee)) => {
+                ValueFlag::Pointer(Box::new((**pointee).clone()))
+            }
+        }
+    }
+
+    /// the parts of a value, named: the fields of a struct, the elements of a tuple or
+    /// a sequence, and what a pointer to a variable points to
+    pub fn children(&self, value: &Value) -> Vec<(String, Value)> {
+        match value {
+            Value::Struct(index, fields) => self
+                .module()
+                .field_names(*index)
+                .into_iter()
+                .zip(fields.iter().cloned())
+                .collect(),
+            Value::Tuple(elements) => elements
+                .iter()
+                .enumerate()
+                .map(|(i, x)| (i.to_string(), x.clone()))
+                .collect(),
+            Value::Pointer(Pointer::Cell(cell)) => vec![("@".to_string(), cell.borrow().clone())],
+            value => value
+                .elements()
+                .unwrap_or_default()
+                .into_iter()
+                .enumerate()
+                .map(|(i, x)| (format!("[{}]", i), x))
+                .collect(),
+        }
+    }
+}
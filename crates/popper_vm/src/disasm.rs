@@ -0,0 +1,206 @@
+//! Text form of the bytecode, printed by `--dump-bytecode`.
+
+use crate::bytecode::{Constant, Function, Instr, Module};
+use std::fmt::{Display, Formatter, Result};
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Constant::Int(kind, x) if kind.is_signed() => write!(f, "{}: {}", x, kind),
+            Constant::Int(kind, x) => write!(f, "{}: {}", *x as u64, kind),
+            Constant::Float(x) => write!(f, "{}: float", x),
+            Constant::Bool(x) => write!(f, "{}", x),
+            Constant::Char(x) => write!(f, "{:?}", *x as char),
+            Constant::String(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+impl Display for Instr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let (name, operands): (&str, Vec<u32>) = match *self {
+            Instr::Const(x) => ("const", vec![x]),
+            Instr::Unit => ("unit", vec![]),
+            Instr::Load(x) => ("load", vec![x]),
+            Instr::Store(x) => ("store", vec![x]),
+            Instr::Let(x) => ("let", vec![x]),
+            Instr::RefLocal(x) => ("ref_local", vec![x]),
+            Instr::Pop => ("pop", vec![]),
+            Instr::Dup => ("dup", vec![]),
+            Instr::Add => ("add", vec![]),
+            Instr::Sub => ("sub", vec![]),
+            Instr::Mul => ("mul", vec![]),
+            Instr::Div => ("div", vec![]),
+            Instr::Mod => ("mod", vec![]),
+            Instr::Pow => ("pow", vec![]),
+            Instr::Eq => ("eq", vec![]),
+            Instr::Ne => ("ne", vec![]),
+            Instr::Lt => ("lt", vec![]),
+            Instr::Le => ("le", vec![]),
+            Instr::Gt => ("gt", vec![]),
+            Instr::Ge => ("ge", vec![]),
+            Instr::And => ("and", vec![]),
+            Instr::Or => ("or", vec![]),
+            Instr::Neg => ("neg", vec![]),
+            Instr::Not => ("not", vec![]),
+            Instr::Cast(x) => ("cast", vec![x]),
+            Instr::Jump(x) => ("jump", vec![x]),
+            Instr::JumpIfFalse(x) => ("jump_if_false", vec![x]),
+            Instr::Call(x, argc) => ("call", vec![x, argc]),
+            Instr::CallForeign(x, argc) => ("call_foreign", vec![x, argc]),
+            Instr::Return => ("return", vec![]),
+            Instr::MakeList(x) => ("make_list", vec![x]),
+            Instr::MakeVec(x) => ("make_vec", vec![x]),
+            Instr::MakeTuple(x) => ("make_tuple", vec![x]),
+            Instr::MakeStruct(x) => ("make_struct", vec![x]),
+            Instr::GetField(x) => ("get_field", vec![x]),
+            Instr::SetField(x) => ("set_field", vec![x]),
+            Instr::GetElement(x) => ("get_element", vec![x]),
+            Instr::SetElement(x) => ("set_element", vec![x]),
+            Instr::Index => ("index", vec![]),
+            Instr::SetIndex => ("set_index", vec![]),
+            Instr::Slice { start, end } => {
+                let name = match (start, end) {
+                    (true, true) => "slice",
+                    (true, false) => "slice_from",
+                    (false, true) => "slice_to",
+                    (false, false) => "slice_all",
+                };
+                (name, vec![])
+            }
+            Instr::Len => ("len", vec![]),
+            Instr::Push => ("push", vec![]),
+            Instr::PopVec => ("pop_vec", vec![]),
+            Instr::Deref => ("deref", vec![]),
+            Instr::StoreDeref => ("store_deref", vec![]),
+            Instr::VaArg(x) => ("va_arg", vec![x]),
+            Instr::Assert => ("assert", vec![]),
+        };
+        write!(f, "{}", name)?;
+        for (i, operand) in operands.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " " } else { ", " }, operand)?;
+        }
+        Ok(())
+    }
+}
+
+impl Module {
+    /// what an operand of `instr` refers to in the tables of the module
+    fn comment(&self, instr: Instr) -> Option<String> {
+        let constant = |x: u32| self.constants.get(x as usize).map(|x| x.to_string());
+        match instr {
+            Instr::Const(x) => constant(x),
+            Instr::GetField(x) | Instr::SetField(x) => constant(x),
+            Instr::Cast(x) | Instr::VaArg(x) => self.types.get(x as usize).map(|x| x.to_string()),
+            Instr::Call(x, _) => self.functions.get(x as usize).map(|x| x.name.clone()),
+            Instr::CallForeign(x, _) => self.foreign.get(x as usize).map(|x| x.name.clone()),
+            Instr::MakeStruct(x) => self.structs.get(x as usize).map(|x| x.name.clone()),
+            _ => None,
+        }
+    }
+
+    fn fmt_function(&self, f: &mut Formatter<'_>, function: &Function) -> Result {
+        let file = self
+            .files
+            .get(function.file as usize)
+            .map_or("?", |x| x.as_str());
+        writeln!(
+            f,
+            "fn {}: {} argument(s){}, {} local(s), in {}",
+            function.name,
+            function.arity,
+            if function.is_var_args { " and ..." } else { "" },
+            function.locals,
+            file
+        )?;
+        for variable in &function.variables {
+            let ty = variable.ty.as_ref().map(|x| format!(": {}", x));
+            writeln!(
+                f,
+                "  let {}{} in {}, from {:04} to {:04}",
+                variable.name,
+                ty.unwrap_or_default(),
+                variable.slot,
+                variable.start,
+                variable.end
+            )?;
+        }
+        let mut locations = function.locations.iter().peekable();
+        for (pc, instr) in function.code.iter().enumerate() {
+            let mut line = String::new();
+            while let Some(location) = locations.next_if(|x| x.pc as usize <= pc) {
+                line = format!("{}:{}", location.line, location.column);
+            }
+            let text = instr.to_string();
+            match self.comment(*instr) {
+                Some(comment) => {
+                    writeln!(f, "  {:>7} {:04}  {:<20} ; {}", line, pc, text, comment)?
+                }
+                None => writeln!(f, "  {:>7} {:04}  {}", line, pc, text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// the disassembly of the module: its tables, then the code of each function, each
+/// instruction after the line and column it comes from when it starts a statement or
+/// an expression
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "constants:")?;
+        for (i, constant) in self.constants.iter().enumerate() {
+            writeln!(f, "  {:>4}  {}", i, constant)?;
+        }
+        if !self.types.is_empty() {
+            writeln!(f, "types:")?;
+            for (i, ty) in self.types.iter().enumerate() {
+                writeln!(f, "  {:>4}  {}", i, ty)?;
+            }
+        }
+        if !self.structs.is_empty() {
+            writeln!(f, "structs:")?;
+            for (i, layout) in self.structs.iter().enumerate() {
+                let fields = layout
+                    .fields
+                    .iter()
+                    .zip(&layout.types)
+                    .map(|(name, ty)| match self.constants.get(*name as usize) {
+                        Some(Constant::String(name)) => format!("{}: {}", name, ty),
+                        _ => ty.to_string(),
+                    })
+                    .collect::<Vec<_>>();
+                writeln!(f, "  {:>4}  {} {{ {} }}", i, layout.name, fields.join(", "))?;
+            }
+        }
+        if !self.foreign.is_empty() {
+            writeln!(f, "foreign:")?;
+            for (i, function) in self.foreign.iter().enumerate() {
+                let mut args = function
+                    .args
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<_>>();
+                if function.is_var_args {
+                    args.push("...".to_string());
+                }
+                writeln!(
+                    f,
+                    "  {:>4}  {}({}): {}",
+                    i,
+                    function.name,
+                    args.join(", "),
+                    function.return_type
+                )?;
+            }
+        }
+        for library in &self.libraries {
+            writeln!(f, "library {} in {}", library.file, library.dir.display())?;
+        }
+        for function in &self.functions {
+            writeln!(f)?;
+            self.fmt_function(f, function)?;
+        }
+        Ok(())
+    }
+}
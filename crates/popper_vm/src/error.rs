@@ -0,0 +1,68 @@
+use crate::limits::LimitError;
+use popper_ffi::FfiError;
+use std::fmt::Display;
+
+/// why a program can't be compiled to bytecode, loaded or run
+#[derive(Debug, Clone, PartialEq)]
+pub enum VmError {
+    /// the program uses something the VM doesn't run
+    Unsupported(String),
+    /// a bytecode file can't be read, or its code isn't valid
+    InvalidBytecode(String),
+    /// a foreign function can't be declared or called
+    Ffi(FfiError),
+    /// the program doesn't define a `main` function
+    NoMain,
+    /// the program panicked
+    Panic(Panic),
+    /// the program went over a limit of the VM
+    Limit(LimitError),
+    /// the program called `exit` with this code, its calls unwound
+    Exit(i32),
+}
+
+/// a panic of the program, with where it happened when it's known
+#[derive(Debug, Clone, PartialEq)]
+pub struct Panic {
+    pub message: String,
+    /// file, line and column
+    pub location: Option<(String, u32, u32)>,
+}
+
+impl Panic {
+    /// status of a process stopped by a panic, the one of the compiled programs
+    pub const STATUS: i32 = 101;
+}
+
+impl Display for Panic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some((file, line, column)) => {
+                write!(f, "panic at {}:{}:{}: {}", file, line, column, self.message)
+            }
+            None => write!(f, "panic: {}", self.message),
+        }
+    }
+}
+
+impl Display for VmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VmError::Unsupported(what) => write!(f, "{} isn't supported by the VM", what),
+            VmError::InvalidBytecode(err) => write!(f, "invalid bytecode: {}", err),
+            VmError::Ffi(err) => write!(f, "{}", err),
+            VmError::NoMain => write!(f, "the program has no `main` function"),
+            VmError::Panic(panic) => write!(f, "{}", panic),
+            VmError::Limit(err) => write!(f, "{}", err),
+            VmError::Exit(code) => write!(f, "the program exited with code {}", code),
+        }
+    }
+}
+
+impl From<FfiError> for VmError {
+    fn from(err: FfiError) -> Self {
+        VmError::Ffi(err)
+    }
+}
+
+impl std::error::Error for VmError {}
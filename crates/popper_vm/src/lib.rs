@@ -0,0 +1,32 @@
+pub mod bytecode;
+mod compiler;
+mod debug;
+mod disasm;
+mod error;
+mod limits;
+mod native;
+mod value;
+mod vm;
+
+pub use bytecode::Module;
+pub use compiler::Compiler;
+pub use debug::{Debugger, Local, StackFrame};
+pub use error::{Panic, VmError};
+pub use limits::{Limit, LimitError, Limits};
+pub use value::{Pointer, Value};
+pub use vm::{HostFunction, Vm};
+
+use popper_ast::Statement;
+
+///
+/// compile is used to compile a checked program to a bytecode module, with the
+/// modules it imports
+/// # Arguments
+/// * `stmts` - statements of the program, checked by the semantic analyzer
+/// * `file` - path of the program, to resolve its imports and locate its panics
+/// * `source` - source code of the program
+///
+/// return: the module, or what the VM doesn't support in the program
+pub fn compile(stmts: &[Statement], file: &str, source: &str) -> Result<Module, VmError> {
+    Compiler::new(file, source).compile(stmts)
+}
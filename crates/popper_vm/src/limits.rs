@@ -0,0 +1,182 @@
+//! Limits of what a program run by the VM may use, to run programs which aren't
+//! trusted: going over one stops the program with [`VmError::Limit`], its calls
+//! unwound, the host going on.
+
+use crate::value::Pointer;
+use crate::{Value, VmError};
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// limits given to [`Vm::with_limits`](crate::Vm::with_limits), none by default. They
+/// apply to each call from the host, like [`Vm::run_main`](crate::Vm::run_main)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Limits {
+    pub instructions: Option<u64>,
+    /// bytes of the strings, lists, tuples, structs and `vec`s the program keeps alive
+    pub heap: Option<usize>,
+    /// number of calls being run at once
+    pub depth: Option<usize>,
+    /// checked between instructions and when a foreign function returns: one which
+    /// blocks, like `read_line` waiting for input, isn't interrupted
+    pub timeout: Option<Duration>,
+    /// the functions of the `extern` and `external` blocks which may be called. When
+    /// it's `None` and another limit is set, only the functions of the host may be:
+    /// a program which isn't trusted doesn't call C unless it's allowed to
+    pub externs: Option<HashSet<String>>,
+}
+
+impl Limits {
+    pub fn with_instructions(mut self, instructions: u64) -> Self {
+        self.instructions = Some(instructions);
+        self
+    }
+
+    pub fn with_heap(mut self, bytes: usize) -> Self {
+        self.heap = Some(bytes);
+        self
+    }
+
+    pub fn with_depth(mut self, depth: usize) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// allow calling the foreign functions `names` only, none when it's empty
+    pub fn with_externs<I, S>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let externs = self.externs.get_or_insert_with(HashSet::new);
+        externs.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        *self == Limits::default()
+    }
+
+    /// whether the program may call the foreign function `name`, `host` when it's a
+    /// function of the host
+    pub fn allows_extern(&self, name: &str, host: bool) -> bool {
+        match &self.externs {
+            Some(externs) => externs.contains(name),
+            None => host || self.is_unlimited(),
+        }
+    }
+}
+
+/// a limit of [`Limits`] a program went over
+#[derive(Debug, Clone, PartialEq)]
+pub enum Limit {
+    Instructions(u64),
+    Heap(usize),
+    Depth(usize),
+    Timeout(Duration),
+    /// a foreign function which isn't allowed was called
+    Extern(String),
+}
+
+/// the program went over a limit, where it did when it's known
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitError {
+    pub limit: Limit,
+    /// file, line and column
+    pub location: Option<(String, u32, u32)>,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Instructions(max) => write!(f, "ran more than {} instructions", max),
+            Limit::Heap(max) => write!(f, "used more than {} bytes of heap", max),
+            Limit::Depth(max) => write!(f, "nested more than {} calls", max),
+            Limit::Timeout(max) => write!(f, "ran longer than {:?}", max),
+            Limit::Extern(name) => write!(f, "called `{}`, which isn't allowed", name),
+        }
+    }
+}
+
+impl Display for LimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some((file, line, column)) => {
+                write!(f, "limit at {}:{}:{}: {}", file, line, column, self.limit)
+            }
+            None => write!(f, "limit: {}", self.limit),
+        }
+    }
+}
+
+impl From<LimitError> for VmError {
+    fn from(err: LimitError) -> Self {
+        VmError::Limit(err)
+    }
+}
+
+/// bytes of heap a value owns itself, not the values it contains
+pub(crate) fn size(value: &Value) -> usize {
+    let values = |n: usize| n * std::mem::size_of::<Value>();
+    match value {
+        Value::String(s) => s.len(),
+        Value::List(elements) | Value::Tuple(elements) | Value::Struct(_, elements) => {
+            values(elements.len())
+        }
+        Value::Vec(elements) | Value::Slice(elements, ..) => values(elements.borrow().capacity()),
+        Value::Pointer(Pointer::Cell(_)) => values(1),
+        _ => 0,
+    }
+}
+
+/// measures the heap values keep alive, a value shared by several counting once
+#[derive(Default)]
+pub(crate) struct Heap {
+    seen: HashSet<usize>,
+    pub bytes: usize,
+}
+
+impl Heap {
+    pub fn value(&mut self, value: &Value) {
+        let address = match value {
+            Value::String(s) => Rc::as_ptr(s) as *const u8 as usize,
+            Value::List(elements) | Value::Tuple(elements) | Value::Struct(_, elements) => {
+                Rc::as_ptr(elements) as usize
+            }
+            Value::Vec(elements) | Value::Slice(elements, ..) => Rc::as_ptr(elements) as usize,
+            Value::Pointer(Pointer::Cell(cell)) => Rc::as_ptr(cell) as usize,
+            _ => return,
+        };
+        if !self.seen.insert(address) {
+            return;
+        }
+        self.bytes += size(value);
+        match value {
+            Value::List(elements) | Value::Tuple(elements) | Value::Struct(_, elements) => {
+                elements.iter().for_each(|x| self.value(x))
+            }
+            Value::Vec(elements) | Value::Slice(elements, ..) => {
+                elements.borrow().iter().for_each(|x| self.value(x))
+            }
+            Value::Pointer(Pointer::Cell(cell)) => self.value(&cell.borrow()),
+            _ => {}
+        }
+    }
+}
+
+/// what the call from the host used so far
+#[derive(Default)]
+pub(crate) struct Usage {
+    pub instructions: u64,
+    pub deadline: Option<Instant>,
+    /// heap measured when it was last walked
+    pub live: usize,
+    /// bytes allocated since
+    pub allocated: usize,
+}
@@ -0,0 +1,110 @@
+//! Functions of the runtime library, declared in the `extern` blocks of the `std/`
+//! modules. The compiled code links them from C; the VM implements them, as they
+//! return Popper strings and `vec`s, or stop the program rather than the host.
+
+use crate::{Value, Vm, VmError};
+use popper_ast::IntKind;
+use popper_flag::ValueFlag;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub(crate) type Native = fn(&mut Vm, &[Value]) -> Result<Value, VmError>;
+
+pub(crate) fn lookup(name: &str) -> Option<Native> {
+    let native: Native = match name {
+        "popper_std_eprint" => |_, args| {
+            eprint!("{}", string(args, 0));
+            Ok(int(0))
+        },
+        "exit" => |vm, args| {
+            vm.flush();
+            Err(VmError::Exit(integer(args, 0) as i32))
+        },
+        "popper_std_flush" => |vm, _| {
+            vm.flush();
+            Ok(int(0))
+        },
+        "popper_std_read_line" => |_, _| {
+            let mut line = String::new();
+            std::io::stdin().lock().read_line(&mut line).ok();
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            Ok(Value::string(line.strip_suffix('\r').unwrap_or(line)))
+        },
+        "popper_std_dealloc" => |vm, args| {
+            let ptr = vm.to_ffi(&args[0])?;
+            let ty = ValueFlag::Pointer(Box::new(ValueFlag::Char));
+            vm.call_c("free", &[ty], &ValueFlag::None, &[ptr])?;
+            Ok(int(0))
+        },
+        "popper_std_arg_count" => |vm, _| Ok(int(vm.args().len() as i64)),
+        "popper_std_args" => |vm, _| {
+            let args = vm.args().iter().map(|x| Value::string(x)).collect();
+            Ok(Value::vec(args))
+        },
+        "popper_std_env_var" => |_, args| {
+            Ok(Value::string(
+                &std::env::var(string(args, 0)).unwrap_or_default(),
+            ))
+        },
+        "popper_std_read_file" => |vm, args| {
+            let len = std::fs::metadata(string(args, 0)).map_or(0, |x| x.len());
+            vm.allocate(len as usize, true)?;
+            match std::fs::read(string(args, 0)) {
+                Ok(content) => Ok(Value::string(&String::from_utf8_lossy(&content))),
+                Err(_) => Err(vm.panic(&format!("unable to read `{}`", string(args, 0)))),
+            }
+        },
+        "popper_std_write_file" => |_, args| {
+            let written = std::fs::write(string(args, 0), string(args, 1));
+            Ok(int(if written.is_ok() { 0 } else { -1 }))
+        },
+        "popper_std_append_file" => |_, args| {
+            let written = std::fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(string(args, 0))
+                .and_then(|mut file| file.write_all(string(args, 1).as_bytes()));
+            Ok(int(if written.is_ok() { 0 } else { -1 }))
+        },
+        "popper_std_exists" => {
+            |_, args| Ok(Value::Bool(std::path::Path::new(string(args, 0)).exists()))
+        }
+        "popper_std_now" => |_, _| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            Ok(Value::Int(IntKind::I64, now.as_secs() as i64))
+        },
+        "popper_std_millis" => |_, _| {
+            let start = *START.get_or_init(Instant::now);
+            Ok(Value::Int(IntKind::I64, start.elapsed().as_millis() as i64))
+        },
+        // no longer than the timeout of the VM
+        "popper_std_sleep_ms" => |vm, args| {
+            let duration = Duration::from_millis(integer(args, 0).max(0) as u64);
+            std::thread::sleep(vm.remaining().map_or(duration, |x| x.min(duration)));
+            Ok(int(0))
+        },
+        _ => return None,
+    };
+    Some(native)
+}
+
+/// origin of the monotonic clock of `popper_std_millis`
+static START: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+
+fn int(x: i64) -> Value {
+    Value::int(IntKind::I32, x)
+}
+
+/// the arguments are checked by the analyzer
+fn integer(args: &[Value], i: usize) -> i64 {
+    args[i].as_int().expect("an integer argument")
+}
+
+fn string(args: &[Value], i: usize) -> &str {
+    match &args[i] {
+        Value::String(s) => s,
+        value => panic!("{} isn't a string", value),
+    }
+}
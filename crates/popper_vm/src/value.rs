@@ -0,0 +1,134 @@
+use popper_ast::IntKind;
+use popper_flag::ValueFlag;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::rc::Rc;
+
+/// value of the VM. Lists, tuples and structs are values, copied when they're
+/// modified; `vec`s and slices share their elements
+#[derive(Debug, Clone)]
+pub enum Value {
+    Unit,
+    /// an integer of the given kind, unsigned ones keeping their bits
+    Int(IntKind, i64),
+    Bool(bool),
+    Char(u8),
+    /// a `float`, rounded to 32 bits after each operation
+    Float(f64),
+    String(Rc<str>),
+    List(Rc<Vec<Value>>),
+    Tuple(Rc<Vec<Value>>),
+    /// index of the struct in the struct table, and its fields
+    Struct(u32, Rc<Vec<Value>>),
+    Vec(Rc<RefCell<Vec<Value>>>),
+    /// elements of a `vec` or of a copy of a list, from `start` to `end`
+    Slice(Rc<RefCell<Vec<Value>>>, usize, usize),
+    Pointer(Pointer),
+}
+
+#[derive(Debug, Clone)]
+pub enum Pointer {
+    /// a variable of the program
+    Cell(Rc<RefCell<Value>>),
+    /// memory of the C library, and the type of what it points to
+    Raw(usize, Rc<ValueFlag>),
+}
+
+impl Pointer {
+    pub fn address(&self) -> usize {
+        match self {
+            Pointer::Cell(cell) => Rc::as_ptr(cell) as usize,
+            Pointer::Raw(address, _) => *address,
+        }
+    }
+}
+
+impl Value {
+    /// an integer of kind `kind`, wrapped to its range
+    pub fn int(kind: IntKind, value: i64) -> Self {
+        Value::Int(kind, truncate(kind, value))
+    }
+
+    pub fn string(s: &str) -> Self {
+        Value::String(Rc::from(s))
+    }
+
+    pub fn vec(elements: Vec<Value>) -> Self {
+        Value::Vec(Rc::new(RefCell::new(elements)))
+    }
+
+    /// integer value of integers, chars and booleans
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Value::Int(_, x) => Some(*x),
+            Value::Char(x) => Some(*x as i64),
+            Value::Bool(x) => Some(*x as i64),
+            _ => None,
+        }
+    }
+
+    /// elements of a list, a `vec` or a slice
+    pub fn elements(&self) -> Option<Vec<Value>> {
+        match self {
+            Value::List(elements) => Some(elements.to_vec()),
+            Value::Vec(elements) => Some(elements.borrow().clone()),
+            Value::Slice(elements, start, end) => Some(elements.borrow()[*start..*end].to_vec()),
+            _ => None,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Unit, Value::Unit) => true,
+            (Value::Int(_, a), Value::Int(_, b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Char(a), Value::Char(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Tuple(a), Value::Tuple(b)) => a == b,
+            (Value::Struct(a, x), Value::Struct(b, y)) => a == b && x == y,
+            (Value::Pointer(a), Value::Pointer(b)) => a.address() == b.address(),
+            (a, b) => match (a.elements(), b.elements()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |values: &[Value]| {
+            values
+                .iter()
+                .map(|x| x.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Value::Unit => write!(f, "()"),
+            Value::Int(kind, x) if kind.is_signed() => write!(f, "{}", x),
+            Value::Int(_, x) => write!(f, "{}", *x as u64),
+            Value::Bool(x) => write!(f, "{}", x),
+            Value::Char(x) => write!(f, "{:?}", *x as char),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::String(x) => write!(f, "{:?}", x),
+            Value::Tuple(values) => write!(f, "({})", join(values)),
+            Value::Struct(_, fields) => write!(f, "{{ {} }}", join(fields)),
+            Value::Pointer(ptr) => write!(f, "{:#x}", ptr.address()),
+            value => write!(f, "[{}]", join(&value.elements().unwrap_or_default())),
+        }
+    }
+}
+
+/// the low bits of `value` for an integer of kind `kind`, sign extended when it's signed
+pub fn truncate(kind: IntKind, value: i64) -> i64 {
+    let shift = 64 - kind.bits();
+    if kind.is_signed() {
+        (value << shift) >> shift
+    } else {
+        ((value as u64) << shift >> shift) as i64
+    }
+}
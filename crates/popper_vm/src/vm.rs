@@ -0,0 +1,976 @@
+use crate::bytecode::{Constant, Instr, Module};
+use crate::debug::Debugger;
+use crate::limits::{self, Heap, Limit, LimitError, Limits, Usage};
+use crate::native::{self, Native};
+use crate::value::{truncate, Pointer};
+use crate::{Panic, Value, VmError};
+use popper_ast::IntKind;
+use popper_codegen::library::Library;
+use popper_ffi::{Ffi, Value as FfiValue};
+use popper_flag::ValueFlag;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// local of a function: pointers taken to it with `ref` share its value
+#[derive(Clone)]
+enum Slot {
+    Value(Value),
+    Cell(Rc<RefCell<Value>>),
+}
+
+struct Frame {
+    function: u32,
+    pc: usize,
+    /// index of the first local of the function
+    base: usize,
+    /// arguments given to the `...`, read by `arg`
+    varargs: VecDeque<Value>,
+}
+
+/// a function of the program which isn't running the program, given to
+/// [`Vm::with_host`]: its error stops the program
+pub type HostFunction = Rc<dyn Fn(&mut Vm, &[Value]) -> Result<Value, VmError>>;
+
+/// a function of the foreign table
+#[derive(Clone, Copy)]
+enum Foreign {
+    Native(Native),
+    /// index of the function given by the host
+    Host(usize),
+    /// declared to popper_ffi
+    C,
+}
+
+///
+/// Vm runs a bytecode module. Values live on an operand stack, the locals of the
+/// functions being called on a stack of their own.
+///
+/// The functions of the `extern` and `external` blocks are the runtime functions
+/// the VM implements, or C functions called through libffi: the libraries of the
+/// `external` blocks are loaded when the VM is created
+pub struct Vm {
+    module: Rc<Module>,
+    ffi: Ffi,
+    foreign: Vec<Foreign>,
+    host: Vec<HostFunction>,
+    stack: Vec<Value>,
+    locals: Vec<Slot>,
+    frames: Vec<Frame>,
+    /// arguments of the program, starting with its path
+    args: Vec<String>,
+    debugger: Option<Box<dyn Debugger>>,
+    limits: Limits,
+    usage: Usage,
+}
+
+impl Vm {
+    pub fn new(module: Module) -> Result<Self, VmError> {
+        Self::with_host(module, HashMap::new())
+    }
+
+    /// a VM running the foreign functions named in `host` with these functions, instead
+    /// of the ones of the runtime or of C
+    pub fn with_host(module: Module, host: HashMap<String, HostFunction>) -> Result<Self, VmError> {
+        let mut ffi = Ffi::new();
+        for library in &module.libraries {
+            ffi.load_library(&Library::new(&library.file, &library.dir))?;
+        }
+        for layout in &module.structs {
+            ffi.declare_struct_fields(&layout.name, layout.types.clone());
+        }
+        let mut foreign = Vec::new();
+        let mut functions = Vec::new();
+        for function in &module.foreign {
+            if let Some(f) = host.get(&function.name) {
+                foreign.push(Foreign::Host(functions.len()));
+                functions.push(f.clone());
+                continue;
+            }
+            match native::lookup(&function.name) {
+                Some(native) => foreign.push(Foreign::Native(native)),
+                None => {
+                    ffi.declare(
+                        &function.name,
+                        &function.args,
+                        &function.return_type,
+                        function.is_var_args,
+                    )?;
+                    foreign.push(Foreign::C);
+                }
+            }
+        }
+        Ok(Self {
+            module: Rc::new(module),
+            ffi,
+            foreign,
+            host: functions,
+            stack: Vec::new(),
+            locals: Vec::new(),
+            frames: Vec::new(),
+            args: Vec::new(),
+            debugger: None,
+            limits: Limits::default(),
+            usage: Usage::default(),
+        })
+    }
+
+    /// arguments of the program, starting with its path
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// call `debugger` before each statement, and when the program panics
+    pub fn with_debugger(mut self, debugger: Box<dyn Debugger>) -> Self {
+        self.debugger = Some(debugger);
+        self
+    }
+
+    /// stop the program when it goes over `limits`
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// the number of calls being run
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// run `main`, giving it the arguments of the program if it takes them.
+    ///
+    /// return: the exit code, what `main` returns or what it gives `exit`
+    pub fn run_main(&mut self) -> Result<i32, VmError> {
+        let main = self.module.function("main").ok_or(VmError::NoMain)?;
+        let args = match self.module.functions[main as usize].arity {
+            0 => Vec::new(),
+            _ => {
+                let args = self.args.iter().map(|x| Value::string(x)).collect();
+                vec![Value::vec(args)]
+            }
+        };
+        let result = self.call_index(main, args);
+        // before a panic is printed
+        self.flush();
+        match result {
+            Ok(Value::Int(_, code)) => Ok(code as i32),
+            Ok(_) => Ok(0),
+            Err(VmError::Exit(code)) => Ok(code),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// flush the output of the program, written by the VM and by the C library
+    pub fn flush(&mut self) {
+        std::io::Write::flush(&mut std::io::stdout()).ok();
+        let null = FfiValue::Pointer(std::ptr::null_mut());
+        let file = ValueFlag::Pointer(Box::new(ValueFlag::Char));
+        self.call_c("fflush", &[file], &ValueFlag::Integer, &[null])
+            .ok();
+    }
+
+    /// call a function of the module by name
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, VmError> {
+        let function = self
+            .module
+            .function(name)
+            .ok_or_else(|| VmError::Ffi(popper_ffi::FfiError::UnknownFunction(name.to_string())))?;
+        self.call_index(function, args)
+    }
+
+    fn call_index(&mut self, function: u32, args: Vec<Value>) -> Result<Value, VmError> {
+        let depth = self.frames.len();
+        if depth == 0 {
+            self.usage = Usage {
+                deadline: self.limits.timeout.map(|x| Instant::now() + x),
+                ..Usage::default()
+            };
+        }
+        let (stack, locals) = (self.stack.len(), self.locals.len());
+        self.stack.extend(args);
+        let argc = self.stack.len() - stack;
+        let result = self.enter(function, argc).and_then(|_| self.execute(depth));
+        if let (Err(VmError::Panic(panic)), Some(mut debugger)) = (&result, self.debugger.take()) {
+            debugger.panic(self, panic);
+            self.debugger = Some(debugger);
+        }
+        if result.is_err() {
+            // a panic unwinds the calls
+            self.frames.truncate(depth);
+            self.stack.truncate(stack);
+            self.locals.truncate(locals);
+        }
+        result
+    }
+
+    /// call a function with the `argc` values on top of the stack
+    fn enter(&mut self, function: u32, argc: usize) -> Result<(), VmError> {
+        if let Some(max) = self.limits.depth {
+            if self.frames.len() >= max {
+                return Err(self.limit(Limit::Depth(max)));
+            }
+        }
+        let f = &self.module.functions[function as usize];
+        let mut args = self.stack.split_off(self.stack.len() - argc);
+        let varargs = args.split_off((f.arity as usize).min(argc));
+        let base = self.locals.len();
+        self.locals.extend(args.into_iter().map(Slot::Value));
+        self.locals.resize(
+            base + (f.locals as usize).max(argc),
+            Slot::Value(Value::Unit),
+        );
+        self.frames.push(Frame {
+            function,
+            pc: 0,
+            base,
+            varargs: varargs.into(),
+        });
+        Ok(())
+    }
+
+    /// run until the call at `depth` returns
+    fn execute(&mut self, depth: usize) -> Result<Value, VmError> {
+        let module = self.module.clone();
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let function = &module.functions[frame.function as usize];
+            let instr = function.code[frame.pc];
+            frame.pc += 1;
+            let base = frame.base;
+            if self.debugger.is_some() && function.is_statement(frame.pc - 1) {
+                let mut debugger = self.debugger.take().unwrap();
+                debugger.statement(self);
+                self.debugger = Some(debugger);
+            }
+            self.step()?;
+
+            match instr {
+                Instr::Const(index) => {
+                    let value = match &module.constants[index as usize] {
+                        Constant::Int(kind, x) => Value::Int(*kind, *x),
+                        Constant::Float(x) => Value::Float(*x),
+                        Constant::Bool(x) => Value::Bool(*x),
+                        Constant::Char(x) => Value::Char(*x),
+                        Constant::String(x) => Value::string(x),
+                    };
+                    self.stack.push(value);
+                }
+                Instr::Unit => self.stack.push(Value::Unit),
+                Instr::Load(slot) => {
+                    let value = self.local(base + slot as usize);
+                    self.stack.push(value);
+                }
+                Instr::Store(slot) => {
+                    let value = self.pop();
+                    match &mut self.locals[base + slot as usize] {
+                        Slot::Value(old) => *old = coerce(old, value),
+                        Slot::Cell(cell) => {
+                            let value = coerce(&cell.borrow(), value);
+                            *cell.borrow_mut() = value;
+                        }
+                    }
+                }
+                Instr::Let(slot) => {
+                    let value = self.pop();
+                    self.locals[base + slot as usize] = Slot::Value(value);
+                }
+                Instr::RefLocal(slot) => {
+                    let local = &mut self.locals[base + slot as usize];
+                    let cell = match local {
+                        Slot::Cell(cell) => cell.clone(),
+                        Slot::Value(value) => {
+                            let cell = Rc::new(RefCell::new(std::mem::replace(value, Value::Unit)));
+                            *local = Slot::Cell(cell.clone());
+                            cell
+                        }
+                    };
+                    self.stack.push(Value::Pointer(Pointer::Cell(cell)));
+                }
+                Instr::Pop => {
+                    self.pop();
+                }
+                Instr::Dup => {
+                    let value = self.stack.last().unwrap().clone();
+                    self.stack.push(value);
+                }
+                Instr::Add
+                | Instr::Sub
+                | Instr::Mul
+                | Instr::Div
+                | Instr::Mod
+                | Instr::Pow
+                | Instr::Eq
+                | Instr::Ne
+                | Instr::Lt
+                | Instr::Le
+                | Instr::Gt
+                | Instr::Ge
+                | Instr::And
+                | Instr::Or => {
+                    let rhs = self.pop();
+                    let lhs = self.pop();
+                    let value = binary(instr, lhs, rhs).map_err(|msg| self.panic(&msg))?;
+                    self.stack.push(value);
+                }
+                Instr::Neg => {
+                    let value = match self.pop() {
+                        Value::Int(kind, x) => Value::int(kind, x.wrapping_neg()),
+                        Value::Float(x) => Value::Float(-x),
+                        value => return Err(self.panic(&format!("`-{}`", value))),
+                    };
+                    self.stack.push(value);
+                }
+                Instr::Not => {
+                    let value = self.pop();
+                    self.stack.push(Value::Bool(value == Value::Bool(false)));
+                }
+                Instr::Cast(ty) => {
+                    let value = self.pop();
+                    self.stack.push(cast(value, &module.types[ty as usize]));
+                }
+                Instr::Jump(target) => self.frames.last_mut().unwrap().pc = target as usize,
+                Instr::JumpIfFalse(target) => {
+                    if self.pop() == Value::Bool(false) {
+                        self.frames.last_mut().unwrap().pc = target as usize;
+                    }
+                }
+                Instr::Call(function, argc) => self.enter(function, argc as usize)?,
+                Instr::CallForeign(function, argc) => {
+                    let args = self.stack.split_off(self.stack.len() - argc as usize);
+                    let value = self.call_foreign(function, &args)?;
+                    self.stack.push(value);
+                    self.check_time()?;
+                }
+                Instr::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.locals.truncate(frame.base);
+                    if self.frames.len() == depth {
+                        return Ok(value);
+                    }
+                    self.stack.push(value);
+                }
+                Instr::MakeList(n) => {
+                    let elements = self.pop_n(n);
+                    self.stack.push(Value::List(Rc::new(elements)));
+                }
+                Instr::MakeVec(n) => {
+                    let elements = self.pop_n(n);
+                    self.stack.push(Value::vec(elements));
+                }
+                Instr::MakeTuple(n) => {
+                    let elements = self.pop_n(n);
+                    self.stack.push(Value::Tuple(Rc::new(elements)));
+                }
+                Instr::MakeStruct(index) => {
+                    let n = module.structs[index as usize].fields.len() as u32;
+                    let fields = self.pop_n(n);
+                    self.stack.push(Value::Struct(index, Rc::new(fields)));
+                }
+                Instr::GetField(name) => {
+                    let Value::Struct(index, fields) = self.pop() else {
+                        return Err(invalid("field read from a value which isn't a struct"));
+                    };
+                    let field = self.field(index, name)?;
+                    self.stack.push(fields[field].clone());
+                }
+                Instr::SetField(name) => {
+                    let value = self.pop();
+                    let Value::Struct(index, mut fields) = self.pop() else {
+                        return Err(invalid("field set in a value which isn't a struct"));
+                    };
+                    let field = self.field(index, name)?;
+                    let fields_mut = Rc::make_mut(&mut fields);
+                    fields_mut[field] = coerce(&fields_mut[field], value);
+                    self.stack.push(Value::Struct(index, fields));
+                }
+                Instr::GetElement(index) => {
+                    let Value::Tuple(elements) = self.pop() else {
+                        return Err(invalid("element read from a value which isn't a tuple"));
+                    };
+                    let Some(element) = elements.get(index as usize) else {
+                        return Err(invalid("no such element in the tuple"));
+                    };
+                    self.stack.push(element.clone());
+                }
+                Instr::SetElement(index) => {
+                    let value = self.pop();
+                    let Value::Tuple(mut elements) = self.pop() else {
+                        return Err(invalid("element set in a value which isn't a tuple"));
+                    };
+                    let index = index as usize;
+                    if index >= elements.len() {
+                        return Err(invalid("no such element in the tuple"));
+                    }
+                    let elements_mut = Rc::make_mut(&mut elements);
+                    elements_mut[index] = coerce(&elements_mut[index], value);
+                    self.stack.push(Value::Tuple(elements));
+                }
+                Instr::Index => {
+                    let index = self.pop();
+                    let seq = self.pop();
+                    let i = self.check_index(&seq, &index)?;
+                    let value = match seq {
+                        Value::List(elements) => elements[i].clone(),
+                        Value::Vec(elements) => elements.borrow()[i].clone(),
+                        Value::Slice(elements, start, _) => elements.borrow()[start + i].clone(),
+                        value => return Err(invalid(&format!("{} can't be indexed", value))),
+                    };
+                    self.stack.push(value);
+                }
+                Instr::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let mut seq = self.pop();
+                    let i = self.check_index(&seq, &index)?;
+                    match &mut seq {
+                        Value::List(elements) => {
+                            let elements = Rc::make_mut(elements);
+                            elements[i] = coerce(&elements[i], value);
+                        }
+                        Value::Vec(elements) => {
+                            let mut elements = elements.borrow_mut();
+                            elements[i] = coerce(&elements[i], value);
+                        }
+                        Value::Slice(elements, start, _) => {
+                            let mut elements = elements.borrow_mut();
+                            let i = *start + i;
+                            elements[i] = coerce(&elements[i], value);
+                        }
+                        value => return Err(invalid(&format!("{} can't be indexed", value))),
+                    }
+                    self.stack.push(seq);
+                }
+                Instr::Slice { start, end } => {
+                    let end = end.then(|| self.pop());
+                    let start = start.then(|| self.pop());
+                    let seq = self.pop();
+                    let value = self.slice(seq, start, end)?;
+                    self.stack.push(value);
+                }
+                Instr::Len => {
+                    let len = match self.pop() {
+                        Value::String(s) => s.len(),
+                        Value::List(elements) => elements.len(),
+                        Value::Vec(elements) => elements.borrow().len(),
+                        Value::Slice(_, start, end) => end - start,
+                        value => return Err(invalid(&format!("{} has no length", value))),
+                    };
+                    self.stack.push(Value::int(IntKind::I32, len as i64));
+                }
+                Instr::Push => {
+                    let value = self.pop();
+                    let Value::Vec(elements) = self.pop() else {
+                        return Err(invalid("`push` to a value which isn't a `vec`"));
+                    };
+                    let mut elements = elements.borrow_mut();
+                    let value = match elements.first() {
+                        Some(first) => coerce(first, value),
+                        None => value,
+                    };
+                    elements.push(value);
+                }
+                Instr::PopVec => {
+                    let Value::Vec(elements) = self.pop() else {
+                        return Err(invalid("`pop` from a value which isn't a `vec`"));
+                    };
+                    let value = elements.borrow_mut().pop();
+                    match value {
+                        Some(value) => self.stack.push(value),
+                        None => return Err(self.panic("pop from an empty vec")),
+                    }
+                }
+                Instr::Deref => {
+                    let Value::Pointer(ptr) = self.pop() else {
+                        return Err(invalid("dereference of a value which isn't a pointer"));
+                    };
+                    let value = match ptr {
+                        Pointer::Cell(cell) => cell.borrow().clone(),
+                        Pointer::Raw(0, _) => return Err(self.panic("null pointer dereference")),
+                        Pointer::Raw(address, ty) => {
+                            let value = unsafe { self.ffi.read(address as *const _, &ty)? };
+                            self.returned(value, &ty)
+                        }
+                    };
+                    self.stack.push(value);
+                }
+                Instr::StoreDeref => {
+                    let value = self.pop();
+                    let Value::Pointer(ptr) = self.pop() else {
+                        return Err(invalid("store through a value which isn't a pointer"));
+                    };
+                    match ptr {
+                        Pointer::Cell(cell) => {
+                            let value = coerce(&cell.borrow(), value);
+                            *cell.borrow_mut() = value;
+                        }
+                        Pointer::Raw(0, _) => return Err(self.panic("null pointer dereference")),
+                        Pointer::Raw(address, ty) => {
+                            let value = self.to_ffi(&value)?;
+                            unsafe { self.ffi.write(address as *mut _, &ty, &value)? };
+                        }
+                    }
+                }
+                Instr::VaArg(ty) => {
+                    let value = self.frames.last_mut().unwrap().varargs.pop_front();
+                    let Some(value) = value else {
+                        return Err(self.panic("`arg` read more arguments than were given"));
+                    };
+                    self.stack.push(cast(value, &module.types[ty as usize]));
+                }
+                Instr::Assert => {
+                    let msg = self.pop();
+                    if self.pop() == Value::Bool(false) {
+                        let Value::String(msg) = msg else {
+                            return Err(invalid("assertion message which isn't a string"));
+                        };
+                        return Err(self.panic(&format!("assertion failed: {}", msg)));
+                    }
+                }
+            }
+
+            if self.limits.heap.is_some() {
+                let top = self.stack.last();
+                let bytes = match (instr, top) {
+                    (Instr::Push, _) => std::mem::size_of::<Value>(),
+                    // a `vec` is modified in place
+                    (Instr::SetIndex, Some(Value::List(_)))
+                    | (
+                        Instr::Const(_)
+                        | Instr::Add
+                        | Instr::RefLocal(_)
+                        | Instr::CallForeign(..)
+                        | Instr::MakeList(_)
+                        | Instr::MakeVec(_)
+                        | Instr::MakeTuple(_)
+                        | Instr::MakeStruct(_)
+                        | Instr::SetField(_)
+                        | Instr::SetElement(_)
+                        | Instr::Slice { .. },
+                        Some(_),
+                    ) => top.map_or(0, limits::size),
+                    _ => 0,
+                };
+                self.allocate(bytes, false)?;
+            }
+        }
+    }
+
+    /// count an instruction, checking the limits on instructions and time
+    fn step(&mut self) -> Result<(), VmError> {
+        self.usage.instructions += 1;
+        if let Some(max) = self.limits.instructions {
+            if self.usage.instructions > max {
+                return Err(self.limit(Limit::Instructions(max)));
+            }
+        }
+        // reading the clock is slower than an instruction
+        if self.usage.instructions.is_multiple_of(1024) {
+            self.check_time()?;
+        }
+        Ok(())
+    }
+
+    fn check_time(&self) -> Result<(), VmError> {
+        match (self.usage.deadline, self.limits.timeout) {
+            (Some(deadline), Some(timeout)) if Instant::now() >= deadline => {
+                Err(self.limit(Limit::Timeout(timeout)))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// time left before the timeout
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        self.usage
+            .deadline
+            .map(|x| x.saturating_duration_since(Instant::now()))
+    }
+
+    /// count `bytes` the program allocated, or is about to when they're `pending`,
+    /// measuring the heap it keeps alive when it may be over the limit
+    pub(crate) fn allocate(&mut self, bytes: usize, pending: bool) -> Result<(), VmError> {
+        let Some(max) = self.limits.heap else {
+            return Ok(());
+        };
+        self.usage.allocated += bytes;
+        if self.usage.live + self.usage.allocated <= max {
+            return Ok(());
+        }
+        let mut heap = Heap::default();
+        self.stack.iter().for_each(|x| heap.value(x));
+        for slot in &self.locals {
+            match slot {
+                Slot::Value(value) => heap.value(value),
+                Slot::Cell(cell) => heap.value(&Value::Pointer(Pointer::Cell(cell.clone()))),
+            }
+        }
+        for frame in &self.frames {
+            frame.varargs.iter().for_each(|x| heap.value(x));
+        }
+        self.usage.live = heap.bytes;
+        self.usage.allocated = if pending { bytes } else { 0 };
+        if self.usage.live + self.usage.allocated > max {
+            return Err(self.limit(Limit::Heap(max)));
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("the bytecode keeps the stack balanced")
+    }
+
+    fn pop_n(&mut self, n: u32) -> Vec<Value> {
+        self.stack.split_off(self.stack.len() - n as usize)
+    }
+
+    /// position of a field, named by a constant, in a struct of the struct table
+    fn field(&self, index: u32, name: u32) -> Result<usize, VmError> {
+        self.module.structs[index as usize]
+            .fields
+            .iter()
+            .position(|x| *x == name)
+            .ok_or_else(|| invalid("no such field in the struct"))
+    }
+
+    fn check_index(&self, seq: &Value, index: &Value) -> Result<usize, VmError> {
+        let len = match seq {
+            Value::List(elements) => elements.len(),
+            Value::Vec(elements) => elements.borrow().len(),
+            Value::Slice(_, start, end) => end - start,
+            _ => 0,
+        };
+        let index = index.as_int().unwrap_or(-1);
+        if index < 0 || index as usize >= len {
+            return Err(self.panic(&format!("index {} out of range for length {}", index, len)));
+        }
+        Ok(index as usize)
+    }
+
+    fn slice(
+        &self,
+        seq: Value,
+        start: Option<Value>,
+        end: Option<Value>,
+    ) -> Result<Value, VmError> {
+        let len = match &seq {
+            Value::String(s) => s.len(),
+            seq => seq.elements().map_or(0, |x| x.len()),
+        };
+        let start = start.and_then(|x| x.as_int()).unwrap_or(0);
+        let end = end.and_then(|x| x.as_int()).unwrap_or(len as i64);
+        if start < 0 || end < start || end > len as i64 {
+            let of = if matches!(seq, Value::String(_)) {
+                "string of length"
+            } else {
+                "length"
+            };
+            return Err(self.panic(&format!(
+                "slice {}..{} out of range for {} {}",
+                start, end, of, len
+            )));
+        }
+        let (start, end) = (start as usize, end as usize);
+        Ok(match seq {
+            Value::String(s) => Value::string(&String::from_utf8_lossy(&s.as_bytes()[start..end])),
+            Value::List(elements) => {
+                Value::Slice(Rc::new(RefCell::new(elements.to_vec())), start, end)
+            }
+            Value::Vec(elements) => Value::Slice(elements, start, end),
+            Value::Slice(elements, offset, _) => {
+                Value::Slice(elements, offset + start, offset + end)
+            }
+            value => return Err(invalid(&format!("{} can't be sliced", value))),
+        })
+    }
+
+    fn call_foreign(&mut self, function: u32, args: &[Value]) -> Result<Value, VmError> {
+        let name = &self.module.foreign[function as usize].name;
+        let host = matches!(self.foreign[function as usize], Foreign::Host(_));
+        if !self.limits.allows_extern(name, host) {
+            return Err(self.limit(Limit::Extern(name.clone())));
+        }
+        match self.foreign[function as usize] {
+            Foreign::Native(native) => native(self, args),
+            Foreign::Host(index) => {
+                let f = self.host[index].clone();
+                f(self, args)
+            }
+            Foreign::C => {
+                let module = self.module.clone();
+                let function = &module.foreign[function as usize];
+                let args = args
+                    .iter()
+                    .map(|x| self.to_ffi(x))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let value = self.ffi.call(&function.name, &args)?;
+                Ok(self.returned(value, &function.return_type))
+            }
+        }
+    }
+
+    /// call a function of the C library, declaring it on first use
+    pub(crate) fn call_c(
+        &mut self,
+        name: &str,
+        args: &[ValueFlag],
+        return_type: &ValueFlag,
+        values: &[FfiValue],
+    ) -> Result<Value, VmError> {
+        if !self.ffi.is_declared(name) {
+            self.ffi.declare(name, args, return_type, false)?;
+        }
+        let value = self.ffi.call(name, values)?;
+        Ok(self.returned(value, return_type))
+    }
+
+    /// a value given to C
+    pub(crate) fn to_ffi(&self, value: &Value) -> Result<FfiValue, VmError> {
+        Ok(match value {
+            Value::Unit => FfiValue::Unit,
+            Value::Int(kind, x) => FfiValue::Int(*kind, *x),
+            Value::Bool(x) => FfiValue::Bool(*x),
+            Value::Char(x) => FfiValue::Char(*x),
+            Value::Float(x) => FfiValue::Float(*x),
+            Value::String(x) => FfiValue::String(x.to_string()),
+            Value::List(values) | Value::Tuple(values) | Value::Struct(_, values) => {
+                FfiValue::Struct(
+                    values
+                        .iter()
+                        .map(|x| self.to_ffi(x))
+                        .collect::<Result<_, _>>()?,
+                )
+            }
+            Value::Pointer(Pointer::Raw(address, _)) => FfiValue::Pointer(*address as *mut _),
+            Value::Pointer(Pointer::Cell(_)) => {
+                return Err(VmError::Unsupported(
+                    "a pointer to a variable given to C".to_string(),
+                ))
+            }
+            Value::Vec(_) | Value::Slice(..) => {
+                return Err(VmError::Unsupported(
+                    "a `vec` or a slice given to C".to_string(),
+                ))
+            }
+        })
+    }
+
+    /// a value returned by C as a `ty`
+    fn returned(&self, value: FfiValue, ty: &ValueFlag) -> Value {
+        match value {
+            FfiValue::Unit => Value::Unit,
+            FfiValue::Int(kind, x) => Value::Int(kind, x),
+            FfiValue::Bool(x) => Value::Bool(x),
+            FfiValue::Char(x) => Value::Char(x),
+            FfiValue::Float(x) => Value::Float(x),
+            FfiValue::String(x) => Value::string(&x),
+            FfiValue::Pointer(ptr) => {
+                let pointee = match ty {
+                    ValueFlag::Pointer(pointee) => (**pointee).clone(),
+                    // a null string
+                    _ => ValueFlag::Char,
+                };
+                Value::Pointer(Pointer::Raw(ptr as usize, Rc::new(pointee)))
+            }
+            FfiValue::Struct(values) => {
+                let types: Vec<ValueFlag> = match ty {
+                    ValueFlag::Struct(name) | ValueFlag::StructInstance(name) => {
+                        let index = self.module.struct_layout(name).unwrap();
+                        self.module.structs[index as usize].types.clone()
+                    }
+                    ValueFlag::Tuple(types) => types.clone(),
+                    ValueFlag::List(ty, len) => vec![(**ty).clone(); *len],
+                    _ => Vec::new(),
+                };
+                let values: Vec<_> = values
+                    .into_iter()
+                    .zip(&types)
+                    .map(|(value, ty)| self.returned(value, ty))
+                    .collect();
+                match ty {
+                    ValueFlag::Struct(name) | ValueFlag::StructInstance(name) => {
+                        let index = self.module.struct_layout(name).unwrap();
+                        Value::Struct(index, Rc::new(values))
+                    }
+                    ValueFlag::List(..) => Value::List(Rc::new(values)),
+                    _ => Value::Tuple(Rc::new(values)),
+                }
+            }
+        }
+    }
+
+    /// the function, the instruction being run and the first local of the calls, the
+    /// outermost first
+    pub(crate) fn frames(&self) -> impl DoubleEndedIterator<Item = (u32, usize, usize)> + '_ {
+        self.frames
+            .iter()
+            .map(|x| (x.function, x.pc.saturating_sub(1), x.base))
+    }
+
+    pub(crate) fn local(&self, index: usize) -> Value {
+        match &self.locals[index] {
+            Slot::Value(value) => value.clone(),
+            Slot::Cell(cell) => cell.borrow().clone(),
+        }
+    }
+
+    /// a panic at the instruction being run, like the ones of the host functions
+    pub fn panic(&self, message: &str) -> VmError {
+        VmError::Panic(Panic {
+            message: message.to_string(),
+            location: self.location(),
+        })
+    }
+
+    /// the program went over `limit` at the instruction being run
+    fn limit(&self, limit: Limit) -> VmError {
+        VmError::Limit(LimitError {
+            limit,
+            location: self.location(),
+        })
+    }
+
+    /// file, line and column of the instruction being run
+    fn location(&self) -> Option<(String, u32, u32)> {
+        let frame = self.frames.last()?;
+        let function = &self.module.functions[frame.function as usize];
+        let location = function.location(frame.pc.saturating_sub(1))?;
+        let file = self.module.files.get(function.file as usize)?;
+        Some((file.clone(), location.line, location.column))
+    }
+}
+
+/// the error of an instruction given a value the compiler doesn't give it
+fn invalid(what: &str) -> VmError {
+    VmError::InvalidBytecode(what.to_string())
+}
+
+/// `value` stored where `old` was: integers keep the type of the value they replace
+fn coerce(old: &Value, value: Value) -> Value {
+    match (old, value) {
+        (Value::Int(kind, _), Value::Int(other, x)) if *kind != other => Value::int(*kind, x),
+        (_, value) => value,
+    }
+}
+
+/// `value as ty`
+fn cast(value: Value, ty: &ValueFlag) -> Value {
+    let unsigned = |value: &Value| matches!(value, Value::Int(kind, _) if !kind.is_signed());
+    match ty {
+        ty if ty.int_kind().is_some() => {
+            let kind = ty.int_kind().unwrap();
+            match value {
+                Value::Float(x) => Value::int(kind, x as i64),
+                Value::Pointer(ptr) => Value::int(kind, ptr.address() as i64),
+                value => Value::int(kind, value.as_int().unwrap_or_default()),
+            }
+        }
+        ValueFlag::Char => match value {
+            Value::Float(x) => Value::Char(x as u8),
+            value => Value::Char(value.as_int().unwrap_or_default() as u8),
+        },
+        ValueFlag::Boolean => Value::Bool(value.as_int().unwrap_or_default() != 0),
+        ValueFlag::Float => match value {
+            Value::Float(x) => Value::Float(x),
+            value if unsigned(&value) => Value::Float(value.as_int().unwrap() as u64 as f32 as f64),
+            value => Value::Float(value.as_int().unwrap_or_default() as f32 as f64),
+        },
+        ValueFlag::Pointer(pointee) => match value {
+            Value::Pointer(Pointer::Cell(cell)) => Value::Pointer(Pointer::Cell(cell)),
+            Value::Pointer(Pointer::Raw(address, _)) => {
+                Value::Pointer(Pointer::Raw(address, Rc::new((**pointee).clone())))
+            }
+            value => Value::Pointer(Pointer::Raw(
+                value.as_int().unwrap_or_default() as usize,
+                Rc::new((**pointee).clone()),
+            )),
+        },
+        _ => value,
+    }
+}
+
+/// a binary operation, or the message of the panic it causes
+fn binary(instr: Instr, lhs: Value, rhs: Value) -> Result<Value, String> {
+    match (lhs, rhs) {
+        (Value::Int(a_kind, a), Value::Int(b_kind, b)) => {
+            // integer literals are `int`s when their type isn't known
+            let kind = if a_kind == b_kind || b_kind == IntKind::I32 {
+                a_kind
+            } else {
+                b_kind
+            };
+            let (a, b) = (truncate(kind, a), truncate(kind, b));
+            integer(instr, kind, a, b)
+        }
+        (Value::Float(a), Value::Float(b)) => Ok(match instr {
+            Instr::Add => Value::Float((a + b) as f32 as f64),
+            Instr::Sub => Value::Float((a - b) as f32 as f64),
+            Instr::Mul => Value::Float((a * b) as f32 as f64),
+            Instr::Div => Value::Float((a / b) as f32 as f64),
+            Instr::Mod => Value::Float((a % b) as f32 as f64),
+            Instr::Pow => Value::Float(a.powf(b) as f32 as f64),
+            instr => Value::Bool(compare(instr, a.partial_cmp(&b))),
+        }),
+        (Value::String(a), Value::String(b)) if instr == Instr::Add => {
+            Ok(Value::string(&format!("{}{}", a, b)))
+        }
+        (Value::String(a), Value::String(b)) => Ok(Value::Bool(compare(instr, Some(a.cmp(&b))))),
+        (Value::Char(a), Value::Char(b)) => Ok(Value::Bool(compare(instr, Some(a.cmp(&b))))),
+        (Value::Bool(a), Value::Bool(b)) if instr == Instr::And => Ok(Value::Bool(a && b)),
+        (Value::Bool(a), Value::Bool(b)) if instr == Instr::Or => Ok(Value::Bool(a || b)),
+        (a, b) => match instr {
+            Instr::Eq => Ok(Value::Bool(a == b)),
+            Instr::Ne => Ok(Value::Bool(a != b)),
+            instr => Err(format!("{:?} of {} and {}", instr, a, b)),
+        },
+    }
+}
+
+fn integer(instr: Instr, kind: IntKind, a: i64, b: i64) -> Result<Value, String> {
+    let unsigned = !kind.is_signed();
+    let ordering = if unsigned {
+        (a as u64).cmp(&(b as u64))
+    } else {
+        a.cmp(&b)
+    };
+    Ok(match instr {
+        Instr::Add => Value::int(kind, a.wrapping_add(b)),
+        Instr::Sub => Value::int(kind, a.wrapping_sub(b)),
+        Instr::Mul => Value::int(kind, a.wrapping_mul(b)),
+        Instr::Div | Instr::Mod if b == 0 => return Err("division by zero".to_string()),
+        Instr::Div if unsigned => Value::int(kind, ((a as u64) / (b as u64)) as i64),
+        Instr::Mod if unsigned => Value::int(kind, ((a as u64) % (b as u64)) as i64),
+        Instr::Div => Value::int(kind, a.wrapping_div(b)),
+        Instr::Mod => Value::int(kind, a.wrapping_rem(b)),
+        Instr::Pow if b < 0 && !unsigned => {
+            return Err("integer power with a negative exponent".to_string())
+        }
+        Instr::Pow => Value::int(kind, a.wrapping_pow(b as u32)),
+        instr => Value::Bool(compare(instr, Some(ordering))),
+    })
+}
+
+fn compare(instr: Instr, ordering: Option<std::cmp::Ordering>) -> bool {
+    use std::cmp::Ordering::*;
+    match (instr, ordering) {
+        (Instr::Eq, ordering) => ordering == Some(Equal),
+        (Instr::Ne, ordering) => ordering != Some(Equal),
+        (Instr::Lt, ordering) => ordering == Some(Less),
+        (Instr::Le, ordering) => matches!(ordering, Some(Less | Equal)),
+        (Instr::Gt, ordering) => ordering == Some(Greater),
+        (Instr::Ge, ordering) => matches!(ordering, Some(Greater | Equal)),
+        _ => false,
+    }
+}
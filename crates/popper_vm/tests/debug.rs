@@ -0,0 +1,127 @@
+use popper_ast::IntKind;
+use popper_flag::ValueFlag;
+use popper_parser::parser::try_parse;
+use popper_vm::{Debugger, Local, Panic, StackFrame, Value, Vm};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const SOURCE: &str = r#"
+struct Point {
+    x = int,
+    y = int
+}
+
+func norm(p: struct Point): int {
+    let s = p.x * p.x;
+    return s + p.y * p.y;
+}
+
+func main(): int {
+    let p = init Point { x = 3, y = 4 };
+    let t = (1, true);
+    let n = norm(p);
+    return n / 0;
+}
+"#;
+
+/// where the program stopped, with its calls and the variables of the innermost one
+#[derive(Default)]
+struct Stops {
+    statements: Vec<(Vec<StackFrame>, Vec<Local>)>,
+    panic: Option<(Panic, usize)>,
+}
+
+struct Recorder(Rc<RefCell<Stops>>);
+
+impl Debugger for Recorder {
+    fn statement(&mut self, vm: &mut Vm) {
+        let stop = (vm.backtrace(), vm.locals(0));
+        self.0.borrow_mut().statements.push(stop);
+    }
+
+    fn panic(&mut self, vm: &mut Vm, panic: &Panic) {
+        self.0.borrow_mut().panic = Some((panic.clone(), vm.depth()));
+    }
+}
+
+fn int(x: i64) -> Value {
+    Value::Int(IntKind::I32, x)
+}
+
+fn run() -> (Vm, Stops) {
+    let stmts = try_parse(SOURCE).expect("valid source");
+    let module = popper_vm::compile(&stmts, "debug.pop", SOURCE).expect("supported program");
+    let stops = Rc::new(RefCell::new(Stops::default()));
+    let mut vm = Vm::new(module)
+        .unwrap()
+        .with_debugger(Box::new(Recorder(stops.clone())));
+    assert!(vm.run_main().is_err());
+    let stops = stops.take();
+    (vm, stops)
+}
+
+#[test]
+fn statements() {
+    let (_, stops) = run();
+    let lines: Vec<_> = stops
+        .statements
+        .iter()
+        .map(|(frames, _)| (frames[0].function.as_str(), frames[0].line))
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            ("main", 13),
+            ("main", 14),
+            ("main", 15),
+            ("norm", 8),
+            ("norm", 9),
+            ("main", 16)
+        ]
+    );
+    // the caller is at the call
+    let (frames, _) = &stops.statements[3];
+    assert_eq!(frames[1].line, 15);
+    assert_eq!(frames[1].column, 13);
+}
+
+#[test]
+fn locals() {
+    let (vm, stops) = run();
+    let point = vm.module().struct_layout("Point").unwrap();
+    let p = Value::Struct(point, vec![int(3), int(4)].into());
+
+    // a variable is in scope after its statement
+    let (_, locals) = &stops.statements[1];
+    assert_eq!(
+        locals,
+        &[Local {
+            name: "p".to_string(),
+            ty: ValueFlag::StructInstance("Point".to_string()),
+            value: p.clone(),
+        }]
+    );
+    let (_, locals) = &stops.statements[2];
+    assert_eq!(
+        locals[1].ty,
+        ValueFlag::Tuple(vec![ValueFlag::Integer, ValueFlag::Boolean])
+    );
+    // an argument has the type it's declared with
+    let (_, locals) = &stops.statements[4];
+    assert_eq!(locals[0].ty, ValueFlag::Struct("Point".to_string()));
+    assert_eq!((locals[1].name.as_str(), &locals[1].value), ("s", &int(9)));
+
+    assert_eq!(
+        vm.children(&p),
+        [("x".to_string(), int(3)), ("y".to_string(), int(4))]
+    );
+}
+
+#[test]
+fn panics() {
+    let (_, stops) = run();
+    let (panic, depth) = stops.panic.expect("a panic");
+    assert_eq!(panic.message, "division by zero");
+    // the calls aren't unwound yet
+    assert_eq!(depth, 1);
+}
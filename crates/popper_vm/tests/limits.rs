@@ -0,0 +1,178 @@
+use popper_ast::IntKind;
+use popper_parser::parser::try_parse;
+use popper_vm::{HostFunction, Limit, LimitError, Limits, Module, Value, Vm, VmError};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+const SOURCE: &str = r#"
+extern {
+    func abs(x: int): int,
+    func popper_std_env_var(name: string): string,
+    func popper_std_sleep_ms(ms: int): int
+};
+
+func count(n: int): int {
+    let i = 0;
+    while i lt n {
+        i = i + 1;
+    }
+    return i;
+}
+
+func forever(): int {
+    while true {
+        popper_std_sleep_ms(1);
+    }
+    return 0;
+}
+
+func grow(n: int): int {
+    let v: vec<string> = [];
+    let i = 0;
+    while i lt n {
+        push(v, "element");
+        i = i + 1;
+    }
+    return len(v);
+}
+
+func variable(name: string): string {
+    return popper_std_env_var(name);
+}
+
+func inner(): int {
+    return 1;
+}
+
+func middle(): int {
+    return inner();
+}
+
+func outer(): int {
+    return middle();
+}
+
+func distance(x: int): int {
+    return abs(x);
+}
+
+func double(n: int): int {
+    let s = "abcd";
+    let i = 0;
+    while i lt n {
+        s = s + s;
+        i = i + 1;
+    }
+    return len(s);
+}
+"#;
+
+fn module() -> Module {
+    let stmts = try_parse(SOURCE).expect("valid source");
+    popper_vm::compile(&stmts, "limits.pop", SOURCE).expect("supported program")
+}
+
+fn limited(limits: Limits) -> Vm {
+    Vm::new(module())
+        .expect("declared foreign functions")
+        .with_limits(limits)
+}
+
+fn int(x: i64) -> Value {
+    Value::Int(IntKind::I32, x)
+}
+
+fn limit(result: Result<Value, VmError>) -> Limit {
+    match result {
+        Err(VmError::Limit(err)) => err.limit,
+        result => panic!("expected a limit, got {:?}", result),
+    }
+}
+
+#[test]
+fn instructions() {
+    let mut vm = limited(Limits::default().with_instructions(1000));
+    assert_eq!(vm.call("count", vec![int(10)]), Ok(int(10)));
+    let Err(VmError::Limit(LimitError { limit, location })) = vm.call("count", vec![int(1000)])
+    else {
+        panic!("too many instructions");
+    };
+    assert_eq!(limit, Limit::Instructions(1000));
+    assert_eq!(location.map(|x| x.0), Some("limits.pop".to_string()));
+    // each call from the host has its own instructions, and the VM goes on
+    assert_eq!(vm.call("count", vec![int(10)]), Ok(int(10)));
+}
+
+#[test]
+fn timeout() {
+    let timeout = Duration::from_millis(50);
+    let limits = Limits::default()
+        .with_timeout(timeout)
+        .with_externs(["popper_std_sleep_ms"]);
+    let mut vm = limited(limits);
+    let start = Instant::now();
+    assert_eq!(limit(vm.call("forever", vec![])), Limit::Timeout(timeout));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn heap() {
+    let mut vm = limited(Limits::default().with_heap(64 * 1024));
+    assert_eq!(vm.call("grow", vec![int(100)]), Ok(int(100)));
+    assert_eq!(
+        limit(vm.call("grow", vec![int(100_000)])),
+        Limit::Heap(64 * 1024)
+    );
+    // strings count too
+    assert_eq!(vm.call("double", vec![int(4)]), Ok(int(64)));
+    assert_eq!(
+        limit(vm.call("double", vec![int(30)])),
+        Limit::Heap(64 * 1024)
+    );
+}
+
+#[test]
+fn depth() {
+    let mut vm = limited(Limits::default().with_depth(2));
+    assert_eq!(vm.call("middle", vec![]), Ok(int(1)));
+    let Err(VmError::Limit(err)) = vm.call("outer", vec![]) else {
+        panic!("too many calls");
+    };
+    assert_eq!(
+        err.to_string(),
+        "limit at limits.pop:42:12: nested more than 2 calls"
+    );
+}
+
+#[test]
+fn externs() {
+    let mut vm = limited(Limits::default().with_externs(["abs"]));
+    assert_eq!(vm.call("distance", vec![int(-5)]), Ok(int(5)));
+    assert_eq!(
+        limit(vm.call("variable", vec![Value::string("HOME")])),
+        Limit::Extern("popper_std_env_var".to_string())
+    );
+
+    let mut vm = limited(Limits::default().with_externs(Vec::<String>::new()));
+    assert_eq!(
+        limit(vm.call("distance", vec![int(-5)])),
+        Limit::Extern("abs".to_string())
+    );
+
+    // none when another limit is set, but the functions of the host
+    let limits = Limits::default().with_depth(10);
+    let mut vm = limited(limits.clone());
+    assert_eq!(
+        limit(vm.call("distance", vec![int(-5)])),
+        Limit::Extern("abs".to_string())
+    );
+    let abs: HostFunction = Rc::new(|_: &mut Vm, _: &[Value]| Ok(int(7)));
+    let host = HashMap::from([("abs".to_string(), abs)]);
+    let mut vm = Vm::with_host(module(), host).unwrap().with_limits(limits);
+    assert_eq!(vm.call("distance", vec![int(-5)]), Ok(int(7)));
+    assert_eq!(
+        limit(vm.call("variable", vec![Value::string("HOME")])),
+        Limit::Extern("popper_std_env_var".to_string())
+    );
+}
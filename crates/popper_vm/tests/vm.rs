@@ -0,0 +1,314 @@
+use popper_ast::IntKind;
+use popper_parser::parser::try_parse;
+use popper_vm::bytecode::{Constant, Function, Instr};
+use popper_vm::{HostFunction, Module, Panic, Value, Vm, VmError};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+const SOURCE: &str = r#"
+struct Point {
+    x = int,
+    y = int
+}
+
+extern {
+    func abs(x: int): int,
+    func popper_std_env_var(name: string): string
+};
+
+func sum_to(n: int): int {
+    let total = 0;
+    let i = 1;
+    while i le n {
+        total = total + i;
+        i = i + 1;
+    }
+    return total;
+}
+
+func max(a: int, b: int): int {
+    if a gt b {
+        return a;
+    } else {
+        return b;
+    }
+}
+
+func moved(p: struct Point, dx: int): struct Point {
+    p.x = p.x + dx;
+    return p;
+}
+
+func swap(t: (int, string)): (string, int) {
+    return (t.1, t.0);
+}
+
+func evens(n: int): vec<int> {
+    let v: vec<int> = [];
+    let i = 0;
+    while i lt n {
+        let twice = i / 2 * 2;
+        if twice eq i {
+            push(v, i);
+        }
+        i = i + 1;
+    }
+    return v;
+}
+
+func middle(v: vec<int>): [int] {
+    return v:1..3;
+}
+
+func total(v: vec<int>): int {
+    let t = 0;
+    for x in v {
+        t = t + x;
+    }
+    return t;
+}
+
+func increment(): int {
+    let x = 41;
+    let p = ref x;
+    @p = @p + 1;
+    return x;
+}
+
+func at(v: vec<int>, i: int): int {
+    return v:i;
+}
+
+func divide(a: int, b: int): int {
+    return a / b;
+}
+
+func lowest(): int {
+    let a: i8 = -128;
+    return a as int - 1;
+}
+
+func distance(n: int): int {
+    return abs(n);
+}
+
+func variable(name: string): string {
+    return popper_std_env_var(name);
+}
+
+func main(): int {
+    return sum_to(4);
+}
+
+extern {
+    func exit(code: int): unit
+};
+
+func quit(code: int): int {
+    exit(code);
+    return 0;
+}
+"#;
+
+fn module() -> Module {
+    let stmts = try_parse(SOURCE).expect("valid source");
+    popper_vm::compile(&stmts, "vm.pop", SOURCE).expect("supported program")
+}
+
+fn vm() -> Vm {
+    Vm::new(module()).expect("declared foreign functions")
+}
+
+fn int(x: i64) -> Value {
+    Value::Int(IntKind::I32, x)
+}
+
+fn ints(xs: &[i64]) -> Vec<Value> {
+    xs.iter().map(|x| int(*x)).collect()
+}
+
+#[test]
+fn control_flow() {
+    let mut vm = vm();
+    assert_eq!(vm.call("sum_to", vec![int(10)]), Ok(int(55)));
+    assert_eq!(vm.call("max", vec![int(3), int(7)]), Ok(int(7)));
+    assert_eq!(vm.call("max", vec![int(9), int(7)]), Ok(int(9)));
+    assert_eq!(vm.call("lowest", vec![]), Ok(int(-129)));
+    assert_eq!(vm.run_main(), Ok(10));
+}
+
+#[test]
+fn aggregates() {
+    let mut vm = vm();
+    let point = vm.module().struct_layout("Point").unwrap();
+    let p = Value::Struct(point, ints(&[1, 2]).into());
+    assert_eq!(
+        vm.call("moved", vec![p.clone(), int(3)]),
+        Ok(Value::Struct(point, ints(&[4, 2]).into()))
+    );
+    // the argument is a copy
+    assert_eq!(p, Value::Struct(point, ints(&[1, 2]).into()));
+    assert_eq!(
+        vm.call(
+            "swap",
+            vec![Value::Tuple(vec![int(1), Value::string("a")].into())]
+        ),
+        Ok(Value::Tuple(vec![Value::string("a"), int(1)].into()))
+    );
+}
+
+#[test]
+fn sequences() {
+    let mut vm = vm();
+    let evens = vm.call("evens", vec![int(7)]).unwrap();
+    assert_eq!(evens, Value::vec(ints(&[0, 2, 4, 6])));
+    assert_eq!(vm.call("total", vec![evens.clone()]), Ok(int(12)));
+    let middle = vm.call("middle", vec![evens]).unwrap();
+    assert_eq!(middle.elements(), Some(ints(&[2, 4])));
+    assert_eq!(vm.call("increment", vec![]), Ok(int(42)));
+}
+
+#[test]
+fn panics() {
+    let mut vm = vm();
+    let v = Value::vec(ints(&[1, 2]));
+    assert_eq!(
+        vm.call("at", vec![v, int(5)]),
+        Err(VmError::Panic(Panic {
+            message: "index 5 out of range for length 2".to_string(),
+            location: Some(("vm.pop".to_string(), 72, 12)),
+        }))
+    );
+    let Err(VmError::Panic(panic)) = vm.call("divide", vec![int(1), int(0)]) else {
+        panic!("division by zero");
+    };
+    assert_eq!(panic.to_string(), "panic at vm.pop:76:12: division by zero");
+    // the VM can be used after a panic
+    assert_eq!(vm.call("divide", vec![int(9), int(2)]), Ok(int(4)));
+}
+
+#[test]
+fn foreign_functions() {
+    let mut vm = vm();
+    // from libc
+    assert_eq!(vm.call("distance", vec![int(-5)]), Ok(int(5)));
+    // implemented by the VM
+    assert_eq!(
+        vm.call("variable", vec![Value::string("POPPER_VM_UNSET_VARIABLE")]),
+        Ok(Value::string(""))
+    );
+    // stops the program, not the host
+    assert_eq!(vm.call("quit", vec![int(3)]), Err(VmError::Exit(3)));
+    assert_eq!(vm.call("distance", vec![int(-5)]), Ok(int(5)));
+}
+
+#[test]
+fn host_functions() {
+    let shout: HostFunction = Rc::new(|vm, args| match &args[0] {
+        Value::String(s) if s.is_empty() => Err(vm.panic("nothing to shout")),
+        Value::String(s) => Ok(Value::string(&s.to_uppercase())),
+        _ => unreachable!(),
+    });
+    // instead of the function of the runtime, and of the one of C
+    let host = HashMap::from([
+        ("popper_std_env_var".to_string(), shout),
+        (
+            "abs".to_string(),
+            Rc::new(|_: &mut Vm, _: &[Value]| Ok(int(7))) as HostFunction,
+        ),
+    ]);
+    let mut vm = Vm::with_host(module(), host).unwrap();
+    assert_eq!(
+        vm.call("variable", vec![Value::string("ab")]),
+        Ok(Value::string("AB"))
+    );
+    assert_eq!(vm.call("distance", vec![int(-5)]), Ok(int(7)));
+    let Err(VmError::Panic(panic)) = vm.call("variable", vec![Value::string("")]) else {
+        panic!("a panic of the host function");
+    };
+    assert_eq!(panic.message, "nothing to shout");
+}
+
+#[test]
+fn bytecode_files() {
+    let module = module();
+    let loaded = Module::from_bytes(&module.to_bytes()).expect("valid bytecode");
+    assert_eq!(loaded, module);
+    let mut vm = Vm::new(loaded).unwrap();
+    assert_eq!(vm.call("sum_to", vec![int(3)]), Ok(int(6)));
+
+    assert!(matches!(
+        Module::from_bytes(b"not bytecode"),
+        Err(VmError::InvalidBytecode(_))
+    ));
+    let mut old = module.to_bytes();
+    old[4] = 0;
+    assert!(matches!(
+        Module::from_bytes(&old),
+        Err(VmError::InvalidBytecode(_))
+    ));
+}
+
+/// `module` with the code of `sum_to` changed, written and read back
+fn corrupted(f: impl Fn(&mut Vec<Instr>)) -> Result<Module, VmError> {
+    let mut module = module();
+    let index = module.function("sum_to").unwrap();
+    f(&mut module.functions[index as usize].code);
+    Module::from_bytes(&module.to_bytes())
+}
+
+#[test]
+fn invalid_bytecode() {
+    let invalid = |module: Result<Module, VmError>| match module {
+        Err(VmError::InvalidBytecode(msg)) => msg,
+        module => panic!("invalid bytecode expected, got {:?}", module),
+    };
+    let msg = invalid(corrupted(|code| code[0] = Instr::Const(u32::MAX)));
+    assert_eq!(
+        msg,
+        format!("function `sum_to`: no constant {} at 0", u32::MAX)
+    );
+    let msg = invalid(corrupted(|code| code.push(Instr::Jump(1000))));
+    assert!(msg.contains("no instruction 1000"), "{}", msg);
+    let msg = invalid(corrupted(|code| code.insert(0, Instr::Pop)));
+    assert_eq!(msg, "function `sum_to`: the stack runs out at 0");
+    // the `return` at the end and the one of the `return` statement
+    let msg = invalid(corrupted(|code| code.truncate(code.len() - 3)));
+    assert!(msg.contains("ends without a `return`"), "{}", msg);
+    let msg = invalid(corrupted(|code| code[0] = Instr::Load(100)));
+    assert!(msg.contains("no local 100"), "{}", msg);
+    let msg = invalid(corrupted(|code| code[0] = Instr::Call(0, 5)));
+    assert!(msg.contains("called with 5 argument(s)"), "{}", msg);
+
+    // the kinds of the values are checked when the code runs
+    let module = Module {
+        constants: vec![Constant::Int(IntKind::I32, 1)],
+        files: vec!["len.pop".to_string()],
+        functions: vec![Function {
+            name: "main".to_string(),
+            arity: 0,
+            is_var_args: false,
+            locals: 0,
+            code: vec![Instr::Const(0), Instr::Len, Instr::Return],
+            file: 0,
+            locations: vec![],
+            statements: vec![],
+            variables: vec![],
+        }],
+        ..Module::default()
+    };
+    let mut vm = Vm::new(Module::from_bytes(&module.to_bytes()).unwrap()).unwrap();
+    assert_eq!(
+        vm.call("main", vec![]),
+        Err(VmError::InvalidBytecode("1 has no length".to_string()))
+    );
+}
+
+#[test]
+fn disassembly() {
+    let text = module().to_string();
+    assert!(text.contains("0  Point { x: int, y: int }"));
+    assert!(text.contains("abs(int): int"));
+    assert!(text.contains("fn sum_to: 1 argument(s), 3 local(s), in vm.pop"));
+    assert!(text.contains("call_foreign 0, 1    ; abs"));
+}
@@ -0,0 +1,1012 @@
+use std::collections::{HashMap, HashSet};
+
+/// words of Popper that can't name a field or an argument
+const KEYWORDS: &[&str] = &[
+    "arg", "as", "bool", "break", "char", "else", "eq", "export", "extern", "external", "false",
+    "for", "func", "ge", "gt", "i16", "i32", "i64", "i8", "if", "import", "in", "init", "int",
+    "le", "let", "lt", "ne", "pub", "ref", "return", "string", "struct", "test", "true", "u16",
+    "u32", "u64", "u8", "unit", "usize", "vec", "while",
+];
+
+/// words of C declarations that don't change the type
+const QUALIFIERS: &[&str] = &[
+    "const",
+    "volatile",
+    "restrict",
+    "__restrict",
+    "__restrict__",
+    "extern",
+    "static",
+    "inline",
+    "__inline",
+    "__inline__",
+    "register",
+    "_Noreturn",
+    "__extension__",
+    "__THROW",
+    "__wur",
+];
+
+/// words followed by a parenthesized group that doesn't change the type
+const ANNOTATIONS: &[&str] = &[
+    "__attribute__",
+    "__attribute",
+    "__declspec",
+    "__asm__",
+    "__asm",
+    "asm",
+    "__nonnull",
+    "_Alignas",
+];
+
+///
+/// bindgen is used to translate the declarations of a C header to Popper: functions
+/// become an `extern` block, structs become structs, and the enumerators and the
+/// integer `#define`s become functions returning their value.
+/// Popper has no `float` nor `void`: the declarations using `float` and `double`, and
+/// the functions returning `void`, are left out.
+/// The header isn't preprocessed, the headers it includes aren't read
+/// # Arguments
+/// * `header` - content of the header
+/// * `header_name` - name of the header, given in the module documentation
+///
+/// return: the Popper source of the bindings
+pub fn bindgen(header: &str, header_name: &str) -> String {
+    let mut bindings = Bindings::default();
+    let tokens = bindings.preprocess(header);
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        bindings: &mut bindings,
+    };
+    parser.declarations();
+    bindings.source(header_name)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(String),
+    Str,
+    Punct(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum CType {
+    Void,
+    Bool,
+    Float,
+    /// `char`, without `signed` nor `unsigned`
+    Char,
+    /// Popper name of an integer type
+    Int(&'static str),
+    Struct(String),
+    /// a union, or a struct whose definition isn't in the header
+    Opaque(String),
+    Pointer(Box<CType>),
+    Array(Box<CType>, Option<usize>),
+    Function,
+}
+
+struct Field {
+    name: String,
+    ty: CType,
+}
+
+/// parameters of a function, unnamed ones have no name
+struct Params {
+    args: Vec<(Option<String>, CType)>,
+    is_var_args: bool,
+}
+
+struct Function {
+    name: String,
+    params: Params,
+    return_type: CType,
+}
+
+/// declarations collected from the header, in order
+#[derive(Default)]
+struct Bindings {
+    constants: Vec<(String, i64)>,
+    constant_values: HashMap<String, i64>,
+    typedefs: HashMap<String, CType>,
+    /// every struct with a body, the ones which can't be translated have no fields
+    struct_names: HashSet<String>,
+    structs: Vec<(String, Option<Vec<Field>>)>,
+    functions: Vec<Function>,
+    /// declarations left out, with the reason
+    skipped: Vec<String>,
+    /// macros defined as nothing or as qualifiers, like `#define API extern`
+    qualifiers: HashSet<String>,
+}
+
+impl Bindings {
+    /// remove the comments and the preprocessor lines, keeping the integer `#define`s,
+    /// and split the header in tokens
+    fn preprocess(&mut self, header: &str) -> Vec<Token> {
+        let header = header.replace("\\\r\n", " ").replace("\\\n", " ");
+        let header = strip_comments(&header);
+        let mut tokens = Vec::new();
+        for line in header.lines() {
+            let trimmed = line.trim_start();
+            match trimmed.strip_prefix('#') {
+                Some(directive) => self.directive(directive.trim_start()),
+                None => tokens.extend(tokenize(line)),
+            }
+        }
+        tokens
+    }
+
+    fn directive(&mut self, directive: &str) {
+        let Some(define) = directive.strip_prefix("define") else {
+            return;
+        };
+        let define = define.trim_start();
+        let name_len = define
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(define.len());
+        let (name, value) = define.split_at(name_len);
+        // function-like macros
+        if name.is_empty() || value.starts_with('(') {
+            return;
+        }
+        let tokens = tokenize(value);
+        if tokens.iter().all(|token| {
+            matches!(token, Token::Ident(word) if QUALIFIERS.contains(&word.as_str()) || self.qualifiers.contains(word))
+        }) {
+            self.qualifiers.insert(name.to_string());
+            return;
+        }
+        let mut eval = Eval {
+            tokens: &tokens,
+            pos: 0,
+            constants: &self.constant_values,
+        };
+        if let Some(value) = eval.expr().filter(|_| eval.pos == tokens.len()) {
+            self.add_constant(name, value);
+        }
+    }
+
+    fn add_constant(&mut self, name: &str, value: i64) {
+        if self
+            .constant_values
+            .insert(name.to_string(), value)
+            .is_none()
+        {
+            self.constants.push((name.to_string(), value));
+        }
+    }
+
+    fn skip(&mut self, name: &str, reason: &str) {
+        self.skipped.push(format!("`{}`: {}", name, reason));
+    }
+
+    /// follow the typedefs
+    fn resolve(&self, ty: &CType) -> CType {
+        match ty {
+            CType::Opaque(name) if self.typedefs.contains_key(name) => {
+                self.resolve(&self.typedefs[name])
+            }
+            CType::Opaque(name) if self.struct_names.contains(name) => CType::Struct(name.clone()),
+            CType::Pointer(ty) => CType::Pointer(Box::new(self.resolve(ty))),
+            CType::Array(ty, len) => CType::Array(Box::new(self.resolve(ty)), *len),
+            ty => ty.clone(),
+        }
+    }
+
+    /// Popper type of a C type, or why there is none
+    fn popper_type(&self, ty: &CType, is_argument: bool) -> Result<String, String> {
+        match self.resolve(ty) {
+            // `unit` isn't compiled natively
+            CType::Void => Err("Popper has no `void` type".to_string()),
+            CType::Bool => Ok("bool".to_string()),
+            CType::Char => Ok("char".to_string()),
+            CType::Int(name) => Ok(name.to_string()),
+            CType::Float => Err("Popper has no floating point type".to_string()),
+            CType::Struct(name) if !is_argument => Ok(format!("struct {}", name)),
+            CType::Struct(name) | CType::Opaque(name) => {
+                Err(format!("`{}` can't be passed by value", name))
+            }
+            CType::Function => Err("function types aren't supported".to_string()),
+            CType::Array(elem, Some(len)) if !is_argument => {
+                Ok(format!("[{}: {}]", self.popper_type(&elem, false)?, len))
+            }
+            CType::Array(elem, _) => self.pointer_type(&elem, is_argument),
+            CType::Pointer(ty) => self.pointer_type(&ty, is_argument),
+        }
+    }
+
+    fn pointer_type(&self, ty: &CType, is_argument: bool) -> Result<String, String> {
+        match self.resolve(ty) {
+            // C strings are given as Popper strings
+            CType::Char if is_argument => Ok("string".to_string()),
+            CType::Void | CType::Opaque(_) | CType::Function | CType::Float => {
+                Ok("*u8".to_string())
+            }
+            CType::Struct(name) => Ok(format!("*struct {}", name)),
+            ty => Ok(format!("*{}", self.popper_type(&ty, false)?)),
+        }
+    }
+
+    /// the bindings, formatted
+    fn source(&self, header_name: &str) -> String {
+        let mut source = format!(
+            "//! Bindings of `{}`, generated by `popper bindgen`.\n",
+            header_name
+        );
+        for reason in self.skipped.iter() {
+            source.push_str(&format!("//! left out {}\n", reason));
+        }
+
+        for (name, fields) in self.structs.iter() {
+            let Some(fields) = fields else {
+                continue;
+            };
+            let fields: Result<Vec<String>, String> = fields
+                .iter()
+                .map(|field| {
+                    Ok(format!(
+                        "    pub {} = {}",
+                        identifier(&field.name),
+                        self.popper_type(&field.ty, false)?
+                    ))
+                })
+                .collect();
+            match fields {
+                Ok(fields) if !fields.is_empty() => source.push_str(&format!(
+                    "\npub struct {} {{\n{}\n}}\n",
+                    name,
+                    fields.join(",\n")
+                )),
+                Ok(_) => source.push_str(&format!("\n// `{}` has no fields\n", name)),
+                Err(err) => source.push_str(&format!("\n// `{}` is left out: {}\n", name, err)),
+            }
+        }
+
+        let mut signs = Vec::new();
+        let mut seen = HashSet::new();
+        for Function {
+            name,
+            params,
+            return_type,
+        } in self.functions.iter()
+        {
+            if !seen.insert(name) {
+                continue;
+            }
+            if KEYWORDS.contains(&name.as_str()) {
+                source.push_str(&format!(
+                    "\n// `{}` is left out: it's a Popper keyword\n",
+                    name
+                ));
+                continue;
+            }
+            match self.signature(name, params, return_type) {
+                Ok(sign) => signs.push(sign),
+                Err(err) => source.push_str(&format!("\n// `{}` is left out: {}\n", name, err)),
+            }
+        }
+        if !signs.is_empty() {
+            source.push_str(&format!("\nextern {{\n{}\n}};\n", signs.join(",\n")));
+        }
+
+        for (name, value) in self.constants.iter() {
+            if KEYWORDS.contains(&name.as_str()) || seen.contains(name) {
+                continue;
+            }
+            let ty = if i32::try_from(*value).is_ok() {
+                "int"
+            } else {
+                "i64"
+            };
+            // Popper has no unary minus
+            let value = match *value {
+                i64::MIN => format!("0 - {} - 1", i64::MAX),
+                value if value < 0 => format!("0 - {}", value.unsigned_abs()),
+                value => value.to_string(),
+            };
+            source.push_str(&format!(
+                "\npub func {}(): {} {{\n    return {};\n}}\n",
+                name, ty, value
+            ));
+        }
+        source
+    }
+
+    fn signature(
+        &self,
+        name: &str,
+        params: &Params,
+        return_type: &CType,
+    ) -> Result<String, String> {
+        let mut names = HashSet::new();
+        let mut popper_args = Vec::new();
+        for (i, (arg, ty)) in params.args.iter().enumerate() {
+            let mut arg = arg
+                .as_deref()
+                .map(identifier)
+                .unwrap_or_else(|| format!("arg{}", i));
+            if !names.insert(arg.clone()) {
+                arg = format!("{}{}", arg, i);
+            }
+            popper_args.push(format!("{}: {}", arg, self.popper_type(ty, true)?));
+        }
+        let mut args = popper_args.join(", ");
+        if params.is_var_args {
+            args.push_str(if args.is_empty() { "..." } else { " ..." });
+        }
+        let return_type = match self.resolve(return_type) {
+            CType::Pointer(ty) => self.pointer_type(&ty, false)?,
+            // structs aren't returned by value with the C calling convention either
+            ty => self.popper_type(&ty, true)?,
+        };
+        Ok(format!("    func {}({}): {}", name, args, return_type))
+    }
+}
+
+/// a C name usable as a Popper field or argument
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn strip_comments(source: &str) -> String {
+    let mut out = String::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut last = ' ';
+                for c in chars.by_ref() {
+                    // keep the lines, a directive ends with its line
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    if last == '*' && c == '/' {
+                        break;
+                    }
+                    last = c;
+                }
+                out.push(' ');
+            }
+            ('"', _) | ('\'', _) => {
+                out.push(c);
+                let mut escaped = false;
+                for d in chars.by_ref() {
+                    out.push(d);
+                    if d == c && !escaped {
+                        break;
+                    }
+                    escaped = d == '\\' && !escaped;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+fn tokenize(line: &str) -> Vec<Token> {
+    const PUNCTS: &[&str] = &[
+        "...", "<<", ">>", "{", "}", "(", ")", "[", "]", ";", ",", "*", "=", ":", "-", "+", "~",
+        "|", "&", "^", "!", "/", "%", "<", ">", "?", ".",
+    ];
+    let mut tokens = Vec::new();
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i] as char;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(line[start..i].to_string()));
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.') {
+                i += 1;
+            }
+            tokens.push(Token::Number(line[start..i].to_string()));
+        } else if c == '"' || c == '\'' {
+            i += 1;
+            while i < bytes.len() && bytes[i] as char != c {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            i += 1;
+            tokens.push(Token::Str);
+        } else if let Some(punct) = PUNCTS.iter().find(|x| line[i..].starts_with(**x)) {
+            tokens.push(Token::Punct(punct));
+            i += punct.len();
+        } else {
+            i += line[i..].chars().next().map_or(1, |c| c.len_utf8());
+        }
+    }
+    tokens
+}
+
+/// value of an integer literal: decimal, hexadecimal or octal, with its suffixes
+fn parse_int(literal: &str) -> Option<i64> {
+    let literal = literal.trim_end_matches(['u', 'U', 'l', 'L']);
+    if let Some(hex) = literal
+        .strip_prefix("0x")
+        .or_else(|| literal.strip_prefix("0X"))
+    {
+        u64::from_str_radix(hex, 16).ok().map(|x| x as i64)
+    } else if literal.len() > 1 && literal.starts_with('0') {
+        i64::from_str_radix(&literal[1..], 8).ok()
+    } else {
+        literal.parse().ok()
+    }
+}
+
+/// evaluation of the integer constant expressions of `#define`s and enums
+struct Eval<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    constants: &'a HashMap<String, i64>,
+}
+
+impl Eval<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn expr(&mut self) -> Option<i64> {
+        self.binary(0)
+    }
+
+    /// binary operators, by increasing precedence
+    fn binary(&mut self, level: usize) -> Option<i64> {
+        const LEVELS: &[&[&str]] = &[
+            &["|"],
+            &["^"],
+            &["&"],
+            &["<<", ">>"],
+            &["+", "-"],
+            &["*", "/", "%"],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(Token::Punct(op)) = self.peek() {
+            let op = *op;
+            if !LEVELS[level].contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = match op {
+                "|" => lhs | rhs,
+                "^" => lhs ^ rhs,
+                "&" => lhs & rhs,
+                "<<" => lhs.checked_shl(rhs as u32)?,
+                ">>" => lhs.checked_shr(rhs as u32)?,
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                "/" => lhs.checked_div(rhs)?,
+                _ => lhs.checked_rem(rhs)?,
+            };
+        }
+        Some(lhs)
+    }
+
+    fn unary(&mut self) -> Option<i64> {
+        let token = self.peek()?.clone();
+        self.pos += 1;
+        match token {
+            Token::Punct("-") => self.unary().map(|x| x.wrapping_neg()),
+            Token::Punct("+") => self.unary(),
+            Token::Punct("~") => self.unary().map(|x| !x),
+            Token::Punct("(") => {
+                // casts to an integer type
+                while let Some(Token::Ident(word)) = self.peek() {
+                    if self.constants.contains_key(word) {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+                if self.peek() == Some(&Token::Punct(")")) {
+                    self.pos += 1;
+                    return self.unary();
+                }
+                let value = self.expr()?;
+                (self.peek() == Some(&Token::Punct(")"))).then(|| {
+                    self.pos += 1;
+                    value
+                })
+            }
+            Token::Number(literal) => parse_int(&literal),
+            Token::Ident(name) => self.constants.get(&name).copied(),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    bindings: &'a mut Bindings,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn peek_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn eat_punct(&mut self, punct: &str) -> bool {
+        let found = self.peek_punct(punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Ident(name)) => {
+                let name = name.clone();
+                self.pos += 1;
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    /// skip a group opened by the current token, and the tokens it contains
+    fn skip_group(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Punct("(" | "[" | "{") => depth += 1,
+                Token::Punct(")" | "]" | "}") => depth -= 1,
+                _ => {}
+            }
+            self.pos += 1;
+            if depth <= 0 {
+                break;
+            }
+        }
+    }
+
+    /// skip to the end of the current declaration
+    fn recover(&mut self) {
+        while let Some(token) = self.peek() {
+            match token {
+                Token::Punct(";") => {
+                    self.pos += 1;
+                    break;
+                }
+                Token::Punct("(" | "[" | "{") => self.skip_group(),
+                _ => self.pos += 1,
+            }
+        }
+    }
+
+    fn skip_qualifiers(&mut self) {
+        loop {
+            match self.peek() {
+                Some(Token::Ident(word))
+                    if QUALIFIERS.contains(&word.as_str())
+                        || self.bindings.qualifiers.contains(word) =>
+                {
+                    self.pos += 1
+                }
+                Some(Token::Ident(word)) if ANNOTATIONS.contains(&word.as_str()) => {
+                    self.pos += 1;
+                    if self.peek_punct("(") {
+                        self.skip_group();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn declarations(&mut self) {
+        while self.pos < self.tokens.len() {
+            // `extern "C" {` and its `}`, from C++ guards
+            if self.peek() == Some(&Token::Ident("extern".to_string()))
+                && self.tokens.get(self.pos + 1) == Some(&Token::Str)
+            {
+                self.pos += 2;
+                self.eat_punct("{");
+                continue;
+            }
+            if self.eat_punct("}") || self.eat_punct(";") {
+                continue;
+            }
+            // a macro of another header, like `__BEGIN_DECLS`, before a declaration
+            if matches!(self.peek(), Some(Token::Ident(_)))
+                && self.tokens.get(self.pos + 1) == Some(&Token::Ident("typedef".to_string()))
+            {
+                self.pos += 1;
+            }
+            let start = self.pos;
+            if self.declaration().is_none() {
+                self.pos = start;
+                self.recover();
+            }
+        }
+    }
+
+    fn declaration(&mut self) -> Option<()> {
+        self.skip_qualifiers();
+        let is_typedef = self.peek() == Some(&Token::Ident("typedef".to_string()));
+        if is_typedef {
+            self.pos += 1;
+        }
+        let base = self.specifier()?;
+        if self.eat_punct(";") {
+            return Some(());
+        }
+        loop {
+            let (name, ty, params) = self.declarator(base.clone())?;
+            self.skip_qualifiers();
+            let name = name?;
+            // attribute macros of another header, like `__wur` or `__nonnull ((1))`
+            if params.is_some() {
+                while let Some(Token::Ident(_)) = self.peek() {
+                    self.pos += 1;
+                    if self.peek_punct("(") {
+                        self.skip_group();
+                    }
+                }
+            }
+            // anything else comes from a macro of another header
+            if !["{", ";", ",", "="].iter().any(|x| self.peek_punct(x)) {
+                return None;
+            }
+            if is_typedef {
+                self.bindings.typedefs.insert(name, ty);
+            } else if self.peek_punct("{") {
+                // a definition, in the header: usually `static inline`, without a symbol
+                self.skip_group();
+                self.bindings
+                    .skip(&name, "functions defined in the header aren't imported");
+                return Some(());
+            } else if let Some(params) = params {
+                self.bindings.functions.push(Function {
+                    name,
+                    params,
+                    return_type: ty,
+                });
+            } else {
+                // variables can't be declared by Popper
+                self.bindings.skip(&name, "variables can't be imported");
+            }
+            if self.eat_punct("=") {
+                while !self.peek_punct(",") && !self.peek_punct(";") && self.peek().is_some() {
+                    self.skip_group();
+                }
+            }
+            if self.eat_punct(";") {
+                return Some(());
+            }
+            if !self.eat_punct(",") {
+                return None;
+            }
+        }
+    }
+
+    /// the type before the declarators: `unsigned long`, `struct point`, `size_t`...
+    fn specifier(&mut self) -> Option<CType> {
+        let mut words: Vec<String> = Vec::new();
+        let mut ty = None;
+        loop {
+            self.skip_qualifiers();
+            let Some(Token::Ident(word)) = self.peek().cloned() else {
+                break;
+            };
+            match word.as_str() {
+                "struct" | "union" | "enum" if ty.is_none() && words.is_empty() => {
+                    self.pos += 1;
+                    ty = Some(self.tagged(&word)?);
+                }
+                "void" | "char" | "short" | "int" | "long" | "signed" | "unsigned" | "float"
+                | "double" | "_Bool" | "bool" | "__int128"
+                    if ty.is_none() =>
+                {
+                    self.pos += 1;
+                    words.push(word);
+                }
+                // a typedef name, when there is no type yet
+                _ if ty.is_none() && words.is_empty() => {
+                    self.pos += 1;
+                    ty = Some(named_type(&word));
+                }
+                // the name before was a macro of another header, like `ZEXTERN int f();`
+                "void" | "char" | "short" | "int" | "long" | "signed" | "unsigned" | "struct"
+                | "union" | "enum"
+                    if matches!(&ty, Some(CType::Opaque(name)) if !self.bindings.typedefs.contains_key(name)) =>
+                {
+                    ty = None;
+                }
+                _ => break,
+            }
+        }
+        match ty {
+            Some(ty) => Some(ty),
+            None if !words.is_empty() => builtin_type(&words),
+            None => None,
+        }
+    }
+
+    /// `struct name`, `union name` or `enum name`, with their body
+    fn tagged(&mut self, kind: &str) -> Option<CType> {
+        self.skip_qualifiers();
+        let tag = self.ident();
+        if !self.peek_punct("{") {
+            let tag = tag?;
+            return Some(match kind {
+                "enum" => CType::Int("int"),
+                _ => CType::Opaque(tag),
+            });
+        }
+        match kind {
+            "enum" => {
+                self.enum_body()?;
+                Some(CType::Int("int"))
+            }
+            "union" => {
+                self.skip_group();
+                let tag = tag.unwrap_or_else(|| "union".to_string());
+                self.bindings.skip(&tag, "unions aren't supported");
+                Some(CType::Opaque(tag))
+            }
+            _ => {
+                let fields = self.struct_body();
+                // anonymous structs are named after their typedef
+                let name = match tag {
+                    Some(tag) => tag,
+                    None => {
+                        let Some(Token::Ident(name)) = self.peek().cloned() else {
+                            return None;
+                        };
+                        name
+                    }
+                };
+                self.bindings.struct_names.insert(name.clone());
+                if fields.is_none() {
+                    self.bindings
+                        .skip(&name, "bit fields and nested structs aren't supported");
+                }
+                self.bindings.structs.push((name.clone(), fields));
+                Some(CType::Struct(name))
+            }
+        }
+    }
+
+    fn enum_body(&mut self) -> Option<()> {
+        self.eat_punct("{");
+        let mut next = 0;
+        while !self.eat_punct("}") {
+            let name = self.ident()?;
+            let value = if self.eat_punct("=") {
+                let start = self.pos;
+                while !self.peek_punct(",") && !self.peek_punct("}") {
+                    self.peek()?;
+                    self.skip_group();
+                }
+                let mut eval = Eval {
+                    tokens: &self.tokens[start..self.pos],
+                    pos: 0,
+                    constants: &self.bindings.constant_values,
+                };
+                eval.expr()?
+            } else {
+                next
+            };
+            self.bindings.add_constant(&name, value);
+            next = value.wrapping_add(1);
+            self.eat_punct(",");
+        }
+        Some(())
+    }
+
+    /// fields of a struct, none when one of them can't be translated
+    fn struct_body(&mut self) -> Option<Vec<Field>> {
+        let end = {
+            let start = self.pos;
+            self.skip_group();
+            let end = self.pos;
+            self.pos = start + 1;
+            end
+        };
+        let mut fields = Some(Vec::new());
+        while self.pos < end - 1 {
+            let start = self.pos;
+            match self.field_declaration() {
+                Some(mut declared) => {
+                    if let Some(fields) = fields.as_mut() {
+                        fields.append(&mut declared)
+                    }
+                }
+                None => {
+                    fields = None;
+                    self.pos = start;
+                    self.recover();
+                }
+            }
+        }
+        self.pos = end;
+        fields
+    }
+
+    fn field_declaration(&mut self) -> Option<Vec<Field>> {
+        let base = self.specifier()?;
+        // nested structs and unions without a name
+        if self.peek_punct(";") {
+            return None;
+        }
+        let mut fields = Vec::new();
+        loop {
+            let (name, ty, params) = self.declarator(base.clone())?;
+            if params.is_some() || self.peek_punct(":") {
+                return None;
+            }
+            self.skip_qualifiers();
+            fields.push(Field { name: name?, ty });
+            if self.eat_punct(";") {
+                return Some(fields);
+            }
+            if !self.eat_punct(",") {
+                return None;
+            }
+        }
+    }
+
+    /// a declarator: its name, its type and the parameters when it declares a function
+    fn declarator(&mut self, base: CType) -> Option<(Option<String>, CType, Option<Params>)> {
+        let mut ty = base;
+        loop {
+            self.skip_qualifiers();
+            if !self.eat_punct("*") {
+                break;
+            }
+            ty = CType::Pointer(Box::new(ty));
+        }
+        self.skip_qualifiers();
+
+        // `(*name)(args)`: a pointer to a function
+        if self.peek_punct("(") && self.tokens.get(self.pos + 1) == Some(&Token::Punct("*")) {
+            self.pos += 2;
+            while self.eat_punct("*") {}
+            self.skip_qualifiers();
+            let name = self.ident();
+            while !self.peek_punct(")") {
+                self.peek()?;
+                self.skip_group();
+            }
+            self.pos += 1;
+            while self.peek_punct("(") || self.peek_punct("[") {
+                self.skip_group();
+            }
+            return Some((name, CType::Pointer(Box::new(CType::Function)), None));
+        }
+
+        let name = self.ident();
+        let mut params = None;
+        let mut lens = Vec::new();
+        loop {
+            if self.peek_punct("(") {
+                params = Some(self.params()?);
+            } else if self.eat_punct("[") {
+                let start = self.pos;
+                while !self.peek_punct("]") {
+                    self.peek()?;
+                    self.skip_group();
+                }
+                let mut eval = Eval {
+                    tokens: &self.tokens[start..self.pos],
+                    pos: 0,
+                    constants: &self.bindings.constant_values,
+                };
+                lens.push(eval.expr().and_then(|x| usize::try_from(x).ok()));
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        // `int a[2][3]` is an array of 2 arrays of 3 ints
+        for len in lens.into_iter().rev() {
+            ty = CType::Array(Box::new(ty), len);
+        }
+        Some((name, ty, params))
+    }
+
+    fn params(&mut self) -> Option<Params> {
+        self.eat_punct("(");
+        let mut params = Vec::new();
+        let mut is_var_args = false;
+        while !self.eat_punct(")") {
+            if self.eat_punct("...") {
+                is_var_args = true;
+                continue;
+            }
+            let base = self.specifier()?;
+            let (name, ty, fn_params) = self.declarator(base)?;
+            let ty = match fn_params {
+                Some(_) => CType::Pointer(Box::new(CType::Function)),
+                // arrays are passed as pointers
+                None => match ty {
+                    CType::Array(elem, _) => CType::Pointer(elem),
+                    ty => ty,
+                },
+            };
+            if !(ty == CType::Void && name.is_none()) {
+                params.push((name, ty));
+            }
+            if !self.eat_punct(",") && !self.peek_punct(")") {
+                return None;
+            }
+        }
+        Some(Params {
+            args: params,
+            is_var_args,
+        })
+    }
+}
+
+/// type named by a typedef, resolved once the header is read
+fn named_type(name: &str) -> CType {
+    match name {
+        "int8_t" => CType::Int("i8"),
+        "int16_t" => CType::Int("i16"),
+        "int32_t" => CType::Int("int"),
+        "int64_t" | "ssize_t" | "ptrdiff_t" | "intptr_t" | "off_t" | "time_t" | "intmax_t" => {
+            CType::Int("i64")
+        }
+        "uint8_t" => CType::Int("u8"),
+        "uint16_t" => CType::Int("u16"),
+        "uint32_t" => CType::Int("u32"),
+        "uint64_t" | "uintmax_t" => CType::Int("u64"),
+        "size_t" | "uintptr_t" => CType::Int("usize"),
+        "bool" | "_Bool" => CType::Bool,
+        name => CType::Opaque(name.to_string()),
+    }
+}
+
+/// type made of C keywords, for a 64 bits target where `long` has 64 bits
+fn builtin_type(words: &[String]) -> Option<CType> {
+    let has = |word: &str| words.iter().any(|x| x == word);
+    let longs = words.iter().filter(|x| *x == "long").count();
+    let unsigned = has("unsigned");
+    Some(if has("void") {
+        CType::Void
+    } else if has("_Bool") || has("bool") {
+        CType::Bool
+    } else if has("float") || has("double") || has("__int128") {
+        CType::Float
+    } else if has("char") {
+        match (unsigned, has("signed")) {
+            (true, _) => CType::Int("u8"),
+            (false, true) => CType::Int("i8"),
+            _ => CType::Char,
+        }
+    } else if has("short") {
+        CType::Int(if unsigned { "u16" } else { "i16" })
+    } else if longs > 0 {
+        CType::Int(if unsigned { "u64" } else { "i64" })
+    } else {
+        CType::Int(if unsigned { "u32" } else { "int" })
+    })
+}
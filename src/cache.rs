@@ -0,0 +1,130 @@
+use popper_ast::Statement;
+use popper_codegen::target::Target;
+use popper_common::hash::hash_str;
+use popper_flag::Environment;
+use std::path::{Path, PathBuf};
+
+/// version of the compiler, part of every key so a new compiler ignores older artefacts
+const COMPILER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+///
+/// Cache stores the artefacts of each module under `<target>/cache`:
+/// its parsed AST, its checked interface and its object file.
+///
+/// The AST is keyed by the hash of the source, the interface and the object by
+/// the key of the module, which also covers the modules it imports.
+/// The cache never fails a build: when its directory can't be created, nothing is
+/// reused and the objects are built in the target directory
+#[derive(Debug, Clone)]
+pub struct Cache {
+    dir: PathBuf,
+    enabled: bool,
+}
+
+impl Cache {
+    pub fn new(target_path: &Path) -> Self {
+        let dir = target_path.join("cache");
+        match std::fs::create_dir_all(&dir) {
+            Ok(()) => Self { dir, enabled: true },
+            Err(err) => {
+                eprintln!(
+                    "warning: cannot create cache directory `{}`: {}, building without cache",
+                    dir.display(),
+                    err
+                );
+                Self {
+                    dir: target_path.to_path_buf(),
+                    enabled: false,
+                }
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// key made of the compiler version and `parts`
+    pub fn key(parts: &[&str]) -> String {
+        hash_str(&[&[COMPILER_VERSION], parts].concat().join("\n"))
+    }
+
+    /// key of a module from the hash of its source and the keys of the modules it imports:
+    /// it changes when the module or one of its dependencies does
+    pub fn module_key(source_hash: &str, imports: &[String]) -> String {
+        let mut parts = vec![source_hash];
+        parts.extend(imports.iter().map(|x| x.as_str()));
+        Self::key(&parts)
+    }
+
+    pub fn load_ast(&self, source_hash: &str) -> Option<Vec<Statement>> {
+        self.load(&self.ast_path(source_hash))
+    }
+
+    pub fn store_ast(&self, source_hash: &str, ast: &[Statement]) {
+        self.store(&self.ast_path(source_hash), ast)
+    }
+
+    pub fn load_interface(&self, module_key: &str) -> Option<Environment> {
+        self.load(&self.interface_path(module_key))
+    }
+
+    pub fn store_interface(&self, module_key: &str, interface: &Environment) {
+        self.store(&self.interface_path(module_key), interface)
+    }
+
+    /// object file of the module in `file`, compiled with the given options. The
+    /// symbols of a module are named after its file, which is part of the key, like
+    /// every field of the target: the import module of wasm is written in the object
+    pub fn object_path(
+        &self,
+        module_key: &str,
+        file: &Path,
+        debug: bool,
+        checks: bool,
+        target: &Target,
+    ) -> PathBuf {
+        let key = Self::key(&[
+            module_key,
+            &file.display().to_string(),
+            &debug.to_string(),
+            &checks.to_string(),
+            &format!("{:?}", target),
+        ]);
+        self.dir.join(key).with_extension("o")
+    }
+
+    /// whether the object at `path`, from [`Cache::object_path`], was built before
+    pub fn has_object(&self, path: &Path) -> bool {
+        self.enabled && path.exists()
+    }
+
+    fn ast_path(&self, source_hash: &str) -> PathBuf {
+        self.dir
+            .join(Self::key(&[source_hash]))
+            .with_extension("ast.json")
+    }
+
+    fn interface_path(&self, module_key: &str) -> PathBuf {
+        self.dir.join(module_key).with_extension("interface.json")
+    }
+
+    fn load<T: serde::de::DeserializeOwned>(&self, path: &Path) -> Option<T> {
+        if !self.enabled {
+            return None;
+        }
+        let content = std::fs::read_to_string(path).ok()?;
+        // an unreadable artefact is rebuilt
+        serde_json::from_str(&content).ok()
+    }
+
+    /// an artefact that can't be stored is rebuilt by the next build
+    fn store<T: serde::Serialize + ?Sized>(&self, path: &Path, value: &T) {
+        if !self.enabled {
+            return;
+        }
+        if let Ok(content) = serde_json::to_string(value) {
+            let _ = std::fs::write(path, content);
+        }
+    }
+}
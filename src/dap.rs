@@ -0,0 +1,605 @@
+use popper_vm::{Debugger, Module, Panic, Value, Vm, VmError};
+use serde_json::{json, Value as Json};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+
+pub type DapError = Box<dyn std::error::Error + Send + Sync>;
+
+/// the only thread of the program
+const THREAD: i64 = 1;
+
+///
+/// run is used to debug a program on the VM, serving the Debug Adapter Protocol over
+/// stdio until the client disconnects.
+/// The program starts once the client sends `configurationDone`, with the `args` of
+/// the `launch` request, and stops at its first statement if `stopOnEntry` is set.
+/// What it writes to its stdout is sent to the client as `output` events
+/// # Arguments
+/// * `module` - bytecode of the program
+/// * `file_name` - path of the program, its first argument
+///
+/// return: the exit code of the program
+pub fn run(module: Module, file_name: &str) -> Result<i32, DapError> {
+    let (writer, stdout) = redirect_stdout()?;
+    let client = Client::new(writer);
+    let forward = stdout.map(|x| client.forward(x));
+    let requests = read_requests(Box::new(BufReader::new(std::io::stdin())));
+    let session = Rc::new(RefCell::new(Session::new(
+        &module,
+        client.clone(),
+        requests,
+    )));
+
+    let launch = session.borrow_mut().configure()?;
+    let args = std::iter::once(file_name.to_string())
+        .chain(launch.args)
+        .collect();
+    session.borrow_mut().step = launch.stop_on_entry.then_some(Step::Entry);
+    let result = Vm::new(module).and_then(|vm| {
+        vm.with_args(args)
+            .with_debugger(Box::new(Hook(session.clone())))
+            .run_main()
+    });
+    let code = match result {
+        Ok(code) => code,
+        // sent when the program stopped on it
+        Err(VmError::Panic(_)) => Panic::STATUS,
+        Err(err) => {
+            client.output("stderr", &format!("{}\n", err));
+            1
+        }
+    };
+    if let Some(forward) = forward {
+        close_stdout();
+        forward.join().ok();
+    }
+    client.event("exited", json!({ "exitCode": code }));
+    client.event("terminated", json!({}));
+    session.borrow_mut().serve_until_disconnect();
+    Ok(code)
+}
+
+/// arguments of the `launch` request
+struct Launch {
+    args: Vec<String>,
+    stop_on_entry: bool,
+}
+
+/// what makes the program stop at the next statements, besides the breakpoints
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Entry,
+    /// `pause`
+    Pause,
+    /// `stepIn`: at the next statement
+    In,
+    /// `next`: at the next statement of a call at most this deep
+    Over(usize),
+    /// `stepOut`: at the next statement of a call less deep
+    Out(usize),
+}
+
+/// what `variablesReference` refers to, while the program is stopped
+enum Handle {
+    /// the variables of a frame of the backtrace
+    Locals(usize),
+    Value(Value),
+}
+
+/// what to do after a request
+enum Next {
+    Wait,
+    Resume,
+}
+
+struct Session {
+    client: Client,
+    requests: Receiver<Json>,
+    /// statement lines of the source files, by canonical path, breakpoints moving to
+    /// the next one
+    lines: HashMap<PathBuf, (String, BTreeSet<u32>)>,
+    /// lines to stop at, by source file as the module names it
+    breakpoints: HashMap<String, HashSet<u32>>,
+    step: Option<Step>,
+    handles: Vec<Handle>,
+    /// whether the program ended
+    ended: bool,
+    disconnected: bool,
+}
+
+/// the session, given to the VM
+struct Hook(Rc<RefCell<Session>>);
+
+impl Debugger for Hook {
+    fn statement(&mut self, vm: &mut Vm) {
+        self.0.borrow_mut().statement(vm);
+    }
+
+    fn panic(&mut self, vm: &mut Vm, panic: &Panic) {
+        let mut session = self.0.borrow_mut();
+        session.client.output("stderr", &format!("{}\n", panic));
+        session.stop(
+            vm,
+            json!({
+                "reason": "exception",
+                "description": "Panic",
+                "text": panic.message,
+            }),
+        );
+    }
+}
+
+impl Session {
+    fn new(module: &Module, client: Client, requests: Receiver<Json>) -> Self {
+        let mut lines: HashMap<PathBuf, (String, BTreeSet<u32>)> = HashMap::new();
+        for function in &module.functions {
+            let file = &module.files[function.file as usize];
+            let path = std::fs::canonicalize(file).unwrap_or_else(|_| PathBuf::from(file));
+            let (_, statements) = lines
+                .entry(path)
+                .or_insert_with(|| (file.clone(), BTreeSet::new()));
+            for pc in &function.statements {
+                if let Some(location) = function.location(*pc as usize) {
+                    statements.insert(location.line);
+                }
+            }
+        }
+        Self {
+            client,
+            requests,
+            lines,
+            breakpoints: HashMap::new(),
+            step: None,
+            handles: Vec::new(),
+            ended: false,
+            disconnected: false,
+        }
+    }
+
+    /// serve the requests before the program starts, until `configurationDone`
+    fn configure(&mut self) -> Result<Launch, DapError> {
+        let mut launch = Launch {
+            args: Vec::new(),
+            stop_on_entry: false,
+        };
+        loop {
+            let request = self.requests.recv()?;
+            let arguments = &request["arguments"];
+            match request["command"].as_str().unwrap_or_default() {
+                "initialize" => {
+                    self.client.respond(
+                        &request,
+                        json!({
+                            "supportsConfigurationDoneRequest": true,
+                            "supportsEvaluateForHovers": true,
+                            "supportsTerminateRequest": true,
+                        }),
+                    );
+                    self.client.event("initialized", json!({}));
+                }
+                "launch" => {
+                    let args = arguments["args"].as_array().cloned().unwrap_or_default();
+                    launch.args = args
+                        .iter()
+                        .filter_map(|x| x.as_str().map(str::to_string))
+                        .collect();
+                    launch.stop_on_entry = arguments["stopOnEntry"].as_bool().unwrap_or(false);
+                    self.client.respond(&request, json!({}));
+                }
+                "configurationDone" => {
+                    self.client.respond(&request, json!({}));
+                    return Ok(launch);
+                }
+                "disconnect" | "terminate" => {
+                    self.client.respond(&request, json!({}));
+                    std::process::exit(0);
+                }
+                _ => {
+                    self.handle(&request, None);
+                }
+            }
+        }
+    }
+
+    fn statement(&mut self, vm: &mut Vm) {
+        // the requests sent while the program runs
+        loop {
+            match self.requests.try_recv() {
+                Ok(request) => {
+                    self.handle(&request, None);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => std::process::exit(0),
+            }
+        }
+        let depth = vm.depth();
+        let reason = match self.step {
+            Some(Step::Entry) => Some("entry"),
+            Some(Step::Pause) => Some("pause"),
+            Some(Step::In) => Some("step"),
+            Some(Step::Over(d)) if depth <= d => Some("step"),
+            Some(Step::Out(d)) if depth < d => Some("step"),
+            _ => None,
+        };
+        let reason = reason.or_else(|| {
+            let frame = vm.frame(0)?;
+            let lines = self.breakpoints.get(&frame.file)?;
+            lines.contains(&frame.line).then_some("breakpoint")
+        });
+        if let Some(reason) = reason {
+            self.stop(vm, json!({ "reason": reason }));
+        }
+    }
+
+    /// tell the client the program stopped, and serve its requests until it resumes
+    fn stop(&mut self, vm: &mut Vm, mut body: Json) {
+        vm.flush();
+        body["threadId"] = json!(THREAD);
+        body["allThreadsStopped"] = json!(true);
+        self.client.event("stopped", body);
+        self.step = None;
+        loop {
+            let Ok(request) = self.requests.recv() else {
+                std::process::exit(0);
+            };
+            if let Next::Resume = self.handle(&request, Some(vm)) {
+                break;
+            }
+        }
+        self.handles.clear();
+    }
+
+    /// serve the requests once the program ended
+    fn serve_until_disconnect(&mut self) {
+        self.ended = true;
+        while !self.disconnected {
+            let Ok(request) = self.requests.recv() else {
+                return;
+            };
+            self.handle(&request, None);
+        }
+    }
+
+    /// answer a request, `vm` being the program when it's stopped
+    fn handle(&mut self, request: &Json, vm: Option<&mut Vm>) -> Next {
+        let arguments = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or_default();
+        match (command, vm) {
+            ("setBreakpoints", _) => {
+                let path = arguments["source"]["path"].as_str().unwrap_or_default();
+                let lines = arguments["breakpoints"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|x| x["line"].as_u64().unwrap_or(0) as u32)
+                    .collect::<Vec<_>>();
+                let breakpoints = self.set_breakpoints(Path::new(path), &lines);
+                self.client
+                    .respond(request, json!({ "breakpoints": breakpoints }));
+            }
+            ("threads", _) => {
+                self.client.respond(
+                    request,
+                    json!({ "threads": [{ "id": THREAD, "name": "main" }] }),
+                );
+            }
+            ("pause", _) => {
+                self.step = Some(Step::Pause);
+                self.client.respond(request, json!({}));
+            }
+            ("disconnect" | "terminate", _) => {
+                self.client.respond(request, json!({}));
+                self.disconnected = true;
+                if command == "terminate" {
+                    self.client.event("terminated", json!({}));
+                }
+                // the program doesn't go on without the client
+                if !self.ended {
+                    std::process::exit(0);
+                }
+            }
+            ("stackTrace", Some(vm)) => {
+                let frames = vm
+                    .backtrace()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(id, frame)| {
+                        let name = Path::new(&frame.file)
+                            .file_name()
+                            .map_or(frame.file.clone(), |x| x.to_string_lossy().to_string());
+                        let path = std::fs::canonicalize(&frame.file)
+                            .map_or(frame.file.clone(), |x| x.display().to_string());
+                        json!({
+                            "id": id,
+                            "name": frame.function,
+                            "source": { "name": name, "path": path },
+                            "line": frame.line,
+                            "column": frame.column,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                self.client.respond(
+                    request,
+                    json!({ "stackFrames": frames, "totalFrames": frames.len() }),
+                );
+            }
+            ("scopes", Some(_)) => {
+                let frame = arguments["frameId"].as_u64().unwrap_or(0) as usize;
+                let reference = self.handle_of(Handle::Locals(frame));
+                self.client.respond(
+                    request,
+                    json!({ "scopes": [{
+                        "name": "Locals",
+                        "presentationHint": "locals",
+                        "variablesReference": reference,
+                        "expensive": false,
+                    }] }),
+                );
+            }
+            ("variables", Some(vm)) => {
+                let reference = arguments["variablesReference"].as_u64().unwrap_or(0) as usize;
+                let variables = match reference.checked_sub(1).map(|x| &self.handles[x]) {
+                    Some(Handle::Locals(frame)) => vm
+                        .locals(*frame)
+                        .into_iter()
+                        .map(|x| (x.name, x.ty, x.value))
+                        .collect(),
+                    Some(Handle::Value(value)) => vm
+                        .children(value)
+                        .into_iter()
+                        .map(|(name, value)| (name, vm.type_of(&value), value))
+                        .collect(),
+                    None => Vec::new(),
+                };
+                let variables = variables
+                    .into_iter()
+                    .map(|(name, ty, value)| {
+                        let mut variable = self.variable(vm, value);
+                        variable["name"] = json!(name);
+                        variable["type"] = json!(ty.to_string());
+                        variable
+                    })
+                    .collect::<Vec<_>>();
+                self.client
+                    .respond(request, json!({ "variables": variables }));
+            }
+            ("evaluate", Some(vm)) => {
+                // the variables of the frame, like hovers show them
+                let frame = arguments["frameId"].as_u64().unwrap_or(0) as usize;
+                let expression = arguments["expression"].as_str().unwrap_or_default();
+                match vm.locals(frame).into_iter().find(|x| x.name == expression) {
+                    Some(local) => {
+                        let variable = self.variable(vm, local.value);
+                        self.client.respond(
+                            request,
+                            json!({
+                                "result": variable["value"],
+                                "type": local.ty.to_string(),
+                                "variablesReference": variable["variablesReference"],
+                            }),
+                        );
+                    }
+                    None => self.client.fail(
+                        request,
+                        &format!("`{}` isn't a variable in scope", expression),
+                    ),
+                }
+            }
+            ("continue", Some(_)) => {
+                self.client
+                    .respond(request, json!({ "allThreadsContinued": true }));
+                return Next::Resume;
+            }
+            ("next" | "stepIn" | "stepOut", Some(vm)) => {
+                self.step = Some(match command {
+                    "next" => Step::Over(vm.depth()),
+                    "stepIn" => Step::In,
+                    _ => Step::Out(vm.depth()),
+                });
+                self.client.respond(request, json!({}));
+                return Next::Resume;
+            }
+            ("stackTrace" | "scopes" | "variables" | "evaluate", None) => {
+                self.client.fail(request, "the program isn't stopped");
+            }
+            _ => self
+                .client
+                .fail(request, &format!("`{}` isn't supported", command)),
+        }
+        Next::Wait
+    }
+
+    /// set the breakpoints of a file, each line moved to the next statement
+    ///
+    /// return: the breakpoints, with the lines they're at
+    fn set_breakpoints(&mut self, path: &Path, lines: &[u32]) -> Vec<Json> {
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let Some((file, statements)) = self.lines.get(&path) else {
+            return lines
+                .iter()
+                .map(|x| json!({ "verified": false, "line": x, "message": "not in the program" }))
+                .collect();
+        };
+        let mut set = HashSet::new();
+        let breakpoints = lines
+            .iter()
+            .map(|line| match statements.range(line..).next() {
+                Some(actual) => {
+                    set.insert(*actual);
+                    json!({ "verified": true, "line": actual })
+                }
+                None => json!({ "verified": false, "line": line, "message": "no code there" }),
+            })
+            .collect();
+        self.breakpoints.insert(file.clone(), set);
+        breakpoints
+    }
+
+    fn handle_of(&mut self, handle: Handle) -> usize {
+        self.handles.push(handle);
+        self.handles.len()
+    }
+
+    /// a value as a DAP variable, which can be expanded if it has parts
+    fn variable(&mut self, vm: &Vm, value: Value) -> Json {
+        let shown = match &value {
+            Value::Struct(index, _) => {
+                let name = &vm.module().structs[*index as usize].name;
+                format!("{} {}", name, value)
+            }
+            value => value.to_string(),
+        };
+        let reference = match vm.children(&value).is_empty() {
+            true => 0,
+            false => self.handle_of(Handle::Value(value)),
+        };
+        json!({ "value": shown, "variablesReference": reference })
+    }
+}
+
+/// the connection to the client, shared with the thread sending the output of the
+/// program
+#[derive(Clone)]
+struct Client {
+    inner: Arc<Mutex<ClientOutput>>,
+}
+
+struct ClientOutput {
+    writer: Box<dyn Write + Send>,
+    seq: i64,
+}
+
+impl Client {
+    fn new(writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(ClientOutput { writer, seq: 1 })),
+        }
+    }
+
+    fn send(&self, mut message: Json) {
+        let mut output = self.inner.lock().unwrap();
+        message["seq"] = json!(output.seq);
+        output.seq += 1;
+        let content = message.to_string();
+        let framed = format!("Content-Length: {}\r\n\r\n{}", content.len(), content);
+        output.writer.write_all(framed.as_bytes()).ok();
+        output.writer.flush().ok();
+    }
+
+    fn respond(&self, request: &Json, body: Json) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }));
+    }
+
+    fn fail(&self, request: &Json, message: &str) {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }));
+    }
+
+    fn event(&self, event: &str, body: Json) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn output(&self, category: &str, text: &str) {
+        self.event("output", json!({ "category": category, "output": text }));
+    }
+
+    /// send what the program writes to `stdout` as it writes it, until it's closed
+    fn forward(&self, mut stdout: std::fs::File) -> std::thread::JoinHandle<()> {
+        let client = self.clone();
+        std::thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            while let Ok(n @ 1..) = stdout.read(&mut buffer) {
+                client.output("stdout", &String::from_utf8_lossy(&buffer[..n]));
+            }
+        })
+    }
+}
+
+/// read the requests of the client on a thread of their own, so the ones sent while the
+/// program runs are read at its next statement
+fn read_requests(mut input: Box<dyn BufRead + Send>) -> Receiver<Json> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        while let Some(message) = read_message(&mut input) {
+            if message["type"] == "request" && sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+/// a message with its `Content-Length` header, `None` at the end of the input
+fn read_message(input: &mut dyn BufRead) -> Option<Json> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut content = vec![0; length?];
+    input.read_exact(&mut content).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+/// the client speaks on stdout, so what the program writes there goes to a pipe
+///
+/// return: the connection to the client, and the end of the pipe the output of the
+/// program is read from
+#[cfg(unix)]
+fn redirect_stdout() -> Result<(Box<dyn Write + Send>, Option<std::fs::File>), DapError> {
+    use std::os::fd::FromRawFd;
+    let mut fds = [0; 2];
+    // SAFETY: the descriptors are checked, and owned by the files made of them
+    unsafe {
+        let client = libc::dup(1);
+        if client < 0 || libc::pipe(fds.as_mut_ptr()) < 0 || libc::dup2(fds[1], 1) < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        libc::close(fds[1]);
+        Ok((
+            Box::new(std::fs::File::from_raw_fd(client)),
+            Some(std::fs::File::from_raw_fd(fds[0])),
+        ))
+    }
+}
+
+/// the output of the program is written with the messages on other systems
+#[cfg(not(unix))]
+fn redirect_stdout() -> Result<(Box<dyn Write + Send>, Option<std::fs::File>), DapError> {
+    Ok((Box::new(std::io::stdout()), None))
+}
+
+/// close the pipe of the output of the program, once it ended
+fn close_stdout() {
+    #[cfg(unix)]
+    // SAFETY: nothing writes to stdout anymore
+    unsafe {
+        libc::close(1);
+    }
+}
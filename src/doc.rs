@@ -0,0 +1,337 @@
+use popper_ast::{Arguments, Statement, StructStmt, Type};
+use popper_flag::{Environment, ValueFlag};
+use popper_semantic_analyzer::analyze_module;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::module_loader::ModuleLoader;
+use popper_error::generate_color;
+
+const STYLE: &str = "body { font-family: sans-serif; max-width: 60em; margin: auto; padding: 1em; }
+pre, code { font-family: monospace; background: #f4f4f4; }
+pre { padding: 0.5em; }
+.doc { margin-left: 1em; white-space: pre-wrap; }
+.item { margin-top: 1.5em; }";
+
+/// documented items of a module, the ones its importers see
+struct ModulePage {
+    /// name of the page, without extension
+    page: String,
+    file: PathBuf,
+    doc: Option<String>,
+    items: Vec<Item>,
+}
+
+enum Item {
+    Function {
+        name: String,
+        signature: String,
+        doc: Option<String>,
+    },
+    Struct {
+        name: String,
+        /// name, type, `pub`, documentation
+        fields: Vec<(String, String, bool, Option<String>)>,
+        doc: Option<String>,
+    },
+}
+
+///
+/// generate_doc is used to check `file` and the modules it imports, and write a page
+/// documenting the `pub` items of each of them in `target_path/modules`, with an
+/// `index.html` listing them
+/// # Arguments
+/// * `file` - entry file
+/// * `lib_paths` - directories searched for imported modules
+/// * `target_path` - directory the pages are written to
+///
+/// return: the path of the index, if all the modules are valid
+pub fn generate_doc(file: &Path, lib_paths: Vec<PathBuf>, target_path: &Path) -> Option<PathBuf> {
+    let mut loader = ModuleLoader::new(lib_paths);
+    loader.load(file)?;
+
+    let mut interfaces = HashMap::new();
+    let mut pages = Vec::new();
+    let mut names = HashSet::new();
+    for module in loader.modules() {
+        let env = match analyze_module(module.stmts.clone(), interfaces.clone()) {
+            Ok(env) => env,
+            Err(errs) => {
+                let file_name = module.file.display().to_string();
+                errs.into_iter()
+                    .for_each(|x| x.report(generate_color(), &module.source, &file_name));
+                return None;
+            }
+        };
+
+        let stem = module
+            .file
+            .file_stem()
+            .and_then(|x| x.to_str())
+            .unwrap_or("module")
+            .to_string();
+        let mut page = stem.clone();
+        let mut i = 1;
+        while !names.insert(page.clone()) {
+            page = format!("{}_{}", stem, i);
+            i += 1;
+        }
+
+        pages.push(module_page(page, module.file.clone(), &module.stmts, &env));
+        interfaces.insert(module.file.clone(), env);
+    }
+
+    // page defining each struct, to link the types
+    let struct_pages: HashMap<String, String> = pages
+        .iter()
+        .flat_map(|page| {
+            page.items.iter().filter_map(|item| match item {
+                Item::Struct { name, .. } => Some((name.clone(), page.page.clone())),
+                _ => None,
+            })
+        })
+        .collect();
+
+    // a module can be named `index` too
+    let modules_path = target_path.join("modules");
+    std::fs::create_dir_all(&modules_path).expect("Unable to create the doc directory");
+    for page in pages.iter() {
+        std::fs::write(
+            modules_path.join(format!("{}.html", page.page)),
+            render_module(page, &struct_pages),
+        )
+        .expect("Unable to write the doc");
+    }
+    let index = target_path.join("index.html");
+    std::fs::write(&index, render_index(&pages)).expect("Unable to write the doc");
+    Some(index)
+}
+
+/// collect the `pub` items of a checked module
+fn module_page(page: String, file: PathBuf, stmts: &[Statement], env: &Environment) -> ModulePage {
+    let mut doc: Option<String> = None;
+    let mut items = Vec::new();
+    for stmt in stmts {
+        match stmt {
+            Statement::ModuleDoc(module_doc) => doc = Some(module_doc.doc.clone()),
+            Statement::Function(function) if function.is_pub => items.push(Item::Function {
+                name: function.name.clone(),
+                signature: signature(
+                    &function.name,
+                    &function.arguments,
+                    &function.returntype,
+                    env,
+                ),
+                doc: function.doc.clone(),
+            }),
+            Statement::Struct(struct_stmt) if struct_stmt.is_pub => {
+                items.push(struct_item(struct_stmt))
+            }
+            _ => {}
+        }
+    }
+
+    ModulePage {
+        page,
+        file,
+        doc,
+        items,
+    }
+}
+
+fn struct_item(struct_stmt: &StructStmt) -> Item {
+    Item::Struct {
+        name: struct_stmt.name.clone(),
+        fields: struct_stmt
+            .fields
+            .iter()
+            .map(|field| {
+                (
+                    field.name.clone(),
+                    ValueFlag::from_ty(field.ty.clone()).to_string(),
+                    field.is_pub,
+                    field.doc.clone(),
+                )
+            })
+            .collect(),
+        doc: struct_stmt.doc.clone(),
+    }
+}
+
+/// signature of a `pub` function, with the types the analyzer gave it
+fn signature(name: &str, arguments: &Arguments, return_type: &Type, env: &Environment) -> String {
+    let (types, return_type, is_var_args) =
+        match env.get_variable(name).and_then(|x| x.value.get_value()) {
+            Some(ValueFlag::Function(types, return_type, is_var_args)) => {
+                (types, *return_type, is_var_args)
+            }
+            _ => (
+                arguments
+                    .args
+                    .iter()
+                    .map(|arg| ValueFlag::from_ty(arg.ty.clone()))
+                    .collect(),
+                ValueFlag::from_ty(return_type.clone()),
+                false,
+            ),
+        };
+
+    let mut args: Vec<String> = arguments
+        .args
+        .iter()
+        .zip(types.iter())
+        .map(|(arg, ty)| format!("{}: {}", arg.name, ty))
+        .collect();
+    if is_var_args {
+        args.push("...".to_string());
+    }
+    format!("pub func {}({}): {}", name, args.join(", "), return_type)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// escape a type or a signature, linking the structs it names to their page
+fn link_types(text: &str, struct_pages: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("struct(") {
+        let name_start = start + "struct(".len();
+        let Some(len) = rest[name_start..].find(')') else {
+            break;
+        };
+        let name = &rest[name_start..name_start + len];
+        out.push_str(&escape(&rest[..name_start]));
+        match struct_pages.get(name) {
+            Some(page) => out.push_str(&format!(
+                "<a href=\"{}.html#{}\">{}</a>",
+                escape(page),
+                escape(name),
+                escape(name)
+            )),
+            None => out.push_str(&escape(name)),
+        }
+        rest = &rest[name_start + len..];
+    }
+    out.push_str(&escape(rest));
+    out
+}
+
+fn render_doc(doc: &Option<String>) -> String {
+    match doc {
+        Some(doc) => format!("<div class=\"doc\">{}</div>\n", escape(doc)),
+        None => String::new(),
+    }
+}
+
+fn render_page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape(title),
+        STYLE,
+        body
+    )
+}
+
+fn render_module(page: &ModulePage, struct_pages: &HashMap<String, String>) -> String {
+    let mut body = format!(
+        "<p><a href=\"../index.html\">index</a></p>\n<h1>{}</h1>\n<p><code>{}</code></p>\n",
+        escape(&page.page),
+        escape(&page.file.display().to_string())
+    );
+    body.push_str(&render_doc(&page.doc));
+
+    for (title, kind) in [("Structs", 0), ("Functions", 1)] {
+        let items: Vec<&Item> = page
+            .items
+            .iter()
+            .filter(|item| {
+                matches!(
+                    (item, kind),
+                    (Item::Struct { .. }, 0) | (Item::Function { .. }, 1)
+                )
+            })
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+        body.push_str(&format!("<h2>{}</h2>\n", title));
+        for item in items {
+            body.push_str(&render_item(item, struct_pages));
+        }
+    }
+
+    render_page(&page.page, &body)
+}
+
+fn render_item(item: &Item, struct_pages: &HashMap<String, String>) -> String {
+    match item {
+        Item::Function {
+            name,
+            signature,
+            doc,
+        } => format!(
+            "<div class=\"item\" id=\"{}\">\n<pre>{}</pre>\n{}</div>\n",
+            escape(name),
+            link_types(signature, struct_pages),
+            render_doc(doc)
+        ),
+        Item::Struct { name, fields, doc } => {
+            // the private fields are shown too, they are part of the layout
+            let mut layout = format!("pub struct {} {{\n", escape(name));
+            for (field, ty, is_pub, _) in fields {
+                layout.push_str(&format!(
+                    "    {}{}: {},\n",
+                    if *is_pub { "pub " } else { "" },
+                    escape(field),
+                    link_types(ty, struct_pages)
+                ));
+            }
+            layout.push('}');
+
+            let mut fields_doc = String::new();
+            for (field, _, _, field_doc) in fields.iter().filter(|x| x.3.is_some()) {
+                fields_doc.push_str(&format!(
+                    "<li><code>{}</code>{}</li>\n",
+                    escape(field),
+                    render_doc(field_doc)
+                ));
+            }
+            if !fields_doc.is_empty() {
+                fields_doc = format!("<ul>\n{}</ul>\n", fields_doc);
+            }
+
+            format!(
+                "<div class=\"item\" id=\"{}\">\n<pre>{}</pre>\n{}{}</div>\n",
+                escape(name),
+                layout,
+                render_doc(doc),
+                fields_doc
+            )
+        }
+    }
+}
+
+fn render_index(pages: &[ModulePage]) -> String {
+    let mut body = String::from("<h1>Modules</h1>\n<ul>\n");
+    for page in pages {
+        let summary = page
+            .doc
+            .as_ref()
+            .and_then(|doc| doc.lines().next())
+            .map(|line| format!(" - {}", escape(line)))
+            .unwrap_or_default();
+        body.push_str(&format!(
+            "<li><a href=\"modules/{}.html\">{}</a>{}</li>\n",
+            escape(&page.page),
+            escape(&page.page),
+            summary
+        ));
+    }
+    body.push_str("</ul>\n");
+    render_page("Modules", &body)
+}
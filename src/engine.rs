@@ -0,0 +1,518 @@
+//! Embedding Popper in a Rust program: the host loads a program from a string, gives
+//! it Rust functions it declares like `extern` ones, and calls its functions on the
+//! bytecode VM.
+
+use crate::module_loader::{Diagnostic, ModuleLoader};
+use crate::program_diagnostics;
+use popper_ast::{IntKind, Statement};
+use popper_flag::ValueFlag;
+use popper_parser::parser::try_parse;
+use popper_vm::{HostFunction, Limits, Value, Vm, VmError};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+///
+/// Engine runs a Popper program for a host program: it's checked like the compiled
+/// ones, and run by the bytecode VM
+#[derive(Default)]
+pub struct Engine {
+    lib_paths: Vec<PathBuf>,
+    limits: Limits,
+    /// signatures of the functions of the host, declared before the program
+    externs: Vec<Statement>,
+    functions: HashMap<String, HostFunction>,
+    vm: Option<Vm>,
+}
+
+/// why the engine can't load a program or call one of its functions
+#[derive(Debug)]
+pub enum EngineError {
+    /// the program, or a module it imports, is invalid
+    Invalid(Vec<Diagnostic>),
+    /// no program is loaded
+    NotLoaded,
+    /// the program has no function of this name
+    UnknownFunction(String),
+    /// a function was given arguments it doesn't take, or returned a value of another
+    /// type than the one asked
+    Type(String),
+    /// the program went wrong when it ran, or can't run
+    Vm(VmError),
+}
+
+/// a struct of the program, its fields named
+#[derive(Debug, Clone, PartialEq)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<(String, Value)>,
+}
+
+/// a Rust value given to the program
+pub trait ToValue {
+    fn to_value(&self, vm: &Vm) -> Result<Value, EngineError>;
+}
+
+/// a Rust value read from the program
+pub trait FromValue: Sized {
+    fn from_value(value: &Value, vm: &Vm) -> Option<Self>;
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// directories searched for the modules the program imports, before the ones of
+    /// `POPPER_PATH`
+    pub fn with_lib_path(mut self, path: PathBuf) -> Self {
+        self.lib_paths.push(path);
+        self
+    }
+
+    /// limits of what the program may use each time it's called
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    ///
+    /// register is used to give the programs loaded next a Rust function, which they
+    /// call like a function of an `extern` block
+    /// # Arguments
+    /// * `sign` - signature of the function, like in an `extern` block:
+    ///   `func name(x: int): int`
+    /// * `function` - the function, given the arguments; its error is a panic of the
+    ///   program
+    pub fn register<F>(&mut self, sign: &str, function: F) -> Result<(), EngineError>
+    where
+        F: Fn(&Vm, &[Value]) -> Result<Value, String> + 'static,
+    {
+        let source = format!("extern {{ {} }};", sign);
+        let stmt = match try_parse(&source) {
+            Ok(mut stmts) if stmts.len() == 1 => stmts.remove(0),
+            Ok(_) => return Err(EngineError::Type(format!("`{}` isn't a signature", sign))),
+            Err(err) => {
+                return Err(EngineError::Invalid(vec![Diagnostic::new(
+                    Path::new("<extern>"),
+                    &err,
+                )]))
+            }
+        };
+        let name = match &stmt {
+            Statement::Extern(extern_stmt) if extern_stmt.signs.len() == 1 => {
+                extern_stmt.signs[0].name.clone()
+            }
+            _ => return Err(EngineError::Type(format!("`{}` isn't a signature", sign))),
+        };
+        let function: HostFunction =
+            Rc::new(
+                move |vm: &mut Vm, args: &[Value]| match function(vm, args) {
+                    Ok(value) => Ok(value),
+                    Err(message) => Err(vm.panic(&message)),
+                },
+            );
+        self.externs.push(stmt);
+        self.functions.insert(name, function);
+        Ok(())
+    }
+
+    ///
+    /// load is used to check a program and compile it to bytecode, replacing the
+    /// program loaded before
+    /// # Arguments
+    /// * `file_name` - path the program is known by, its imports being looked up next
+    ///   to it
+    /// * `source` - source code of the program
+    pub fn load(&mut self, file_name: &str, source: &str) -> Result<(), EngineError> {
+        self.vm = None;
+        let mut loader = ModuleLoader::new(self.lib_paths.clone()).keep_diagnostics();
+        let Some(stmts) = loader.load_source(Path::new(file_name), source.to_string()) else {
+            return Err(EngineError::Invalid(loader.diagnostics().to_vec()));
+        };
+        let stmts: Vec<Statement> = self.externs.iter().cloned().chain(stmts).collect();
+
+        let diagnostics = program_diagnostics(stmts.clone(), file_name);
+        if !diagnostics.is_empty() {
+            return Err(EngineError::Invalid(diagnostics));
+        }
+        let module = popper_vm::compile(&stmts, file_name, source)?;
+        let vm = Vm::with_host(module, self.functions.clone())?.with_limits(self.limits.clone());
+        self.vm = Some(vm);
+        Ok(())
+    }
+
+    /// the VM running the program loaded
+    pub fn vm(&self) -> Option<&Vm> {
+        self.vm.as_ref()
+    }
+
+    ///
+    /// call is used to call a function of the program
+    /// # Arguments
+    /// * `name` - name of the function
+    /// * `args` - arguments, of the types the function declares
+    ///
+    /// return: what the function returns, as a `T`
+    pub fn call<T: FromValue>(
+        &mut self,
+        name: &str,
+        args: &[&dyn ToValue],
+    ) -> Result<T, EngineError> {
+        let vm = self.vm.as_mut().ok_or(EngineError::NotLoaded)?;
+        let index = vm
+            .module()
+            .function(name)
+            .ok_or_else(|| EngineError::UnknownFunction(name.to_string()))?;
+        let function = &vm.module().functions[index as usize];
+        let arity = function.arity as usize;
+        if args.len() < arity || (args.len() > arity && !function.is_var_args) {
+            return Err(EngineError::Type(format!(
+                "`{}` takes {} arguments, not {}",
+                name,
+                function.arity,
+                args.len()
+            )));
+        }
+
+        let mut values = Vec::new();
+        for (slot, arg) in args.iter().enumerate() {
+            let value = arg.to_value(vm)?;
+            // the arguments of the `...` have no type
+            let declared = function
+                .variables
+                .iter()
+                .find(|x| x.slot as usize == slot && slot < arity)
+                .and_then(|x| x.ty.clone());
+            let given = vm.type_of(&value);
+            if let Some(declared) = declared.filter(|x| !fits(x, &given)) {
+                return Err(EngineError::Type(format!(
+                    "argument {} of `{}` has type `{}`, given `{}`",
+                    slot + 1,
+                    name,
+                    declared,
+                    given
+                )));
+            }
+            values.push(value);
+        }
+
+        let value = vm.call(name, values)?;
+        vm.flush();
+        T::from_value(&value, vm).ok_or_else(|| {
+            EngineError::Type(format!(
+                "`{}` returned `{}`, not `{}`",
+                name,
+                vm.type_of(&value),
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+
+    /// `value`, returned by the program, as a `T`
+    pub fn read<T: FromValue>(&self, value: &Value) -> Result<T, EngineError> {
+        let vm = self.vm.as_ref().ok_or(EngineError::NotLoaded)?;
+        T::from_value(value, vm).ok_or_else(|| {
+            EngineError::Type(format!(
+                "`{}` isn't `{}`",
+                vm.type_of(value),
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+}
+
+/// a value of type `given` can be given where a `declared` is expected: the elements of
+/// an empty sequence are `unit`s
+fn fits(declared: &ValueFlag, given: &ValueFlag) -> bool {
+    match (declared, given) {
+        (ValueFlag::Struct(a), ValueFlag::StructInstance(b)) => a == b,
+        (ValueFlag::Vec(a), ValueFlag::Vec(b)) | (ValueFlag::Slice(a), ValueFlag::Slice(b)) => {
+            **b == ValueFlag::None || fits(a, b)
+        }
+        (ValueFlag::List(a, n), ValueFlag::List(b, m)) => n == m && (*m == 0 || fits(a, b)),
+        (ValueFlag::Tuple(a), ValueFlag::Tuple(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| fits(a, b))
+        }
+        (ValueFlag::Pointer(_), ValueFlag::Pointer(_)) => true,
+        (declared, given) => declared == given,
+    }
+}
+
+impl Struct {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    pub fn with_field(mut self, name: &str, value: Value) -> Self {
+        self.fields.push((name.to_string(), value));
+        self
+    }
+
+    /// the field `name`, as a `T`
+    pub fn get<T: FromValue>(&self, name: &str, vm: &Vm) -> Option<T> {
+        let (_, value) = self.fields.iter().find(|(field, _)| field == name)?;
+        T::from_value(value, vm)
+    }
+}
+
+impl Display for EngineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::Invalid(diagnostics) => {
+                let diagnostics = diagnostics
+                    .iter()
+                    .map(|x| format!("{}: {}", x.file.display(), x.message))
+                    .collect::<Vec<_>>();
+                write!(f, "invalid program: {}", diagnostics.join(", "))
+            }
+            EngineError::NotLoaded => write!(f, "no program is loaded"),
+            EngineError::UnknownFunction(name) => write!(f, "no function `{}`", name),
+            EngineError::Type(err) => write!(f, "{}", err),
+            EngineError::Vm(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<VmError> for EngineError {
+    fn from(err: VmError) -> Self {
+        EngineError::Vm(err)
+    }
+}
+
+impl ToValue for Value {
+    fn to_value(&self, _: &Vm) -> Result<Value, EngineError> {
+        Ok(self.clone())
+    }
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value, _: &Vm) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl ToValue for () {
+    fn to_value(&self, _: &Vm) -> Result<Value, EngineError> {
+        Ok(Value::Unit)
+    }
+}
+
+impl FromValue for () {
+    fn from_value(value: &Value, _: &Vm) -> Option<Self> {
+        matches!(value, Value::Unit).then_some(())
+    }
+}
+
+impl ToValue for bool {
+    fn to_value(&self, _: &Vm) -> Result<Value, EngineError> {
+        Ok(Value::Bool(*self))
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value, _: &Vm) -> Option<Self> {
+        match value {
+            Value::Bool(x) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! int_value {
+    ($($ty:ty => $kind:expr),*) => {
+        $(
+            impl ToValue for $ty {
+                fn to_value(&self, _: &Vm) -> Result<Value, EngineError> {
+                    Ok(Value::int($kind, *self as i64))
+                }
+            }
+
+            /// from an integer of any kind in its range
+            impl FromValue for $ty {
+                fn from_value(value: &Value, _: &Vm) -> Option<Self> {
+                    match value {
+                        Value::Int(kind, x) if kind.is_signed() => (*x).try_into().ok(),
+                        Value::Int(_, x) => (*x as u64).try_into().ok(),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+int_value!(
+    i8 => IntKind::I8,
+    i16 => IntKind::I16,
+    i32 => IntKind::I32,
+    i64 => IntKind::I64,
+    u8 => IntKind::U8,
+    u16 => IntKind::U16,
+    u32 => IntKind::U32,
+    u64 => IntKind::U64,
+    usize => IntKind::USize
+);
+
+/// rounded to a `float`, 32 bits
+impl ToValue for f64 {
+    fn to_value(&self, _: &Vm) -> Result<Value, EngineError> {
+        Ok(Value::Float(*self as f32 as f64))
+    }
+}
+
+impl FromValue for f64 {
+    fn from_value(value: &Value, _: &Vm) -> Option<Self> {
+        match value {
+            Value::Float(x) => Some(*x),
+            _ => None,
+        }
+    }
+}
+
+impl ToValue for f32 {
+    fn to_value(&self, _: &Vm) -> Result<Value, EngineError> {
+        Ok(Value::Float(*self as f64))
+    }
+}
+
+impl FromValue for f32 {
+    fn from_value(value: &Value, vm: &Vm) -> Option<Self> {
+        f64::from_value(value, vm).map(|x| x as f32)
+    }
+}
+
+impl ToValue for str {
+    fn to_value(&self, _: &Vm) -> Result<Value, EngineError> {
+        Ok(Value::string(self))
+    }
+}
+
+impl ToValue for String {
+    fn to_value(&self, vm: &Vm) -> Result<Value, EngineError> {
+        self.as_str().to_value(vm)
+    }
+}
+
+impl ToValue for &str {
+    fn to_value(&self, vm: &Vm) -> Result<Value, EngineError> {
+        (*self).to_value(vm)
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value, _: &Vm) -> Option<Self> {
+        match value {
+            Value::String(x) => Some(x.to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// a `vec`
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(&self, vm: &Vm) -> Result<Value, EngineError> {
+        let elements = self.iter().map(|x| x.to_value(vm));
+        Ok(Value::vec(elements.collect::<Result<_, _>>()?))
+    }
+}
+
+/// from a list, a `vec` or a slice
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value, vm: &Vm) -> Option<Self> {
+        value
+            .elements()?
+            .iter()
+            .map(|x| T::from_value(x, vm))
+            .collect()
+    }
+}
+
+/// a list
+impl<T: ToValue, const N: usize> ToValue for [T; N] {
+    fn to_value(&self, vm: &Vm) -> Result<Value, EngineError> {
+        let elements = self.iter().map(|x| x.to_value(vm));
+        Ok(Value::List(Rc::new(elements.collect::<Result<_, _>>()?)))
+    }
+}
+
+macro_rules! tuple_value {
+    ($(($($ty:ident $i:tt),*)),*) => {
+        $(
+            impl<$($ty: ToValue),*> ToValue for ($($ty,)*) {
+                fn to_value(&self, vm: &Vm) -> Result<Value, EngineError> {
+                    Ok(Value::Tuple(Rc::new(vec![$(self.$i.to_value(vm)?),*])))
+                }
+            }
+
+            impl<$($ty: FromValue),*> FromValue for ($($ty,)*) {
+                fn from_value(value: &Value, vm: &Vm) -> Option<Self> {
+                    match value {
+                        Value::Tuple(elements) if elements.len() == [$($i),*].len() => {
+                            Some(($($ty::from_value(&elements[$i], vm)?,)*))
+                        }
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+tuple_value!((A 0, B 1), (A 0, B 1, C 2), (A 0, B 1, C 2, D 3));
+
+/// a struct of the program, its fields given in any order
+impl ToValue for Struct {
+    fn to_value(&self, vm: &Vm) -> Result<Value, EngineError> {
+        let index = vm
+            .module()
+            .struct_layout(&self.name)
+            .ok_or_else(|| EngineError::Type(format!("no struct `{}`", self.name)))?;
+        let layout = &vm.module().structs[index as usize];
+        let names = vm.module().field_names(index);
+        if names.len() != self.fields.len() {
+            return Err(EngineError::Type(format!(
+                "`{}` has {} fields, not {}",
+                self.name,
+                names.len(),
+                self.fields.len()
+            )));
+        }
+        let mut fields = Vec::new();
+        for (name, ty) in names.iter().zip(&layout.types) {
+            let Some((_, value)) = self.fields.iter().find(|(field, _)| field == name) else {
+                return Err(EngineError::Type(format!(
+                    "no field `{}` given to `{}`",
+                    name, self.name
+                )));
+            };
+            let given = vm.type_of(value);
+            if !fits(ty, &given) {
+                return Err(EngineError::Type(format!(
+                    "field `{}` of `{}` has type `{}`, given `{}`",
+                    name, self.name, ty, given
+                )));
+            }
+            fields.push(value.clone());
+        }
+        Ok(Value::Struct(index, Rc::new(fields)))
+    }
+}
+
+impl FromValue for Struct {
+    fn from_value(value: &Value, vm: &Vm) -> Option<Self> {
+        let Value::Struct(index, _) = value else {
+            return None;
+        };
+        Some(Struct {
+            name: vm.module().structs[*index as usize].name.clone(),
+            fields: vm.children(value),
+        })
+    }
+}
@@ -0,0 +1,308 @@
+use popper_ast::{IntKind, Statement, StructStmt};
+use popper_flag::{Environment, ValueFlag};
+use std::collections::{HashMap, HashSet};
+
+/// declarations of the runtime, to build and read the strings given to Popper functions
+const RUNTIME_DECLARATIONS: &str = "typedef uint8_t popper_bool;
+typedef uint8_t popper_char;
+
+/* `string`, `vec<T>` and `[T]` are pointers to runtime values */
+typedef struct popper_string popper_string;
+typedef struct popper_vec popper_vec;
+typedef struct popper_slice popper_slice;
+
+popper_string *popper_str_new(const char *data, int32_t len);
+int32_t popper_str_len(const popper_string *s);
+const char *popper_str_cstr(const popper_string *s);
+";
+
+///
+/// generate_header is used to write the C header of a library: the structs of its
+/// modules, and the `export func`s it defines.
+///
+/// Structs are laid out like C structs, the fields in declaration order with their
+/// natural alignment. Tuples become structs whose fields are named `_0`, `_1`...
+/// Structs, tuples and lists aren't passed by value with the C calling convention,
+/// so the functions taking or returning them are left out of the header: pass a
+/// pointer instead
+/// # Arguments
+/// * `name` - name of the library
+/// * `modules` - statements of each module, with the environment it was checked in
+///
+/// return: the content of the header
+pub fn generate_header(name: &str, modules: &[(Vec<Statement>, Environment)]) -> String {
+    let guard = format!(
+        "POPPER_{}_H",
+        name.to_uppercase()
+            .replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+    );
+    let mut header = HeaderWriter::default();
+    let mut declarations = String::new();
+
+    let structs: HashMap<String, StructStmt> = modules
+        .iter()
+        .flat_map(|(stmts, _)| stmts.iter())
+        .filter_map(|stmt| match stmt {
+            Statement::Struct(s) => Some((s.name.clone(), s.clone())),
+            _ => None,
+        })
+        .collect();
+    // the structs can point to each other
+    for (stmts, _) in modules {
+        for stmt in stmts {
+            if let Statement::Struct(s) = stmt {
+                declarations.push_str(&format!(
+                    "typedef struct {} {};
+",
+                    s.name, s.name
+                ));
+            }
+        }
+    }
+    for (stmts, _) in modules {
+        for stmt in stmts {
+            if let Statement::Struct(s) = stmt {
+                header.write_struct(s, &structs);
+            }
+        }
+    }
+
+    for (stmts, env) in modules {
+        for stmt in stmts {
+            let Statement::Function(function) = stmt else {
+                continue;
+            };
+            if !function.is_export {
+                continue;
+            }
+            let Some(ValueFlag::Function(args, return_type, is_var_args)) = env
+                .get_variable(&function.name)
+                .and_then(|x| x.value.get_value())
+            else {
+                continue;
+            };
+            header.write_function(
+                &function.name,
+                &function
+                    .arguments
+                    .args
+                    .iter()
+                    .map(|arg| arg.name.clone())
+                    .collect::<Vec<_>>(),
+                &args,
+                &return_type,
+                is_var_args,
+                &function.doc,
+            );
+        }
+    }
+
+    format!(
+        "/* Generated by `popper build`, do not edit. */\n\n#ifndef {guard}\n#define {guard}\n\n#include <stddef.h>\n#include <stdint.h>\n\n#ifdef __cplusplus\nextern \"C\" {{\n#endif\n\n{}\n{}\n{}{}#ifdef __cplusplus\n}}\n#endif\n\n#endif /* {guard} */\n",
+        RUNTIME_DECLARATIONS,
+        declarations,
+        header.types,
+        header.functions,
+        guard = guard
+    )
+}
+
+#[derive(Default)]
+struct HeaderWriter {
+    /// struct definitions, each one after the structs it contains
+    types: String,
+    functions: String,
+    written: HashSet<String>,
+    tuples: HashMap<String, String>,
+}
+
+impl HeaderWriter {
+    fn write_struct(&mut self, s: &StructStmt, structs: &HashMap<String, StructStmt>) {
+        if !self.written.insert(s.name.clone()) {
+            return;
+        }
+        let fields: Vec<(String, ValueFlag)> = s
+            .fields
+            .iter()
+            .map(|field| (field.name.clone(), ValueFlag::from_ty(field.ty.clone())))
+            .collect();
+        // the structs contained by value are defined first
+        for (_, ty) in fields.iter() {
+            self.write_dependencies(ty, structs);
+        }
+
+        let mut definition = comment(&s.doc, "");
+        definition.push_str(&format!("struct {} {{\n", s.name));
+        for ((name, ty), field) in fields.iter().zip(s.fields.iter()) {
+            definition.push_str(&comment(&field.doc, "    "));
+            definition.push_str(&format!("    {};\n", self.declaration(ty, name)));
+        }
+        definition.push_str("};\n\n");
+        self.types.push_str(&definition);
+    }
+
+    fn write_dependencies(&mut self, ty: &ValueFlag, structs: &HashMap<String, StructStmt>) {
+        match ty {
+            ValueFlag::Struct(name) | ValueFlag::StructInstance(name) => {
+                if let Some(s) = structs.get(name) {
+                    self.write_struct(s, structs);
+                }
+            }
+            ValueFlag::List(ty, _) => self.write_dependencies(ty, structs),
+            ValueFlag::Tuple(types) => {
+                types
+                    .iter()
+                    .for_each(|ty| self.write_dependencies(ty, structs));
+                self.tuple(types);
+            }
+            _ => {}
+        }
+    }
+
+    /// name of the struct standing for a tuple type, defined on first use
+    fn tuple(&mut self, types: &[ValueFlag]) -> String {
+        let key = ValueFlag::Tuple(types.to_vec()).to_string();
+        if let Some(name) = self.tuples.get(&key) {
+            return name.clone();
+        }
+        let name = format!("popper_tuple{}", self.tuples.len());
+        let mut definition = format!("/* {} */\ntypedef struct {} {{\n", key, name);
+        for (i, ty) in types.iter().enumerate() {
+            definition.push_str(&format!(
+                "    {};\n",
+                self.declaration(ty, &format!("_{}", i))
+            ));
+        }
+        definition.push_str(&format!("}} {};\n\n", name));
+        self.types.push_str(&definition);
+        self.tuples.insert(key, name.clone());
+        name
+    }
+
+    /// C declaration of `name` with the type `ty`, arrays needing the size after the name
+    fn declaration(&mut self, ty: &ValueFlag, name: &str) -> String {
+        match ty {
+            ValueFlag::List(elem, len) => self.declaration(elem, &format!("{}[{}]", name, len)),
+            ty => with_name(&self.c_type(ty), name),
+        }
+    }
+
+    fn c_type(&mut self, ty: &ValueFlag) -> String {
+        match ty {
+            ValueFlag::Integer => "int32_t".to_string(),
+            ValueFlag::SizedInteger(kind) => int_type(*kind).to_string(),
+            ValueFlag::Char => "popper_char".to_string(),
+            ValueFlag::Boolean => "popper_bool".to_string(),
+            ValueFlag::Float => "float".to_string(),
+            ValueFlag::None => "void".to_string(),
+            ValueFlag::String => "popper_string *".to_string(),
+            ValueFlag::Vec(_) => "popper_vec *".to_string(),
+            ValueFlag::Slice(_) => "popper_slice *".to_string(),
+            ValueFlag::Pointer(ty) => match ty.as_ref() {
+                ValueFlag::List(elem, _) => with_name(&self.c_type(elem), "*"),
+                ty => with_name(&self.c_type(ty), "*"),
+            },
+            ValueFlag::Struct(name) | ValueFlag::StructInstance(name) => name.clone(),
+            ValueFlag::Tuple(types) => self.tuple(types),
+            ValueFlag::List(elem, _) => with_name(&self.c_type(elem), "*"),
+            ValueFlag::Function(..) | ValueFlag::Module(_) => "void *".to_string(),
+        }
+    }
+
+    fn write_function(
+        &mut self,
+        name: &str,
+        arg_names: &[String],
+        args: &[ValueFlag],
+        return_type: &ValueFlag,
+        is_var_args: bool,
+        doc: &Option<String>,
+    ) {
+        if let Some(ty) = args
+            .iter()
+            .chain(std::iter::once(return_type))
+            .find(|ty| !passed_by_value(ty))
+        {
+            self.functions.push_str(&format!(
+                "/* `{}` is left out: `{}` can't be passed by value to or from C */\n\n",
+                name, ty
+            ));
+            return;
+        }
+
+        let mut params: Vec<String> = arg_names
+            .iter()
+            .zip(args.iter())
+            .map(|(arg, ty)| self.declaration(ty, arg))
+            .collect();
+        if is_var_args {
+            params.push("...".to_string());
+        }
+        if params.is_empty() {
+            params.push("void".to_string());
+        }
+        let return_type = self.c_type(return_type);
+        self.functions.push_str(&comment(doc, ""));
+        self.functions.push_str(&format!(
+            "{}({});\n\n",
+            with_name(&return_type, name),
+            params.join(", ")
+        ));
+    }
+}
+
+/// if a value of type `ty` is passed the same way by Popper and by C
+fn passed_by_value(ty: &ValueFlag) -> bool {
+    !matches!(
+        ty,
+        ValueFlag::Struct(_)
+            | ValueFlag::StructInstance(_)
+            | ValueFlag::Tuple(_)
+            | ValueFlag::List(..)
+            | ValueFlag::Function(..)
+            | ValueFlag::Module(_)
+    )
+}
+
+/// `ty` followed by `name`, pointers being written `T *name`
+fn with_name(ty: &str, name: &str) -> String {
+    if ty.ends_with('*') {
+        format!("{}{}", ty, name)
+    } else {
+        format!("{} {}", ty, name)
+    }
+}
+
+fn int_type(kind: IntKind) -> &'static str {
+    match kind {
+        IntKind::I8 => "int8_t",
+        IntKind::I16 => "int16_t",
+        IntKind::I32 => "int32_t",
+        IntKind::I64 => "int64_t",
+        IntKind::U8 => "uint8_t",
+        IntKind::U16 => "uint16_t",
+        IntKind::U32 => "uint32_t",
+        IntKind::U64 => "uint64_t",
+        IntKind::USize => "size_t",
+    }
+}
+
+/// documentation as a C comment
+fn comment(doc: &Option<String>, indent: &str) -> String {
+    match doc {
+        Some(doc) => {
+            let mut comment = format!("{}/**\n", indent);
+            for line in doc.lines() {
+                if line.is_empty() {
+                    comment.push_str(&format!("{} *\n", indent));
+                } else {
+                    // the comment would end in the middle of the doc
+                    comment.push_str(&format!("{} * {}\n", indent, line.replace("*/", "* /")));
+                }
+            }
+            comment.push_str(&format!("{} */\n", indent));
+            comment
+        }
+        None => String::new(),
+    }
+}
@@ -1,12 +1,30 @@
+pub mod bindgen;
+pub mod cache;
+pub mod dap;
+pub mod doc;
+pub mod engine;
+pub mod header;
+pub mod lsp;
+pub mod manifest;
+pub mod module_loader;
+pub mod test_runner;
+
 use std::process::Output;
 
+pub use engine::Engine;
 use popper_ast::Statement;
-use popper_codegen::{output::Output as CompilerOutput, Compiler};
+pub use popper_codegen::library::Library;
+pub use popper_codegen::target::Target;
+use popper_codegen::{output::Output as CompilerOutput, runtime::runtime_sources, Compiler};
 use popper_parser::parser::parse;
 // use popper_codegen::compiler::Compiler;
+use cache::Cache;
+use manifest::{Link, Manifest};
+use module_loader::{Diagnostic, ModuleLoader};
 use popper_error::generate_color;
-use popper_semantic_analyzer::analyze;
-use std::path::Path;
+use popper_semantic_analyzer::{analyze, analyze_module, analyze_types};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 // use popper_inkwell::compiler::Compiler as InkwellCompiler;
 // use popper_inkwell::Context;
 
@@ -21,6 +39,27 @@ pub fn get_ast(input: &str, file: &str) -> Option<Vec<Statement>> {
     parse(input, file)
 }
 
+///
+/// format_source is used to format a file, reporting the parse error if it has one,
+/// or the comment it would lose
+/// # Arguments
+/// * `input` - input string
+/// * `file` - file name
+///
+/// return: `Option<String>`
+pub fn format_source(input: &str, file: &str) -> Option<String> {
+    match popper_fmt::format(parse(input, file)?, input) {
+        Ok(formatted) => Some(formatted),
+        Err(comment) => {
+            eprintln!(
+                "{}:{}: unable to keep the comment `{}` there, the file isn't formatted",
+                file, comment.line, comment.text
+            );
+            None
+        }
+    }
+}
+
 ///
 /// check_program is used to check program
 /// # Arguments
@@ -48,6 +87,22 @@ pub fn check_program(ast: Vec<Statement>, source: &str, file_name: &str) -> bool
     }
 }
 
+///
+/// program_diagnostics is used to check program, keeping its errors instead of
+/// reporting them
+/// # Arguments
+/// * `ast` - ast
+/// * `file_name` - file name, the one of the diagnostics
+///
+/// return: the errors of the program, none when it's valid
+pub fn program_diagnostics(ast: Vec<Statement>, file_name: &str) -> Vec<Diagnostic> {
+    analyze(ast)
+        .into_iter()
+        .filter_map(Result::err)
+        .map(|err| Diagnostic::new(Path::new(file_name), err.as_ref()))
+        .collect()
+}
+
 // pub fn compile_to_inkwell_llvm<'a>(mir: Module) -> (String, Vec<String>) {
 //     let context = Context::create();
 //     let mut compiler = InkwellCompiler::new(mir, &context);
@@ -55,12 +110,305 @@ pub fn check_program(ast: Vec<Statement>, source: &str, file_name: &str) -> bool
 //     (compiler.build(), compiler.get_used_cdylibs())
 // }
 
-pub fn compile(ast: Vec<Statement>, file_name: &str, debug: bool) -> CompilerOutput {
+///
+/// compile is used to compile the ast to LLVM
+/// # Arguments
+/// * `ast` - ast
+/// * `source` - source code, used to locate the failing runtime checks and assertions
+/// * `file_name` - file name
+/// * `debug` - debug build
+/// * `checks` - emit runtime checks: bounds, division by zero and null pointers
+/// * `target` - machine the code is compiled for
+pub fn compile(
+    ast: Vec<Statement>,
+    source: &str,
+    file_name: &str,
+    debug: bool,
+    checks: bool,
+    target: &Target,
+) -> CompilerOutput {
+    // the program was checked, only the types are wanted
+    let (types, _) = analyze_types(ast.clone(), HashMap::new());
     let mut compiler = Compiler::new(ast, file_name);
+    compiler.set_target(target.clone());
+    compiler.set_types(types);
+    compiler.set_source(source);
+    if checks {
+        compiler.enable_checks(source);
+    }
     compiler.compile(debug)
 }
 
-pub fn execute_llvm(llvm: String, file_name: String, target_path: String, debug: bool) {
+///
+/// build_runtime is used to compile the runtime library into `<target>/libs`, or
+/// `<target>/libs/<triple>` when cross-compiling
+/// # Arguments
+/// * `target_path` - target directory
+/// * `target` - machine the runtime is compiled for
+/// * `debug` - print the output of the C compiler
+///
+/// return: the object files to link with, an error when the C compiler failed
+pub fn build_runtime(
+    target_path: &Path,
+    target: &Target,
+    debug: bool,
+) -> Result<Vec<std::path::PathBuf>, ToolError> {
+    use std::process::Command;
+
+    let mut libs_path = target_path.join("libs");
+    if !target.is_host() {
+        libs_path = libs_path.join(&target.triple);
+    }
+    std::fs::create_dir_all(&libs_path).expect("Cannot create libs directory");
+
+    let mut objects = Vec::new();
+    for (name, source) in runtime_sources(target) {
+        let source_path = libs_path.join(name);
+        // headers are included by the sources
+        if name.ends_with(".h") {
+            std::fs::write(&source_path, source).expect("Cannot write file");
+            continue;
+        }
+        let object_path = source_path.with_extension("o");
+
+        let up_to_date = object_path.exists()
+            && std::fs::read_to_string(&source_path).is_ok_and(|x| x == *source);
+        if !up_to_date {
+            std::fs::write(&source_path, source).expect("Cannot write file");
+            detail_output(
+                "clang runtime",
+                Command::new("clang")
+                    .args(target.clang_args())
+                    .args(target.pic_args())
+                    .arg("-c")
+                    .arg(source_path.clone())
+                    .arg("-o")
+                    .arg(object_path.clone())
+                    .output()
+                    .expect("failed to execute process `clang` "),
+                debug,
+            )
+            .inspect_err(|_| {
+                // the source is compiled again by the next build
+                let _ = std::fs::remove_file(&source_path);
+            })?;
+        }
+        objects.push(object_path);
+    }
+    Ok(objects)
+}
+
+/// what `popper build` produces
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CrateType {
+    /// an executable
+    #[default]
+    Bin,
+    /// a static library, `lib<name>.a`, with its C header
+    StaticLib,
+    /// a shared library, `lib<name>.so`, with its C header
+    CDylib,
+}
+
+impl std::str::FromStr for CrateType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bin" => Ok(CrateType::Bin),
+            "staticlib" => Ok(CrateType::StaticLib),
+            "cdylib" => Ok(CrateType::CDylib),
+            s => Err(format!(
+                "unknown crate type `{}`, expected `bin`, `staticlib` or `cdylib`",
+                s
+            )),
+        }
+    }
+}
+
+///
+/// build_package is used to compile a package, with its dependencies, to an executable
+/// or a library named after the package. Libraries come with a C header, `<name>.h`,
+/// declaring their `export func`s.
+/// Each module is checked and compiled to its own object file, cached in `<target>/cache`:
+/// only the modules that changed, or whose imports changed, are rebuilt
+/// # Arguments
+/// * `manifest` - manifest of the package
+/// * `lib_paths` - directories searched for imported modules, after the source directories
+/// * `target_path` - target directory
+/// * `debug` - debug build
+/// * `checks` - emit runtime checks
+/// * `target` - machine the package is compiled for
+/// * `crate_type` - what is built
+///
+/// return: the path of the executable or of the library, if the package compiled
+pub fn build_package(
+    manifest: &Manifest,
+    lib_paths: Vec<PathBuf>,
+    target_path: &Path,
+    debug: bool,
+    checks: bool,
+    target: &Target,
+    crate_type: CrateType,
+) -> Option<PathBuf> {
+    if crate_type == CrateType::CDylib && target.is_wasm() {
+        eprintln!("WebAssembly has no shared libraries, build a `bin` module instead");
+        return None;
+    }
+
+    let dependencies = match manifest.dependencies() {
+        Ok(dependencies) => dependencies,
+        Err(err) => {
+            eprintln!("{}", err);
+            return None;
+        }
+    };
+
+    let cache = Cache::new(target_path);
+    let mut loader =
+        ModuleLoader::new([manifest.source_dirs(), lib_paths].concat()).with_cache(cache.clone());
+    let mut link = manifest.link();
+    for (name, dependency) in dependencies {
+        loader.add_package(name, dependency.source_dirs());
+        link.extend(dependency.link());
+    }
+    loader.load(&manifest.entry())?;
+
+    let mut interfaces = HashMap::new();
+    let mut header_modules = Vec::new();
+    let mut objects = Vec::new();
+    for module in loader.modules() {
+        let file_name = module.file.display().to_string();
+        let interface = match cache.load_interface(&module.key) {
+            Some(interface) => interface,
+            None => match analyze_module(module.stmts.clone(), interfaces.clone()) {
+                Ok(interface) => {
+                    cache.store_interface(&module.key, &interface);
+                    interface
+                }
+                Err(errs) => {
+                    errs.into_iter()
+                        .for_each(|x| x.report(generate_color(), &module.source, &file_name));
+                    return None;
+                }
+            },
+        };
+        header_modules.push((module.stmts.clone(), interface.clone()));
+        interfaces.insert(module.file.clone(), interface);
+        link.exports.extend(exported_functions(&module.stmts));
+        link.add_libraries(&external_libraries(&module.stmts, &module.file));
+
+        let object = cache.object_path(&module.key, &module.file, debug, checks, target);
+        if !cache.has_object(&object) {
+            let (types, _) = analyze_types(module.stmts.clone(), interfaces.clone());
+            let mut compiler = Compiler::new(module.stmts.clone(), &file_name);
+            compiler.set_target(target.clone());
+            compiler.set_types(types);
+            compiler.link_modules();
+            compiler.set_source(&module.source);
+            if checks {
+                compiler.enable_checks(&module.source);
+            }
+            let llvm = compiler.compile(debug).print_llvm_to_string();
+            let file_ll = object.with_extension("ll");
+            let compiled = compile_object(llvm, &file_ll, &object, debug, target);
+            // only the object is reused
+            let _ = std::fs::remove_file(file_ll);
+            if let Err(err) = compiled {
+                // an object left by a failed build isn't cached
+                let _ = std::fs::remove_file(&object);
+                eprintln!("{}", err);
+                return None;
+            }
+        }
+        objects.push(object);
+    }
+
+    let name = &manifest.package.name;
+    let output = match crate_type {
+        CrateType::Bin => target_path
+            .join(name)
+            .with_extension(target.exe_extension()),
+        CrateType::StaticLib => target_path.join(format!("lib{}.a", name)),
+        CrateType::CDylib => target_path.join(format!("lib{}.{}", name, target.dylib_extension())),
+    };
+    let built = match crate_type {
+        CrateType::Bin => link_executable(&objects, &output, target_path, debug, &link, target),
+        CrateType::StaticLib => archive(&objects, &output, target_path, debug, target),
+        CrateType::CDylib => {
+            link_command(&objects, target_path, debug, &link, target).and_then(|mut command| {
+                detail_output(
+                    "clang",
+                    command
+                        .arg("-shared")
+                        .arg("-o")
+                        .arg(&output)
+                        .output()
+                        .expect("failed to execute process `clang` "),
+                    debug,
+                )
+            })
+        }
+    };
+    if let Err(err) = built {
+        eprintln!("{}", err);
+        return None;
+    }
+    if crate_type != CrateType::Bin {
+        std::fs::write(
+            target_path.join(name).with_extension("h"),
+            header::generate_header(name, &header_modules),
+        )
+        .expect("Cannot write file");
+    }
+    Some(output)
+}
+
+/// put object files in a static library, with the runtime
+fn archive(
+    objects: &[PathBuf],
+    file_lib_path: &Path,
+    target_path: &Path,
+    debug: bool,
+    target: &Target,
+) -> Result<(), ToolError> {
+    use std::process::Command;
+
+    let runtime = build_runtime(target_path, target, debug)?;
+    // the archive would keep the members of a previous build
+    let _ = std::fs::remove_file(file_lib_path);
+    detail_output(
+        "ar",
+        Command::new(if target.is_host() { "ar" } else { "llvm-ar" })
+            .arg("rcs")
+            .arg(file_lib_path)
+            .args(objects)
+            .args(runtime)
+            .output()
+            .expect("failed to execute process `ar` "),
+        debug,
+    )
+}
+
+///
+/// build_executable is used to compile LLVM IR to an executable in `target_path`
+/// # Arguments
+/// * `llvm` - LLVM IR
+/// * `file_name` - name of the compiled file, the executable is named after it
+/// * `target_path` - target directory
+/// * `debug` - print the output of the tools
+/// * `link` - libraries and objects to link with, besides the runtime
+/// * `target` - machine the LLVM IR is compiled for
+///
+/// return: the path of the executable, an error when a tool failed
+pub fn build_executable(
+    llvm: String,
+    file_name: String,
+    target_path: String,
+    debug: bool,
+    link: &Link,
+    target: &Target,
+) -> Result<PathBuf, ToolError> {
     use std::process::Command;
 
     let file_name =
@@ -68,7 +416,7 @@ pub fn execute_llvm(llvm: String, file_name: String, target_path: String, debug:
     let target_path = Path::new(&target_path).to_path_buf();
     let file_o = file_name.with_extension("o");
     let file_ll = file_name.with_extension("ll");
-    let file_exe = file_name.with_extension("");
+    let file_exe = file_name.with_extension(target.exe_extension());
 
     if !target_path.exists() {
         detail_output(
@@ -78,68 +426,293 @@ pub fn execute_llvm(llvm: String, file_name: String, target_path: String, debug:
                 .output()
                 .expect("failed to execute process"),
             debug,
-        );
+        )?;
     }
 
     let file_o_path = target_path.join(file_o);
     let file_ll_path = target_path.join(file_ll);
     let file_exe_path = target_path.join(file_exe);
 
-    if !file_ll_path.exists() {
-        Command::new("touch")
-            .arg(file_ll_path.clone())
+    compile_object(llvm, &file_ll_path, &file_o_path, debug, target)?;
+    link_executable(
+        std::slice::from_ref(&file_o_path),
+        &file_exe_path,
+        &target_path,
+        debug,
+        link,
+        target,
+    )?;
+
+    let _ = detail_output(
+        "rm o",
+        Command::new("rm")
+            .arg(file_o_path.clone())
             .output()
-            .expect("failed to execute process");
-    }
-    std::fs::write(file_ll_path.clone(), llvm).expect("Cannot write file");
+            .expect("failed to execute process `rm` "),
+        debug,
+    );
+
+    Command::new("rm")
+        .arg(file_ll_path.clone())
+        .output()
+        .expect("failed to execute process `rm` ");
+
+    Ok(file_exe_path)
+}
+
+///
+/// compile_object is used to compile LLVM IR to an object file for `target`
+/// # Arguments
+/// * `llvm` - LLVM IR, compiled for `target`
+/// * `file_ll_path` - where the IR is written
+/// * `file_o_path` - path of the object file
+/// * `debug` - print the output of `llc`
+/// * `target` - machine the object is compiled for
+///
+/// return: an error when `llc` failed
+pub fn compile_object(
+    llvm: String,
+    file_ll_path: &Path,
+    file_o_path: &Path,
+    debug: bool,
+    target: &Target,
+) -> Result<(), ToolError> {
+    use std::process::Command;
+
+    std::fs::write(file_ll_path, llvm).expect("Cannot write file");
 
     detail_output(
         "llc",
         Command::new("llc")
-            .arg(file_ll_path.clone())
+            .args(target.llc_args())
+            .arg(file_ll_path)
             .arg("-filetype=obj")
             .arg("-o")
-            .arg(file_o_path.clone())
+            .arg(file_o_path)
             .output()
             .expect("failed to execute process `llc` "),
         debug,
-    );
+    )
+}
+
+/// link object files with the runtime, built in `target_path`
+fn link_executable(
+    objects: &[PathBuf],
+    file_exe_path: &Path,
+    target_path: &Path,
+    debug: bool,
+    link: &Link,
+    target: &Target,
+) -> Result<(), ToolError> {
+    let mut command = link_command(objects, target_path, debug, link, target)?;
+    if target.is_wasm() {
+        // `extern` functions are imported from the host
+        command
+            .arg("-Wl,--allow-undefined")
+            .arg("-Wl,--export-if-defined=main")
+            .args(link.exports.iter().map(|x| format!("-Wl,--export={}", x)));
+        if target.is_freestanding() {
+            command.arg("-Wl,--no-entry");
+        }
+    }
 
     detail_output(
         "clang",
-        Command::new("clang")
-            .arg(file_o_path.clone())
+        command
             .arg("-o")
-            .arg(file_exe_path.clone())
+            .arg(file_exe_path)
             .output()
             .expect("failed to execute process `gcc` "),
         debug,
-    );
+    )
+}
 
-    detail_output(
-        "rm o",
-        Command::new("rm")
-            .arg(file_o_path.clone())
-            .output()
-            .expect("failed to execute process `rm` "),
-        debug,
-    );
+/// `clang` linking object files with the runtime, built in `target_path`
+fn link_command(
+    objects: &[PathBuf],
+    target_path: &Path,
+    debug: bool,
+    link: &Link,
+    target: &Target,
+) -> Result<std::process::Command, ToolError> {
+    let runtime = build_runtime(target_path, target, debug)?;
 
-    Command::new("rm")
-        .arg(file_ll_path.clone())
-        .output()
-        .expect("failed to execute process `rm` ");
+    let mut command = std::process::Command::new("clang");
+    command
+        .args(target.clang_args())
+        .args(objects)
+        .args(runtime)
+        .args(link.objects.iter())
+        .args(link.libs.iter().map(|x| format!("-l{}", x)));
+    if !target.is_wasm() {
+        // shared libraries given by path are found next to where they were linked from
+        for object in link.objects.iter() {
+            if !matches!(
+                object.extension().and_then(|x| x.to_str()),
+                Some("so" | "dylib")
+            ) {
+                continue;
+            }
+            if let Some(dir) = object.parent().and_then(|x| x.canonicalize().ok()) {
+                command.arg(format!("-Wl,-rpath,{}", dir.display()));
+            }
+        }
+    }
+    Ok(command)
+}
 
-    detail_output(
-        "exec",
-        Command::new(format!("./{}", file_exe_path.clone().to_str().unwrap()))
-            .output()
-            .expect("failed to execute process your program "),
-        debug,
-    );
+/// libraries of the `external` blocks of a module, `file` being its path
+pub fn external_libraries(stmts: &[Statement], file: &Path) -> Vec<Library> {
+    let dir = file.parent().unwrap_or(Path::new(""));
+    stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Statement::External(external) => Some(Library::new(&external.file, dir)),
+            _ => None,
+        })
+        .collect()
 }
 
-fn detail_output(name: &str, output: Output, debug: bool) {
+/// functions exported by the WebAssembly modules: the `export func`s of `stmts`
+/// and of the modules they import
+pub fn exported_functions(stmts: &[Statement]) -> Vec<String> {
+    let mut exports = Vec::new();
+    for stmt in stmts {
+        match stmt {
+            Statement::Function(f) if f.is_export => exports.push(f.name.clone()),
+            Statement::Import(i) => exports.extend(exported_functions(&i.module_stmts)),
+            _ => {}
+        }
+    }
+    exports.sort();
+    exports.dedup();
+    exports
+}
+
+///
+/// execute_llvm is used to build LLVM IR to an executable and run it, its standard input
+/// and outputs being the ones of the current process
+/// # Arguments
+/// * `llvm` - LLVM IR
+/// * `file_name` - name of the compiled file, the executable is named after it
+/// * `target_path` - target directory
+/// * `debug` - print the output of the tools
+/// * `link` - libraries and objects to link with, like the ones of `external` blocks
+/// * `args` - arguments given to the program
+///
+/// return: the exit code of the program
+pub fn execute_llvm(
+    llvm: String,
+    file_name: String,
+    target_path: String,
+    debug: bool,
+    link: &Link,
+    args: &[String],
+) -> i32 {
+    use std::process::Command;
+
+    let file_exe_path =
+        match build_executable(llvm, file_name, target_path, debug, link, &Target::host()) {
+            Ok(file_exe_path) => file_exe_path,
+            Err(err) => {
+                eprintln!("{}", err);
+                return 1;
+            }
+        };
+
+    let status = Command::new(Path::new(".").join(&file_exe_path))
+        .args(args)
+        .status()
+        .expect("failed to execute process your program ");
+    exit_code(status)
+}
+
+///
+/// compile_to_bytecode is used to compile the ast to the bytecode of the VM, which
+/// runs it without LLVM nor a C compiler
+/// # Arguments
+/// * `ast` - ast, checked
+/// * `source` - source code, used to locate the panics
+/// * `file_name` - file name
+///
+/// return: the bytecode module, `None` when the program uses what the VM doesn't run
+pub fn compile_to_bytecode(
+    ast: &[Statement],
+    source: &str,
+    file_name: &str,
+) -> Option<popper_vm::Module> {
+    match popper_vm::compile(ast, file_name, source) {
+        Ok(module) => Some(module),
+        Err(err) => {
+            eprintln!("{}: {}", file_name, err);
+            None
+        }
+    }
+}
+
+///
+/// execute_bytecode is used to run a bytecode module on the VM, a panic of the
+/// program being printed like the compiled programs print it
+/// # Arguments
+/// * `module` - bytecode module
+/// * `file_name` - path of the program, its first argument
+/// * `args` - arguments given to the program
+/// * `limits` - limits of what the program may use, stopping it when it goes over one
+///
+/// return: the exit code of the program
+pub fn execute_bytecode(
+    module: popper_vm::Module,
+    file_name: &str,
+    args: &[String],
+    limits: popper_vm::Limits,
+) -> i32 {
+    let args = std::iter::once(file_name.to_string())
+        .chain(args.iter().cloned())
+        .collect();
+    let result =
+        popper_vm::Vm::new(module).and_then(|vm| vm.with_args(args).with_limits(limits).run_main());
+    match result {
+        Ok(code) => code,
+        Err(popper_vm::VmError::Panic(panic)) => {
+            eprintln!("{}", panic);
+            popper_vm::Panic::STATUS
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            1
+        }
+    }
+}
+
+/// exit code of a process, `128 + signal` when it was killed by a signal like shells do
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+/// a tool run to build the program, like `llc` or `clang`, which failed
+#[derive(Debug)]
+pub struct ToolError {
+    pub name: String,
+    pub status: std::process::ExitStatus,
+}
+
+impl std::fmt::Display for ToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}` failed with {}", self.name, self.status)
+    }
+}
+
+impl std::error::Error for ToolError {}
+
+/// print the output of the tool `name`, an error when it failed
+fn detail_output(name: &str, output: Output, debug: bool) -> Result<(), ToolError> {
     let stdout = String::from_utf8(output.stdout).unwrap();
     let stderr = String::from_utf8(output.stderr).unwrap();
     if debug {
@@ -150,4 +723,11 @@ fn detail_output(name: &str, output: Output, debug: bool) {
     } else if !stdout.is_empty() {
         println!("{}", stdout);
     }
+    if !output.status.success() {
+        return Err(ToolError {
+            name: name.to_string(),
+            status: output.status,
+        });
+    }
+    Ok(())
 }
@@ -0,0 +1,593 @@
+use crate::manifest::Manifest;
+use crate::module_loader::{Diagnostic, ModuleLoader};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as LspNotification, PublishDiagnostics,
+};
+use lsp_types::request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, Request as LspRequest,
+};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DiagnosticSeverity, DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse,
+    GotoDefinitionParams, GotoDefinitionResponse, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, Location, MarkupContent, MarkupKind, OneOf, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, SymbolKind, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use popper_ast::{Span, Statement};
+use popper_flag::{Environment, ValueFlag, VariableFlag};
+use popper_semantic_analyzer::{analyze_module, analyze_symbols, Symbols};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+pub type LspError = Box<dyn std::error::Error + Send + Sync>;
+
+/// capabilities of the server, sent to the client on initialization
+pub fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![".".to_string(), ">".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    }
+}
+
+///
+/// run is used to serve the Language Server Protocol over `connection`, until the client
+/// exits.
+/// The server runs over stdio with `Connection::stdio()`, or in process with
+/// `Connection::memory()`
+/// # Arguments
+/// * `connection` - connection to the client
+/// * `lib_paths` - directories searched for imported modules, before the ones of `POPPER_PATH`
+pub fn run(connection: Connection, lib_paths: Vec<PathBuf>) -> Result<(), LspError> {
+    connection.initialize(serde_json::to_value(capabilities())?)?;
+    Server::new(&connection, lib_paths).main_loop()
+}
+
+/// an open document
+struct Document {
+    source: String,
+    /// path the document is loaded as, see [`document_path`]
+    path: PathBuf,
+    /// statements of the last version that parsed
+    stmts: Vec<Statement>,
+    /// symbols of the last version that was analyzed, kept while the document doesn't
+    /// parse so completion works on the line being typed
+    symbols: Symbols,
+    /// environment of the modules imported by the last version analyzed, by file
+    modules: Vec<(PathBuf, Environment)>,
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    lib_paths: Vec<PathBuf>,
+    documents: HashMap<Url, Document>,
+    /// documents with diagnostics published, so they are cleared once fixed
+    published: HashSet<Url>,
+}
+
+impl<'a> Server<'a> {
+    fn new(connection: &'a Connection, lib_paths: Vec<PathBuf>) -> Self {
+        Self {
+            connection,
+            lib_paths,
+            documents: HashMap::new(),
+            published: HashSet::new(),
+        }
+    }
+
+    fn main_loop(&mut self) -> Result<(), LspError> {
+        for msg in &self.connection.receiver {
+            match msg {
+                Message::Request(req) => {
+                    if self.connection.handle_shutdown(&req)? {
+                        return Ok(());
+                    }
+                    self.handle_request(req)?;
+                }
+                Message::Notification(not) => self.handle_notification(not)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// answer a request, with an error when its parameters are invalid
+    fn handle_request(&mut self, req: Request) -> Result<(), LspError> {
+        let id = req.id.clone();
+        let result = match req.method.as_str() {
+            HoverRequest::METHOD => params(req).and_then(|x| serde_json::to_value(self.hover(x))),
+            GotoDefinition::METHOD => {
+                params(req).and_then(|x| serde_json::to_value(self.definition(x)))
+            }
+            Completion::METHOD => {
+                params(req).and_then(|x| serde_json::to_value(self.completion(x)))
+            }
+            DocumentSymbolRequest::METHOD => {
+                params(req).and_then(|x| serde_json::to_value(self.document_symbols(x)))
+            }
+            method => {
+                let response = Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unknown request `{}`", method),
+                );
+                self.connection.sender.send(Message::Response(response))?;
+                return Ok(());
+            }
+        };
+        let response = match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err(err) => Response::new_err(
+                id,
+                ErrorCode::InvalidParams as i32,
+                format!("invalid parameters: {}", err),
+            ),
+        };
+        self.connection.sender.send(Message::Response(response))?;
+        Ok(())
+    }
+
+    /// handle a notification, ignoring it when its parameters are invalid as there's
+    /// nobody to answer
+    fn handle_notification(&mut self, not: Notification) -> Result<(), LspError> {
+        let method = not.method.clone();
+        match method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let Some(params) = notification_params::<lsp_types::DidOpenTextDocumentParams>(not)
+                else {
+                    return Ok(());
+                };
+                let document = params.text_document;
+                self.open(document.uri.clone(), document.text);
+                self.analyze(&document.uri)?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let Some(params) =
+                    notification_params::<lsp_types::DidChangeTextDocumentParams>(not)
+                else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                // the documents are synced in full, the last change holds the whole text
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.open(uri.clone(), change.text);
+                    self.analyze(&uri)?;
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let Some(params) =
+                    notification_params::<lsp_types::DidCloseTextDocumentParams>(not)
+                else {
+                    return Ok(());
+                };
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                if self.published.remove(&uri) {
+                    self.publish(uri, Vec::new())?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// store the new content of a document, keeping what was learned from the previous one
+    fn open(&mut self, uri: Url, source: String) {
+        match self.documents.get_mut(&uri) {
+            Some(document) => document.source = source,
+            None => {
+                let document = Document {
+                    source,
+                    path: document_path(&uri),
+                    stmts: Vec::new(),
+                    symbols: Symbols::default(),
+                    modules: Vec::new(),
+                };
+                self.documents.insert(uri, document);
+            }
+        }
+    }
+
+    /// parse and analyze a document with the modules it imports, then publish the
+    /// errors found in each of them
+    fn analyze(&mut self, uri: &Url) -> Result<(), LspError> {
+        let document = &self.documents[uri];
+        let path = document.path.clone();
+        let source = document.source.clone();
+
+        let mut loader = self.loader(&path);
+        let loaded = loader.load_source(&path, source).is_some();
+        let mut diagnostics = loader.diagnostics().to_vec();
+
+        if loaded {
+            let mut interfaces = HashMap::new();
+            for module in loader.modules() {
+                if module.file == path {
+                    let (symbols, errs) = analyze_symbols(module.stmts.clone(), interfaces.clone());
+                    diagnostics.extend(errs.iter().map(|x| Diagnostic::new(&path, x.as_ref())));
+
+                    let document = self.documents.get_mut(uri).unwrap();
+                    document.stmts = module.stmts.clone();
+                    document.symbols = symbols;
+                    document.modules = interfaces.into_iter().collect();
+                    break;
+                }
+
+                match analyze_module(module.stmts.clone(), interfaces.clone()) {
+                    Ok(interface) => {
+                        interfaces.insert(module.file.clone(), interface);
+                    }
+                    Err(errs) => {
+                        // the modules importing this one can't be analyzed
+                        diagnostics.extend(
+                            errs.iter()
+                                .map(|x| Diagnostic::new(&module.file, x.as_ref())),
+                        );
+                        break;
+                    }
+                }
+            }
+        }
+
+        let mut by_file: HashMap<PathBuf, Vec<Diagnostic>> = HashMap::new();
+        by_file.insert(path.clone(), Vec::new());
+        for diagnostic in diagnostics {
+            by_file
+                .entry(diagnostic.file.clone())
+                .or_default()
+                .push(diagnostic);
+        }
+
+        for (file, diagnostics) in by_file {
+            let uri = match Url::from_file_path(&file) {
+                _ if file == path => uri.clone(),
+                Ok(uri) => uri,
+                Err(()) => continue,
+            };
+            let source = self.source(&file);
+            let diagnostics: Vec<_> = diagnostics
+                .into_iter()
+                .map(|x| lsp_types::Diagnostic {
+                    range: range(&source, x.span),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("popper".to_string()),
+                    message: x.message,
+                    ..lsp_types::Diagnostic::default()
+                })
+                .collect();
+
+            // the document analyzed always gets its diagnostics, the modules it imports
+            // only when they have some or had some
+            if diagnostics.is_empty() {
+                if !self.published.remove(&uri) && file != path {
+                    continue;
+                }
+            } else {
+                self.published.insert(uri.clone());
+            }
+            self.publish(uri, diagnostics)?;
+        }
+        Ok(())
+    }
+
+    /// loader for `file`, seeing the open documents instead of their files.
+    /// When `file` is in a package, its source directories and dependencies are used
+    fn loader(&self, file: &Path) -> ModuleLoader {
+        let mut lib_paths = self.lib_paths.clone();
+        let mut packages = Vec::new();
+        if let Some(manifest) = file.parent().and_then(|x| Manifest::find(x).ok()) {
+            lib_paths = [manifest.source_dirs(), lib_paths].concat();
+            for (name, dependency) in manifest.dependencies().unwrap_or_default() {
+                packages.push((name, dependency.source_dirs()));
+            }
+        }
+
+        let mut loader = ModuleLoader::new(lib_paths).keep_diagnostics();
+        for (name, dirs) in packages {
+            loader.add_package(name, dirs);
+        }
+        for document in self.documents.values() {
+            loader.set_source(document.path.clone(), document.source.clone());
+        }
+        loader
+    }
+
+    /// content of `file`, open or on disk
+    fn source(&self, file: &Path) -> String {
+        self.documents
+            .values()
+            .find(|x| x.path == file)
+            .map(|x| x.source.clone())
+            .unwrap_or_else(|| std::fs::read_to_string(file).unwrap_or_default())
+    }
+
+    fn publish(&self, uri: Url, diagnostics: Vec<lsp_types::Diagnostic>) -> Result<(), LspError> {
+        let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+        let not = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.connection.sender.send(Message::Notification(not))?;
+        Ok(())
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let offset = offset(&document.source, position.position);
+        let (span, variable) = document.symbols.reference_at(offset)?;
+
+        let ty = variable.value.get_value()?;
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!("```popper\n{}: {}\n```", variable.name, ty),
+            }),
+            range: Some(range(&document.source, *span)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri;
+        let document = self.documents.get(&uri)?;
+        let offset = offset(&document.source, position.position);
+        let (_, variable) = document.symbols.reference_at(offset)?;
+
+        let is_local = document
+            .symbols
+            .env_at(offset)
+            .variables()
+            .iter()
+            .any(|x| is_same_variable(x, variable));
+        // otherwise, it may be an item of an imported module
+        let module = document.modules.iter().find(|(_, env)| {
+            !is_local
+                && env
+                    .variables()
+                    .iter()
+                    .any(|x| is_same_variable(x, variable))
+        });
+        let location = match module {
+            Some((file, _)) => {
+                let uri = Url::from_file_path(file).ok()?;
+                Location::new(uri, range(&self.source(file), variable.span))
+            }
+            None => Location::new(uri.clone(), range(&document.source, variable.span)),
+        };
+        Some(GotoDefinitionResponse::Scalar(location))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let document = self.documents.get(&position.text_document.uri)?;
+        let offset = offset(&document.source, position.position);
+        let env = document.symbols.env_at(offset);
+
+        let items = match receiver(&document.source, offset) {
+            Some(receiver) => {
+                let value = env.get_variable(receiver)?.value.get_value()?;
+                member_items(env, value)
+            }
+            None => {
+                let mut seen = HashSet::new();
+                env.get_all_variables_name()
+                    .into_iter()
+                    .filter(|x| seen.insert(x.clone()))
+                    .filter_map(|x| env.get_variable(&x))
+                    .map(variable_item)
+                    .collect()
+            }
+        };
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let document = self.documents.get(&params.text_document.uri)?;
+        let source = &document.source;
+        let detail = |name: &str| {
+            document
+                .symbols
+                .env
+                .get_variable(name)
+                .and_then(|x| x.value.get_value())
+                .map(|x| x.to_string())
+        };
+
+        let symbols = document
+            .stmts
+            .iter()
+            .filter_map(|stmt| match stmt {
+                Statement::Function(function) => Some(document_symbol(
+                    source,
+                    &function.name,
+                    detail(&function.name),
+                    SymbolKind::FUNCTION,
+                    function.span,
+                    None,
+                )),
+                Statement::Struct(struct_stmt) => {
+                    let fields = struct_stmt
+                        .fields
+                        .iter()
+                        .map(|field| {
+                            document_symbol(
+                                source,
+                                &field.name,
+                                Some(field.ty.type_kind.to_string()),
+                                SymbolKind::FIELD,
+                                field.span,
+                                None,
+                            )
+                        })
+                        .collect();
+                    Some(document_symbol(
+                        source,
+                        &struct_stmt.name,
+                        None,
+                        SymbolKind::STRUCT,
+                        struct_stmt.span,
+                        Some(fields),
+                    ))
+                }
+                _ => None,
+            })
+            .collect();
+        Some(DocumentSymbolResponse::Nested(symbols))
+    }
+}
+
+fn params<P: serde::de::DeserializeOwned>(message: impl Into<Message>) -> serde_json::Result<P> {
+    let params = match message.into() {
+        Message::Request(req) => req.params,
+        Message::Notification(not) => not.params,
+        Message::Response(_) => unreachable!(),
+    };
+    serde_json::from_value(params)
+}
+
+/// parameters of a notification, `None` after reporting them when they are invalid
+fn notification_params<P: serde::de::DeserializeOwned>(not: Notification) -> Option<P> {
+    let method = not.method.clone();
+    params(not)
+        .map_err(|err| eprintln!("invalid parameters for `{}`: {}", method, err))
+        .ok()
+}
+
+/// path a document is loaded as: its canonical path when it's a file, its URI otherwise,
+/// so documents which aren't saved are analyzed too
+fn document_path(uri: &Url) -> PathBuf {
+    match uri.to_file_path() {
+        Ok(path) => path.canonicalize().unwrap_or(path),
+        Err(()) => PathBuf::from(uri.as_str()),
+    }
+}
+
+fn is_same_variable(a: &VariableFlag, b: &VariableFlag) -> bool {
+    a.name == b.name && a.span == b.span
+}
+
+/// name before the `.` or `->` preceding the word being typed at `offset`, if any
+fn receiver(source: &str, offset: usize) -> Option<&str> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let before = source[..offset].trim_end_matches(is_ident);
+    let before = before
+        .strip_suffix('.')
+        .or_else(|| before.strip_suffix("->"))?;
+    let start = before.trim_end_matches(is_ident).len();
+    Some(&before[start..]).filter(|x| !x.is_empty())
+}
+
+/// items reachable with `.` on a value: the fields of a struct or the public items of
+/// a module
+fn member_items(env: &Environment, value: ValueFlag) -> Vec<CompletionItem> {
+    match value {
+        ValueFlag::Module(module) => module
+            .variables()
+            .iter()
+            .filter(|x| module.is_public(&x.name))
+            .map(variable_item)
+            .collect(),
+        ValueFlag::Struct(name) | ValueFlag::StructInstance(name) => env
+            .get_struct(&name)
+            .into_iter()
+            .flatten()
+            .filter(|(field, _)| !env.is_private_field(&name, field))
+            .map(|(field, ty)| CompletionItem {
+                label: field.clone(),
+                kind: Some(CompletionItemKind::FIELD),
+                detail: Some(ty.to_string()),
+                ..CompletionItem::default()
+            })
+            .collect(),
+        ValueFlag::Pointer(value) => member_items(env, *value),
+        _ => Vec::new(),
+    }
+}
+
+fn variable_item(variable: &VariableFlag) -> CompletionItem {
+    let value = variable.value.get_value();
+    let kind = match value {
+        Some(ValueFlag::Function(..)) => CompletionItemKind::FUNCTION,
+        Some(ValueFlag::Struct(_)) => CompletionItemKind::STRUCT,
+        Some(ValueFlag::Module(_)) => CompletionItemKind::MODULE,
+        _ => CompletionItemKind::VARIABLE,
+    };
+    CompletionItem {
+        label: variable.name.clone(),
+        kind: Some(kind),
+        detail: value.map(|x| x.to_string()),
+        ..CompletionItem::default()
+    }
+}
+
+#[allow(deprecated)] // `DocumentSymbol::deprecated` has to be set
+fn document_symbol(
+    source: &str,
+    name: &str,
+    detail: Option<String>,
+    kind: SymbolKind,
+    span: Span,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    // the name is the first occurrence of it in the item
+    let name_span = span
+        .extract_from_str(source)
+        .find(name)
+        .map(|x| Span::new(span.start + x, span.start + x + name.len()))
+        .unwrap_or(span);
+    DocumentSymbol {
+        name: name.to_string(),
+        detail,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: range(source, span),
+        selection_range: range(source, name_span),
+        children,
+    }
+}
+
+/// LSP position of a byte offset, in UTF-16 code units as the protocol expects
+fn position(source: &str, offset: usize) -> Position {
+    let mut offset = offset.min(source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |x| x + 1);
+    Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+/// byte offset of an LSP position
+fn offset(source: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match source[line_start..].find('\n') {
+            Some(x) => line_start += x + 1,
+            None => return source.len(),
+        }
+    }
+
+    let mut units = 0;
+    for (i, c) in source[line_start..].char_indices() {
+        if units >= position.character as usize || c == '\n' {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    source.len()
+}
+
+fn range(source: &str, span: Span) -> Range {
+    Range::new(position(source, span.start), position(source, span.end))
+}
@@ -1,8 +1,18 @@
 #![allow(clippy::upper_case_acronyms)]
 use clap::{Parser, Subcommand};
-use popper_compiler::check_program;
-use popper_compiler::get_ast;
-use popper_compiler::{compile, execute_llvm};
+use popper::bindgen::bindgen;
+use popper::check_program;
+use popper::dap;
+use popper::doc::generate_doc;
+use popper::format_source;
+use popper::get_ast;
+use popper::lsp;
+use popper::manifest::{Link, Manifest};
+use popper::module_loader::ModuleLoader;
+use popper::test_runner::run_tests;
+use popper::Target;
+use popper::{build_package, compile, compile_object, execute_llvm, CrateType};
+use popper::{compile_to_bytecode, execute_bytecode};
 use std::io::Write;
 
 #[derive(Parser, Debug)]
@@ -29,12 +39,20 @@ enum Commands {
     Check {
         #[arg(value_hint = clap::ValueHint::DirPath)]
         file: std::path::PathBuf,
+
+        /// directories searched for imported modules, before the ones of `POPPER_PATH`
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
     },
     /// compile to the Popper MIR
     Mirage {
         #[arg(value_hint = clap::ValueHint::DirPath)]
         file: std::path::PathBuf,
 
+        /// directories searched for imported modules, before the ones of `POPPER_PATH`
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
+
         #[arg(short, long, value_hint = clap::ValueHint::DirPath)]
         output: Option<std::path::PathBuf>,
     },
@@ -48,6 +66,51 @@ enum Commands {
 
         #[arg(short, long)]
         debug: bool,
+
+        /// emit runtime checks, on by default with `--debug`
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        checks: Option<bool>,
+
+        /// target triple, the host by default
+        #[arg(long)]
+        target: Option<Target>,
+
+        /// module the `extern` functions of WebAssembly modules are imported from, `env` by default
+        #[arg(long)]
+        import_module: Option<String>,
+
+        /// directories searched for imported modules, before the ones of `POPPER_PATH`
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
+    },
+
+    /// compile a file to an object file, for the host or another target
+    Object {
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: std::path::PathBuf,
+
+        /// path of the object file, the file with the `o` extension by default
+        #[arg(short, long, value_hint = clap::ValueHint::FilePath)]
+        output: Option<std::path::PathBuf>,
+
+        #[arg(short, long)]
+        debug: bool,
+
+        /// emit runtime checks, on by default with `--debug`
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        checks: Option<bool>,
+
+        /// target triple, e.g. `aarch64-unknown-linux-gnu` or `wasm32`, the host by default
+        #[arg(long)]
+        target: Option<Target>,
+
+        /// module the `extern` functions of WebAssembly modules are imported from, `env` by default
+        #[arg(long)]
+        import_module: Option<String>,
+
+        /// directories searched for imported modules, before the ones of `POPPER_PATH`
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
     },
 
     /// Run a popper file
@@ -62,6 +125,176 @@ enum Commands {
         // inkwell: bool,
         #[arg(short, long)]
         debug: bool,
+
+        /// emit runtime checks, on by default with `--debug`
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        checks: Option<bool>,
+
+        /// run the program on the bytecode VM instead of compiling it, like `.popc` files
+        #[arg(long)]
+        vm: bool,
+
+        /// stop the program after this many instructions, running it on the VM
+        #[arg(long, value_name = "N")]
+        max_instructions: Option<u64>,
+
+        /// stop the program when it keeps more bytes alive, running it on the VM
+        #[arg(long, value_name = "BYTES")]
+        max_heap: Option<usize>,
+
+        /// stop the program when it nests more calls, running it on the VM
+        #[arg(long, value_name = "N")]
+        max_depth: Option<usize>,
+
+        /// stop the program after this many milliseconds, running it on the VM. A call
+        /// to C which blocks, like reading a line, isn't interrupted
+        #[arg(long, value_name = "MS")]
+        timeout: Option<u64>,
+
+        /// the only `extern` functions the program may call, running it on the VM: names
+        /// separated by commas, or the option repeated. None with `--allow-extern ''`, or
+        /// when this is left out and another limit is set
+        #[arg(long, value_name = "NAME", num_args = 1, value_delimiter = ',')]
+        allow_extern: Option<Vec<String>>,
+
+        /// directories searched for imported modules, before the ones of `POPPER_PATH`
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
+
+        /// arguments given to the program, after `--`
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+
+    /// compile a file to bytecode, run by `popper run file.popc` without compiling it again
+    Bytecode {
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: std::path::PathBuf,
+
+        /// path of the bytecode file, the file with the `popc` extension by default
+        #[arg(short, long, value_hint = clap::ValueHint::FilePath)]
+        output: Option<std::path::PathBuf>,
+
+        /// print the disassembled bytecode instead of writing it, unless `--output` is given
+        #[arg(long)]
+        dump_bytecode: bool,
+
+        /// directories searched for imported modules, before the ones of `POPPER_PATH`
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
+    },
+
+    /// debug a file on the bytecode VM, speaking the Debug Adapter Protocol over stdio
+    Debug {
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: std::path::PathBuf,
+
+        /// directories searched for imported modules, before the ones of `POPPER_PATH`
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
+    },
+
+    /// run the `test` blocks of a file, each one in its own program
+    Test {
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: std::path::PathBuf,
+
+        /// only run the tests whose name contains this string
+        filter: Option<String>,
+
+        #[arg(short = 't', long, value_hint = clap::ValueHint::DirPath)]
+        target_dir: Option<std::path::PathBuf>,
+
+        /// target triple, e.g. `wasm32-wasi`, the host by default
+        #[arg(long)]
+        target: Option<Target>,
+
+        /// command running the tests of another target, `wasmtime run --invoke main` for WebAssembly
+        #[arg(long)]
+        runner: Option<String>,
+
+        /// directories searched for imported modules, before the ones of `POPPER_PATH`
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
+    },
+
+    /// build the package described by `popper.toml`
+    Build {
+        /// path of `popper.toml`, looked up from the current directory by default
+        #[arg(long, value_hint = clap::ValueHint::FilePath)]
+        manifest_path: Option<std::path::PathBuf>,
+
+        /// target directory, `target_popper` next to the manifest by default
+        #[arg(short = 't', long, value_hint = clap::ValueHint::DirPath)]
+        target_dir: Option<std::path::PathBuf>,
+
+        /// target triple, e.g. `aarch64-unknown-linux-gnu` or `wasm32`, the host by default
+        #[arg(long)]
+        target: Option<Target>,
+
+        /// root of the headers and libraries of the target, given to the C compiler and the linker
+        #[arg(long, value_hint = clap::ValueHint::DirPath)]
+        sysroot: Option<std::path::PathBuf>,
+
+        /// `bin`, or `staticlib` and `cdylib` for a library with its C header
+        #[arg(long, default_value = "bin")]
+        crate_type: CrateType,
+
+        /// module the `extern` functions of WebAssembly modules are imported from, `env` by default
+        #[arg(long)]
+        import_module: Option<String>,
+
+        #[arg(short, long)]
+        debug: bool,
+
+        /// emit runtime checks, on by default with `--debug`
+        #[arg(long, num_args = 0..=1, default_missing_value = "true")]
+        checks: Option<bool>,
+
+        /// directories searched for imported modules, after the source directories
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
+    },
+
+    /// start the language server, speaking LSP over stdio
+    Lsp {
+        /// directories searched for imported modules, before the ones of `POPPER_PATH`
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
+    },
+
+    /// write the documentation of a file and of the modules it imports
+    Doc {
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        file: std::path::PathBuf,
+
+        /// target directory, the pages are written to its `doc` directory
+        #[arg(short, long, value_hint = clap::ValueHint::DirPath)]
+        target: Option<std::path::PathBuf>,
+
+        /// directories searched for imported modules, before the ones of `POPPER_PATH`
+        #[arg(long = "lib-path", value_hint = clap::ValueHint::DirPath)]
+        lib_path: Vec<std::path::PathBuf>,
+    },
+
+    /// format files in place
+    Fmt {
+        /// don't write the files, exit with an error if one of them isn't formatted
+        #[arg(long)]
+        check: bool,
+
+        #[arg(required = true, value_hint = clap::ValueHint::FilePath)]
+        files: Vec<std::path::PathBuf>,
+    },
+
+    /// write the Popper bindings of a C header: its functions, structs, enums and `#define`s
+    Bindgen {
+        #[arg(value_hint = clap::ValueHint::FilePath)]
+        header: std::path::PathBuf,
+
+        /// file the bindings are written to, printed by default
+        #[arg(short, long, value_hint = clap::ValueHint::FilePath)]
+        output: Option<std::path::PathBuf>,
     },
 
     Clean {
@@ -99,10 +332,10 @@ fn main() {
                 eprintln!("Unable to parse file")
             }
         }
-        Commands::Check { file } => {
+        Commands::Check { file, lib_path } => {
             let string_file = file.to_str().expect("Unable to get a str");
             let content = std::fs::read_to_string(string_file).expect("File not found");
-            let ast = get_ast(content.as_str(), string_file);
+            let ast = ModuleLoader::new(lib_path).load(&file);
             if let Some(a) = ast {
                 if check_program(a, content.as_str(), string_file) {
                     println!("Program is valid");
@@ -113,13 +346,25 @@ fn main() {
                 eprintln!("Unable to parse file")
             }
         }
-        Commands::Mirage { file, output } => {
+        Commands::Mirage {
+            file,
+            output,
+            lib_path,
+        } => {
             let string_file = file.to_str().expect("Unable to get a str");
             let content = std::fs::read_to_string(string_file).expect("File not found");
-            let ast = get_ast(content.as_str(), string_file);
+            let ast = ModuleLoader::new(lib_path).load(&file);
             if let Some(a) = ast {
                 if check_program(a.clone(), content.as_str(), string_file) {
-                    let res = compile(a, string_file, false).print_to_string();
+                    let res = compile(
+                        a,
+                        content.as_str(),
+                        string_file,
+                        false,
+                        false,
+                        &Target::host(),
+                    )
+                    .print_to_string();
                     if let Some(out) = output {
                         std::fs::File::open(out)
                             .expect("File Not Found")
@@ -139,13 +384,20 @@ fn main() {
             file,
             output,
             debug,
+            checks,
+            target,
+            import_module,
+            lib_path,
         } => {
             let string_file = file.to_str().expect("Unable to get a str");
             let content = std::fs::read_to_string(string_file).expect("File not found");
-            let ast = get_ast(content.as_str(), string_file);
+            let ast = ModuleLoader::new(lib_path).load(&file);
             if let Some(a) = ast {
                 if check_program(a.clone(), content.as_str(), string_file) {
-                    let res = compile(a, string_file, debug).print_llvm_to_string();
+                    let checks = checks.unwrap_or(debug);
+                    let target = target.unwrap_or_default().with_import_module(import_module);
+                    let res = compile(a, content.as_str(), string_file, debug, checks, &target)
+                        .print_llvm_to_string();
                     if let Some(out) = output {
                         std::fs::File::open(out)
                             .expect("File Not Found")
@@ -161,29 +413,313 @@ fn main() {
                 eprintln!("Unable to parse file")
             }
         }
+        Commands::Object {
+            file,
+            output,
+            debug,
+            checks,
+            target,
+            import_module,
+            lib_path,
+        } => {
+            let string_file = file.to_str().expect("Unable to get a str");
+            let content = std::fs::read_to_string(string_file).expect("File not found");
+            let Some(a) = ModuleLoader::new(lib_path).load(&file) else {
+                eprintln!("Unable to parse file");
+                std::process::exit(1);
+            };
+            if !check_program(a.clone(), content.as_str(), string_file) {
+                println!("Program is invalid");
+                std::process::exit(1);
+            }
+            let checks = checks.unwrap_or(debug);
+            let target = target.unwrap_or_default().with_import_module(import_module);
+            let res = compile(a, content.as_str(), string_file, debug, checks, &target)
+                .print_llvm_to_string();
+            let output = output.unwrap_or(file.with_extension("o"));
+            let compiled =
+                compile_object(res, &output.with_extension("ll"), &output, debug, &target);
+            if !debug {
+                let _ = std::fs::remove_file(output.with_extension("ll"));
+            }
+            if let Err(err) = compiled {
+                eprintln!("{}", err);
+                std::process::exit(1);
+            }
+            println!("Compiled {} for {}", output.display(), target);
+        }
         Commands::Run {
             file,
             target,
             debug,
+            checks,
+            vm,
+            max_instructions,
+            max_heap,
+            max_depth,
+            timeout,
+            allow_extern,
+            lib_path,
+            args,
         } => {
+            let limits = popper_vm::Limits {
+                instructions: max_instructions,
+                heap: max_heap,
+                depth: max_depth,
+                timeout: timeout.map(std::time::Duration::from_millis),
+                externs: allow_extern.map(|x| x.into_iter().filter(|x| !x.is_empty()).collect()),
+            };
+            // only the VM enforces them
+            let vm = vm || !limits.is_unlimited();
             let string_file = file.to_str().expect("Unable to get a str");
+            if file.extension().is_some_and(|x| x == "popc") {
+                let module = popper_vm::Module::load(&file).unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                });
+                std::process::exit(execute_bytecode(module, string_file, &args, limits));
+            }
             let content = std::fs::read_to_string(string_file).expect("File not found");
-            let ast = get_ast(content.as_str(), string_file);
+            let ast = ModuleLoader::new(lib_path).load(&file);
             if let Some(a) = ast {
                 if check_program(a.clone(), content.as_str(), string_file) {
-                    let res = compile(a, string_file, debug).print_llvm_to_string();
+                    if vm {
+                        let Some(module) = compile_to_bytecode(&a, &content, string_file) else {
+                            std::process::exit(1);
+                        };
+                        std::process::exit(execute_bytecode(module, string_file, &args, limits));
+                    }
+                    let checks = checks.unwrap_or(debug);
+                    let output = compile(
+                        a,
+                        content.as_str(),
+                        string_file,
+                        debug,
+                        checks,
+                        &Target::host(),
+                    );
+                    let mut link = Link::default();
+                    link.add_libraries(output.libraries());
                     let target = target.unwrap_or(std::path::PathBuf::from("./target_popper"));
-                    execute_llvm(
-                        res,
+                    let code = execute_llvm(
+                        output.print_llvm_to_string(),
                         string_file.to_string(),
                         target.to_str().unwrap().to_string(),
                         debug,
+                        &link,
+                        &args,
                     );
+                    std::process::exit(code);
                 } else {
                     println!("Program is invalid");
+                    std::process::exit(1);
                 }
             } else {
-                eprintln!("Unable to parse file")
+                eprintln!("Unable to parse file");
+                std::process::exit(1);
+            }
+        }
+        Commands::Bytecode {
+            file,
+            output,
+            dump_bytecode,
+            lib_path,
+        } => {
+            let string_file = file.to_str().expect("Unable to get a str");
+            let module = if file.extension().is_some_and(|x| x == "popc") {
+                popper_vm::Module::load(&file).unwrap_or_else(|err| {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                })
+            } else {
+                let content = std::fs::read_to_string(string_file).expect("File not found");
+                let Some(a) = ModuleLoader::new(lib_path).load(&file) else {
+                    eprintln!("Unable to parse file");
+                    std::process::exit(1);
+                };
+                if !check_program(a.clone(), content.as_str(), string_file) {
+                    println!("Program is invalid");
+                    std::process::exit(1);
+                }
+                let Some(module) = compile_to_bytecode(&a, &content, string_file) else {
+                    std::process::exit(1);
+                };
+                module
+            };
+            if dump_bytecode {
+                print!("{}", module);
+            }
+            if !dump_bytecode || output.is_some() {
+                let output = output.unwrap_or(file.with_extension("popc"));
+                if let Err(err) = module.save(&output) {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+                println!("Compiled {}", output.display());
+            }
+        }
+        Commands::Debug { file, lib_path } => {
+            let string_file = file.to_str().expect("Unable to get a str");
+            let content = std::fs::read_to_string(string_file).expect("File not found");
+            let Some(a) = ModuleLoader::new(lib_path).load(&file) else {
+                eprintln!("Unable to parse file");
+                std::process::exit(1);
+            };
+            if !check_program(a.clone(), content.as_str(), string_file) {
+                eprintln!("Program is invalid");
+                std::process::exit(1);
+            }
+            let Some(module) = compile_to_bytecode(&a, &content, string_file) else {
+                std::process::exit(1);
+            };
+            match dap::run(module, string_file) {
+                Ok(code) => std::process::exit(code),
+                Err(err) => {
+                    eprintln!("Debug adapter error: {}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Test {
+            file,
+            filter,
+            target_dir,
+            target,
+            runner,
+            lib_path,
+        } => {
+            let string_file = file.to_str().expect("Unable to get a str");
+            let content = std::fs::read_to_string(string_file).expect("File not found");
+            let Some(a) = ModuleLoader::new(lib_path).load(&file) else {
+                eprintln!("Unable to parse file");
+                std::process::exit(1);
+            };
+            if !check_program(a.clone(), content.as_str(), string_file) {
+                println!("Program is invalid");
+                std::process::exit(1);
+            }
+            let target_dir = target_dir.unwrap_or(std::path::PathBuf::from("./target_popper"));
+            if !run_tests(
+                &a,
+                content.as_str(),
+                string_file,
+                &target_dir,
+                filter.as_deref(),
+                &target.unwrap_or_default(),
+                runner.as_deref(),
+            )
+            .passed()
+            {
+                std::process::exit(1);
+            }
+        }
+        Commands::Build {
+            manifest_path,
+            target_dir,
+            target,
+            sysroot,
+            import_module,
+            crate_type,
+            debug,
+            checks,
+            lib_path,
+        } => {
+            let manifest = match manifest_path {
+                Some(path) => Manifest::load(&path),
+                None => Manifest::find(std::path::Path::new(".")),
+            };
+            let manifest = match manifest {
+                Ok(manifest) => manifest,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            };
+            let target_dir = target_dir.unwrap_or(manifest.root.join("target_popper"));
+            let checks = checks.unwrap_or(debug);
+            let target = target
+                .unwrap_or_default()
+                .with_sysroot(sysroot)
+                .with_import_module(import_module);
+            match build_package(
+                &manifest,
+                lib_path,
+                &target_dir,
+                debug,
+                checks,
+                &target,
+                crate_type,
+            ) {
+                Some(exe) => println!(
+                    "Built `{}` v{}: {}",
+                    manifest.package.name,
+                    manifest.package.version,
+                    exe.display()
+                ),
+                None => {
+                    eprintln!("Unable to build `{}`", manifest.package.name);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Lsp { lib_path } => {
+            let (connection, io_threads) = lsp_server::Connection::stdio();
+            if let Err(err) = lsp::run(connection, lib_path) {
+                eprintln!("Language server error: {}", err);
+                std::process::exit(1);
+            }
+            io_threads
+                .join()
+                .expect("Unable to stop the language server");
+        }
+        Commands::Doc {
+            file,
+            target,
+            lib_path,
+        } => {
+            let target = target.unwrap_or(std::path::PathBuf::from("./target_popper"));
+            match generate_doc(&file, lib_path, &target.join("doc")) {
+                Some(index) => println!("Documentation written to {}", index.display()),
+                None => {
+                    eprintln!("Unable to document {}", file.display());
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Fmt { check, files } => {
+            let mut failed = false;
+            for file in files {
+                let string_file = file.to_str().expect("Unable to get a str");
+                let content = std::fs::read_to_string(string_file).expect("File not found");
+                let Some(formatted) = format_source(content.as_str(), string_file) else {
+                    failed = true;
+                    continue;
+                };
+                if formatted == content {
+                    continue;
+                }
+                if check {
+                    println!("{} is not formatted", string_file);
+                    failed = true;
+                } else {
+                    std::fs::write(&file, formatted).expect("Cannot write to file");
+                }
+            }
+            if failed {
+                std::process::exit(1);
+            }
+        }
+        Commands::Bindgen { header, output } => {
+            let content = std::fs::read_to_string(&header).expect("File not found");
+            let header_name = header
+                .file_name()
+                .and_then(|x| x.to_str())
+                .unwrap_or("header");
+            let source = bindgen(&content, header_name);
+            let source = format_source(&source, header_name).unwrap_or(source);
+            match output {
+                Some(output) => std::fs::write(output, source).expect("Cannot write to file"),
+                None => print!("{}", source),
             }
         }
         Commands::Clean { target, only_libs } => {
@@ -0,0 +1,200 @@
+use popper_codegen::library::Library;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// name of the manifest file at the root of a package
+pub const MANIFEST_NAME: &str = "popper.toml";
+
+///
+/// Manifest describes a package, it's read from `popper.toml`:
+/// ```toml
+/// [package]
+/// name = "hello"
+/// version = "0.1.0"
+/// entry = "src/main.pop"
+/// source-dirs = ["src"]
+///
+/// [link]
+/// libs = ["m"]
+/// objects = ["c/hello.o"]
+///
+/// [dependencies]
+/// geo = { path = "../geo" }
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct Manifest {
+    pub package: Package,
+    #[serde(default)]
+    pub link: Link,
+    #[serde(default)]
+    pub dependencies: BTreeMap<String, Dependency>,
+    /// directory containing the manifest, paths of the manifest are relative to it
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Package {
+    pub name: String,
+    pub version: String,
+    /// file containing `main`
+    #[serde(default = "default_entry")]
+    pub entry: PathBuf,
+    /// directories searched for the modules of the package
+    #[serde(default = "default_source_dirs")]
+    pub source_dirs: Vec<PathBuf>,
+}
+
+/// what the package needs to be linked with, besides the Popper runtime
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Link {
+    /// C libraries, passed as `-l<lib>`
+    #[serde(default)]
+    pub libs: Vec<String>,
+    /// object files
+    #[serde(default)]
+    pub objects: Vec<PathBuf>,
+    /// functions exported by WebAssembly modules, besides the `export func`s
+    #[serde(default)]
+    pub exports: Vec<String>,
+}
+
+/// a package used by another, its modules are imported as `name.module`
+#[derive(Debug, Clone, Deserialize)]
+pub struct Dependency {
+    pub path: PathBuf,
+}
+
+fn default_entry() -> PathBuf {
+    PathBuf::from("src/main.pop")
+}
+
+fn default_source_dirs() -> Vec<PathBuf> {
+    vec![PathBuf::from("src")]
+}
+
+#[derive(Debug)]
+pub enum ManifestError {
+    NotFound(PathBuf),
+    Io(PathBuf, std::io::Error),
+    Invalid(PathBuf, toml::de::Error),
+}
+
+impl Display for ManifestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ManifestError::NotFound(dir) => write!(
+                f,
+                "could not find `{}` in `{}` or any parent directory",
+                MANIFEST_NAME,
+                dir.display()
+            ),
+            ManifestError::Io(path, err) => {
+                write!(f, "unable to read `{}`: {}", path.display(), err)
+            }
+            ManifestError::Invalid(path, err) => {
+                write!(f, "invalid manifest `{}`: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+impl Manifest {
+    /// find the manifest of the package containing `dir`
+    pub fn find(dir: &Path) -> Result<Self, ManifestError> {
+        let dir = dir
+            .canonicalize()
+            .map_err(|err| ManifestError::Io(dir.to_path_buf(), err))?;
+        dir.ancestors()
+            .map(|x| x.join(MANIFEST_NAME))
+            .find(|x| x.is_file())
+            .ok_or(ManifestError::NotFound(dir.clone()))
+            .and_then(|x| Self::load(&x))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ManifestError> {
+        let path = path
+            .canonicalize()
+            .map_err(|err| ManifestError::Io(path.to_path_buf(), err))?;
+        let content =
+            std::fs::read_to_string(&path).map_err(|err| ManifestError::Io(path.clone(), err))?;
+        let mut manifest: Manifest =
+            toml::from_str(&content).map_err(|err| ManifestError::Invalid(path.clone(), err))?;
+        manifest.root = path.parent().unwrap().to_path_buf();
+        Ok(manifest)
+    }
+
+    pub fn entry(&self) -> PathBuf {
+        self.root.join(&self.package.entry)
+    }
+
+    pub fn source_dirs(&self) -> Vec<PathBuf> {
+        self.package
+            .source_dirs
+            .iter()
+            .map(|x| self.root.join(x))
+            .collect()
+    }
+
+    /// libraries and objects of the package, with paths relative to the manifest resolved
+    pub fn link(&self) -> Link {
+        Link {
+            libs: self.link.libs.clone(),
+            objects: self
+                .link
+                .objects
+                .iter()
+                .map(|x| self.root.join(x))
+                .collect(),
+            exports: self.link.exports.clone(),
+        }
+    }
+
+    /// every package this one depends on, directly or not, with the name it's imported with
+    pub fn dependencies(&self) -> Result<Vec<(String, Manifest)>, ManifestError> {
+        let mut seen = HashSet::new();
+        seen.insert(self.root.clone());
+        let mut dependencies = Vec::new();
+        self.collect_dependencies(&mut seen, &mut dependencies)?;
+        Ok(dependencies)
+    }
+
+    fn collect_dependencies(
+        &self,
+        seen: &mut HashSet<PathBuf>,
+        dependencies: &mut Vec<(String, Manifest)>,
+    ) -> Result<(), ManifestError> {
+        for (name, dependency) in &self.dependencies {
+            let manifest = Self::load(&self.root.join(&dependency.path).join(MANIFEST_NAME))?;
+            if seen.insert(manifest.root.clone()) {
+                manifest.collect_dependencies(seen, dependencies)?;
+                dependencies.push((name.clone(), manifest));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Link {
+    pub fn extend(&mut self, other: Link) {
+        self.libs.extend(other.libs);
+        self.objects.extend(other.objects);
+        self.exports.extend(other.exports);
+    }
+
+    /// link with the libraries of `external` blocks, named ones with `-l`
+    pub fn add_libraries(&mut self, libraries: &[Library]) {
+        for library in libraries {
+            match library {
+                Library::Name(name) if !self.libs.contains(name) => self.libs.push(name.clone()),
+                Library::Path(path) if !self.objects.contains(path) => {
+                    self.objects.push(path.clone())
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use crate::cache::Cache;
+use popper_ast::Span;
+use popper_ast::{ImportStmt, Statement};
+use popper_common::ast_path_to_path::ast_path_to_path;
+use popper_common::hash::hash_str;
+use popper_error::importcycle::ImportCycle;
+use popper_error::modulenotfound::ModuleNotFound;
+use popper_error::{generate_color, Error};
+use popper_parser::parser::try_parse;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// environment variable listing directories to search imported modules in
+pub const POPPER_PATH: &str = "POPPER_PATH";
+
+/// directory the bundled `std/` is found in. Its modules are part of the compiler, not
+/// files on disk, so that it works wherever it's installed
+const STD_ROOT: &str = "<bundled>";
+
+/// modules of the bundled `std/`
+const STD_MODULES: &[(&str, &str)] = &[
+    ("fs.pop", include_str!("../std/fs.pop")),
+    ("io.pop", include_str!("../std/io.pop")),
+    ("math.pop", include_str!("../std/math.pop")),
+    ("mem.pop", include_str!("../std/mem.pop")),
+    ("process.pop", include_str!("../std/process.pop")),
+    ("string.pop", include_str!("../std/string.pop")),
+    ("time.pop", include_str!("../std/time.pop")),
+];
+
+/// a loaded module
+#[derive(Debug, Clone)]
+pub struct Module {
+    /// canonical path of the module
+    pub file: PathBuf,
+    pub source: String,
+    /// changes when the module or one of the modules it imports does, see [`Cache::module_key`]
+    pub key: String,
+    /// statements of the module, with its imports resolved
+    pub stmts: Vec<Statement>,
+}
+
+/// an error found while loading, kept instead of being reported
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub span: Span,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(file: &Path, err: &dyn Error) -> Self {
+        Self {
+            file: file.to_path_buf(),
+            span: err.span(),
+            message: err.message(),
+        }
+    }
+}
+
+///
+/// ModuleLoader parses a program and the modules it imports.
+/// Each file is parsed once and cached by canonical path
+pub struct ModuleLoader {
+    search_paths: Vec<PathBuf>,
+    /// source directories of the packages, by name
+    packages: HashMap<String, Vec<PathBuf>>,
+    modules: HashMap<PathBuf, Module>,
+    /// loaded modules, each one after the modules it imports
+    order: Vec<PathBuf>,
+    /// files being loaded, each one importing the next
+    loading: Vec<PathBuf>,
+    /// parsed files of the previous builds
+    cache: Option<Cache>,
+    /// sources used instead of the files on disk
+    sources: HashMap<PathBuf, String>,
+    /// errors found, when they are kept instead of being reported
+    diagnostics: Option<Vec<Diagnostic>>,
+}
+
+impl ModuleLoader {
+    ///
+    /// # Arguments
+    /// * `lib_paths` - directories searched for modules, before the ones of `POPPER_PATH`
+    ///   and the bundled `std/`
+    pub fn new(lib_paths: Vec<PathBuf>) -> Self {
+        let mut search_paths = lib_paths;
+        if let Some(paths) = std::env::var_os(POPPER_PATH) {
+            search_paths.extend(std::env::split_paths(&paths));
+        }
+        search_paths.push(PathBuf::from(STD_ROOT));
+
+        Self {
+            search_paths,
+            packages: HashMap::new(),
+            modules: HashMap::new(),
+            order: Vec::new(),
+            loading: Vec::new(),
+            cache: None,
+            sources: STD_MODULES
+                .iter()
+                .map(|(name, source)| {
+                    let file = Path::new(STD_ROOT).join("std").join(name);
+                    (file, source.to_string())
+                })
+                .collect(),
+            diagnostics: None,
+        }
+    }
+
+    /// keep the errors found instead of reporting them, see [`ModuleLoader::diagnostics`]
+    pub fn keep_diagnostics(mut self) -> Self {
+        self.diagnostics = Some(Vec::new());
+        self
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        self.diagnostics.as_deref().unwrap_or_default()
+    }
+
+    /// use `source` as the content of `file`, like the unsaved content of an editor
+    pub fn set_source(&mut self, file: PathBuf, source: String) {
+        self.sources.insert(file, source);
+    }
+
+    /// reuse the files parsed by previous builds, when their source didn't change
+    pub fn with_cache(mut self, cache: Cache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// loaded modules, each one after the modules it imports
+    pub fn modules(&self) -> impl Iterator<Item = &Module> {
+        self.order.iter().map(|x| &self.modules[x])
+    }
+
+    /// make the modules in `source_dirs` importable as `name.module`
+    pub fn add_package(&mut self, name: String, source_dirs: Vec<PathBuf>) {
+        self.packages.insert(name, source_dirs);
+    }
+
+    ///
+    /// load is used to parse `file` and, recursively, the modules it imports.
+    /// Errors are reported as they are found
+    ///
+    /// return: `Option<Vec<Statement>>`, with every import resolved
+    pub fn load(&mut self, file: &Path) -> Option<Vec<Statement>> {
+        match file.canonicalize() {
+            Ok(file) => self.load_module(file).map(|x| x.stmts.clone()),
+            Err(err) => {
+                eprintln!("Unable to read `{}`: {}", file.display(), err);
+                None
+            }
+        }
+    }
+
+    ///
+    /// load_source is used to parse `source`, which isn't a file on disk, and the
+    /// modules it imports, like [`ModuleLoader::load`]
+    /// # Arguments
+    /// * `file` - path the source is known by, its imports being looked up next to it
+    /// * `source` - source code
+    ///
+    /// return: `Option<Vec<Statement>>`, with every import resolved
+    pub fn load_source(&mut self, file: &Path, source: String) -> Option<Vec<Statement>> {
+        self.set_source(file.to_path_buf(), source);
+        self.load_module(file.to_path_buf())
+            .map(|x| x.stmts.clone())
+    }
+
+    fn load_module(&mut self, file: PathBuf) -> Option<&Module> {
+        if self.modules.contains_key(&file) {
+            return self.modules.get(&file);
+        }
+
+        let source = match self.read(&file) {
+            Ok(source) => source,
+            Err(err) => {
+                eprintln!("Unable to read `{}`: {}", file.display(), err);
+                return None;
+            }
+        };
+
+        let source_hash = hash_str(&source);
+        let cached = self.cache.as_ref().and_then(|x| x.load_ast(&source_hash));
+        let mut stmts = match cached {
+            Some(stmts) => stmts,
+            None => match try_parse(&source) {
+                Ok(stmts) => {
+                    if let Some(cache) = &self.cache {
+                        cache.store_ast(&source_hash, &stmts);
+                    }
+                    stmts
+                }
+                Err(err) => {
+                    self.report(&err, &file, &source);
+                    return None;
+                }
+            },
+        };
+
+        self.loading.push(file.clone());
+        let imports = self.load_imports(&mut stmts, &file, &source);
+        self.loading.pop();
+
+        let module = Module {
+            file: file.clone(),
+            source,
+            key: Cache::module_key(&source_hash, &imports?),
+            stmts,
+        };
+        self.modules.insert(file.clone(), module);
+        self.order.push(file.clone());
+        self.modules.get(&file)
+    }
+
+    /// resolve the imports of a module
+    ///
+    /// return: the keys of the imported modules
+    fn load_imports(
+        &mut self,
+        stmts: &mut [Statement],
+        file: &Path,
+        source: &str,
+    ) -> Option<Vec<String>> {
+        let mut keys = Vec::new();
+        for stmt in stmts.iter_mut() {
+            let Statement::Import(import) = stmt else {
+                continue;
+            };
+
+            let Some(module) = self.resolve(import, file) else {
+                let err = ModuleNotFound::new(import.path.to_string(), import.path.span());
+                self.report(&err, file, source);
+                return None;
+            };
+
+            if let Some(start) = self.loading.iter().position(|x| *x == module) {
+                let cycle = self.loading[start..]
+                    .iter()
+                    .chain(std::iter::once(&module))
+                    .map(|x| x.display().to_string())
+                    .collect();
+                self.report(&ImportCycle::new(cycle, import.span()), file, source);
+                return None;
+            }
+
+            let loaded = self.load_module(module.clone())?;
+            keys.push(loaded.key.clone());
+            import.resolve(module, loaded.source.clone(), loaded.stmts.clone());
+        }
+
+        Some(keys)
+    }
+
+    fn read(&self, file: &Path) -> std::io::Result<String> {
+        match self.sources.get(file) {
+            Some(source) => Ok(source.clone()),
+            None => std::fs::read_to_string(file),
+        }
+    }
+
+    /// report `err`, found in `file`, or keep it
+    fn report(&mut self, err: &dyn Error, file: &Path, source: &str) {
+        match &mut self.diagnostics {
+            Some(diagnostics) => diagnostics.push(Diagnostic::new(file, err)),
+            None => err.report(generate_color(), source, &file.display().to_string()),
+        }
+    }
+
+    /// file imported by `import` in `file`: looked up in the package named by the first
+    /// segment of the path, in the directory of `file`, then in the search paths.
+    /// The files with a source set, like the bundled `std/`, don't have to be on disk
+    fn resolve(&self, import: &ImportStmt, file: &Path) -> Option<PathBuf> {
+        let path = ast_path_to_path(import.path.clone());
+
+        let mut candidates = Vec::new();
+        if let [package, _, ..] = import.path.segments.as_slice() {
+            if let Some(dirs) = self.packages.get(&package.name) {
+                let path = path.strip_prefix(&package.name).unwrap();
+                candidates.extend(dirs.iter().map(|dir| dir.join(path)));
+            }
+        }
+        candidates.extend(
+            file.parent()
+                .into_iter()
+                .chain(self.search_paths.iter().map(|x| x.as_path()))
+                .map(|dir| dir.join(&path)),
+        );
+
+        candidates.into_iter().find_map(|x| {
+            if self.sources.contains_key(&x) {
+                Some(x)
+            } else if x.is_file() {
+                x.canonicalize().ok()
+            } else {
+                None
+            }
+        })
+    }
+}
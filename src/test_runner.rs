@@ -0,0 +1,242 @@
+use popper_ast::{
+    Arguments, Constant, Expression, Function, Int, Return, Statement, TestStmt, Type, TypeKind,
+};
+use std::path::Path;
+use std::process::Command;
+
+use crate::manifest::Link;
+use crate::{build_executable, compile, Target};
+
+/// outcome of a test
+pub struct TestResult {
+    pub name: String,
+    pub passed: bool,
+    /// what the test printed, shown when it fails
+    pub output: String,
+}
+
+/// outcome of the tests of a module
+pub struct TestSummary {
+    /// results of the tests run, in order
+    pub results: Vec<TestResult>,
+    /// number of tests not matching the filter
+    pub filtered_out: usize,
+}
+
+impl TestSummary {
+    /// whether all the tests run passed
+    pub fn passed(&self) -> bool {
+        self.results.iter().all(|x| x.passed)
+    }
+}
+
+/// `test` blocks of a module
+pub fn collect_tests(stmts: &[Statement]) -> Vec<TestStmt> {
+    stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Statement::Test(test) => Some(test.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+///
+/// test_program is used to build the program running a test: the items of the module,
+/// without its tests and its `main`, and a `main` running the body of the test
+/// # Arguments
+/// * `stmts` - statements of the module
+/// * `test` - the test to run
+///
+/// return: the statements of the program
+pub fn test_program(stmts: &[Statement], test: &TestStmt) -> Vec<Statement> {
+    let span = test.span;
+    let mut program: Vec<Statement> = stmts
+        .iter()
+        .filter(|stmt| match stmt {
+            Statement::Test(_) => false,
+            Statement::Function(function) => function.name != "main",
+            _ => true,
+        })
+        .cloned()
+        .collect();
+
+    let mut body = test.body.statements.clone();
+    body.push(Statement::Return(Return::new(
+        Some(Expression::Constant(Constant::Int(Int::new(span, 0)))),
+        span,
+    )));
+    program.push(Statement::Function(Function::new(
+        "main".to_string(),
+        Arguments::new(vec![], span),
+        Type::new(span, TypeKind::Int, vec![]),
+        body,
+        false,
+        false,
+        span,
+    )));
+    program
+}
+
+///
+/// run_tests is used to run the tests of a checked module, each one compiled to its own
+/// executable in `target_path`, and print their results
+/// # Arguments
+/// * `stmts` - statements of the module, imports loaded
+/// * `source` - source code, used to locate the failing assertions
+/// * `file_name` - file name
+/// * `target_path` - target directory
+/// * `filter` - only run the tests whose name contains it
+/// * `target` - machine the tests are compiled for
+/// * `runner` - command running the tests of another target, the executable being its
+///   last argument. WebAssembly tests are run with `wasmtime` by default
+///
+/// return: the results of the tests, see [`TestSummary::passed`]
+pub fn run_tests(
+    stmts: &[Statement],
+    source: &str,
+    file_name: &str,
+    target_path: &Path,
+    filter: Option<&str>,
+    target: &Target,
+    runner: Option<&str>,
+) -> TestSummary {
+    let tests = collect_tests(stmts);
+    let total = tests.len();
+    let tests: Vec<TestStmt> = tests
+        .into_iter()
+        .filter(|test| filter.is_none_or(|filter| test.name.contains(filter)))
+        .collect();
+    let filtered_out = total - tests.len();
+
+    println!(
+        "running {} test{}",
+        tests.len(),
+        if tests.len() == 1 { "" } else { "s" }
+    );
+
+    let build = TestBuild {
+        target_path,
+        target,
+        runner: match runner {
+            Some(runner) => runner.split_whitespace().collect(),
+            None if target.is_wasm() => vec!["wasmtime", "run", "--invoke", "main"],
+            None => vec![],
+        },
+    };
+
+    let stem = Path::new(file_name)
+        .file_stem()
+        .and_then(|x| x.to_str())
+        .unwrap_or("test");
+    let mut results = Vec::new();
+    for (i, test) in tests.iter().enumerate() {
+        let result = run_test(
+            stmts,
+            test,
+            source,
+            file_name,
+            &format!("{}_test{}", stem, i),
+            &build,
+        );
+        println!(
+            "test {} ... {}",
+            result.name,
+            if result.passed { "ok" } else { "FAILED" }
+        );
+        results.push(result);
+    }
+
+    let failures: Vec<&TestResult> = results.iter().filter(|x| !x.passed).collect();
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for failure in failures.iter() {
+            println!(
+                "\n---- {} ----\n{}",
+                failure.name,
+                failure.output.trim_end()
+            );
+        }
+    }
+
+    println!(
+        "\ntest result: {}. {} passed; {} failed; {} filtered out",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        results.len() - failures.len(),
+        failures.len(),
+        filtered_out
+    );
+    TestSummary {
+        results,
+        filtered_out,
+    }
+}
+
+/// where and for which machine the tests are built, and how they're run
+struct TestBuild<'a> {
+    target_path: &'a Path,
+    target: &'a Target,
+    /// program and arguments running a test, before its executable
+    runner: Vec<&'a str>,
+}
+
+/// compile and run a test, in its own process
+fn run_test(
+    stmts: &[Statement],
+    test: &TestStmt,
+    source: &str,
+    file_name: &str,
+    exe_name: &str,
+    build: &TestBuild,
+) -> TestResult {
+    let output = compile(
+        test_program(stmts, test),
+        source,
+        file_name,
+        false,
+        true,
+        build.target,
+    );
+    let mut link = Link::default();
+    link.add_libraries(output.libraries());
+    let exe = match build_executable(
+        output.print_llvm_to_string(),
+        exe_name.to_string(),
+        build.target_path.to_str().unwrap().to_string(),
+        false,
+        &link,
+        build.target,
+    ) {
+        Ok(exe) => exe,
+        Err(err) => {
+            return TestResult {
+                name: test.name.clone(),
+                passed: false,
+                output: format!("unable to build the test: {}", err),
+            }
+        }
+    };
+
+    let exe = Path::new(".").join(exe);
+    let mut command = match build.runner.split_first() {
+        Some((program, args)) => {
+            let mut command = Command::new(program);
+            command.args(args).arg(exe);
+            command
+        }
+        None => Command::new(exe),
+    };
+    match command.output() {
+        Ok(output) => TestResult {
+            name: test.name.clone(),
+            passed: output.status.success(),
+            output: String::from_utf8_lossy(&output.stdout).to_string()
+                + &String::from_utf8_lossy(&output.stderr),
+        },
+        Err(err) => TestResult {
+            name: test.name.clone(),
+            passed: false,
+            output: format!("unable to run the test: {}", err),
+        },
+    }
+}
@@ -0,0 +1,151 @@
+use popper::bindgen::bindgen;
+use popper::{check_program, format_source, get_ast};
+
+/// bindings of `header`, formatted, checked to be a valid module
+fn bindings(header: &str) -> String {
+    let source = bindgen(header, "fixture.h");
+    let source = format_source(&source, "fixture.pop")
+        .unwrap_or_else(|| panic!("bindings should parse:\n{}", source));
+    let stmts = get_ast(&source, "fixture.pop").unwrap();
+    assert!(
+        check_program(stmts, &source, "fixture.pop"),
+        "bindings should check:\n{}",
+        source
+    );
+    source
+}
+
+/// `items` after the documentation of the module
+fn module(items: &str) -> String {
+    format!(
+        "//! Bindings of `fixture.h`, generated by `popper bindgen`.\n\n{}",
+        items
+    )
+}
+
+#[test]
+fn pointer_to_pointer() {
+    let header = "
+typedef struct node {
+    struct node **children;
+    char **names;
+} node;
+
+int split(const char *text, char ***parts);
+void free_nodes(node **nodes, int count);
+";
+    assert_eq!(
+        bindings(header),
+        module(
+            "pub struct node {
+    pub children = **struct node,
+    pub names = **char
+}
+
+// `free_nodes` is left out: Popper has no `void` type
+
+extern {
+    func split(text: string, parts: ***char): int
+};
+"
+        )
+    );
+}
+
+#[test]
+fn anonymous_enum() {
+    let header = "
+enum {
+    READ = 1,
+    WRITE = 1 << 1,
+    BOTH = READ | WRITE,
+    NEXT
+};
+";
+    assert_eq!(
+        bindings(header),
+        module(
+            "pub func READ(): int {
+    return 1;
+}
+
+pub func WRITE(): int {
+    return 2;
+}
+
+pub func BOTH(): int {
+    return 3;
+}
+
+pub func NEXT(): int {
+    return 4;
+}
+"
+        )
+    );
+}
+
+#[test]
+fn function_pointer_typedef() {
+    let header = "
+typedef int (*callback)(void *data, int code);
+
+struct handler {
+    callback on_event;
+    int code;
+};
+
+int register_callback(callback f, void *data);
+";
+    // function pointers are opaque pointers
+    assert_eq!(
+        bindings(header),
+        module(
+            "pub struct handler {
+    pub on_event = *u8,
+    pub code = int
+}
+
+extern {
+    func register_callback(f: *u8, data: *u8): int
+};
+"
+        )
+    );
+}
+
+#[test]
+fn unsigned_long_long() {
+    let header = "
+typedef unsigned long long hash_t;
+
+unsigned long long hash(const char *data, unsigned long len);
+hash_t combine(hash_t a, long long b);
+";
+    assert_eq!(
+        bindings(header),
+        module(
+            "extern {
+    func hash(data: string, len: u64): u64,
+    func combine(a: u64, b: i64): u64
+};
+"
+        )
+    );
+}
+
+#[test]
+fn variadic() {
+    let header = "
+int log_message(int level, const char *format, ...);
+";
+    assert_eq!(
+        bindings(header),
+        module(
+            "extern {
+    func log_message(level: int, format: string ...): int
+};
+"
+        )
+    );
+}
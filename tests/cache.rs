@@ -0,0 +1,220 @@
+use popper::cache::Cache;
+use popper::manifest::{Manifest, MANIFEST_NAME};
+use popper::module_loader::ModuleLoader;
+use popper::{build_package, compile_object, CrateType, Target};
+use popper_flag::Environment;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// fresh directory for the test `name`, with the files `files` written in it
+fn package_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("popper-cache-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, content) in files {
+        std::fs::write(dir.join(file), content).unwrap();
+    }
+    dir.canonicalize().unwrap()
+}
+
+/// key of every module loaded from `main.pop`, by file name
+fn module_keys(dir: &Path, cache: &Cache) -> HashMap<String, String> {
+    let mut loader = ModuleLoader::new(vec![]).with_cache(cache.clone());
+    loader.load(&dir.join("main.pop")).unwrap();
+    loader
+        .modules()
+        .map(|x| {
+            let name = x.file.file_name().unwrap().to_string_lossy().to_string();
+            (name, x.key.clone())
+        })
+        .collect()
+}
+
+const MAIN: &str = "import util;\nimport other;\n\nfunc main(): int {\n    return util.one();\n}\n";
+const UTIL: &str = "pub func one(): int {\n    return 1;\n}\n";
+const OTHER: &str = "pub func two(): int {\n    return 2;\n}\n";
+
+#[test]
+fn imported_module_changes() {
+    let dir = package_dir(
+        "imported",
+        &[("main.pop", MAIN), ("util.pop", UTIL), ("other.pop", OTHER)],
+    );
+    let cache = Cache::new(&dir.join("target"));
+    assert!(cache.is_enabled());
+    let host = Target::host();
+
+    let before = module_keys(&dir, &cache);
+    cache.store_interface(&before["main.pop"], &Environment::new());
+    // nothing changed, the artefacts of the previous build are reused
+    assert_eq!(module_keys(&dir, &cache), before);
+    assert!(cache.load_interface(&before["main.pop"]).is_some());
+
+    std::fs::write(
+        dir.join("util.pop"),
+        "pub func one(): int {\n    return 2 - 1;\n}\n",
+    )
+    .unwrap();
+    let after = module_keys(&dir, &cache);
+
+    assert_ne!(after["util.pop"], before["util.pop"]);
+    // the importer is rebuilt with the new interface of `util`
+    assert_ne!(after["main.pop"], before["main.pop"]);
+    assert!(cache.load_interface(&after["main.pop"]).is_none());
+    assert_ne!(
+        cache.object_path(
+            &after["main.pop"],
+            &dir.join("main.pop"),
+            false,
+            false,
+            &host
+        ),
+        cache.object_path(
+            &before["main.pop"],
+            &dir.join("main.pop"),
+            false,
+            false,
+            &host
+        )
+    );
+    // a module which doesn't depend on `util` is kept
+    assert_eq!(after["other.pop"], before["other.pop"]);
+}
+
+#[test]
+fn transitive_import_changes() {
+    let dir = package_dir(
+        "transitive",
+        &[
+            ("main.pop", "import util;\n"),
+            ("util.pop", "import other;\n"),
+            ("other.pop", OTHER),
+        ],
+    );
+    let cache = Cache::new(&dir.join("target"));
+
+    let before = module_keys(&dir, &cache);
+    std::fs::write(
+        dir.join("other.pop"),
+        "pub func two(): int {\n    return 3;\n}\n",
+    )
+    .unwrap();
+    let after = module_keys(&dir, &cache);
+
+    for module in ["main.pop", "util.pop", "other.pop"] {
+        assert_ne!(after[module], before[module], "{}", module);
+    }
+}
+
+#[test]
+fn unusable_directory() {
+    // the cache directory can't be created under a file
+    let dir = package_dir("unusable", &[("target", "")]);
+    let cache = Cache::new(&dir.join("target"));
+    assert!(!cache.is_enabled());
+
+    let key = Cache::key(&["module"]);
+    cache.store_interface(&key, &Environment::new());
+    assert!(cache.load_interface(&key).is_none());
+    let object = cache.object_path(&key, &dir.join("main.pop"), false, false, &Target::host());
+    assert!(!cache.has_object(&object));
+}
+
+#[test]
+fn target_changes() {
+    let dir = package_dir("target", &[("main.pop", MAIN)]);
+    let cache = Cache::new(&dir.join("target"));
+    let file = dir.join("main.pop");
+    let key = Cache::key(&["module"]);
+    let object = |target: &Target| cache.object_path(&key, &file, false, false, target);
+
+    let wasm: Target = "wasm32".parse().unwrap();
+    assert_eq!(object(&wasm), object(&wasm.clone()));
+    // every field of the target is part of the key
+    let imports = wasm.clone().with_import_module(Some("host".to_string()));
+    assert_ne!(object(&imports), object(&wasm));
+    let sysroot = wasm.clone().with_sysroot(Some(dir.join("sysroot")));
+    assert_ne!(object(&sysroot), object(&wasm));
+    assert_ne!(object(&sysroot), object(&imports));
+    assert_ne!(object(&Target::host()), object(&wasm));
+}
+
+/// the objects are compiled with `llc`, the runtime with `clang` and archived by `llvm-ar`
+fn has_toolchain() -> bool {
+    let found = ["llc", "clang", "llvm-ar"].iter().all(|tool| {
+        Command::new(tool)
+            .arg("--version")
+            .output()
+            .is_ok_and(|x| x.status.success())
+    });
+    if !found {
+        eprintln!("skipped: `llc`, `clang` and `llvm-ar` are needed to build the package");
+    }
+    found
+}
+
+#[test]
+fn import_module_rebuilds() {
+    if !has_toolchain() {
+        return;
+    }
+    let dir = package_dir(
+        "imports",
+        &[
+            (
+                MANIFEST_NAME,
+                "[package]\nname = \"imports\"\nversion = \"0.1.0\"\nentry = \"main.pop\"\n",
+            ),
+            (
+                "main.pop",
+                "external \"c\" {\n    func host_log(x: int): int\n};\n\nexport func run(): int {\n    return host_log(1);\n}\n",
+            ),
+        ],
+    );
+    let manifest = Manifest::load(&dir.join(MANIFEST_NAME)).unwrap();
+    let target_path = dir.join("target");
+    let cache = Cache::new(&target_path);
+    let key = module_keys(&dir, &cache)["main.pop"].clone();
+
+    let wasm: Target = "wasm32".parse().unwrap();
+    let mut objects = Vec::new();
+    for module in ["env", "host"] {
+        let target = wasm.clone().with_import_module(Some(module.to_string()));
+        build_package(
+            &manifest,
+            vec![],
+            &target_path,
+            false,
+            false,
+            &target,
+            CrateType::StaticLib,
+        )
+        .expect("the package builds");
+        let object = cache.object_path(&key, &dir.join("main.pop"), false, false, &target);
+        objects.push(std::fs::read(object).expect("the object is cached"));
+    }
+    assert_ne!(objects[0], objects[1]);
+}
+
+#[test]
+fn failed_compile() {
+    if Command::new("llc").arg("--version").output().is_err() {
+        eprintln!("skipped: `llc` is needed to compile objects");
+        return;
+    }
+    let dir = package_dir("failed", &[]);
+    let object = dir.join("main.o");
+    let err = compile_object(
+        "this isn't LLVM IR".to_string(),
+        &dir.join("main.ll"),
+        &object,
+        false,
+        &Target::host(),
+    )
+    .unwrap_err();
+    assert_eq!(err.name, "llc");
+    assert!(!err.status.success());
+    // no object is written by a failed `llc`
+    assert!(!object.exists());
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const PROGRAM: &str = r#"extern {
+    func printf(s: string ...): int
+};
+
+func main(): int {
+    printf("hello\n");
+    return 0;
+}
+"#;
+
+/// `hello.pop` written in a fresh directory for the test `name`
+fn program(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("popper-cli-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("hello.pop");
+    std::fs::write(&file, PROGRAM).unwrap();
+    file
+}
+
+fn popper(args: &[&str], file: &PathBuf) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_popper_compiler"))
+        .arg("run")
+        .args(args)
+        .arg(file)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn allow_extern() {
+    let file = program("allow-extern");
+    // the file isn't taken for the name of a function
+    for args in [
+        &["--allow-extern", "printf"][..],
+        &["--allow-extern=printf"],
+        &["--allow-extern", "puts,printf"],
+        &["--allow-extern", "puts", "--allow-extern", "printf"],
+    ] {
+        let output = popper(args, &file);
+        assert!(output.status.success(), "{:?}: {:?}", args, output);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "hello\n",
+            "{:?}",
+            args
+        );
+    }
+
+    for args in [&["--allow-extern", "puts"][..], &["--allow-extern", ""]] {
+        let output = popper(args, &file);
+        assert!(!output.status.success(), "{:?}: {:?}", args, output);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("printf"),
+            "{:?}: {:?}",
+            args,
+            output
+        );
+    }
+}
+
+#[test]
+fn main_returning_unit() {
+    let file = program("unit");
+    std::fs::write(
+        &file,
+        "func main(): unit {\n    let x = 1;\n    if x eq 1 {\n        return;\n    }\n}\n",
+    )
+    .unwrap();
+    let output = popper(&["--vm"], &file);
+    assert!(output.status.success(), "{:?}", output);
+
+    std::fs::write(&file, "func main(x: int): unit {\n}\n").unwrap();
+    let output = popper(&["--vm"], &file);
+    assert!(!output.status.success(), "{:?}", output);
+}
@@ -0,0 +1,220 @@
+use serde_json::{json, Value as Json};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+const PROGRAM: &str = "func square(x: int): int {
+    let y = x * x;
+    return y;
+}
+
+func main(): int {
+    let a = 3;
+    let b = square(a);
+    return b - 9;
+}
+";
+
+/// client of `popper debug`, speaking to it over its stdio
+struct Client {
+    process: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Json>,
+    /// the events received while waiting for a response
+    events: VecDeque<Json>,
+    seq: i64,
+}
+
+impl Client {
+    fn start(program: &Path) -> Self {
+        let mut process = Command::new(env!("CARGO_BIN_EXE_popper_compiler"))
+            .arg("debug")
+            .arg(program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stdin = process.stdin.take().unwrap();
+        let mut stdout = BufReader::new(process.stdout.take().unwrap());
+        let (sender, messages) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            while let Some(message) = read_message(&mut stdout) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        Self {
+            process,
+            stdin,
+            messages,
+            events: VecDeque::new(),
+            seq: 0,
+        }
+    }
+
+    fn receive(&self) -> Json {
+        self.messages
+            .recv_timeout(Duration::from_secs(10))
+            .expect("no message from the debug adapter")
+    }
+
+    /// the body of the response to the request, which should succeed
+    fn request(&mut self, command: &str, arguments: Json) -> Json {
+        self.seq += 1;
+        let content = json!({
+            "seq": self.seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(
+            self.stdin,
+            "Content-Length: {}\r\n\r\n{}",
+            content.len(),
+            content
+        )
+        .unwrap();
+        self.stdin.flush().unwrap();
+        loop {
+            let message = self.receive();
+            if message["type"] == "event" {
+                self.events.push_back(message);
+                continue;
+            }
+            assert_eq!(message["request_seq"], self.seq);
+            assert_eq!(message["success"], true, "{}", message);
+            return message["body"].clone();
+        }
+    }
+
+    /// the body of the next event named `name`, the output of the program skipped
+    fn event(&mut self, name: &str) -> Json {
+        loop {
+            let message = match self.events.pop_front() {
+                Some(message) => message,
+                None => self.receive(),
+            };
+            if message["event"] == "output" {
+                continue;
+            }
+            assert_eq!(message["event"], name, "{}", message);
+            return message["body"].clone();
+        }
+    }
+
+    /// the lines of the frames of the backtrace, the innermost first
+    fn stack_trace(&mut self) -> Vec<(String, u64)> {
+        let body = self.request("stackTrace", json!({ "threadId": 1 }));
+        body["stackFrames"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| {
+                (
+                    x["name"].as_str().unwrap().to_string(),
+                    x["line"].as_u64().unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    /// the locals of the innermost frame, with their types and values
+    fn locals(&mut self) -> Vec<(String, String, String)> {
+        let scopes = self.request("scopes", json!({ "frameId": 0 }));
+        let reference = scopes["scopes"][0]["variablesReference"].clone();
+        let body = self.request("variables", json!({ "variablesReference": reference }));
+        body["variables"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|x| {
+                let field = |name: &str| x[name].as_str().unwrap().to_string();
+                (field("name"), field("type"), field("value"))
+            })
+            .collect()
+    }
+}
+
+/// a message with its `Content-Length` header, `None` at the end of the output
+fn read_message(input: &mut impl BufRead) -> Option<Json> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut content = vec![0; length?];
+    input.read_exact(&mut content).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+fn local(name: &str, ty: &str, value: &str) -> (String, String, String) {
+    (name.to_string(), ty.to_string(), value.to_string())
+}
+
+#[test]
+fn debug_session() {
+    let dir = std::env::temp_dir().join(format!("popper-dap-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let program = dir.join("square.pop");
+    std::fs::write(&program, PROGRAM).unwrap();
+    let mut client = Client::start(&program);
+
+    let capabilities = client.request("initialize", json!({ "adapterID": "popper" }));
+    assert_eq!(capabilities["supportsConfigurationDoneRequest"], true);
+    client.event("initialized");
+
+    let body = client.request(
+        "setBreakpoints",
+        json!({
+            "source": { "path": program },
+            "breakpoints": [{ "line": 2 }, { "line": 20 }],
+        }),
+    );
+    assert_eq!(
+        body["breakpoints"],
+        json!([
+            { "verified": true, "line": 2 },
+            { "verified": false, "line": 20, "message": "no code there" },
+        ])
+    );
+    client.request("launch", json!({ "program": program }));
+    client.request("configurationDone", json!({}));
+
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["reason"], "breakpoint");
+    assert_eq!(
+        client.stack_trace(),
+        [("square".to_string(), 2), ("main".to_string(), 8)]
+    );
+    assert_eq!(client.locals(), [local("x", "int", "3")]);
+
+    client.request("next", json!({ "threadId": 1 }));
+    let stopped = client.event("stopped");
+    assert_eq!(stopped["reason"], "step");
+    assert_eq!(client.stack_trace()[0], ("square".to_string(), 3));
+    assert_eq!(
+        client.locals(),
+        [local("x", "int", "3"), local("y", "int", "9")]
+    );
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("exited"), json!({ "exitCode": 0 }));
+    client.event("terminated");
+    client.request("disconnect", json!({}));
+    drop(client.stdin);
+    assert!(client.process.wait().unwrap().success());
+}
@@ -0,0 +1,79 @@
+use popper::doc::generate_doc;
+use std::path::PathBuf;
+
+const MAIN: &str = "import index;
+
+/// entry of the program
+func main(): int {
+    return index.visible();
+}
+";
+
+const INDEX: &str = "//! a module named like the index
+
+extern {
+    func abs(x: int): int
+};
+
+func hidden(): int {
+    return abs(0 - 1);
+}
+
+/// seen by the importers
+pub func visible(): int {
+    return hidden();
+}
+
+/// a point
+pub struct Point {
+    pub x = int,
+    y = int
+}
+
+struct Private {
+    x = int
+}
+";
+
+/// fresh directory for the test `name`, with the files `files` written in it
+fn package_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("popper-doc-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (file, content) in files {
+        std::fs::write(dir.join(file), content).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn pub_items() {
+    let dir = package_dir("pub", &[("main.pop", MAIN), ("index.pop", INDEX)]);
+    let target = dir.join("doc");
+    let index = generate_doc(&dir.join("main.pop"), vec![], &target).expect("valid modules");
+
+    assert_eq!(index, target.join("index.html"));
+    let index = std::fs::read_to_string(index).unwrap();
+    // the page of the module `index` doesn't replace the index
+    assert!(index.contains("<h1>Modules</h1>"), "{}", index);
+    assert!(index.contains("<a href=\"modules/index.html\">index</a>"));
+    assert!(index.contains("<a href=\"modules/main.html\">main</a>"));
+
+    let page = std::fs::read_to_string(target.join("modules/index.html")).unwrap();
+    assert!(page.contains("pub func visible(): int"), "{}", page);
+    assert!(page.contains("pub struct Point"));
+    // private fields are part of the layout of a struct
+    assert!(page.contains("pub x: int"));
+    assert!(page.contains("    y: int"));
+    for private in ["hidden", "Private", "abs"] {
+        assert!(
+            !page.contains(private),
+            "`{}` documented: {}",
+            private,
+            page
+        );
+    }
+
+    let page = std::fs::read_to_string(target.join("modules/main.html")).unwrap();
+    assert!(!page.contains("func main"), "{}", page);
+}
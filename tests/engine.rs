@@ -0,0 +1,174 @@
+use popper::engine::{EngineError, Struct};
+use popper::Engine;
+use popper_ast::IntKind;
+use popper_vm::{Limit, Limits, Value, VmError};
+
+const PROGRAM: &str = r#"struct Point {
+    x = int,
+    y = int
+}
+
+func add(a: int, b: int): int {
+    return a + b;
+}
+
+func scaled(p: struct Point, k: int): struct Point {
+    return init Point { x = p.x * k, y = p.y * k };
+}
+
+func greet(name: string): string {
+    return shout("hello " + name);
+}
+
+func echo(s: string): string {
+    return shout(s);
+}
+
+func count(n: int): int {
+    let i = 0;
+    while i lt n {
+        i = i + 1;
+    }
+    return i;
+}
+"#;
+
+/// an engine with `shout` registered and `PROGRAM` loaded
+fn engine(limits: Limits) -> Engine {
+    let mut engine = Engine::new().with_limits(limits);
+    engine
+        .register("func shout(s: string): string", |_, args| match &args[0] {
+            Value::String(s) if s.is_empty() => Err("nothing to shout".to_string()),
+            Value::String(s) => Ok(Value::string(&s.to_uppercase())),
+            value => Err(format!("{} isn't a string", value)),
+        })
+        .unwrap();
+    engine.load("engine.pop", PROGRAM).expect("valid program");
+    engine
+}
+
+fn int(x: i64) -> Value {
+    Value::int(IntKind::I32, x)
+}
+
+fn type_error<T: std::fmt::Debug>(result: Result<T, EngineError>) -> String {
+    match result {
+        Err(EngineError::Type(msg)) => msg,
+        result => panic!("expected a type error, got {:?}", result),
+    }
+}
+
+#[test]
+fn register() {
+    let mut engine = engine(Limits::default());
+    let greeting: String = engine.call("greet", &[&"popper"]).unwrap();
+    assert_eq!(greeting, "HELLO POPPER");
+
+    // an error of the host function is a panic of the program
+    let err = engine.call::<String>("echo", &[&""]);
+    let Err(EngineError::Vm(VmError::Panic(panic))) = err else {
+        panic!("a panic expected, got {:?}", err);
+    };
+    assert_eq!(panic.message, "nothing to shout");
+
+    let mut engine = Engine::new();
+    let two = "func a(): int, func b(): int";
+    assert_eq!(
+        type_error(engine.register(two, |_, _| Ok(Value::Unit))),
+        format!("`{}` isn't a signature", two)
+    );
+    assert!(matches!(
+        engine.register("struct Point { x = int }", |_, _| Ok(Value::Unit)),
+        Err(EngineError::Invalid(_))
+    ));
+}
+
+#[test]
+fn load_diagnostics() {
+    let mut engine = Engine::new();
+    assert!(matches!(
+        engine.call::<i32>("add", &[&1, &2]),
+        Err(EngineError::NotLoaded)
+    ));
+
+    let Err(EngineError::Invalid(diagnostics)) = engine.load(
+        "undefined.pop",
+        "func main(): int {\n    return answer;\n}\n",
+    ) else {
+        panic!("an invalid program expected");
+    };
+    let [diagnostic] = diagnostics.as_slice() else {
+        panic!("one diagnostic expected: {:?}", diagnostics);
+    };
+    assert_eq!(diagnostic.file.to_str(), Some("undefined.pop"));
+    assert!(
+        diagnostic.message.contains("answer"),
+        "{}",
+        diagnostic.message
+    );
+
+    let Err(EngineError::Invalid(diagnostics)) = engine.load("syntax.pop", "func main(") else {
+        panic!("an invalid program expected");
+    };
+    assert_eq!(diagnostics.len(), 1);
+    // a program which isn't valid replaces the one loaded before
+    assert!(engine.vm().is_none());
+}
+
+#[test]
+fn call_errors() {
+    let mut engine = engine(Limits::default());
+    assert_eq!(engine.call::<i32>("add", &[&1, &2]).unwrap(), 3);
+    assert!(matches!(
+        engine.call::<i32>("sub", &[&1, &2]),
+        Err(EngineError::UnknownFunction(name)) if name == "sub"
+    ));
+    assert_eq!(
+        type_error(engine.call::<i32>("add", &[&1])),
+        "`add` takes 2 arguments, not 1"
+    );
+    assert_eq!(
+        type_error(engine.call::<i32>("add", &[&1, &"two"])),
+        "argument 2 of `add` has type `int`, given `string`"
+    );
+    assert_eq!(
+        type_error(engine.call::<String>("add", &[&1, &2])),
+        "`add` returned `int`, not `alloc::string::String`"
+    );
+}
+
+#[test]
+fn structs() {
+    let mut engine = engine(Limits::default());
+    let point = Struct::new("Point")
+        .with_field("y", int(2))
+        .with_field("x", int(1));
+    let scaled: Struct = engine.call("scaled", &[&point, &3]).unwrap();
+    assert_eq!(scaled.name, "Point");
+    let vm = engine.vm().unwrap();
+    assert_eq!(scaled.get::<i32>("x", vm), Some(3));
+    assert_eq!(scaled.get::<i64>("y", vm), Some(6));
+    assert_eq!(scaled.get::<String>("x", vm), None);
+    assert_eq!(scaled.get::<i32>("z", vm), None);
+
+    let missing = Struct::new("Point").with_field("x", int(1));
+    assert_eq!(
+        type_error(engine.call::<Struct>("scaled", &[&missing, &3])),
+        "`Point` has 2 fields, not 1"
+    );
+}
+
+#[test]
+fn limits() {
+    let limits = Limits::default().with_instructions(1000);
+    let mut engine = engine(limits);
+    assert_eq!(engine.call::<i32>("count", &[&10]).unwrap(), 10);
+    let err = engine.call::<i32>("count", &[&100_000]);
+    let Err(EngineError::Vm(VmError::Limit(err))) = err else {
+        panic!("a limit expected, got {:?}", err);
+    };
+    assert_eq!(err.limit, Limit::Instructions(1000));
+    // the functions of the host are allowed, and each call has its own instructions
+    let greeting: String = engine.call("greet", &[&"you"]).unwrap();
+    assert_eq!(greeting, "HELLO YOU");
+}
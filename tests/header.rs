@@ -0,0 +1,135 @@
+use popper::get_ast;
+use popper::header::generate_header;
+use popper_semantic_analyzer::analyze_module;
+use std::collections::HashMap;
+use std::process::Command;
+
+const MODULE: &str = r#"struct Point {
+    x = int,
+    y = int
+}
+
+/// a record
+struct Record {
+    flag = bool,
+    id = i64,
+    name = string,
+    origin = struct Point,
+    small = i16,
+    pair = (i8, int),
+    scores = [u8: 3]
+}
+
+export func norm(x: int, y: int): int {
+    return x * x + y * y;
+}
+
+export func origin(r: struct Record): struct Point {
+    return r.origin;
+}
+"#;
+
+fn header() -> String {
+    let stmts = get_ast(MODULE, "record.pop").expect("valid module");
+    let env = analyze_module(stmts.clone(), HashMap::new()).expect("checked module");
+    generate_header("record", &[(stmts, env)])
+}
+
+/// definition of `struct name` in `header`
+fn definition<'a>(header: &'a str, name: &str) -> &'a str {
+    let start = header
+        .find(&format!("struct {} {{", name))
+        .unwrap_or_else(|| panic!("`{}` is defined:\n{}", name, header));
+    let end = start + header[start..].find("};").unwrap();
+    &header[start..end]
+}
+
+#[test]
+fn struct_fields() {
+    let header = header();
+
+    let record = definition(&header, "Record");
+    let fields: Vec<&str> = record.lines().skip(1).map(|x| x.trim()).collect();
+    assert_eq!(
+        fields,
+        [
+            "popper_bool flag;",
+            "int64_t id;",
+            "popper_string *name;",
+            "Point origin;",
+            "int16_t small;",
+            "popper_tuple0 pair;",
+            "uint8_t scores[3];",
+        ]
+    );
+    assert!(header.contains("/**\n * a record\n */\nstruct Record {"));
+    // the structs contained by value come first
+    assert!(header.find("struct Point {").unwrap() < header.find("struct Record {").unwrap());
+    assert!(header.contains("typedef struct popper_tuple0 {\n    int8_t _0;\n    int32_t _1;\n}"));
+
+    assert!(header.contains("int32_t norm(int32_t x, int32_t y);"));
+    assert!(header.contains("/* `origin` is left out"));
+}
+
+/// the layout of the header, checked by a C compiler, is the one of the compiled code:
+/// the fields in order, with their natural alignment
+#[test]
+fn struct_layout() {
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    if Command::new(&cc).arg("--version").output().is_err() {
+        eprintln!("skipped: no C compiler `{}`", cc);
+        return;
+    }
+
+    let dir = std::env::temp_dir().join(format!("popper-header-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("record.h"), header()).unwrap();
+    std::fs::write(
+        dir.join("layout.c"),
+        r#"#include <stdio.h>
+#include "record.h"
+
+#define FIELD(s, f) printf(#f " %zu\n", offsetof(s, f))
+
+int main(void) {
+    printf("size %zu\n", sizeof(Record));
+    FIELD(Record, flag);
+    FIELD(Record, id);
+    FIELD(Record, name);
+    FIELD(Record, origin);
+    FIELD(Record, small);
+    FIELD(Record, pair);
+    FIELD(Record, scores);
+    return 0;
+}
+"#,
+    )
+    .unwrap();
+
+    let exe = dir.join("layout");
+    let output = Command::new(&cc)
+        .arg(dir.join("layout.c"))
+        .arg("-o")
+        .arg(&exe)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let output = Command::new(&exe).output().unwrap();
+
+    let pointer = std::mem::size_of::<usize>();
+    let origin = 16 + pointer;
+    let expected = format!(
+        "size {}\nflag 0\nid 8\nname 16\norigin {}\nsmall {}\npair {}\nscores {}\n",
+        // rounded up to the alignment of `id`
+        (origin + 8 + 4 + 8 + 3).next_multiple_of(8),
+        origin,
+        origin + 8,
+        origin + 8 + 4,
+        origin + 8 + 4 + 8,
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected);
+}
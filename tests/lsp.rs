@@ -0,0 +1,192 @@
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized, Notification as LspNotification,
+    PublishDiagnostics,
+};
+use lsp_types::request::{HoverRequest, Initialize, Request as LspRequest, Shutdown};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidOpenTextDocumentParams, HoverContents, HoverParams,
+    InitializeParams, InitializeResult, Position, PublishDiagnosticsParams,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier,
+};
+use popper::lsp;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+const PROGRAM: &str = "func main(): int {\n    let answer: int = 42;\n    return answer;\n}\n";
+const UNDEFINED: &str = "func main(): int {\n    return answer;\n}\n";
+
+/// client of a server running in process
+struct Client {
+    connection: Connection,
+    server: Option<JoinHandle<Result<(), lsp::LspError>>>,
+    next_id: i32,
+}
+
+impl Client {
+    /// start a server and go through the initialization
+    fn start() -> Self {
+        let (connection, server) = Connection::memory();
+        let server = std::thread::spawn(move || lsp::run(server, vec![]));
+        let mut client = Self {
+            connection,
+            server: Some(server),
+            next_id: 0,
+        };
+
+        let response = client.request(Initialize::METHOD, InitializeParams::default());
+        let result: InitializeResult = serde_json::from_value(response.result.unwrap()).unwrap();
+        assert!(result.capabilities.hover_provider.is_some());
+        client.notify(Initialized::METHOD, lsp_types::InitializedParams {});
+        client
+    }
+
+    fn request(&mut self, method: &str, params: impl serde::Serialize) -> Response {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let req = Request::new(id.clone(), method.to_string(), params);
+        self.connection.sender.send(Message::Request(req)).unwrap();
+        match self.receive() {
+            Message::Response(response) if response.id == id => response,
+            msg => panic!("expected the response to `{}`, got {:?}", method, msg),
+        }
+    }
+
+    fn notify(&self, method: &str, params: impl serde::Serialize) {
+        let not = Notification::new(method.to_string(), params);
+        self.connection
+            .sender
+            .send(Message::Notification(not))
+            .unwrap();
+    }
+
+    fn receive(&self) -> Message {
+        self.connection
+            .receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("no message from the server")
+    }
+
+    fn diagnostics(&self) -> PublishDiagnosticsParams {
+        match self.receive() {
+            Message::Notification(not) if not.method == PublishDiagnostics::METHOD => {
+                serde_json::from_value(not.params).unwrap()
+            }
+            msg => panic!("expected diagnostics, got {:?}", msg),
+        }
+    }
+
+    fn open(&self, uri: &Url, text: &str) {
+        let params = DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri.clone(),
+                "popper".to_string(),
+                1,
+                text.to_string(),
+            ),
+        };
+        self.notify(DidOpenTextDocument::METHOD, params);
+    }
+
+    fn shutdown(mut self) {
+        let response = self.request(Shutdown::METHOD, ());
+        assert!(response.error.is_none());
+        self.notify(Exit::METHOD, ());
+        let server = self.server.take().unwrap();
+        assert!(server.join().unwrap().is_ok());
+    }
+}
+
+fn hover_params(uri: &Url, line: u32, character: u32) -> HoverParams {
+    HoverParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri.clone()),
+            Position::new(line, character),
+        ),
+        work_done_progress_params: Default::default(),
+    }
+}
+
+#[test]
+fn initialize_and_shutdown() {
+    Client::start().shutdown();
+}
+
+#[test]
+fn diagnostics() {
+    let client = Client::start();
+    let uri = Url::from_file_path("/popper-lsp/diagnostics.pop").unwrap();
+
+    client.open(&uri, UNDEFINED);
+    let published = client.diagnostics();
+    assert_eq!(published.uri, uri);
+    let [diagnostic] = published.diagnostics.as_slice() else {
+        panic!("one diagnostic: {:?}", published.diagnostics);
+    };
+    assert_eq!(diagnostic.range.start, Position::new(1, 11));
+
+    // fixed, the diagnostics are cleared
+    let params = DidChangeTextDocumentParams {
+        text_document: VersionedTextDocumentIdentifier::new(uri.clone(), 2),
+        content_changes: vec![TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: PROGRAM.to_string(),
+        }],
+    };
+    client.notify(DidChangeTextDocument::METHOD, params);
+    let published = client.diagnostics();
+    assert_eq!(published.uri, uri);
+    assert!(published.diagnostics.is_empty());
+
+    client.shutdown();
+}
+
+#[test]
+fn diagnostics_of_unsaved_document() {
+    let client = Client::start();
+    let uri = Url::parse("untitled:Untitled-1").unwrap();
+
+    client.open(&uri, UNDEFINED);
+    let published = client.diagnostics();
+    assert_eq!(published.uri, uri);
+    assert_eq!(published.diagnostics.len(), 1);
+
+    client.shutdown();
+}
+
+#[test]
+fn hover() {
+    let mut client = Client::start();
+    let uri = Url::from_file_path("/popper-lsp/hover.pop").unwrap();
+    client.open(&uri, PROGRAM);
+    assert!(client.diagnostics().diagnostics.is_empty());
+
+    let response = client.request(HoverRequest::METHOD, hover_params(&uri, 2, 12));
+    let hover: lsp_types::Hover = serde_json::from_value(response.result.unwrap()).unwrap();
+    let HoverContents::Markup(contents) = hover.contents else {
+        panic!("markup expected");
+    };
+    assert!(contents.value.contains("answer: int"), "{}", contents.value);
+
+    client.shutdown();
+}
+
+#[test]
+fn invalid_params() {
+    let mut client = Client::start();
+
+    let response = client.request(HoverRequest::METHOD, serde_json::json!({ "line": 1 }));
+    assert_eq!(
+        response.error.map(|x| x.code),
+        Some(ErrorCode::InvalidParams as i32)
+    );
+    // the server keeps going, even after a malformed notification
+    client.notify(DidOpenTextDocument::METHOD, serde_json::json!({}));
+    let uri = Url::from_file_path("/popper-lsp/invalid.pop").unwrap();
+    client.open(&uri, UNDEFINED);
+    assert_eq!(client.diagnostics().diagnostics.len(), 1);
+
+    client.shutdown();
+}
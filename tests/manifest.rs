@@ -0,0 +1,189 @@
+use popper::manifest::{Manifest, ManifestError, MANIFEST_NAME};
+use std::path::{Path, PathBuf};
+
+/// fresh directory for the test `name`, with the files `files` written in it
+fn package_dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("popper-manifest-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    for (file, content) in files {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    dir.canonicalize().unwrap()
+}
+
+#[test]
+fn parse() {
+    let dir = package_dir(
+        "parse",
+        &[(
+            MANIFEST_NAME,
+            r#"
+[package]
+name = "hello"
+version = "0.1.0"
+entry = "app/hello.pop"
+source-dirs = ["app", "lib"]
+
+[link]
+libs = ["m"]
+objects = ["c/hello.o"]
+exports = ["hello"]
+"#,
+        )],
+    );
+    let manifest = Manifest::load(&dir.join(MANIFEST_NAME)).unwrap();
+
+    assert_eq!(manifest.package.name, "hello");
+    assert_eq!(manifest.package.version, "0.1.0");
+    assert_eq!(manifest.root, dir);
+    assert_eq!(manifest.entry(), dir.join("app/hello.pop"));
+    assert_eq!(
+        manifest.source_dirs(),
+        vec![dir.join("app"), dir.join("lib")]
+    );
+    let link = manifest.link();
+    assert_eq!(link.libs, vec!["m".to_string()]);
+    assert_eq!(link.objects, vec![dir.join("c/hello.o")]);
+    assert_eq!(link.exports, vec!["hello".to_string()]);
+    assert!(manifest.dependencies().unwrap().is_empty());
+}
+
+#[test]
+fn defaults() {
+    let dir = package_dir(
+        "defaults",
+        &[(
+            MANIFEST_NAME,
+            "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n",
+        )],
+    );
+    let manifest = Manifest::load(&dir.join(MANIFEST_NAME)).unwrap();
+
+    assert_eq!(manifest.entry(), dir.join("src/main.pop"));
+    assert_eq!(manifest.source_dirs(), vec![dir.join("src")]);
+    assert!(manifest.link.libs.is_empty());
+    assert!(manifest.link.objects.is_empty());
+    assert!(manifest.dependencies.is_empty());
+}
+
+#[test]
+fn missing_fields() {
+    let dir = package_dir(
+        "missing",
+        &[
+            ("version/popper.toml", "[package]\nname = \"hello\"\n"),
+            ("package/popper.toml", "[link]\nlibs = [\"m\"]\n"),
+        ],
+    );
+
+    for (manifest, field) in [("version", "version"), ("package", "package")] {
+        let path = dir.join(manifest).join(MANIFEST_NAME);
+        match Manifest::load(&path) {
+            Err(ManifestError::Invalid(file, err)) => {
+                assert_eq!(file, path);
+                assert!(
+                    err.to_string()
+                        .contains(&format!("missing field `{}`", field)),
+                    "{}",
+                    err
+                );
+            }
+            other => panic!("expected an invalid manifest, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn find() {
+    let dir = package_dir(
+        "find",
+        &[
+            (
+                MANIFEST_NAME,
+                "[package]\nname = \"hello\"\nversion = \"0.1.0\"\n",
+            ),
+            ("src/main.pop", ""),
+        ],
+    );
+
+    let manifest = Manifest::find(&dir.join("src")).unwrap();
+    assert_eq!(manifest.root, dir);
+    assert!(matches!(
+        Manifest::find(Path::new("/popper/does/not/exist")),
+        Err(ManifestError::Io(..))
+    ));
+}
+
+#[test]
+fn dependencies() {
+    let dir = package_dir(
+        "dependencies",
+        &[
+            (
+                "app/popper.toml",
+                r#"
+[package]
+name = "app"
+version = "0.1.0"
+
+[dependencies]
+geo = { path = "../geo" }
+util = { path = "../util" }
+"#,
+            ),
+            (
+                "geo/popper.toml",
+                r#"
+[package]
+name = "geo"
+version = "0.1.0"
+
+[link]
+objects = ["c/geo.o"]
+
+[dependencies]
+util = { path = "../util" }
+"#,
+            ),
+            (
+                "util/popper.toml",
+                "[package]\nname = \"util\"\nversion = \"0.1.0\"\n",
+            ),
+        ],
+    );
+    let manifest = Manifest::load(&dir.join("app").join(MANIFEST_NAME)).unwrap();
+
+    let dependencies = manifest.dependencies().unwrap();
+    let found: Vec<_> = dependencies
+        .iter()
+        .map(|(name, x)| (name.as_str(), x.root.clone()))
+        .collect();
+    // `util` is used by both packages but comes once, before `geo` which depends on it
+    assert_eq!(
+        found,
+        vec![("util", dir.join("util")), ("geo", dir.join("geo"))]
+    );
+    assert_eq!(
+        dependencies[1].1.link().objects,
+        vec![dir.join("geo/c/geo.o")]
+    );
+}
+
+#[test]
+fn missing_dependency() {
+    let dir = package_dir(
+        "missing-dependency",
+        &[(
+            MANIFEST_NAME,
+            "[package]\nname = \"app\"\nversion = \"0.1.0\"\n\n[dependencies]\ngeo = { path = \"../geo\" }\n",
+        )],
+    );
+    let manifest = Manifest::load(&dir.join(MANIFEST_NAME)).unwrap();
+
+    match manifest.dependencies() {
+        Err(ManifestError::Io(path, _)) => assert!(path.ends_with("geo/popper.toml")),
+        other => panic!("expected a missing dependency, got {:?}", other),
+    }
+}
@@ -0,0 +1,54 @@
+use popper::module_loader::ModuleLoader;
+use popper_ast::Statement;
+use std::path::{Path, PathBuf};
+
+/// loader of the modules `files`, which aren't on disk
+fn loader(files: &[(&str, &str)]) -> ModuleLoader {
+    let mut loader = ModuleLoader::new(vec![]).keep_diagnostics();
+    for (file, source) in files {
+        loader.set_source(PathBuf::from(file), source.to_string());
+    }
+    loader
+}
+
+#[test]
+fn import_cycle() {
+    let mut loader = loader(&[
+        ("/cycle/a.pop", "import b;\n"),
+        ("/cycle/b.pop", "import a;\n"),
+    ]);
+    assert!(loader
+        .load_source(Path::new("/cycle/a.pop"), "import b;\n".to_string())
+        .is_none());
+
+    let [diagnostic] = loader.diagnostics() else {
+        panic!("one diagnostic: {:?}", loader.diagnostics());
+    };
+    assert_eq!(diagnostic.file, Path::new("/cycle/b.pop"));
+    assert_eq!(
+        diagnostic.message,
+        "Import cycle: /cycle/a.pop -> /cycle/b.pop -> /cycle/a.pop"
+    );
+    assert_eq!(diagnostic.span.start, 0);
+}
+
+#[test]
+fn bundled_std() {
+    let mut loader = loader(&[]);
+    let stmts = loader
+        .load_source(Path::new("/main.pop"), "import std.io;\n".to_string())
+        .expect("std.io is bundled");
+
+    let [Statement::Import(import)] = stmts.as_slice() else {
+        panic!("one import");
+    };
+    assert_eq!(
+        import.file.as_deref(),
+        Some(Path::new("<bundled>/std/io.pop"))
+    );
+    assert!(import
+        .source
+        .as_ref()
+        .is_some_and(|x| x.contains("pub func println")));
+    assert!(!import.module_stmts.is_empty());
+}
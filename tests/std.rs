@@ -0,0 +1,101 @@
+use popper::test_runner::{collect_tests, run_tests as run_native, test_program};
+use popper::{check_program, get_ast, Target};
+use popper_ast::IntKind;
+use popper_vm::{Value, Vm};
+use std::process::Command;
+
+/// the native tests are compiled with `llc` and linked with `clang`
+fn has_toolchain() -> bool {
+    let found = ["llc", "clang"].iter().all(|tool| {
+        Command::new(tool)
+            .arg("--version")
+            .output()
+            .is_ok_and(|x| x.status.success())
+    });
+    if !found {
+        eprintln!("skipped natively: `llc` and `clang` are needed to build the tests");
+    }
+    found
+}
+
+/// run the tests of a module of the standard library in the VM, and natively when the
+/// toolchain is there
+fn run_tests(name: &str, source: &str) {
+    let file = format!("std/{}.pop", name);
+    let stmts = get_ast(source, &file).expect("valid module");
+    assert!(check_program(stmts.clone(), source, &file));
+
+    let tests = collect_tests(&stmts);
+    assert!(!tests.is_empty());
+    for test in tests {
+        let program = test_program(&stmts, &test);
+        let module = popper_vm::compile(&program, &file, source).expect("supported module");
+        let mut vm = Vm::new(module)
+            .expect("declared foreign functions")
+            .with_args(vec![file.clone()]);
+        assert_eq!(
+            vm.call("main", vec![]),
+            Ok(Value::Int(IntKind::I32, 0)),
+            "test {} of {}",
+            test.name,
+            file
+        );
+    }
+
+    if has_toolchain() {
+        let target_path =
+            std::env::temp_dir().join(format!("popper-std-{}-{}", name, std::process::id()));
+        let summary = run_native(
+            &stmts,
+            source,
+            &file,
+            &target_path,
+            None,
+            &Target::default(),
+            None,
+        );
+        for result in &summary.results {
+            assert!(
+                result.passed,
+                "test {} of {}: {}",
+                result.name, file, result.output
+            );
+        }
+        assert!(summary.passed());
+    }
+}
+
+#[test]
+fn math() {
+    run_tests("math", include_str!("../std/math.pop"));
+}
+
+#[test]
+fn string() {
+    run_tests("string", include_str!("../std/string.pop"));
+}
+
+#[test]
+fn process() {
+    run_tests("process", include_str!("../std/process.pop"));
+}
+
+#[test]
+fn io() {
+    run_tests("io", include_str!("../std/io.pop"));
+}
+
+#[test]
+fn mem() {
+    run_tests("mem", include_str!("../std/mem.pop"));
+}
+
+#[test]
+fn fs() {
+    run_tests("fs", include_str!("../std/fs.pop"));
+}
+
+#[test]
+fn time() {
+    run_tests("time", include_str!("../std/time.pop"));
+}
@@ -0,0 +1,129 @@
+use popper::test_runner::{collect_tests, run_tests, test_program};
+use popper::{get_ast, Target};
+use popper_ast::Statement;
+use std::path::PathBuf;
+use std::process::Command;
+
+const MODULE: &str = r#"func square(x: int): int {
+    return x * x;
+}
+
+func main(): int {
+    return 1;
+}
+
+test "square of a positive number" {
+    assert(square(3) eq 9, "3 * 3 should be 9");
+}
+
+test "square of a negative number" {
+    assert(square(-2) eq -4, "squares are positive");
+}
+
+test "square of zero" {
+    assert(square(0) eq 0, "0 * 0 should be 0");
+}
+"#;
+
+fn module() -> Vec<Statement> {
+    get_ast(MODULE, "square.pop").unwrap()
+}
+
+fn target_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("popper-tests-{}-{}", name, std::process::id()))
+}
+
+/// the tests are compiled with `llc` and linked with `clang`
+fn has_toolchain() -> bool {
+    let found = ["llc", "clang"].iter().all(|tool| {
+        Command::new(tool)
+            .arg("--version")
+            .output()
+            .is_ok_and(|x| x.status.success())
+    });
+    if !found {
+        eprintln!("skipped: `llc` and `clang` are needed to build the tests");
+    }
+    found
+}
+
+#[test]
+fn program_of_a_test() {
+    let stmts = module();
+    let tests = collect_tests(&stmts);
+    assert_eq!(
+        tests.iter().map(|x| x.name.as_str()).collect::<Vec<_>>(),
+        [
+            "square of a positive number",
+            "square of a negative number",
+            "square of zero"
+        ]
+    );
+
+    let program = test_program(&stmts, &tests[1]);
+    assert!(!program.iter().any(|x| matches!(x, Statement::Test(_))));
+    let functions: Vec<_> = program
+        .iter()
+        .filter_map(|x| match x {
+            Statement::Function(f) => Some(f),
+            _ => None,
+        })
+        .collect();
+    // the `main` of the module is replaced by the test
+    let [square, main] = functions.as_slice() else {
+        panic!("two functions expected");
+    };
+    assert_eq!(square.name, "square");
+    assert_eq!(main.name, "main");
+    assert_eq!(main.body.len(), tests[1].body.statements.len() + 1);
+    assert!(matches!(main.body.last(), Some(Statement::Return(_))));
+}
+
+#[test]
+fn filter_out_every_test() {
+    let summary = run_tests(
+        &module(),
+        MODULE,
+        "square.pop",
+        &target_dir("filter-out"),
+        Some("cube"),
+        &Target::default(),
+        None,
+    );
+    assert!(summary.results.is_empty());
+    assert_eq!(summary.filtered_out, 3);
+    assert!(summary.passed());
+}
+
+#[test]
+fn passing_and_failing_tests() {
+    if !has_toolchain() {
+        return;
+    }
+    let summary = run_tests(
+        &module(),
+        MODULE,
+        "square.pop",
+        &target_dir("run"),
+        Some("square of a"),
+        &Target::default(),
+        None,
+    );
+
+    assert_eq!(summary.filtered_out, 1);
+    let [positive, negative] = summary.results.as_slice() else {
+        panic!("two tests expected");
+    };
+    assert_eq!(positive.name, "square of a positive number");
+    assert!(positive.passed, "{}", positive.output);
+    assert_eq!(negative.name, "square of a negative number");
+    assert!(!negative.passed);
+    assert!(
+        negative
+            .output
+            .contains("assertion failed: squares are positive"),
+        "{}",
+        negative.output
+    );
+    assert!(!summary.passed());
+}